@@ -0,0 +1,359 @@
+// The broadcasting protocol of Assetto Corsa Competizione (the one used by the broadcasting
+// sample in the game folder), spoken over udp with the port set in broadcasting.json.
+//
+// We register as an application, after which the game sends a realtime update of the session
+// and each car every update interval. Entry list and track data are only send on request.
+//
+// Everything is little endian, strings are prefixed with their length as u16.
+
+use std::time::Duration;
+
+const PROTOCOL_VERSION: u8 = 4;
+
+const REGISTER_COMMAND_APPLICATION: u8 = 1;
+const UNREGISTER_COMMAND_APPLICATION: u8 = 9;
+const REQUEST_ENTRY_LIST: u8 = 10;
+const REQUEST_TRACK_DATA: u8 = 11;
+
+const REGISTRATION_RESULT: u8 = 1;
+const REALTIME_UPDATE: u8 = 2;
+const REALTIME_CAR_UPDATE: u8 = 3;
+const ENTRY_LIST: u8 = 4;
+const TRACK_DATA: u8 = 5;
+const ENTRY_LIST_CAR: u8 = 6;
+const BROADCASTING_EVENT: u8 = 7;
+
+/// Used by the game for times that are not set (like the best lap before finishing a lap)
+const NO_TIME: i32 = i32::MAX;
+
+pub(crate) const SESSION_TYPES: &[&str] = &["practice", "qualifying", "superpole", "race", "hotlap", "hotstint", "hotlap_superpole", "replay", "unknown"];
+pub(crate) const SESSION_PHASES: &[&str] = &["none", "starting", "pre_formation", "formation_lap", "pre_session", "session", "session_over", "post_session", "result_ui", "unknown"];
+pub(crate) const CAR_LOCATIONS: &[&str] = &["none", "track", "pitlane", "pit_entry", "pit_exit", "unknown"];
+
+fn session_type(raw: u8) -> &'static str {
+    match raw {
+        0 => "practice",
+        4 => "qualifying",
+        9 => "superpole",
+        10 => "race",
+        11 => "hotlap",
+        12 => "hotstint",
+        13 => "hotlap_superpole",
+        14 => "replay",
+        _ => "unknown"
+    }
+}
+
+fn lookup(list: &'static [&'static str], raw: u8) -> &'static str {
+    list.get(raw as usize).copied().unwrap_or("unknown")
+}
+
+fn write_string(buf: &mut Vec<u8>, text: &str) {
+    buf.extend_from_slice(&(text.len() as u16).to_le_bytes());
+    buf.extend_from_slice(text.as_bytes());
+}
+
+pub(crate) fn register(display_name: &str, password: &str, update_interval: Duration, command_password: &str) -> Vec<u8> {
+    let mut buf = vec![REGISTER_COMMAND_APPLICATION, PROTOCOL_VERSION];
+    write_string(&mut buf, display_name);
+    write_string(&mut buf, password);
+    buf.extend_from_slice(&(update_interval.as_millis().min(i32::MAX as u128) as i32).to_le_bytes());
+    write_string(&mut buf, command_password);
+    buf
+}
+
+fn connection_request(sort: u8, connection_id: i32) -> Vec<u8> {
+    let mut buf = vec![sort];
+    buf.extend_from_slice(&connection_id.to_le_bytes());
+    buf
+}
+
+pub(crate) fn unregister(connection_id: i32) -> Vec<u8> {
+    connection_request(UNREGISTER_COMMAND_APPLICATION, connection_id)
+}
+
+pub(crate) fn request_entry_list(connection_id: i32) -> Vec<u8> {
+    connection_request(REQUEST_ENTRY_LIST, connection_id)
+}
+
+pub(crate) fn request_track_data(connection_id: i32) -> Vec<u8> {
+    connection_request(REQUEST_TRACK_DATA, connection_id)
+}
+
+/// Reads the values of a message in order
+struct Reader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(value)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        Some(String::from_utf8_lossy(self.take(len)?).to_string())
+    }
+
+    /// Times in ms, None if not set
+    fn time(&mut self) -> Option<Option<i32>> {
+        let value = self.i32()?;
+        Some(if value == NO_TIME { None } else { Some(value) })
+    }
+
+    fn lap(&mut self) -> Option<Lap> {
+        let time = self.time()?;
+        let _car_index = self.u16()?;
+        let _driver_index = self.u16()?;
+
+        let split_count = self.u8()?;
+        for _ in 0..split_count {
+            self.i32()?;
+        }
+
+        let invalid = self.u8()? != 0;
+        let _valid_for_best = self.u8()?;
+        let _outlap = self.u8()?;
+        let _inlap = self.u8()?;
+
+        Some(Lap { time, invalid })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Lap {
+    /// In ms
+    pub(crate) time: Option<i32>,
+    pub(crate) invalid: bool
+}
+
+#[derive(Debug)]
+pub(crate) struct SessionUpdate {
+    pub(crate) session_index: u16,
+    pub(crate) session_type: &'static str,
+    pub(crate) phase: &'static str,
+    /// In ms
+    pub(crate) session_time: f32,
+    /// In ms
+    pub(crate) session_end_time: f32,
+    pub(crate) focused_car: i32,
+    /// In ms
+    pub(crate) time_of_day: f32,
+    pub(crate) ambient_temp: u8,
+    pub(crate) track_temp: u8,
+    /// 0 to 1
+    pub(crate) rain: f32,
+    /// 0 to 1
+    pub(crate) wetness: f32,
+    pub(crate) best_lap: Lap
+}
+
+#[derive(Debug)]
+pub(crate) struct CarUpdate {
+    pub(crate) car_index: u16,
+    pub(crate) driver_index: u16,
+    /// -1 is reverse, 0 neutral
+    pub(crate) gear: i64,
+    pub(crate) location: &'static str,
+    /// In km/h
+    pub(crate) speed: u16,
+    pub(crate) position: u16,
+    /// 0 to 1 around the lap
+    pub(crate) spline_position: f32,
+    pub(crate) laps: u16,
+    /// To the best lap, in ms
+    pub(crate) delta: i32,
+    pub(crate) best_lap: Lap,
+    pub(crate) last_lap: Lap,
+    pub(crate) current_lap: Lap
+}
+
+#[derive(Debug)]
+pub(crate) struct Driver {
+    pub(crate) first_name: String,
+    pub(crate) last_name: String,
+    pub(crate) short_name: String
+}
+
+#[derive(Debug)]
+pub(crate) struct EntryCar {
+    pub(crate) car_index: u16,
+    pub(crate) team_name: String,
+    pub(crate) race_number: i32,
+    pub(crate) drivers: Vec<Driver>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BroadcastEvent {
+    GreenFlag,
+    SessionOver,
+    Penalty,
+    Accident,
+    LapCompleted,
+    BestSessionLap,
+    BestPersonalLap,
+    Other
+}
+
+#[derive(Debug)]
+pub(crate) enum Inbound {
+    RegistrationResult { connection_id: i32, success: bool, error: String },
+    Session(SessionUpdate),
+    Car(CarUpdate),
+    /// The cars in the session, each one is then send as EntryCar
+    EntryList(Vec<u16>),
+    Track { name: String, length: i32 },
+    EntryCar(EntryCar),
+    Event(BroadcastEvent)
+}
+
+/// Parses a message from the game, None if it is unknown or malformed
+pub(crate) fn parse(data: &[u8]) -> Option<Inbound> {
+    let mut reader = Reader { data };
+
+    Some(match reader.u8()? {
+        REGISTRATION_RESULT => {
+            let connection_id = reader.i32()?;
+            let success = reader.u8()? != 0;
+            let _readonly = reader.u8()?;
+            let error = reader.string()?;
+
+            Inbound::RegistrationResult { connection_id, success, error }
+        },
+        REALTIME_UPDATE => {
+            let _event_index = reader.u16()?;
+            let session_index = reader.u16()?;
+            let session_type = session_type(reader.u8()?);
+            let phase = lookup(SESSION_PHASES, reader.u8()?);
+            let session_time = reader.f32()?;
+            let session_end_time = reader.f32()?;
+            let focused_car = reader.i32()?;
+            let _camera_set = reader.string()?;
+            let _camera = reader.string()?;
+            let _hud_page = reader.string()?;
+
+            if reader.u8()? != 0 {
+                // Replay session and remaining time
+                reader.f32()?;
+                reader.f32()?;
+            }
+
+            let time_of_day = reader.f32()?;
+            let ambient_temp = reader.u8()?;
+            let track_temp = reader.u8()?;
+            let _clouds = reader.u8()?;
+            let rain = reader.u8()? as f32 / 10.0;
+            let wetness = reader.u8()? as f32 / 10.0;
+            let best_lap = reader.lap()?;
+
+            Inbound::Session(SessionUpdate {
+                session_index, session_type, phase, session_time, session_end_time, focused_car,
+                time_of_day, ambient_temp, track_temp, rain, wetness, best_lap
+            })
+        },
+        REALTIME_CAR_UPDATE => {
+            let car_index = reader.u16()?;
+            let driver_index = reader.u16()?;
+            let _driver_count = reader.u8()?;
+            // Send with an offset of 2, so reverse is 0
+            let gear = reader.u8()? as i64 - 2;
+            let _world_x = reader.f32()?;
+            let _world_y = reader.f32()?;
+            let _yaw = reader.f32()?;
+            let location = lookup(CAR_LOCATIONS, reader.u8()?);
+            let speed = reader.u16()?;
+            let position = reader.u16()?;
+            let _cup_position = reader.u16()?;
+            let _track_position = reader.u16()?;
+            let spline_position = reader.f32()?;
+            let laps = reader.u16()?;
+            let delta = reader.i32()?;
+            let best_lap = reader.lap()?;
+            let last_lap = reader.lap()?;
+            let current_lap = reader.lap()?;
+
+            Inbound::Car(CarUpdate {
+                car_index, driver_index, gear, location, speed, position, spline_position, laps,
+                delta, best_lap, last_lap, current_lap
+            })
+        },
+        ENTRY_LIST => {
+            let _connection_id = reader.i32()?;
+            let count = reader.u16()?;
+
+            Inbound::EntryList((0..count).map(|_| reader.u16()).collect::<Option<Vec<u16>>>()?)
+        },
+        TRACK_DATA => {
+            let _connection_id = reader.i32()?;
+            let name = reader.string()?;
+            let _track_id = reader.i32()?;
+            let length = reader.i32()?;
+
+            // Camera sets and hud pages follow, which we don't need
+            Inbound::Track { name, length }
+        },
+        ENTRY_LIST_CAR => {
+            let car_index = reader.u16()?;
+            let _model = reader.u8()?;
+            let team_name = reader.string()?;
+            let race_number = reader.i32()?;
+            let _cup_category = reader.u8()?;
+            let _current_driver = reader.u8()?;
+            let _nationality = reader.u16()?;
+
+            let count = reader.u8()?;
+            let mut drivers = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let first_name = reader.string()?;
+                let last_name = reader.string()?;
+                let short_name = reader.string()?;
+                let _category = reader.u8()?;
+                let _nationality = reader.u16()?;
+
+                drivers.push(Driver { first_name, last_name, short_name });
+            }
+
+            Inbound::EntryCar(EntryCar { car_index, team_name, race_number, drivers })
+        },
+        BROADCASTING_EVENT => {
+            let event = match reader.u8()? {
+                1 => BroadcastEvent::GreenFlag,
+                2 => BroadcastEvent::SessionOver,
+                3 => BroadcastEvent::Penalty,
+                4 => BroadcastEvent::Accident,
+                5 => BroadcastEvent::LapCompleted,
+                6 => BroadcastEvent::BestSessionLap,
+                7 => BroadcastEvent::BestPersonalLap,
+                _ => BroadcastEvent::Other
+            };
+            let _msg = reader.string()?;
+            let _time = reader.i32()?;
+            let _car_index = reader.i32()?;
+
+            Inbound::Event(event)
+        },
+        _ => return None
+    })
+}
@@ -0,0 +1,423 @@
+use std::{collections::HashMap, net::UdpSocket, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::JoinHandle, time::{Duration, Instant}};
+
+use datarace_plugin_api::wrappers::{ArrayHandle, EventHandle, Message, PluginError, PluginHandle, Property, PropertyHandle, SessionMarker};
+
+mod broadcast;
+
+use broadcast::{BroadcastEvent, CarUpdate, EntryCar, Inbound, Lap, SessionUpdate};
+
+pub(crate) type PluginState = State;
+
+datarace_plugin_api::macros::free_string_fn!();
+
+datarace_plugin_api::macros::plugin_descriptor_fn!("acc", 0, 1, 0);
+
+// The properties are documented in docs/ACC.md
+datarace_plugin_api::macros::propertys_initor!{ create_properties, "acc",
+    (CONNECTED, "connected", false),
+    (TRACK, "track", ""),
+    (TRACK_LENGTH, "track_length", 0),
+    (SESSION_TIME, "session_time", Duration::from_secs(0)),
+    (SESSION_END_TIME, "session_end_time", Duration::from_secs(0)),
+    (TIME_OF_DAY, "time_of_day", Duration::from_secs(0)),
+    (AMBIENT_TEMP, "ambient_temp", 0),
+    (TRACK_TEMP, "track_temp", 0),
+    (RAIN, "rain", 0.0),
+    (WETNESS, "wetness", 0.0),
+    (BEST_SESSION_LAP, "best_session_lap", Duration::from_secs(0)),
+    (CAR_COUNT, "car_count", 0),
+
+    (CAR_INDEX, "car.index", 0),
+    (CAR_POSITION, "car.position", 0),
+    (CAR_GEAR, "car.gear", 0),
+    (CAR_SPEED, "car.speed", 0),
+    (CAR_LAPS, "car.laps", 0),
+    (CAR_SPLINE, "car.spline", 0.0),
+    (CAR_DELTA, "car.delta", Duration::from_secs(0)),
+    (CAR_LAP_TIME, "car.lap_time", Duration::from_secs(0)),
+    (CAR_LAP_INVALID, "car.lap_invalid", false),
+    (CAR_LAST_LAP, "car.last_lap", Duration::from_secs(0)),
+    (CAR_BEST_LAP, "car.best_lap", Duration::from_secs(0)),
+    (CAR_NUMBER, "car.number", 0),
+    (CAR_DRIVER, "car.driver", ""),
+    (CAR_TEAM, "car.team", ""),
+
+    (LEADERBOARD_NUMBER, "leaderboard.number", [0; 64]),
+    (LEADERBOARD_DRIVER, "leaderboard.driver", [""; 64]),
+    (LEADERBOARD_LAPS, "leaderboard.laps", [0; 64]),
+    (LEADERBOARD_LAST_LAP, "leaderboard.last_lap", [Duration::from_secs(0); 64]),
+    (LEADERBOARD_BEST_LAP, "leaderboard.best_lap", [Duration::from_secs(0); 64])
+}
+
+const SESSION_TYPE: PropertyHandle = datarace_plugin_api::macros::generate_property_handle!("acc.session_type");
+const SESSION_PHASE: PropertyHandle = datarace_plugin_api::macros::generate_property_handle!("acc.session_phase");
+const CAR_LOCATION: PropertyHandle = datarace_plugin_api::macros::generate_property_handle!("acc.car.location");
+
+const GREEN_FLAG: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.green_flag");
+const SESSION_OVER: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.session_over");
+const PENALTY: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.penalty");
+const ACCIDENT: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.accident");
+const LAP_COMPLETED: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.lap_completed");
+const BEST_SESSION_LAP_EVENT: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.best_session_lap");
+const BEST_PERSONAL_LAP: EventHandle = datarace_plugin_api::macros::generate_event_handle!("acc.best_personal_lap");
+
+const EVENTS: &[EventHandle] = &[GREEN_FLAG, SESSION_OVER, PENALTY, ACCIDENT, LAP_COMPLETED, BEST_SESSION_LAP_EVENT, BEST_PERSONAL_LAP];
+
+/// Size of the leaderboard arrays, the game allows at most 64 cars
+const MAX_CARS: usize = 64;
+
+/// How long we wait for a message before checking if we should stop
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(200);
+/// How often we try to register while the game is not answering
+const REGISTER_INTERVAL: Duration = Duration::from_secs(2);
+/// Without any message for this long we treat the game as closed
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Limits how often the entry list is requested when unknown cars show up
+const ENTRY_LIST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings read from the environment (with the same names as in broadcasting.json)
+struct Settings {
+    address: String,
+    password: String,
+    command_password: String,
+    update_interval: Duration
+}
+
+impl Settings {
+    fn from_env() -> Result<Self, String> {
+        let env = |name: &str| std::env::var(name).ok().filter(|val| !val.is_empty());
+
+        let update_interval = match env("DATARACE_ACC_UPDATE_INTERVAL") {
+            Some(val) => Duration::from_millis(val.parse().map_err(|e| format!("environment variable DATARACE_ACC_UPDATE_INTERVAL: {}", e))?),
+            None => Duration::from_millis(100)
+        };
+
+        Ok(Settings {
+            address: env("DATARACE_ACC_ADDRESS").unwrap_or("127.0.0.1:9000".to_string()),
+            password: env("DATARACE_ACC_PASSWORD").unwrap_or("asd".to_string()),
+            command_password: env("DATARACE_ACC_COMMAND_PASSWORD").unwrap_or_default(),
+            update_interval
+        })
+    }
+}
+
+pub(crate) struct State {
+    settings: Mutex<Option<Settings>>,
+    running: Arc<AtomicBool>,
+    worker: Mutex<Option<JoinHandle<()>>>
+}
+
+#[datarace_plugin_api::macros::plugin_init]
+fn handle_init(handle: PluginHandle) -> Result<PluginState,String> {
+    let settings = Settings::from_env()?;
+
+    create_properties(&handle)?;
+    handle.create_enum_property("session_type", SESSION_TYPE, broadcast::SESSION_TYPES, "unknown")
+        .to_result().map_err(|e| e.to_string())?;
+    handle.create_enum_property("session_phase", SESSION_PHASE, broadcast::SESSION_PHASES, "none")
+        .to_result().map_err(|e| e.to_string())?;
+    handle.create_enum_property("car.location", CAR_LOCATION, broadcast::CAR_LOCATIONS, "none")
+        .to_result().map_err(|e| e.to_string())?;
+
+    for ev in EVENTS {
+        handle.create_event(*ev).to_result().map_err(|e| e.to_string())?;
+    }
+
+    Ok(State { settings: Mutex::new(Some(settings)), running: Arc::new(AtomicBool::new(true)), worker: Mutex::new(None) })
+}
+
+#[datarace_plugin_api::macros::plugin_update]
+fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), PluginError> {
+    match msg {
+        Message::StartupFinished => {
+            let state = datarace_plugin_api::macros::get_state!(handle).ok_or("No state".to_string())?;
+            let settings = state.settings.lock().ok().and_then(|mut lock| lock.take()).ok_or("Worker already started".to_string())?;
+
+            // The arrays are created during init, so we can retrieve their handles now
+            let array = |prop: PropertyHandle| match handle.get_property_value(prop) {
+                Ok(Property::Array(arr)) => Ok(arr),
+                Ok(_) => Err("Leaderboard property is not an array".to_string()),
+                Err(e) => Err(e.to_string())
+            };
+            let leaderboard = Leaderboard {
+                number: array(LEADERBOARD_NUMBER)?,
+                driver: array(LEADERBOARD_DRIVER)?,
+                laps: array(LEADERBOARD_LAPS)?,
+                last_lap: array(LEADERBOARD_LAST_LAP)?,
+                best_lap: array(LEADERBOARD_BEST_LAP)?
+            };
+
+            let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+            socket.connect(settings.address.as_str()).map_err(|e| format!("Unable to use address {}: {}", settings.address, e))?;
+            socket.set_read_timeout(Some(RECEIVE_TIMEOUT)).map_err(|e| e.to_string())?;
+
+            let mut client = Client::new(handle.clone(), socket, settings, leaderboard);
+            let running = state.running.clone();
+            let worker = std::thread::spawn(move || client.run(running));
+
+            if let Ok(mut lock) = state.worker.lock() {
+                *lock = Some(worker);
+            }
+        },
+        Message::Lock | Message::Unlock => {
+            // The worker takes the lock itself before updating properties
+        },
+        Message::Shutdown => {
+            if let Some(state) = datarace_plugin_api::macros::get_state!(handle) {
+                state.running.store(false, Ordering::Release);
+
+                let worker = state.worker.lock().ok().and_then(|mut lock| lock.take());
+                if let Some(worker) = worker {
+                    if worker.join().is_err() {
+                        handle.log_error("Broadcasting worker panicked");
+                    }
+                }
+            }
+
+            unsafe { datarace_plugin_api::macros::drop_state_now!(handle) }
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
+
+struct Leaderboard {
+    number: ArrayHandle,
+    driver: ArrayHandle,
+    laps: ArrayHandle,
+    last_lap: ArrayHandle,
+    best_lap: ArrayHandle
+}
+
+struct Entry {
+    number: i32,
+    team: String,
+    drivers: Vec<String>
+}
+
+impl Entry {
+    fn driver(&self, index: u16) -> String {
+        self.drivers.get(index as usize).cloned().unwrap_or_default()
+    }
+}
+
+/// Connection to the game, run on the worker thread
+struct Client {
+    handle: PluginHandle,
+    socket: UdpSocket,
+    settings: Settings,
+    leaderboard: Leaderboard,
+
+    connection_id: Option<i32>,
+    last_register: Option<Instant>,
+    last_received: Instant,
+    last_entry_request: Option<Instant>,
+
+    entries: HashMap<u16, Entry>,
+    focused_car: Option<u16>,
+    session_index: Option<u16>,
+    focused_laps: Option<u16>
+}
+
+impl Client {
+    fn new(handle: PluginHandle, socket: UdpSocket, settings: Settings, leaderboard: Leaderboard) -> Self {
+        Client {
+            handle, socket, settings, leaderboard,
+            connection_id: None,
+            last_register: None,
+            last_received: Instant::now(),
+            last_entry_request: None,
+            entries: HashMap::new(),
+            focused_car: None,
+            session_index: None,
+            focused_laps: None
+        }
+    }
+
+    fn send(&self, data: &[u8]) {
+        // Fails while the game is not running, we retry registering anyway
+        let _ = self.socket.send(data);
+    }
+
+    fn run(&mut self, running: Arc<AtomicBool>) {
+        let mut buf = [0_u8; 2048];
+
+        while running.load(Ordering::Acquire) {
+            if self.connection_id.is_none() && self.last_register.is_none_or(|last| last.elapsed() >= REGISTER_INTERVAL) {
+                self.send(&broadcast::register("DataRace", &self.settings.password, self.settings.update_interval, &self.settings.command_password));
+                self.last_register = Some(Instant::now());
+            }
+
+            match self.socket.recv(&mut buf) {
+                Ok(len) => {
+                    self.last_received = Instant::now();
+
+                    if let Some(msg) = broadcast::parse(&buf[..len]) {
+                        let handle = self.handle.clone();
+                        let _lock = handle.lock_plugin();
+                        self.process(msg);
+                    }
+                },
+                Err(_) => {
+                    // Timeout, or the game is not listening
+                    if self.connection_id.is_some() && self.last_received.elapsed() >= CONNECTION_TIMEOUT {
+                        let handle = self.handle.clone();
+                        let _lock = handle.lock_plugin();
+                        self.disconnect();
+                    }
+                }
+            }
+        }
+
+        if let Some(id) = self.connection_id {
+            self.send(&broadcast::unregister(id));
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.connection_id = None;
+        self.entries.clear();
+        self.focused_car = None;
+        self.session_index = None;
+        self.focused_laps = None;
+
+        self.handle.update_property(CONNECTED, Property::from(false));
+        self.handle.log_info("Disconnected from ACC");
+    }
+
+    fn request_entry_list(&mut self) {
+        let Some(id) = self.connection_id else {
+            return;
+        };
+
+        if self.last_entry_request.is_none_or(|last| last.elapsed() >= ENTRY_LIST_INTERVAL) {
+            self.send(&broadcast::request_entry_list(id));
+            self.last_entry_request = Some(Instant::now());
+        }
+    }
+
+    fn process(&mut self, msg: Inbound) {
+        match msg {
+            Inbound::RegistrationResult { connection_id, success, error } => {
+                if !success {
+                    // We try again, in case the password is changed in the meantime
+                    self.handle.log_error(format!("ACC refused the connection: {}", error));
+                    return;
+                }
+
+                self.connection_id = Some(connection_id);
+                self.handle.update_property(CONNECTED, Property::from(true));
+                self.handle.log_info("Connected to ACC");
+
+                self.send(&broadcast::request_track_data(connection_id));
+                self.request_entry_list();
+            },
+            Inbound::Session(update) => self.process_session(update),
+            Inbound::Car(update) => self.process_car(update),
+            Inbound::EntryList(cars) => {
+                self.entries.retain(|index, _| cars.contains(index));
+                self.handle.update_property(CAR_COUNT, Property::from(cars.len() as i64));
+            },
+            Inbound::Track { name, length } => {
+                self.handle.update_property(TRACK, Property::from(name));
+                self.handle.update_property(TRACK_LENGTH, Property::from(length as i64));
+            },
+            Inbound::EntryCar(car) => self.process_entry(car),
+            Inbound::Event(event) => {
+                let ev = match event {
+                    BroadcastEvent::GreenFlag => GREEN_FLAG,
+                    BroadcastEvent::SessionOver => SESSION_OVER,
+                    BroadcastEvent::Penalty => PENALTY,
+                    BroadcastEvent::Accident => ACCIDENT,
+                    BroadcastEvent::LapCompleted => LAP_COMPLETED,
+                    BroadcastEvent::BestSessionLap => BEST_SESSION_LAP_EVENT,
+                    BroadcastEvent::BestPersonalLap => BEST_PERSONAL_LAP,
+                    BroadcastEvent::Other => return
+                };
+
+                self.handle.trigger_event(ev);
+            }
+        }
+    }
+
+    fn process_session(&mut self, update: SessionUpdate) {
+        if self.session_index.is_some_and(|index| index != update.session_index) {
+            self.handle.mark_session(SessionMarker::Session);
+            self.focused_laps = None;
+        }
+        self.session_index = Some(update.session_index);
+        self.focused_car = u16::try_from(update.focused_car).ok();
+
+        self.handle.update_property(SESSION_TYPE, Property::from(update.session_type));
+        self.handle.update_property(SESSION_PHASE, Property::from(update.phase));
+        self.handle.update_property(SESSION_TIME, Property::from_millis(update.session_time as i64));
+        self.handle.update_property(SESSION_END_TIME, Property::from_millis(update.session_end_time as i64));
+        self.handle.update_property(TIME_OF_DAY, Property::from_millis(update.time_of_day as i64));
+        self.handle.update_property(AMBIENT_TEMP, Property::from(update.ambient_temp as i64));
+        self.handle.update_property(TRACK_TEMP, Property::from(update.track_temp as i64));
+        self.handle.update_property(RAIN, Property::from(update.rain as f64));
+        self.handle.update_property(WETNESS, Property::from(update.wetness as f64));
+        self.handle.update_property(BEST_SESSION_LAP, lap_time(update.best_lap));
+    }
+
+    fn process_car(&mut self, update: CarUpdate) {
+        let Some(entry) = self.entries.get(&update.car_index) else {
+            // A car joined, the update is dropped till we know the car
+            self.request_entry_list();
+            return;
+        };
+
+        let driver = entry.driver(update.driver_index);
+
+        if let Some(index) = (update.position as usize).checked_sub(1).filter(|index| *index < MAX_CARS) {
+            self.leaderboard.number.set(&self.handle, index, Property::from(entry.number as i64));
+            self.leaderboard.driver.set(&self.handle, index, Property::from(driver.as_str()));
+            self.leaderboard.laps.set(&self.handle, index, Property::from(update.laps as i64));
+            self.leaderboard.last_lap.set(&self.handle, index, lap_time(update.last_lap));
+            self.leaderboard.best_lap.set(&self.handle, index, lap_time(update.best_lap));
+        }
+
+        if self.focused_car != Some(update.car_index) {
+            return;
+        }
+
+        self.handle.update_property(CAR_INDEX, Property::from(update.car_index as i64));
+        self.handle.update_property(CAR_POSITION, Property::from(update.position as i64));
+        self.handle.update_property(CAR_GEAR, Property::from(update.gear));
+        self.handle.update_property(CAR_SPEED, Property::from(update.speed as i64));
+        self.handle.update_property(CAR_LOCATION, Property::from(update.location));
+        self.handle.update_property(CAR_LAPS, Property::from(update.laps as i64));
+        self.handle.update_property(CAR_SPLINE, Property::from(update.spline_position as f64));
+        self.handle.update_property(CAR_DELTA, Property::from_millis(update.delta as i64));
+        self.handle.update_property(CAR_LAP_TIME, lap_time(update.current_lap));
+        self.handle.update_property(CAR_LAP_INVALID, Property::from(update.current_lap.invalid));
+        self.handle.update_property(CAR_LAST_LAP, lap_time(update.last_lap));
+        self.handle.update_property(CAR_BEST_LAP, lap_time(update.best_lap));
+        self.handle.update_property(CAR_NUMBER, Property::from(entry.number as i64));
+        self.handle.update_property(CAR_DRIVER, Property::from(driver));
+        self.handle.update_property(CAR_TEAM, Property::from(entry.team.as_str()));
+
+        if self.focused_laps.is_some_and(|laps| update.laps > laps) {
+            self.handle.mark_session(SessionMarker::Lap);
+        }
+        self.focused_laps = Some(update.laps);
+    }
+
+    fn process_entry(&mut self, car: EntryCar) {
+        let drivers = car.drivers.iter().map(|driver| {
+            if driver.first_name.is_empty() && driver.last_name.is_empty() {
+                driver.short_name.clone()
+            } else {
+                format!("{} {}", driver.first_name, driver.last_name).trim().to_string()
+            }
+        }).collect();
+
+        self.entries.insert(car.car_index, Entry { number: car.race_number, team: car.team_name, drivers });
+    }
+}
+
+/// Laps without a time (like the best lap before finishing one) are 0
+fn lap_time(lap: Lap) -> Property {
+    Property::from_millis(lap.time.unwrap_or_default() as i64)
+}
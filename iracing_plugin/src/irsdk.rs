@@ -0,0 +1,294 @@
+// Reads the telemetry iRacing publishes through shared memory (the irsdk).
+//
+// The memory starts with a header, followed by the descriptions of all variables (name, type and
+// offset) and a ring of (usually 3) buffers holding the values, with the tick count telling which
+// one is the newest. The simulator writes the next buffer while we copy the newest, so we check
+// the tick count did not change during the copy.
+// As the memory changes at any time it is never borrowed as a slice, only copied out of.
+//
+// Only opening the memory is windows specific, on other systems iRacing is never connected.
+
+use std::{collections::HashMap, sync::atomic::{fence, Ordering}};
+
+const HEADER_SIZE: usize = 112;
+const VAR_HEADER_SIZE: usize = 144;
+const VAR_NAME_OFFSET: usize = 16;
+const VAR_NAME_LEN: usize = 32;
+const MAX_BUFS: usize = 4;
+/// Bit in the status of the header set while a session is running
+const STATUS_CONNECTED: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VarType {
+    Char,
+    Bool,
+    Int,
+    BitField,
+    Float,
+    Double
+}
+
+impl VarType {
+    fn from_raw(value: i32) -> Option<Self> {
+        Some(match value {
+            0 => VarType::Char,
+            1 => VarType::Bool,
+            2 => VarType::Int,
+            3 => VarType::BitField,
+            4 => VarType::Float,
+            5 => VarType::Double,
+            _ => return None
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            VarType::Char | VarType::Bool => 1,
+            VarType::Int | VarType::BitField | VarType::Float => 4,
+            VarType::Double => 8
+        }
+    }
+}
+
+/// Where a variable is found in the value buffer
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Var {
+    sort: VarType,
+    offset: usize,
+    /// Number of entries, more then 1 for arrays (like the values per car)
+    pub(crate) count: usize
+}
+
+fn read_i32(mem: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(mem.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// The values of a single tick, copied out of the shared memory
+pub(crate) struct Sample {
+    data: Vec<u8>,
+    pub(crate) tick: i32
+}
+
+impl Sample {
+    fn raw(&self, var: &Var, index: usize) -> Option<&[u8]> {
+        if index >= var.count {
+            return None;
+        }
+
+        let start = var.offset + index * var.sort.size();
+        self.data.get(start..start + var.sort.size())
+    }
+
+    /// Numbers (and booleans as 0/1) as float
+    pub(crate) fn f64(&self, var: &Var, index: usize) -> Option<f64> {
+        let raw = self.raw(var, index)?;
+
+        Some(match var.sort {
+            VarType::Float => f32::from_le_bytes(raw.try_into().ok()?) as f64,
+            VarType::Double => f64::from_le_bytes(raw.try_into().ok()?),
+            _ => self.i64(var, index)? as f64
+        })
+    }
+
+    /// Integers, bitfields, chars and booleans, floats are truncated
+    pub(crate) fn i64(&self, var: &Var, index: usize) -> Option<i64> {
+        let raw = self.raw(var, index)?;
+
+        Some(match var.sort {
+            VarType::Char | VarType::Bool => raw[0] as i64,
+            VarType::Int => i32::from_le_bytes(raw.try_into().ok()?) as i64,
+            VarType::BitField => u32::from_le_bytes(raw.try_into().ok()?) as i64,
+            VarType::Float | VarType::Double => self.f64(var, index)? as i64
+        })
+    }
+
+    pub(crate) fn bool(&self, var: &Var, index: usize) -> Option<bool> {
+        Some(self.i64(var, index)? != 0)
+    }
+}
+
+/// The parts of the header we need, and the variables
+pub(crate) struct Layout {
+    vars: HashMap<String, Var>,
+    buf_len: usize,
+    /// Offset of the header of the variables, if this changes the layout has to be read again
+    var_header_offset: i32,
+    num_vars: i32
+}
+
+impl Layout {
+    /// Reads the variables listed in the header
+    fn read(tel: &Telemetry, mem_header: &[u8]) -> Option<Self> {
+        let num_vars = read_i32(mem_header, 24)?;
+        let var_header_offset = read_i32(mem_header, 28)?;
+        let buf_len = usize::try_from(read_i32(mem_header, 36)?).ok()?;
+
+        let count = usize::try_from(num_vars).ok()?;
+        let var_headers = tel.copy(usize::try_from(var_header_offset).ok()?, count.checked_mul(VAR_HEADER_SIZE)?)?;
+
+        let mut vars = HashMap::new();
+        for i in 0..count {
+            let start = i * VAR_HEADER_SIZE;
+            let header = var_headers.get(start..start + VAR_HEADER_SIZE)?;
+
+            let (Some(sort), Some(offset), Some(count)) = (
+                read_i32(header, 0).and_then(VarType::from_raw),
+                read_i32(header, 4).and_then(|o| usize::try_from(o).ok()),
+                read_i32(header, 8).and_then(|c| usize::try_from(c).ok())
+            ) else {
+                continue;
+            };
+
+            let name = &header[VAR_NAME_OFFSET..VAR_NAME_OFFSET + VAR_NAME_LEN];
+            let name = String::from_utf8_lossy(name.split(|c| *c == 0).next().unwrap_or_default()).to_string();
+
+            vars.insert(name, Var { sort, offset, count });
+        }
+
+        Some(Layout { vars, buf_len, var_header_offset, num_vars })
+    }
+
+    pub(crate) fn var(&self, name: &str) -> Option<&Var> {
+        self.vars.get(name)
+    }
+}
+
+/// Returns the connected status, and the tick and offset of the newest buffer
+fn newest_buffer(mem: &[u8]) -> Option<(bool, i32, usize)> {
+    let connected = read_i32(mem, 4)? & STATUS_CONNECTED != 0;
+    let num_buf = usize::try_from(read_i32(mem, 32)?).ok()?.min(MAX_BUFS);
+
+    let (tick, offset) = (0..num_buf)
+        .filter_map(|i| Some((read_i32(mem, 48 + i * 16)?, read_i32(mem, 52 + i * 16)?)))
+        .max_by_key(|(tick, _)| *tick)?;
+
+    Some((connected, tick, usize::try_from(offset).ok()?))
+}
+
+/// Copies the newest values, None if the session is not running (or the memory is not valid)
+pub(crate) fn read_sample(tel: &Telemetry, layout: &mut Option<Layout>) -> Option<Sample> {
+    let mut header = tel.copy(0, HEADER_SIZE)?;
+
+    // The layout changes when a new session (with different variables) is loaded
+    let outdated = layout.as_ref().is_none_or(|l| Some(l.var_header_offset) != read_i32(&header, 28) || Some(l.num_vars) != read_i32(&header, 24));
+    if outdated {
+        *layout = Layout::read(tel, &header);
+    }
+    let buf_len = layout.as_ref()?.buf_len;
+
+    // The simulator could have moved on to writing this buffer while we copied it
+    for _ in 0..2 {
+        let (connected, tick, offset) = newest_buffer(&header)?;
+        if !connected {
+            return None;
+        }
+
+        let data = tel.copy(offset, buf_len)?;
+        // The header has to be read again only after the values were copied
+        fence(Ordering::Acquire);
+        header = tel.copy(0, HEADER_SIZE)?;
+        if newest_buffer(&header).is_some_and(|(_, after, _)| after == tick) {
+            return Some(Sample { data, tick });
+        }
+    }
+
+    None
+}
+
+/// The shared memory of iRacing, opened while the simulator is running
+#[cfg(windows)]
+pub(crate) struct Telemetry {
+    mapping: windows_sys::Win32::Foundation::HANDLE,
+    view: windows_sys::Win32::System::Memory::MEMORY_MAPPED_VIEW_ADDRESS,
+    len: usize,
+    data_valid: windows_sys::Win32::Foundation::HANDLE
+}
+
+#[cfg(windows)]
+impl Telemetry {
+    /// None if iRacing is not running
+    pub(crate) fn open() -> Option<Self> {
+        use windows_sys::Win32::{Foundation::CloseHandle, System::{Memory::{MapViewOfFile, VirtualQuery, FILE_MAP_READ, MEMORY_BASIC_INFORMATION}, Threading::{OpenEventW, SYNCHRONIZATION_SYNCHRONIZE}}};
+
+        let wide = |text: &str| text.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+
+        unsafe {
+            let mapping = windows_sys::Win32::System::Memory::OpenFileMappingW(FILE_MAP_READ, 0, wide("Local\\IRSDKMemMapFileName").as_ptr());
+            if mapping == 0 {
+                return None;
+            }
+
+            let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+            if view.Value.is_null() {
+                CloseHandle(mapping);
+                return None;
+            }
+
+            // The size of the view is not part of the header, so we ask how much was mapped
+            let mut info: MEMORY_BASIC_INFORMATION = std::mem::zeroed();
+            let len = if VirtualQuery(view.Value, &mut info, std::mem::size_of::<MEMORY_BASIC_INFORMATION>()) != 0 {
+                info.RegionSize
+            } else {
+                HEADER_SIZE
+            };
+
+            let data_valid = OpenEventW(SYNCHRONIZATION_SYNCHRONIZE, 0, wide("Local\\IRSDKDataValidEvent").as_ptr());
+
+            Some(Telemetry { mapping, view, len, data_valid })
+        }
+    }
+
+    /// Waits (up to the timeout in ms) for the simulator to write new values
+    pub(crate) fn wait(&self, timeout: u32) {
+        if self.data_valid != 0 {
+            unsafe { windows_sys::Win32::System::Threading::WaitForSingleObject(self.data_valid, timeout) };
+        } else {
+            std::thread::sleep(std::time::Duration::from_millis(timeout as u64));
+        }
+    }
+
+    /// Copies len bytes at offset out of the memory, None if they are not within it
+    fn copy(&self, offset: usize, len: usize) -> Option<Vec<u8>> {
+        if offset.checked_add(len)? > self.len {
+            return None;
+        }
+
+        let mut buf = vec![0u8; len];
+        unsafe { std::ptr::copy_nonoverlapping(self.view.Value.cast::<u8>().add(offset), buf.as_mut_ptr(), len) };
+        Some(buf)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        use windows_sys::Win32::{Foundation::CloseHandle, System::Memory::UnmapViewOfFile};
+
+        unsafe {
+            UnmapViewOfFile(self.view);
+            CloseHandle(self.mapping);
+            if self.data_valid != 0 {
+                CloseHandle(self.data_valid);
+            }
+        }
+    }
+}
+
+/// iRacing only exists on windows, so it is never running
+#[cfg(not(windows))]
+pub(crate) struct Telemetry;
+
+#[cfg(not(windows))]
+impl Telemetry {
+    pub(crate) fn open() -> Option<Self> {
+        None
+    }
+
+    pub(crate) fn wait(&self, timeout: u32) {
+        std::thread::sleep(std::time::Duration::from_millis(timeout as u64));
+    }
+
+    fn copy(&self, _offset: usize, _len: usize) -> Option<Vec<u8>> {
+        None
+    }
+}
@@ -0,0 +1,220 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::JoinHandle, time::Duration};
+
+use datarace_plugin_api::wrappers::{ArrayHandle, Message, PluginError, PluginHandle, Property, PropertyHandle, SessionMarker};
+
+mod irsdk;
+
+pub(crate) type PluginState = State;
+
+datarace_plugin_api::macros::free_string_fn!();
+
+datarace_plugin_api::macros::plugin_descriptor_fn!("iracing", 0, 1, 0);
+
+// The properties are documented in docs/IRacing.md, units are the ones used by iRacing
+datarace_plugin_api::macros::propertys_initor!{ create_properties, "iracing",
+    (CONNECTED, "connected", false),
+    (SPEED, "speed", 0.0),
+    (RPM, "rpm", 0.0),
+    (GEAR, "gear", 0),
+    (THROTTLE, "throttle", 0.0),
+    (BRAKE, "brake", 0.0),
+    (CLUTCH, "clutch", 0.0),
+    (STEERING, "steering", 0.0),
+    (FUEL, "fuel", 0.0),
+    (LAP, "lap", 0),
+    (LAP_DIST_PCT, "lap_dist_pct", 0.0),
+    (LAP_TIME, "lap_time", Property::from_sec(0.0)),
+    (LAST_LAP_TIME, "last_lap_time", Property::from_sec(0.0)),
+    (BEST_LAP_TIME, "best_lap_time", Property::from_sec(0.0)),
+    (POSITION, "position", 0),
+    (SESSION_TIME, "session_time", Property::from_sec(0.0)),
+    (ON_PIT_ROAD, "on_pit_road", false),
+    (ON_TRACK, "on_track", false),
+    (WATER_TEMP, "water_temp", 0.0),
+    (OIL_TEMP, "oil_temp", 0.0),
+    (CAR_IDX_LAP_DIST_PCT, "car_idx_lap_dist_pct", [0.0; 64]),
+    (CAR_IDX_POSITION, "car_idx_position", [0; 64])
+}
+
+/// How long we wait for new telemetry before checking if we should stop
+const WAIT_TIMEOUT_MS: u32 = 200;
+/// How often we try to open the telemetry while iRacing is not running
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Float,
+    Int,
+    Bool,
+    /// Seconds in the telemetry
+    Duration
+}
+
+/// Which telemetry variable is published in which property
+const VALUES: &[(PropertyHandle, &str, Kind)] = &[
+    (SPEED, "Speed", Kind::Float),
+    (RPM, "RPM", Kind::Float),
+    (GEAR, "Gear", Kind::Int),
+    (THROTTLE, "Throttle", Kind::Float),
+    (BRAKE, "Brake", Kind::Float),
+    (CLUTCH, "Clutch", Kind::Float),
+    (STEERING, "SteeringWheelAngle", Kind::Float),
+    (FUEL, "FuelLevel", Kind::Float),
+    (LAP, "Lap", Kind::Int),
+    (LAP_DIST_PCT, "LapDistPct", Kind::Float),
+    (LAP_TIME, "LapCurrentLapTime", Kind::Duration),
+    (LAST_LAP_TIME, "LapLastLapTime", Kind::Duration),
+    (BEST_LAP_TIME, "LapBestLapTime", Kind::Duration),
+    (POSITION, "PlayerCarPosition", Kind::Int),
+    (SESSION_TIME, "SessionTime", Kind::Duration),
+    (ON_PIT_ROAD, "OnPitRoad", Kind::Bool),
+    (ON_TRACK, "IsOnTrack", Kind::Bool),
+    (WATER_TEMP, "WaterTemp", Kind::Float),
+    (OIL_TEMP, "OilTemp", Kind::Float)
+];
+
+/// Per car values, published as arrays
+const ARRAYS: &[(PropertyHandle, &str, Kind)] = &[
+    (CAR_IDX_LAP_DIST_PCT, "CarIdxLapDistPct", Kind::Float),
+    (CAR_IDX_POSITION, "CarIdxPosition", Kind::Int)
+];
+
+pub(crate) struct State {
+    running: Arc<AtomicBool>,
+    worker: Mutex<Option<JoinHandle<()>>>
+}
+
+#[datarace_plugin_api::macros::plugin_init]
+fn handle_init(handle: PluginHandle) -> Result<PluginState,String> {
+    create_properties(&handle)?;
+
+    Ok(State { running: Arc::new(AtomicBool::new(true)), worker: Mutex::new(None) })
+}
+
+#[datarace_plugin_api::macros::plugin_update]
+fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), PluginError> {
+    match msg {
+        Message::StartupFinished => {
+            let state = datarace_plugin_api::macros::get_state!(handle).ok_or("No state".to_string())?;
+
+            // The arrays are created during init, so we can retrieve their handles now
+            let mut arrays = Vec::new();
+            for (prop, name, kind) in ARRAYS {
+                match handle.get_property_value(*prop) {
+                    Ok(Property::Array(arr)) => arrays.push((arr, *name, *kind)),
+                    Ok(_) => return Err(format!("Property for {} is not an array", name).into()),
+                    Err(e) => return Err(e.to_string().into())
+                }
+            }
+
+            let worker_handle = handle.clone();
+            let running = state.running.clone();
+            let worker = std::thread::spawn(move || run(worker_handle, running, arrays));
+
+            if let Ok(mut lock) = state.worker.lock() {
+                *lock = Some(worker);
+            }
+        },
+        Message::Lock | Message::Unlock => {
+            // The worker takes the lock itself before updating properties
+        },
+        Message::Shutdown => {
+            if let Some(state) = datarace_plugin_api::macros::get_state!(handle) {
+                state.running.store(false, Ordering::Release);
+
+                let worker = state.worker.lock().ok().and_then(|mut lock| lock.take());
+                if let Some(worker) = worker {
+                    if worker.join().is_err() {
+                        handle.log_error("Telemetry worker panicked");
+                    }
+                }
+            }
+
+            unsafe { datarace_plugin_api::macros::drop_state_now!(handle) }
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
+
+/// Worker thread reading the telemetry and publishing it until shutdown
+fn run(handle: PluginHandle, running: Arc<AtomicBool>, arrays: Vec<(ArrayHandle, &str, Kind)>) {
+    let mut telemetry = None;
+    let mut layout = None;
+    let mut connected = false;
+    let mut last_tick = None;
+    let mut last_lap = None;
+
+    while running.load(Ordering::Acquire) {
+        let Some(tel) = telemetry.as_ref() else {
+            telemetry = irsdk::Telemetry::open();
+            if telemetry.is_none() {
+                std::thread::sleep(RECONNECT_INTERVAL);
+            }
+            continue;
+        };
+
+        tel.wait(WAIT_TIMEOUT_MS);
+        let sample = irsdk::read_sample(tel, &mut layout);
+
+        let _lock = handle.lock_plugin();
+        if connected != sample.is_some() {
+            connected = sample.is_some();
+            handle.update_property(CONNECTED, Property::from(connected));
+            handle.log_info(if connected { "Connected to iRacing" } else { "Disconnected from iRacing" });
+        }
+
+        let (Some(sample), Some(layout)) = (sample, layout.as_ref()) else {
+            // iRacing closed (or is still loading), we open the memory again to get the new one
+            telemetry = None;
+            last_tick = None;
+            drop(_lock);
+            std::thread::sleep(RECONNECT_INTERVAL);
+            continue;
+        };
+
+        if last_tick == Some(sample.tick) {
+            continue;
+        }
+        last_tick = Some(sample.tick);
+
+        for (prop, name, kind) in VALUES {
+            let Some(var) = layout.var(name) else {
+                continue;
+            };
+
+            if let Some(value) = read_value(&sample, var, 0, *kind) {
+                handle.update_property(*prop, value);
+            }
+        }
+
+        for (arr, name, kind) in arrays.iter() {
+            let Some(var) = layout.var(name) else {
+                continue;
+            };
+
+            let values: Vec<Property> = (0..var.count.min(arr.len())).filter_map(|i| read_value(&sample, var, i, *kind)).collect();
+            if values.len() == var.count.min(arr.len()) {
+                arr.set_range(&handle, 0, values);
+            }
+        }
+
+        let lap = layout.var("Lap").and_then(|var| sample.i64(var, 0));
+        if let (Some(lap), Some(previous)) = (lap, last_lap) {
+            if lap > previous {
+                handle.mark_session(SessionMarker::Lap);
+            }
+        }
+        last_lap = lap;
+    }
+}
+
+fn read_value(sample: &irsdk::Sample, var: &irsdk::Var, index: usize, kind: Kind) -> Option<Property> {
+    Some(match kind {
+        Kind::Float => Property::from(sample.f64(var, index)?),
+        Kind::Int => Property::from(sample.i64(var, index)?),
+        Kind::Bool => Property::from(sample.bool(var, index)?),
+        Kind::Duration => Property::from_sec(sample.f64(var, index)?)
+    })
+}
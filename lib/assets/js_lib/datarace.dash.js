@@ -20,6 +20,10 @@ function read_string(obj) {
 		return obj.Bool.toString();
 	} else if (obj.Dur != null) {
 		return (obj.Dur / US_PER_SEC).toString();
+	} else if (obj.Time != null) {
+		return format_time(obj, "datetime");
+	} else if (obj.Vector != null) {
+		return obj.Vector.join(", ");
 	} else if (obj.Arr != null && obj.Arr[0] != null) {
 		return read_string(obj.Arr[0]);
 	} else {
@@ -57,6 +61,8 @@ function read_int(obj) {
 		return obj.Bool === true ? 1 : 0;
 	} else if (obj.Dur != null) {
 		return Math.round(obj.Dur / US_PER_SEC);
+	} else if (obj.Time != null) {
+		return Math.round(obj.Time / US_PER_SEC);
 	} else if (obj.Arr != null && obj.Arr[0] != null) {
 		return read_int(obj.Arr[0]);
 	} else {
@@ -80,6 +86,8 @@ function read_float(obj) {
 		return obj.Bool === true ? 1 : 0;
 	} else if (obj.Dur != null) {
 		return obj.Dur / US_PER_SEC;
+	} else if (obj.Time != null) {
+		return obj.Time / US_PER_SEC;
 	} else if (obj.Arr != null && obj.Arr[0] != null) {
 		return read_float(obj.Arr[0]);
 	} else {
@@ -112,6 +120,8 @@ function read_bool(obj) {
 		return obj.Bool;
 	} else if (obj.Dur != null) {
 		return obj.Dur === 1 ? true : false;
+	} else if (obj.Time != null) {
+		return obj.Time === 1 ? true : false;
 	} else if (obj.Arr != null && obj.Arr[0] != null) {
 		return read_bool(obj.Arr[0]);
 	} else {
@@ -139,6 +149,8 @@ function read_dur(obj) {
 		return obj.Bool ? US_PER_SEC : 0;
 	} else if (obj.Dur != null) {
 		return obj.Dur;
+	} else if (obj.Time != null) {
+		return obj.Time;
 	} else if (obj.Arr != null && obj.Arr[0] != null) {
 		return read_dur(obj.Arr[0]);
 	} else {
@@ -147,6 +159,59 @@ function read_dur(obj) {
 	}
 }
 
+/// Returns a js Date, or null if the value is not a Timestamp (or a number of seconds since the epoch)
+function read_time(obj) {
+	if (obj == null) {
+		return null;
+	}
+
+	if (obj.Time != null) {
+		return new Date(obj.Time / 1000);
+	} else if (obj.Int != null) {
+		return new Date(obj.Int * 1000);
+	} else if (obj.Float != null) {
+		return new Date(obj.Float * 1000);
+	} else if (obj.Arr != null && obj.Arr[0] != null) {
+		return read_time(obj.Arr[0]);
+	} else {
+		// None or any other type
+		return null;
+	}
+}
+
+/// Returns the Vector as object with x, y and z, or null if the value is not a Vector
+function read_vector(obj) {
+	if (obj == null) {
+		return null;
+	}
+
+	if (obj.Vector != null) {
+		return { x: obj.Vector[0], y: obj.Vector[1], z: obj.Vector[2] };
+	} else if (obj.Arr != null && obj.Arr[0] != null) {
+		return read_vector(obj.Arr[0]);
+	} else {
+		// None or any other type
+		return null;
+	}
+}
+
+/// Formats the Timestamp in the local time zone and language of the browser.
+/// mode is "date", "time" or "datetime"
+function format_time(obj, mode) {
+	const date = read_time(obj);
+	if (date == null) {
+		return "";
+	}
+
+	if (mode === "date") {
+		return date.toLocaleDateString();
+	} else if (mode === "time") {
+		return date.toLocaleTimeString();
+	} else {
+		return date.toLocaleString();
+	}
+}
+
 function read_arr(obj, index) {
 	if (obj == null) {
 		return null;
@@ -175,6 +240,15 @@ function read_arr(obj, index) {
 		return obj;
 	} else if (obj.Dur != null) {
 		return obj;
+	} else if (obj.Time != null) {
+		return obj;
+	} else if (obj.Vector != null) {
+		// Indexing a vector gives the component (0 x, 1 y, 2 z)
+		if (obj.Vector.length > index) {
+			return { Float: obj.Vector[index] };
+		} else {
+			return null;
+		}
 	} else if (obj.Arr != null) {
 		if (obj.Arr.length > index) {
 			return obj.Arr[index];
@@ -187,11 +261,392 @@ function read_arr(obj, index) {
 	}
 }
 
+/// Returns length elements starting at start as an Arr,
+/// with start moved back so the window stays full near the end (useful for windowing around a position).
+/// Str gives the characters, Vector the components, any other single value itself.
+function read_arr_range(obj, start, length) {
+	if (obj == null || isNaN(start) || isNaN(length) || length <= 0) {
+		return { Arr: [] };
+	}
+
+	var list;
+	if (obj.Str != null) {
+		list = Array.from(obj.Str, function(c) { return { Str: c }; });
+	} else if (obj.Vector != null) {
+		list = obj.Vector.map(function(v) { return { Float: v }; });
+	} else if (obj.Arr != null) {
+		list = obj.Arr;
+	} else if (obj.Int != null || obj.Float != null || obj.Bool != null || obj.Dur != null || obj.Time != null) {
+		list = [obj];
+	} else {
+		// None or any other type
+		return { Arr: [] };
+	}
+
+	start = Math.max(0, Math.min(start, list.length - length));
+	return { Arr: list.slice(start, start + length) };
+}
+
+/// Reads every element of an Arr as string and joins them with the separator
+function join_arr(obj, separator) {
+	if (obj == null || obj.Arr == null) {
+		return read_string(obj);
+	}
+
+	return obj.Arr.map(read_string).join(separator);
+}
+
+/// Reads a parameter from the query of the page, undefined if it is missing or not one of the allowed values
+/// (so it is left out when send to the server)
+function query_choice(name, allowed) {
+	const value = new URLSearchParams(window.location.search).get(name);
+	return allowed.includes(value) ? value : undefined;
+}
+
+/// Decodes the binary update frame (see encode_binary in web/socket.rs)
+/// into the same [key, value] array the json update event delivers
+function decode_update_bin(buffer) {
+	const view = new DataView(buffer);
+	const decoder = new TextDecoder();
+	let offset = 0;
+
+	function read_value() {
+		const tag = view.getUint8(offset);
+		offset += 1;
+
+		switch (tag) {
+			case 1:
+				offset += 8;
+				return { Int: Number(view.getBigInt64(offset - 8, true)) };
+			case 2:
+				offset += 8;
+				return { Float: view.getFloat64(offset - 8, true) };
+			case 3:
+				offset += 1;
+				return { Bool: view.getUint8(offset - 1) !== 0 };
+			case 4: {
+				const len = view.getUint32(offset, true);
+				offset += 4 + len;
+				return { Str: decoder.decode(new Uint8Array(buffer, offset - len, len)) };
+			}
+			case 5:
+				offset += 8;
+				return { Dur: Number(view.getBigInt64(offset - 8, true)) };
+			case 6: {
+				const count = view.getUint32(offset, true);
+				offset += 4;
+				const arr = [];
+				for (let i = 0; i < count; i++) {
+					arr.push(read_value());
+				}
+				return { Arr: arr };
+			}
+			case 7: {
+				const count = view.getUint32(offset, true);
+				offset += 4;
+				const changes = [];
+				for (let i = 0; i < count; i++) {
+					const index = view.getUint32(offset, true);
+					offset += 4;
+					changes.push([index, read_value()]);
+				}
+				return { ArrUpdate: changes };
+			}
+			case 8:
+				offset += 8;
+				return { Time: Number(view.getBigInt64(offset - 8, true)) };
+			case 9:
+				offset += 24;
+				return { Vector: [view.getFloat64(offset - 24, true), view.getFloat64(offset - 16, true), view.getFloat64(offset - 8, true)] };
+			default:
+				return "None";
+		}
+	}
+
+	const list = [];
+	while (offset < buffer.byteLength) {
+		const plugin = view.getBigUint64(offset, true);
+		const property = view.getBigUint64(offset + 8, true);
+		offset += 16;
+
+		list.push([plugin + "|" + property, read_value()]);
+	}
+
+	return list;
+}
+
+/// Draws a Trace dashboard element onto the canvas.
+/// The previous positions are stored on the canvas, a position is only added if it changed
+function draw_trace(canvas, pos, options) {
+	if (canvas.trace == null) {
+		canvas.trace = [];
+	}
+
+	if (pos != null) {
+		const point = options.plane === "XZ" ? [pos.x, pos.z] : [pos.x, pos.y];
+		const last = canvas.trace[canvas.trace.length - 1];
+
+		if (last == null || last[0] !== point[0] || last[1] !== point[1]) {
+			canvas.trace.push(point);
+			while (canvas.trace.length > options.length + 1) {
+				canvas.trace.shift();
+			}
+		}
+	}
+
+	// Keeping the resolution in line with the scaled size of the element
+	if (canvas.width !== canvas.clientWidth || canvas.height !== canvas.clientHeight) {
+		canvas.width = canvas.clientWidth;
+		canvas.height = canvas.clientHeight;
+	}
+
+	const ctx = canvas.getContext("2d");
+	ctx.clearRect(0, 0, canvas.width, canvas.height);
+	if (canvas.trace.length === 0) {
+		return;
+	}
+
+	const [min_x, min_y, max_x, max_y] = options.bounds;
+	function to_canvas(point) {
+		// y on the canvas goes down, so it is flipped
+		return [(point[0] - min_x) / (max_x - min_x) * canvas.width, (1 - (point[1] - min_y) / (max_y - min_y)) * canvas.height];
+	}
+
+	ctx.strokeStyle = options.color;
+	ctx.fillStyle = options.color;
+	ctx.lineWidth = 2;
+
+	ctx.beginPath();
+	canvas.trace.forEach(function(point, index) {
+		const [x, y] = to_canvas(point);
+		if (index === 0) {
+			ctx.moveTo(x, y);
+		} else {
+			ctx.lineTo(x, y);
+		}
+	});
+	ctx.stroke();
+
+	const [x, y] = to_canvas(canvas.trace[canvas.trace.length - 1]);
+	ctx.beginPath();
+	ctx.arc(x, y, 4, 0, 2 * Math.PI);
+	ctx.fill();
+}
+
+/// Updates a Bar element (see Bar in web/dashboard.rs), div is the fill, which covers the whole element
+/// and is clipped down to the value
+function draw_bar(div, value, options) {
+	if (isNaN(value)) {
+		value = options.min;
+	}
+
+	const range = options.max - options.min;
+	const fraction = range === 0 ? 0 : Math.min(Math.max((value - options.min) / range, 0), 1);
+	const hidden = ((1 - fraction) * 100) + '%';
+	div.style.clipPath = options.vertical ? 'inset(' + hidden + ' 0 0 0)' : 'inset(0 ' + hidden + ' 0 0)';
+
+	if (options.gradient && options.stops.length > 1) {
+		if (div.style.backgroundImage === '') {
+			const stops = options.stops.map(function(stop) {
+				const pos = range === 0 ? 0 : (stop.value - options.min) / range * 100;
+				return stop.color + ' ' + pos + '%';
+			});
+			div.style.backgroundImage = 'linear-gradient(' + (options.vertical ? 'to top' : 'to right') + ', ' + stops.join(', ') + ')';
+		}
+	} else {
+		let color = options.stops.length > 0 ? options.stops[0].color : '';
+		options.stops.forEach(function(stop) {
+			if (value >= stop.value) {
+				color = stop.color;
+			}
+		});
+		div.style.backgroundColor = color;
+	}
+
+	const blink = options.blink_above != null && value > options.blink_above;
+	if (blink && div.blink == null) {
+		div.blink = div.animate({ opacity: [1, 0] }, { duration: 250, iterations: Infinity, direction: 'alternate', easing: 'steps(1)' });
+	} else if (!blink && div.blink != null) {
+		div.blink.cancel();
+		div.blink = null;
+	}
+}
+
+/// Shows or hides the element with the transition of the element (see Transition in web/dashboard.rs).
+/// effect is fade, slide-up, slide-down, slide-left or slide-right, duration in ms.
+/// Only changes are animated, the first call applies the visibility directly
+function set_visible(el, visible, effect, duration) {
+	if (el.visible === visible) {
+		return;
+	}
+	const first = el.visible == null;
+	el.visible = visible;
+
+	if (el.transition != null) {
+		el.transition.cancel();
+		el.transition = null;
+	}
+	if (first) {
+		el.style.display = visible ? 'block' : 'none';
+		return;
+	}
+
+	const offsets = { 'slide-up': 'translateY(100%)', 'slide-down': 'translateY(-100%)', 'slide-left': 'translateX(100%)', 'slide-right': 'translateX(-100%)' };
+	const hidden = effect === 'fade' ? { opacity: 0 } : { transform: offsets[effect] };
+	const shown = effect === 'fade' ? { opacity: el.style.opacity === '' ? 1 : el.style.opacity } : { transform: 'none' };
+
+	el.style.display = 'block';
+	el.transition = el.animate(visible ? [hidden, shown] : [shown, hidden], { duration: duration, easing: 'ease-out' });
+	el.transition.onfinish = function() {
+		el.transition = null;
+		if (!el.visible) {
+			el.style.display = 'none';
+		}
+	};
+}
+
+/// Greys out the element while any of the properties (handles as send in updates) is in STALE,
+/// so a value that stopped updating does not look current. Uses the filter, opacity is left to the element
+function set_stale(el, props) {
+	const stale = props.some(prop => STALE.has(prop));
+	if (el.stale === stale) {
+		return;
+	}
+	el.stale = stale;
+	el.style.filter = stale ? 'grayscale(1) brightness(0.6)' : '';
+}
+
+/// Blinks the element rate times per second, 0 (or less) stops it
+function set_blink(el, rate) {
+	if (el.blink_rate === rate) {
+		return;
+	}
+	el.blink_rate = rate;
+
+	if (el.blink != null) {
+		el.blink.cancel();
+		el.blink = null;
+	}
+	if (rate > 0) {
+		// visibility, so the opacity of the element stays untouched
+		el.blink = el.animate({ visibility: ['visible', 'hidden'] }, { duration: 500 / rate, iterations: Infinity, direction: 'alternate', easing: 'steps(1)' });
+	}
+}
+
+/// Filter of the event trigger with this index on the element (see EventTrigger in web/dashboard.rs).
+/// With debounce (in ms) only the first trigger of a burst passes, with every only every nth of those
+function event_filter(el, index, debounce, every) {
+	if (el.event_filters == null) {
+		el.event_filters = {};
+	}
+	if (el.event_filters[index] == null) {
+		el.event_filters[index] = { last: null, count: 0 };
+	}
+	const state = el.event_filters[index];
+
+	if (debounce > 0) {
+		const now = performance.now();
+		const quiet = state.last == null || now - state.last >= debounce;
+		state.last = now;
+
+		if (!quiet) {
+			return false;
+		}
+	}
+
+	if (every > 1) {
+		state.count += 1;
+		if (state.count < every) {
+			return false;
+		}
+		state.count = 0;
+	}
+
+	return true;
+}
+
+/// Runs the action of an event trigger (see EventTrigger in web/dashboard.rs) on the element.
+/// flash blinks the element, show and hide override the visibility for the duration (in ms)
+function event_action(el, action, duration) {
+	if (action === 'flash') {
+		el.animate({ opacity: [1, 0] }, { duration: 250, iterations: Math.max(1, Math.round(duration / 250)), direction: 'alternate', easing: 'steps(1)' });
+		return;
+	}
+
+	el.event_display = action === 'show' ? 'block' : 'none';
+	el.style.display = el.event_display;
+	clearTimeout(el.event_timeout);
+	el.event_timeout = setTimeout(function() {
+		el.event_display = null;
+		// Rerunning the update restores the visibility from the properties (without a transition)
+		el.visible = null;
+		if (typeof apply_update === 'function') {
+			apply_update([]);
+		}
+	}, duration);
+}
+
+/// Shows a notification (send by the server with the notification event) as a toast in the top right corner.
+/// It is removed after a few seconds, errors stay longer
+function show_notification(note) {
+	let container = document.getElementById('NOTIFY');
+	if (container == null) {
+		container = document.createElement('div');
+		container.id = 'NOTIFY';
+		container.style.cssText = 'position: fixed; top: 1rem; right: 1rem; z-index: 100; display: flex; flex-direction: column; gap: 0.5rem; max-width: 40vw;';
+		document.body.appendChild(container);
+	}
+
+	const colors = { info: '#3A7BD5', warning: '#E8A317', error: '#D9342B' };
+	const toast = document.createElement('div');
+	toast.style.cssText = 'padding: 0.5rem 1rem; background-color: #F2F2F2EE; font-family: sans-serif; border-left: 0.5rem solid ' + (colors[note.severity] || colors.info) + ';';
+
+	const source = document.createElement('b');
+	source.textContent = note.source + ': ';
+	toast.append(source, document.createTextNode(note.text));
+	container.append(toast);
+
+	setTimeout(function() { toast.remove(); }, note.severity === 'error' ? 15000 : 5000);
+}
+
 /// takes a value and a function, and then runs the value through the function
 function pass_into(value, func) {
 	return func(value);
 }
 
+/// The formatter library for the Named Property (see NamedFormatter in web/dashboard.rs),
+/// takes a DataRace Object and returns a string
+function format_named(obj, name, decimals) {
+	switch (name) {
+		case 'kmh':
+			// m/s
+			return (read_float(obj) * 3.6).toFixed(decimals);
+		case 'mph':
+			return (read_float(obj) * 2.2369363).toFixed(decimals);
+		case 'liters':
+			return read_float(obj).toFixed(decimals);
+		case 'gallons':
+			// US gallons
+			return (read_float(obj) / 3.785411784).toFixed(decimals);
+		case 'celsius':
+			return read_float(obj).toFixed(decimals);
+		case 'fahrenheit':
+			return (read_float(obj) * 1.8 + 32).toFixed(decimals);
+		case 'percent':
+			return (read_float(obj) * 100).toFixed(decimals) + '%';
+		case 'gear':
+			var gear = read_int(obj);
+			if (gear < 0) {
+				return 'R';
+			} else if (gear === 0) {
+				return 'N';
+			}
+			return gear.toString();
+		default:
+			return read_string(obj);
+	}
+}
+
 /// Takes regular value, of unknown type, and parses it
 /// 
 /// This is different from read_bool, which take a DataRace Object
@@ -0,0 +1,73 @@
+use datarace_plugin_api::wrappers::{Message, PluginHandle, Property, PropertyHandle};
+
+// Required by the state macros to know the type of your state
+pub(crate) type PluginState = State;
+
+// This is requires to handle deallocating strings
+datarace_plugin_api::macros::free_string_fn!();
+
+// Generates the required plugin description (name and version)
+datarace_plugin_api::macros::plugin_descriptor_fn!("{{name}}", 0, 1, 0);
+
+// Handles are generated at compile time and stored in constants for cheap access
+const SAMPLE_PROP: PropertyHandle = datarace_plugin_api::macros::generate_property_handle!("{{name}}.sample");
+
+// Settings of your plugin, load them during init
+pub(crate) struct Settings {
+    update_step: i64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { update_step: 1 }
+    }
+}
+
+// Allows you to store data between invocations
+pub(crate) struct State {
+    settings: Settings,
+}
+
+// Called once on startup, the returned State is saved.
+// Returning Err will shutdown the plugin
+#[datarace_plugin_api::macros::plugin_init]
+fn handle_init(handle: PluginHandle) -> Result<PluginState, String> {
+    let settings = Settings::default();
+
+    handle.create_property("sample", SAMPLE_PROP, Property::Int(0))
+        .to_result().map_err(|e| e.to_string())?;
+
+    Ok(State { settings })
+}
+
+// Called for every message the plugin receives.
+// Returning Err will shutdown the plugin
+#[datarace_plugin_api::macros::plugin_update]
+fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
+    match msg {
+        Message::StartupFinished => {
+            // All init related Messages are processed, good point to spin up worker threads
+            let state = datarace_plugin_api::macros::get_state!(handle).ok_or("No state".to_string())?;
+
+            if let Ok(Property::Int(val)) = handle.get_property_value(SAMPLE_PROP) {
+                handle.update_property(SAMPLE_PROP, Property::Int(val + state.settings.update_step))
+                    .to_result().map_err(|e| e.to_string())?;
+            }
+
+            handle.log_info("Startup finished");
+        },
+        Message::Lock => {
+            // Stop reading from the handle (including in worker threads) till Unlock
+        },
+        Message::Unlock => {
+            // Write operations are done, you can resume
+        },
+        Message::Shutdown => {
+            // Save your settings here, then the state is dropped
+            unsafe { datarace_plugin_api::macros::drop_state_now!(handle) }
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
@@ -0,0 +1,286 @@
+//! Statistics over properties configured by the user (like the max speed this lap),
+//! published as core properties under datarace.aggregate.{name}
+//!
+//! The source properties are sampled at a fixed rate, resets are triggered by events
+//! (like a lap being completed).
+
+use std::{collections::VecDeque, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc}, time::{Duration, Instant}};
+
+use hashbrown::HashSet;
+use kanal::{AsyncReceiver, AsyncSender};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use crate::{datastore::DataStore, events::EventMessage, pluginloader::LoaderMessage, system, utils::{ValueCache, ValueContainer}, EventFilter, EventHandle, PropertyHandle};
+
+/// How often the source properties are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Window used by derivative if none is set
+const DEFAULT_DERIVATIVE_WINDOW: f64 = 1.0;
+
+/// Samples kept and allocated over all aggregates, updated every sample
+static HISTORY_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+static HISTORY_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+/// Samples and bytes held by the history buffers of the aggregates
+pub(crate) fn history_usage() -> (usize, usize) {
+    let capacity = HISTORY_CAPACITY.load(Ordering::Acquire);
+    (HISTORY_SAMPLES.load(Ordering::Acquire), capacity * std::mem::size_of::<(Instant, f64)>())
+}
+
+/// An entry of aggregates in the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct AggregateConfig {
+    /// Published as datarace.aggregate.{name}
+    name: String,
+    /// Full name of the property the statistic is calculated over
+    source: String,
+    function: AggregateFunction,
+    /// Seconds of history that are used, if not set everything since the last reset is used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    window: Option<f64>,
+    /// Full name of the event resetting the statistic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reset: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AggregateFunction {
+    Min,
+    Max,
+    Avg,
+    /// Change per second
+    Derivative
+}
+
+/// Validates every aggregate, and that no two of them publish the same property
+pub(crate) fn validate_all(list: &[AggregateConfig]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for aggregate in list {
+        aggregate.validate()?;
+        // Property names are not case sensitive
+        if !names.insert(aggregate.name.to_lowercase()) {
+            return Err(format!("aggregate name {} is used more then once", aggregate.name));
+        }
+    }
+
+    Ok(())
+}
+
+impl AggregateConfig {
+    fn property_name(&self) -> String {
+        format!("aggregate.{}", self.name)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains('.') {
+            return Err(format!("aggregate name {} has to be non empty and can not contain a dot", self.name));
+        }
+        if PropertyHandle::new(self.source.as_str()).is_none() {
+            return Err(format!("aggregate {}: source {} is not a valid property name", self.name, self.source));
+        }
+        if let Some(window) = self.window {
+            // try_from also rejects inf and windows too long for a Duration
+            if window.is_nan() || window <= 0.0 || Duration::try_from_secs_f64(window).is_err() {
+                return Err(format!("aggregate {}: window has to be more then 0 seconds (and finite)", self.name));
+            }
+        }
+        if let Some(reset) = self.reset.as_ref() {
+            if EventHandle::new(reset.as_str()).is_none() {
+                return Err(format!("aggregate {}: reset {} is not a valid event name", self.name, reset));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Aggregate {
+    source: PropertyHandle,
+    function: AggregateFunction,
+    /// None accumulates everything since the last reset
+    window: Option<Duration>,
+    reset: Option<EventHandle>,
+    output: Arc<AtomicU64>,
+
+    /// Samples within the window (only used with a window)
+    samples: VecDeque<(Instant, f64)>,
+    /// Used without a window
+    total: f64,
+    count: u64,
+    extreme: Option<f64>
+}
+
+impl Aggregate {
+    fn new(config: &AggregateConfig, output: Arc<AtomicU64>) -> Option<Self> {
+        let window = match (config.window, config.function) {
+            (Some(window), _) => Some(window),
+            (None, AggregateFunction::Derivative) => Some(DEFAULT_DERIVATIVE_WINDOW),
+            (None, _) => None
+        };
+
+        Some(Aggregate {
+            source: PropertyHandle::new(config.source.as_str())?,
+            function: config.function,
+            window: window.map(Duration::try_from_secs_f64).transpose().ok()?,
+            reset: config.reset.as_ref().and_then(|reset| EventHandle::new(reset.as_str())),
+            output,
+            samples: VecDeque::new(),
+            total: 0.0,
+            count: 0,
+            extreme: None
+        })
+    }
+
+    fn add_sample(&mut self, now: Instant, value: f64) {
+        if let Some(window) = self.window {
+            self.samples.push_back((now, value));
+            while self.samples.front().is_some_and(|(time, _)| now.duration_since(*time) > window) {
+                self.samples.pop_front();
+            }
+        } else {
+            self.total += value;
+            self.count += 1;
+            self.extreme = Some(match (self.function, self.extreme) {
+                (AggregateFunction::Min, Some(ex)) => ex.min(value),
+                (AggregateFunction::Max, Some(ex)) => ex.max(value),
+                _ => value
+            });
+        }
+    }
+
+    fn result(&self) -> Option<f64> {
+        if self.window.is_none() {
+            return match self.function {
+                AggregateFunction::Avg if self.count > 0 => Some(self.total / self.count as f64),
+                AggregateFunction::Avg => None,
+                _ => self.extreme
+            };
+        }
+
+        let values = self.samples.iter().map(|(_, val)| *val);
+        match self.function {
+            AggregateFunction::Min => values.reduce(f64::min),
+            AggregateFunction::Max => values.reduce(f64::max),
+            AggregateFunction::Avg if !self.samples.is_empty() => Some(values.sum::<f64>() / self.samples.len() as f64),
+            AggregateFunction::Avg => None,
+            AggregateFunction::Derivative => {
+                let ((first_time, first), (last_time, last)) = (self.samples.front()?, self.samples.back()?);
+                let secs = last_time.duration_since(*first_time).as_secs_f64();
+
+                if secs > 0.0 {
+                    Some((last - first) / secs)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.total = 0.0;
+        self.count = 0;
+        self.extreme = None;
+    }
+}
+
+pub(crate) struct Aggregates {
+    list: Vec<Aggregate>,
+    sender: AsyncSender<LoaderMessage>,
+    receiver: AsyncReceiver<LoaderMessage>
+}
+
+/// Subscribes us to the event, this also works if the event was not created yet
+async fn subscribe_reset(datastore: &'static tokio::sync::RwLock<DataStore>, ev: EventHandle, sender: &AsyncSender<LoaderMessage>) {
+    let channel = datastore.read().await.get_event_channel();
+    if channel.as_async().send(EventMessage::Subscribe(ev, system::CORE_PLUGIN_ID, sender.clone(), EventFilter::default())).await.is_err() {
+        error!("Unable to subscribe to reset event {}|{} of an aggregate", ev.plugin, ev.event);
+    }
+}
+
+/// Creates the properties of the aggregates in the config, has to be called before the plugins
+/// are loaded, so they can subscribe to them during their init
+pub(crate) async fn create_aggregates(datastore: &'static tokio::sync::RwLock<DataStore>) -> Aggregates {
+    let (sender, receiver) = kanal::unbounded_async();
+    let mut list = Vec::new();
+
+    {
+        let mut ds_w = datastore.write().await;
+        for config in ds_w.get_config().get_aggregates().to_vec() {
+            let output = Arc::<AtomicU64>::default();
+
+            if let Some(aggregate) = Aggregate::new(&config, output.clone()) {
+                system::insert_property(&mut ds_w, config.property_name().as_str(), ValueContainer::Float(output));
+                list.push(aggregate);
+            }
+        }
+    }
+
+    let events: HashSet<EventHandle> = list.iter().filter_map(|agg| agg.reset).collect();
+    for ev in events {
+        subscribe_reset(datastore, ev, &sender).await;
+    }
+
+    debug!("Created {} aggregates", list.len());
+    Aggregates { list, sender, receiver }
+}
+
+/// Samples the sources and updates the aggregates till shutdown
+pub(crate) async fn run_aggregates(datastore: &'static tokio::sync::RwLock<DataStore>, mut aggregates: Aggregates) {
+    if aggregates.list.is_empty() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let ds_r = datastore.read().await;
+                if ds_r.get_shutdown_status() {
+                    break;
+                }
+
+                let now = Instant::now();
+                for agg in aggregates.list.iter_mut() {
+                    let Some(cont) = ds_r.get_property_container(&agg.source) else {
+                        continue;
+                    };
+
+                    let mut cache = ValueCache::default();
+                    cont.read_web(&mut cache);
+                    if let Some(value) = cache.value.to_number() {
+                        agg.add_sample(now, value);
+                    }
+
+                    if let Some(res) = agg.result() {
+                        agg.output.store(u64::from_be_bytes(res.to_be_bytes()), Ordering::Release);
+                    }
+                }
+
+                HISTORY_SAMPLES.store(aggregates.list.iter().map(|agg| agg.samples.len()).sum(), Ordering::Release);
+                HISTORY_CAPACITY.store(aggregates.list.iter().map(|agg| agg.samples.capacity()).sum(), Ordering::Release);
+            },
+            msg = aggregates.receiver.recv() => match msg {
+                Ok(LoaderMessage::EventTriggered(ev)) => {
+                    for agg in aggregates.list.iter_mut().filter(|agg| agg.reset == Some(ev)) {
+                        agg.clear();
+                    }
+                },
+                Ok(LoaderMessage::EventUnsubscribed(ev)) => {
+                    // The event was deleted (like the plugin shutting down), we subscribe again
+                    // so resets work once it is created again
+                    subscribe_reset(datastore, ev, &aggregates.sender).await;
+                },
+                Ok(_) => (),
+                Err(_) => break
+            }
+        }
+    }
+
+    debug!("Aggregates stopped");
+}
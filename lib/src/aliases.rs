@@ -0,0 +1,85 @@
+//! Versioned property namespaces: a plugin renaming its properties declares the new version of its
+//! namespace and registers the old names as aliases of the new properties, so dashboards (and rules,
+//! inputs, etc.) written against the old names keep working while new ones use the new names.
+//!
+//! Aliases are resolved wherever the datastore is read by handle. Plugins subscribing to an alias get
+//! nothing, they are expected to move to the new names.
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::{DataStoreReturnCode, PropertyHandle};
+
+#[derive(Debug, Clone)]
+struct Alias {
+    /// Full name of the alias (plugin.property)
+    name: String,
+    target: PropertyHandle,
+    /// Namespace version the alias name belonged to
+    version: u32
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AliasInfo {
+    pub(crate) alias: String,
+    /// Full name of the target, or its ids if it does not exist (yet)
+    pub(crate) target: String,
+    pub(crate) version: u32
+}
+
+/// Kept in the datastore
+#[derive(Debug, Default)]
+pub(crate) struct PropertyAliases {
+    aliases: HashMap<PropertyHandle, Alias>,
+    /// Plugin id -> declared namespace version, plugins that never declared one are on 1
+    namespaces: HashMap<u64, u32>
+}
+
+impl PropertyAliases {
+    pub(crate) fn set_namespace_version(&mut self, plugin: u64, version: u32) {
+        self.namespaces.insert(plugin, version);
+    }
+
+    pub(crate) fn get_namespace_version(&self, plugin: u64) -> u32 {
+        self.namespaces.get(&plugin).copied().unwrap_or(1)
+    }
+
+    /// Registering the same alias again is fine, but it can not be moved to another target
+    pub(crate) fn add(&mut self, handle: PropertyHandle, name: String, target: PropertyHandle, version: u32) -> DataStoreReturnCode {
+        if let Some(existing) = self.aliases.get(&handle) {
+            return if existing.target == target { DataStoreReturnCode::Ok } else { DataStoreReturnCode::AlreadyExists };
+        }
+
+        self.aliases.insert(handle, Alias { name, target, version });
+        DataStoreReturnCode::Ok
+    }
+
+    /// The property the alias points to, None if the handle is not an alias
+    pub(crate) fn resolve(&self, handle: &PropertyHandle) -> Option<PropertyHandle> {
+        self.aliases.get(handle).map(|alias| alias.target)
+    }
+
+    pub(crate) fn get_name(&self, handle: &PropertyHandle) -> Option<&str> {
+        self.aliases.get(handle).map(|alias| alias.name.as_str())
+    }
+
+    /// Removes the aliases and the namespace version of a plugin that stopped
+    pub(crate) fn remove_plugin(&mut self, plugin: u64) {
+        self.aliases.retain(|handle, _| handle.plugin != plugin);
+        self.namespaces.remove(&plugin);
+    }
+
+    /// All aliases sorted by name, the names of the targets are looked up with the passed function
+    pub(crate) fn list(&self, target_name: impl Fn(&PropertyHandle) -> String) -> Vec<AliasInfo> {
+        let mut list = self.aliases.values()
+            .map(|alias| AliasInfo { alias: alias.name.clone(), target: target_name(&alias.target), version: alias.version })
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| a.alias.cmp(&b.alias));
+        list
+    }
+
+    /// Plugin id and version of every plugin that declared a namespace version
+    pub(crate) fn list_namespaces(&self) -> Vec<(u64, u32)> {
+        self.namespaces.iter().map(|(plugin, version)| (*plugin, *version)).collect()
+    }
+}
@@ -1,9 +1,9 @@
-use std::sync::Arc;
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
 
 use libc::{c_char, c_void};
 use log::{debug, error};
 
-use crate::{events::EventMessage, pluginloader::LoaderMessage, utils::{self, VoidPtrWrapper}, ArrayValueHandle, DataStoreReturnCode, EventHandle, Message, PluginDescription, PluginHandle, PluginNameHash, Property, PropertyHandle, PropertyType, ReturnValue, API_VERSION};
+use crate::{api_types::{ARRAY_HANDLE_ALIVE, ARRAY_HANDLE_DROPPED}, callbacks::{self, PropertyCallback}, clock, collisions, events::EventMessage, frames, leaks::{self, Allocation}, pluginloader::{DatastoreChange, LoaderMessage}, stale, utils::{self, VoidPtrWrapper}, ArrayValueHandle, DataStoreReturnCode, EventFilter, EventHandle, Message, NotificationSeverity, PluginDescription, PluginHandle, PluginNameHash, Property, PropertyHandle, PropertyType, ReturnValue, SessionMarker, SubscriptionGroupUpdate, API_VERSION};
 
 
 macro_rules! get_handle {
@@ -59,12 +59,76 @@ macro_rules! get_string {
             return $re;
         }
     };
+    ($ptr:ident, $re: expr, $han:ident, $func:literal) => {
+        if let Some(msg) = utils::get_string($ptr) {
+            msg
+        } else {
+            string_error($ptr, Some($han), $func);
+            return $re;
+        }
+    };
+}
+
+/// Enables extra validation of the api calls, set by strict_ffi in the config
+static STRICT_FFI: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_strict_ffi(enabled: bool) {
+    STRICT_FFI.store(enabled, Ordering::Relaxed);
+}
+
+fn is_strict() -> bool {
+    STRICT_FFI.load(Ordering::Relaxed)
+}
+
+/// Logs misuse of the api found in strict mode, together with the offending plugin
+fn report(han: Option<&PluginHandle>, function: &str, problem: &str) {
+    error!("Strict FFI: Plugin {} {} in {}", han.map(|h| h.name.as_str()).unwrap_or("<unknown>"), problem, function);
+}
+
+/// Logs why a passed in string could not be read, in strict mode with plugin and function
+fn string_error(ptr: *mut c_char, han: Option<&PluginHandle>, function: &str) {
+    if is_strict() {
+        report(han, function, if ptr.is_null() { "passed a null string" } else { "passed a string that is not valid UTF-8" });
+    } else {
+        error!("Passed in String Corrupt");
+    }
+}
+
+/// In strict mode reports handles of properties the plugin does not own (these would otherwise only
+/// be matched by the property part of the handle)
+fn check_own_property(han: &PluginHandle, prop_handle: &PropertyHandle, function: &str) {
+    if is_strict() && prop_handle.plugin != han.id {
+        report(Some(han), function, "passed a property handle belonging to a different plugin");
+    }
+}
+
+/// Returns the array behind the handle, in strict mode also checks that the handle was not dropped yet
+fn get_array<'a>(array_handle: *mut ArrayValueHandle, han: Option<&PluginHandle>, function: &str) -> Option<&'a ArrayValueHandle> {
+    let arr = unsafe { array_handle.as_ref() };
+
+    if is_strict() {
+        match arr.map(|a| a.tag.load(Ordering::Acquire)) {
+            None => report(han, function, "passed a null array handle"),
+            Some(ARRAY_HANDLE_ALIVE) => (),
+            Some(ARRAY_HANDLE_DROPPED) => {
+                report(han, function, "used an array handle after dropping it");
+                return None;
+            },
+            Some(_) => {
+                report(han, function, "passed an invalid array handle");
+                return None;
+            }
+        }
+    }
+
+    arr
 }
 
 /// Creates a new property (queues it for creation).
 ///
 /// It will return errors if the property handle missmatches the name (and the plugin id missmaches
-/// the current plugin name). But it won't detect id collisions.
+/// the current plugin name). Id collisions (a different name with the same hash) are rejected
+/// with AlreadyExists too, but also logged and listed under /collisions.
 /// In general, the property will not immediatly be created, instead sending it to the loader task,
 /// which will through the update function lock the datastore to add it.
 /// But you can't know how much of a backlog the channel going over the the pluginloader, so it
@@ -78,7 +142,7 @@ macro_rules! get_string {
 #[no_mangle]
 pub extern "C" fn create_property(handle: *mut PluginHandle, name: *mut c_char, prop_handle: PropertyHandle, value: Property) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
-    let msg = get_string!(name, DataStoreReturnCode::ParameterCorrupted);
+    let msg = get_string!(name, DataStoreReturnCode::ParameterCorrupted, han, "create_property");
 
     if let Some(prop_hash) = utils::generate_property_name_hash(msg.as_str()) {
         if prop_handle.property != prop_hash || prop_handle.plugin != han.id {
@@ -89,14 +153,20 @@ pub extern "C" fn create_property(handle: *mut PluginHandle, name: *mut c_char,
         return DataStoreReturnCode::ParameterCorrupted;
     }
 
-    if han.properties.contains_key(&prop_handle.property) {
-        // Id is already registered
+    if let Some(existing) = han.properties.get(&prop_handle.property) {
+        // Id is already registered, usually by the same name, but it can also be a hash collision
+        collisions::check_property(han.name.as_str(), prop_handle.property, existing.short_name.as_str(), msg.as_str());
         return DataStoreReturnCode::AlreadyExists;
     }
+    if !han.quota.take_property() {
+        debug!("Plugin {} reached max_properties of its quota", han.name);
+        return DataStoreReturnCode::QuotaExceeded;
+    }
 
     let prop_container = utils::PropertyContainer::new(msg, value, han);
     if let Err(e) = han.sender.send(LoaderMessage::PropertyCreate(prop_handle.property, prop_container)) {
         error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        han.quota.release_property();
         return DataStoreReturnCode::DataCorrupted; // TODO new type for a not total fail error
     }
     
@@ -104,6 +174,125 @@ pub extern "C" fn create_property(handle: *mut PluginHandle, name: *mut c_char,
     DataStoreReturnCode::Ok
 }
 
+/// Creates a new string property that only accepts the allowed values (queues it for creation).
+///
+/// allowed has to be a string array, value has to be one of the allowed values.
+/// Updates with any other string are rejected with TypeMissmatch.
+/// Neither the name, the array nor the value are deallocated, you retain ownership of them.
+#[no_mangle]
+pub extern "C" fn create_enum_property(handle: *mut PluginHandle, name: *mut c_char, prop_handle: PropertyHandle, allowed: *mut ArrayValueHandle, value: *mut c_char) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    let msg = get_string!(name, DataStoreReturnCode::ParameterCorrupted, han, "create_enum_property");
+    let value = get_string!(value, DataStoreReturnCode::ParameterCorrupted, han, "create_enum_property");
+
+    if let Some(prop_hash) = utils::generate_property_name_hash(msg.as_str()) {
+        if prop_handle.property != prop_hash || prop_handle.plugin != han.id {
+            debug!("Create Property Failed due to name {}", msg);
+            return DataStoreReturnCode::ParameterCorrupted;
+        }
+    } else {
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+
+    let allowed = if let Some(allowed) = get_array(allowed, Some(han), "create_enum_property").and_then(|arr| arr.arr.to_strings()) {
+        allowed
+    } else {
+        return DataStoreReturnCode::TypeMissmatch;
+    };
+
+    if let Some(existing) = han.properties.get(&prop_handle.property) {
+        // Id is already registered, usually by the same name, but it can also be a hash collision
+        collisions::check_property(han.name.as_str(), prop_handle.property, existing.short_name.as_str(), msg.as_str());
+        return DataStoreReturnCode::AlreadyExists;
+    }
+    if !han.quota.take_property() {
+        debug!("Plugin {} reached max_properties of its quota", han.name);
+        return DataStoreReturnCode::QuotaExceeded;
+    }
+
+    let prop_container = if let Some(cont) = utils::PropertyContainer::new_enum(msg, allowed, value) {
+        cont
+    } else {
+        han.quota.release_property();
+        return DataStoreReturnCode::ParameterCorrupted;
+    };
+    if let Err(e) = han.sender.send(LoaderMessage::PropertyCreate(prop_handle.property, prop_container)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        han.quota.release_property();
+        return DataStoreReturnCode::DataCorrupted;
+    }
+
+    DataStoreReturnCode::Ok
+}
+
+/// Declares the version of the property names of this plugin (plugins that never declare one are
+/// on 1), 0 is not a valid version.
+///
+/// Bump it when renaming properties, and register the old names with create_property_alias,
+/// so dashboards (and configs) still using the old names keep working.
+#[no_mangle]
+pub extern "C" fn set_property_namespace_version(handle: *mut PluginHandle, version: u32) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    if version == 0 {
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+
+    queue_change(han, DatastoreChange::NamespaceVersion(version))
+}
+
+/// Returns the namespace version any plugin declared (1 if it did not, or is not running),
+/// so consumers can tell which names a plugin provides.
+///
+/// Returns 0 (not a valid version) if the datastore is locked right now, try again later
+#[no_mangle]
+pub extern "C" fn get_property_namespace_version(handle: *mut PluginHandle, plugin: u64) -> u32 {
+    let han = get_handle!(handle, 1);
+
+    match han.datastore.try_read() {
+        Ok(ds) => ds.get_aliases().get_namespace_version(plugin),
+        Err(_) => 0
+    }
+}
+
+/// Registers the name (and handle) of a property of an older namespace version as an alias of one of
+/// your current properties.
+///
+/// Reads of the alias through the datastore (dashboards, rules, inputs, the web api) are served
+/// by the target. Plugins subscribing to the alias are not, they have to use the new names.
+/// Same as create_property the name is without your plugin name, and has to match the handle.
+/// An existing property of that name takes priority over the alias.
+/// The alias is queued, registering the same alias again is fine, moving it to another target fails (and is logged).
+/// The name is not deallocated, that is your job
+#[no_mangle]
+pub extern "C" fn create_property_alias(handle: *mut PluginHandle, name: *mut c_char, alias_handle: PropertyHandle, target: PropertyHandle, version: u32) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    let msg = get_string!(name, DataStoreReturnCode::ParameterCorrupted, han, "create_property_alias");
+
+    if utils::generate_property_name_hash(msg.as_str()) != Some(alias_handle.property) || alias_handle.plugin != han.id {
+        debug!("Create Property Alias Failed due to name {}", msg);
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+    if target.plugin != han.id || target == alias_handle {
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+    if han.properties.contains_key(&alias_handle.property) {
+        return DataStoreReturnCode::AlreadyExists;
+    }
+
+    let name = format!("{}.{}", han.name, msg).to_lowercase();
+    queue_change(han, DatastoreChange::Alias { alias: alias_handle, name, target, version })
+}
+
+/// Changes to the datastore are done by the loader of the plugin, so the api functions never wait on the lock
+fn queue_change(han: &PluginHandle, change: DatastoreChange) -> DataStoreReturnCode {
+    if let Err(e) = han.sender.send(LoaderMessage::Datastore(change)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        DataStoreReturnCode::DataCorrupted
+    } else {
+        DataStoreReturnCode::Ok
+    }
+}
+
 /// Updates the value for the Property behind a given handle
 /// 
 /// You can only use values of the same type as the inital value (except for arrays).
@@ -116,9 +305,15 @@ pub extern "C" fn create_property(handle: *mut PluginHandle, name: *mut c_char,
 #[no_mangle]
 pub extern  "C" fn update_property(handle: *mut PluginHandle, prop_handle: PropertyHandle, value: Property) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    check_own_property(han, &prop_handle, "update_property");
 
     if let Some(entry) = han.properties.get(&prop_handle.property) {
         if entry.update(value, han) {
+            stale::touch(&prop_handle);
+            // Inside a frame the callbacks are called on commit
+            if !frames::defer_callbacks(&prop_handle) {
+                callbacks::notify(&prop_handle, || entry.read_foreign());
+            }
             return DataStoreReturnCode::Ok;
         } else {
             return DataStoreReturnCode::TypeMissmatch;
@@ -128,13 +323,50 @@ pub extern  "C" fn update_property(handle: *mut PluginHandle, prop_handle: Prope
     DataStoreReturnCode::DoesNotExist
 }
 
+/// Sets the max age of your property in ms: if it is not updated within it, the property is marked
+/// stale until the next update, so dashboards can grey out values when the game stopped sending data.
+/// Arrays count as updated when any of their values is written.
+///
+/// The property does not have to be created yet, deleting it removes the max age.
+/// 0 removes the max age. Properties without one are never stale
+#[no_mangle]
+pub extern "C" fn set_property_max_age(handle: *mut PluginHandle, prop_handle: PropertyHandle, max_age_ms: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    check_own_property(han, &prop_handle, "set_property_max_age");
+
+    if prop_handle.plugin != han.id {
+        return DataStoreReturnCode::NotAuthenticated;
+    }
+
+    stale::set_max_age(prop_handle, max_age_ms);
+    DataStoreReturnCode::Ok
+}
+
+/// Checks if the property (of any plugin) was not updated within its max age (see set_property_max_age).
+///
+/// False for properties without a max age and ones that do not exist.
+/// Old names of renamed properties are not resolved while the datastore is locked
+#[no_mangle]
+pub extern "C" fn is_property_stale(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> bool {
+    let han = get_handle!(handle, false);
+
+    if prop_handle.plugin == han.id || han.subscriptions.contains_key(&prop_handle) {
+        return stale::is_stale(&prop_handle);
+    }
+
+    // Could be the old name of a renamed property
+    let target = han.datastore.try_read().ok().and_then(|ds| ds.get_aliases().resolve(&prop_handle)).unwrap_or(prop_handle);
+
+    stale::is_stale(&target)
+}
+
 /// Returns the value for a given property handle that you previously subscribed to (or that you
 /// created)
 #[no_mangle]
 pub extern "C" fn get_property_value(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> ReturnValue<Property> {
     let han = get_handle_val!(handle);
 
-    ReturnValue::from(if prop_handle.plugin == han.id {
+    let res = if prop_handle.plugin == han.id {
         // Values we created are also accessible
         if let Some(cont) = han.properties.get(&prop_handle.property) {
             Ok(cont.read())
@@ -147,7 +379,130 @@ pub extern "C" fn get_property_value(handle: *mut PluginHandle, prop_handle: Pro
         Ok(store.read(false))
     } else {
         Err(DataStoreReturnCode::DoesNotExist)
-    })
+    };
+
+    if let Ok(prop) = &res {
+        leaks::track_property(prop, han.id);
+    }
+    ReturnValue::from(res)
+}
+
+/// Retrieves the value of a property by name, combining generate_property_handle and get_property_value
+///
+/// Same as get_property_value you have to be subscribed to the property (or have created it).
+/// Handy for one-off reads and script bindings, for regular reads store the PropertyHandle instead,
+/// as this has to hash the name every call.
+///
+/// It is your job to deallocate the nullterminating string
+#[no_mangle]
+pub extern "C" fn get_property_value_by_name(handle: *mut PluginHandle, name: *mut c_char) -> ReturnValue<Property> {
+    let prop_handle = generate_property_handle(name);
+    if prop_handle.code != DataStoreReturnCode::Ok {
+        return ReturnValue::new_from_error(prop_handle.code);
+    }
+
+    get_property_value(handle, prop_handle.value)
+}
+
+/// Retrieves the type of any property (you do not have to be subscribed to it)
+///
+/// This allows checking a property before subscribing, instead of pattern matching the first value.
+/// Returns DoesNotExist if there is no such property (yet).
+#[no_mangle]
+pub extern "C" fn get_property_type(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> ReturnValue<PropertyType> {
+    let han = get_handle_val!(handle);
+
+    ReturnValue::from(lookup_property_type(han, &prop_handle).and_then(|sort| sort.ok_or(DataStoreReturnCode::DoesNotExist)))
+}
+
+/// Checks if a property (of any plugin) exists
+///
+/// Properties of other plugins may not exist yet during startup, wait for StartupFinished
+/// (or the OtherPluginStarted for this plugin) before relying on this.
+/// Also false if the datastore is locked right now (get_property_type returns Busy then)
+#[no_mangle]
+pub extern "C" fn property_exists(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> bool {
+    let han = get_handle!(handle, false);
+
+    lookup_property_type(han, &prop_handle).is_ok_and(|sort| sort.is_some())
+}
+
+/// Own and subscribed properties are answered from the handle, anything else from the datastore.
+/// Busy if the datastore is locked, waiting on it inside init or update could deadlock the runtime
+fn lookup_property_type(han: &PluginHandle, prop_handle: &PropertyHandle) -> Result<Option<PropertyType>, DataStoreReturnCode> {
+    if prop_handle.plugin == han.id {
+        return Ok(han.properties.get(&prop_handle.property).map(|cont| cont.get_type()));
+    }
+
+    if let Some(store) = han.subscriptions.get(prop_handle) {
+        return Ok(Some(store.get_type()));
+    }
+
+    let ds = han.datastore.try_read().map_err(|_| DataStoreReturnCode::Busy)?;
+    Ok(ds.get_property_container(prop_handle).map(|cont| cont.get_type()))
+}
+
+/// Looks up the name (plugin.property) of any property handle, for debugging output.
+///
+/// Names are known from the moment a property is created (and kept after it is deleted),
+/// returns null for handles that never belonged to a property (and while the datastore is locked).
+/// The string is yours, deallocate it with deallocate_string
+#[no_mangle]
+pub extern "C" fn lookup_property_name(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> *mut c_char {
+    let han = get_handle!(handle, std::ptr::null_mut());
+
+    let Some(name) = han.datastore.try_read().ok().and_then(|ds| ds.read_property_name(&prop_handle)) else {
+        return std::ptr::null_mut();
+    };
+
+    let ptr = std::ffi::CString::new(name).expect("string is string").into_raw();
+    leaks::track(ptr, han.id, Allocation::Str);
+    ptr
+}
+
+/// Returns the values an enum property accepts as a read only string array.
+///
+/// Returns null if the property does not exist or is not an enum property (or for properties you did not
+/// subscribe to while the datastore is locked).
+/// Same as any other array handle you have to drop it after use.
+#[no_mangle]
+pub extern "C" fn get_property_allowed_values(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> *mut ArrayValueHandle {
+    let han = get_handle!(handle, std::ptr::null_mut());
+
+    let allowed = if prop_handle.plugin == han.id {
+        han.properties.get(&prop_handle.property).and_then(|cont| cont.allowed_values().map(<[String]>::to_vec))
+    } else if let Some(store) = han.subscriptions.get(&prop_handle) {
+        store.allowed_values().map(<[String]>::to_vec)
+    } else {
+        han.datastore.try_read().ok().and_then(|ds| ds.get_property_container(&prop_handle).and_then(|cont| cont.allowed_values().map(<[String]>::to_vec)))
+    };
+
+    let Some(allowed) = allowed else {
+        return std::ptr::null_mut();
+    };
+
+    let arr_handle = ArrayValueHandle::new(Arc::new(utils::ArrayValueContainer::from_strings(allowed)), false);
+
+    let ptr = Box::into_raw(Box::new(arr_handle));
+    leaks::track(ptr, han.id, Allocation::Array);
+    ptr
+}
+
+/// Lists the properties this plugin currently has registered, as a read only string array of their
+/// full names (plugin.property), which can be passed into generate_property_handle
+///
+/// Creates and deletes still waiting in the queue are not reflected yet.
+/// Same as any other array handle you have to drop it after use.
+#[no_mangle]
+pub extern "C" fn list_own_properties(handle: *mut PluginHandle) -> *mut ArrayValueHandle {
+    let han = get_handle!(handle, std::ptr::null_mut());
+
+    let names = han.properties.values().map(|cont| format!("{}.{}", han.name, cont.short_name)).collect();
+    let arr_handle = ArrayValueHandle::new(Arc::new(utils::ArrayValueContainer::from_strings(names)), false);
+
+    let ptr = Box::into_raw(Box::new(arr_handle));
+    leaks::track(ptr, han.id, Allocation::Array);
+    ptr
 }
 
 /// Generates the PropertyHandle for a certain name
@@ -181,6 +536,7 @@ pub extern "C" fn generate_property_handle(name: *mut c_char) -> ReturnValue<Pro
 #[no_mangle]
 pub extern "C" fn delete_property(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    check_own_property(han, &prop_handle, "delete_property");
 
     if prop_handle.plugin == han.id && han.properties.contains_key(&prop_handle.property) {
         if let Err(e) = han.sender.send(LoaderMessage::PropertyDelete(prop_handle.property)) {
@@ -202,6 +558,7 @@ pub extern "C" fn delete_property(handle: *mut PluginHandle, prop_handle: Proper
 #[no_mangle]
 pub extern "C" fn change_property_type(handle: *mut PluginHandle, prop_handle: PropertyHandle, value: Property) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    check_own_property(han, &prop_handle, "change_property_type");
 
     if prop_handle.plugin == han.id && han.properties.contains_key(&prop_handle.property) {
         let cont = utils::ValueContainer::new(value, han);
@@ -228,6 +585,14 @@ pub extern "C" fn change_property_type(handle: *mut PluginHandle, prop_handle: P
 #[no_mangle]
 pub extern "C" fn subscribe_property(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    if is_strict() && prop_handle.plugin != han.id {
+        // If the datastore is locked we skip this check, blocking here could deadlock
+        if let Ok(ds_r) = han.datastore.try_read() {
+            if !ds_r.has_plugin(prop_handle.plugin) {
+                report(Some(han), "subscribe_property", "passed a property handle of a plugin that is not loaded (yet)");
+            }
+        }
+    }
 
     // TODO: Remove ability to subscribe to your own properties, as it is pointless
     
@@ -239,14 +604,44 @@ pub extern "C" fn subscribe_property(handle: *mut PluginHandle, prop_handle: Pro
     }
 }
 
+/// Subscribes to a property (same as subscribe_property), and additionally registers a callback
+/// that is called directly every time the owner updates the value.
+/// This is for low latency consumers, as there is no need to wait for the next read.
+///
+/// Threading rules:
+/// - The callback is run on the thread of the plugin updating the value, so possibly many
+///   threads at once, and while that plugin is locked
+/// - Keep it short, the updating plugin is blocked until it returns
+/// - The passed in string has to be deallocated (and arrays dropped), same as with get_property_value
+/// - user_data is passed to every call, unsubscribing (or replacing the callback) waits for running calls,
+///   so it can be freed afterwards
+///
+/// If the subscription fails later (you receive OperationFailed) the callback is removed again.
+/// Array values are only delivered when the owner replaces the whole array, changes to single
+/// values via set_array_value are not.
+/// Calling this again for the same property replaces the callback, unsubscribe_property removes it.
+#[no_mangle]
+pub extern "C" fn subscribe_property_callback(handle: *mut PluginHandle, prop_handle: PropertyHandle, callback: PropertyCallback, user_data: *mut libc::c_void) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    let res = subscribe_property(handle, prop_handle);
+    if res == DataStoreReturnCode::Ok {
+        callbacks::register(prop_handle, han.id, callback, user_data);
+    }
+    res
+}
+
 /// Removes subscription for a certain property (it will queue it)
 ///
 /// Same as create/change_property/delete, this (after checking that the property was subscribed to) will send a Message to the loader
 /// which locks the plugin to perform the removal. The queue length is unknown, so it can take
 /// multiple locks and unlocks till this action is performed
+///
+/// A callback registered through subscribe_property_callback is removed immediately (after running calls of it returned)
 #[no_mangle]
 pub extern "C" fn unsubscribe_property(handle: *mut PluginHandle, prop_handle: PropertyHandle) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    callbacks::remove(&prop_handle, han.id);
 
     if !han.subscriptions.contains_key(&prop_handle) {
         return DataStoreReturnCode::DoesNotExist;
@@ -260,6 +655,170 @@ pub extern "C" fn unsubscribe_property(handle: *mut PluginHandle, prop_handle: P
     }
 }
 
+/// Creates a subscription group (or more like queues it), returning the id of the group.
+///
+/// Instead of reading every property, all values of the group that changed are delivered in a
+/// single GroupUpdate message every interval (in milliseconds), no message is sent if nothing changed.
+/// The id is generated from the name, so creating a group with the same name again does nothing.
+///
+/// It is your job to deallocate the nullterminating string
+#[no_mangle]
+pub extern "C" fn create_subscription_group(handle: *mut PluginHandle, name: *mut c_char, interval_ms: u64) -> ReturnValue<u64> {
+    let han = get_handle_val!(handle);
+    let msg = get_string!(name, ReturnValue::new_from_error(DataStoreReturnCode::ParameterCorrupted), han, "create_subscription_group");
+
+    let group = if let Some(group) = utils::generate_group_name_hash(msg.as_str()) {
+        group
+    } else {
+        return ReturnValue::new_from_error(DataStoreReturnCode::ParameterCorrupted);
+    };
+    if interval_ms == 0 {
+        return ReturnValue::new_from_error(DataStoreReturnCode::ParameterCorrupted);
+    }
+
+    if let Err(e) = han.sender.send(LoaderMessage::GroupCreate(group, interval_ms)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        return ReturnValue::new_from_error(DataStoreReturnCode::DataCorrupted);
+    }
+
+    ReturnValue::from(Ok(group))
+}
+
+/// Adds a property to a subscription group (queued), subscribing to it if you are not already
+#[no_mangle]
+pub extern "C" fn add_to_subscription_group(handle: *mut PluginHandle, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    if !han.subscriptions.contains_key(&prop_handle) {
+        let res = subscribe_property(handle, prop_handle);
+        if res != DataStoreReturnCode::Ok {
+            return res;
+        }
+    }
+
+    if let Err(e) = han.sender.send(LoaderMessage::GroupAdd(group, prop_handle)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        DataStoreReturnCode::DataCorrupted
+    } else {
+        DataStoreReturnCode::Ok
+    }
+}
+
+/// Removes a property from a subscription group (queued), this does not unsubscribe you
+#[no_mangle]
+pub extern "C" fn remove_from_subscription_group(handle: *mut PluginHandle, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    if let Err(e) = han.sender.send(LoaderMessage::GroupRemove(group, prop_handle)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        DataStoreReturnCode::DataCorrupted
+    } else {
+        DataStoreReturnCode::Ok
+    }
+}
+
+/// Deletes a subscription group (queued), the properties stay subscribed
+#[no_mangle]
+pub extern "C" fn delete_subscription_group(handle: *mut PluginHandle, group: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    if let Err(e) = han.sender.send(LoaderMessage::GroupDelete(group)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        DataStoreReturnCode::DataCorrupted
+    } else {
+        DataStoreReturnCode::Ok
+    }
+}
+
+/// Creates a frame group, returning the id of the group.
+///
+/// Properties in a frame group are published together: values written between begin_frame and
+/// commit_frame are only seen by dashboards and subscription groups once the frame is committed,
+/// so they never show values of different frames side by side.
+/// The id is generated from the name, so creating a group with the same name again does nothing.
+///
+/// It is your job to deallocate the nullterminating string
+#[no_mangle]
+pub extern "C" fn create_frame_group(handle: *mut PluginHandle, name: *mut c_char) -> ReturnValue<u64> {
+    let han = get_handle_val!(handle);
+    let msg = get_string!(name, ReturnValue::new_from_error(DataStoreReturnCode::ParameterCorrupted), han, "create_frame_group");
+
+    let group = if let Some(group) = utils::generate_group_name_hash(msg.as_str()) {
+        group
+    } else {
+        return ReturnValue::new_from_error(DataStoreReturnCode::ParameterCorrupted);
+    };
+
+    frames::create_group(han.id, group);
+    ReturnValue::from(Ok(group))
+}
+
+/// Adds one of your properties to a frame group (moving it out of its previous group).
+/// The property does not have to be created yet
+#[no_mangle]
+pub extern "C" fn add_to_frame_group(handle: *mut PluginHandle, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    check_own_property(han, &prop_handle, "add_to_frame_group");
+
+    if prop_handle.plugin != han.id {
+        DataStoreReturnCode::NotAuthenticated
+    } else if frames::add_to_group(han.id, group, prop_handle) {
+        DataStoreReturnCode::Ok
+    } else {
+        DataStoreReturnCode::DoesNotExist
+    }
+}
+
+/// Begins a frame, the values of the group written from now on are published with commit_frame.
+///
+/// This waits for dashboards and subscription groups currently reading the group (which takes
+/// microseconds), and returns AlreadyExists if a frame of this group was already begun.
+/// Do not keep a frame open for long, the group is not read during it
+#[no_mangle]
+pub extern "C" fn begin_frame(handle: *mut PluginHandle, group: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    match frames::begin(han.id, group) {
+        Some(true) => DataStoreReturnCode::Ok,
+        Some(false) => DataStoreReturnCode::AlreadyExists,
+        None => DataStoreReturnCode::DoesNotExist
+    }
+}
+
+/// Commits the frame begun with begin_frame, publishing its values.
+/// Callbacks of properties updated during the frame are called now
+#[no_mangle]
+pub extern "C" fn commit_frame(handle: *mut PluginHandle, group: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    let Some(updated) = frames::commit(han.id, group) else {
+        return DataStoreReturnCode::DoesNotExist;
+    };
+    for prop_handle in updated {
+        if let Some(entry) = han.properties.get(&prop_handle.property) {
+            callbacks::notify(&prop_handle, || entry.read_foreign());
+        }
+    }
+
+    DataStoreReturnCode::Ok
+}
+
+/// Deallocates the list of a GroupUpdate message
+///
+/// This does not deallocate the values in it, strings have to be deallocated and arrays dropped
+/// (before or after this)
+#[no_mangle]
+pub extern "C" fn drop_group_update(update: SubscriptionGroupUpdate) {
+    if update.updates.is_null() {
+        return;
+    }
+
+    leaks::release(update.updates);
+    unsafe {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(update.updates, update.len)));
+    }
+}
+
 /// Generates the EventHandle for a certain name
 /// 
 /// It is advisable to generate these EventHandles at Compile time (macro etc) where possible to avoid
@@ -344,9 +903,22 @@ pub extern "C" fn delete_event(handle: *mut PluginHandle, event: EventHandle) ->
 /// will miss the first trigger.
 #[no_mangle]
 pub extern "C" fn subscribe_event(handle: *mut PluginHandle, event: EventHandle) -> DataStoreReturnCode {
+    subscribe_event_filtered(handle, event, EventFilter::default())
+}
+
+/// Subscribes to an event, but only informs you of the triggers passing the filter
+/// (the filter is applied in the event loop, so the rest are never queued for you).
+///
+/// With debounce_ms you only receive the first trigger of a burst (a trigger after debounce_ms without any),
+/// with every_nth only every nth trigger (of those that passed the debounce).
+///
+/// Subscribing again (also through subscribe_event) replaces the filter and resets its state.
+/// Otherwise this works the same as subscribe_event
+#[no_mangle]
+pub extern "C" fn subscribe_event_filtered(handle: *mut PluginHandle, event: EventHandle, filter: EventFilter) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
 
-    if han.event_channel.send(EventMessage::Subscribe(event, han.id, han.sender.clone().to_async())).is_ok() {
+    if han.event_channel.send(EventMessage::Subscribe(event, han.id, han.sender.clone().to_async(), filter)).is_ok() {
         DataStoreReturnCode::Ok
     } else {
         DataStoreReturnCode::DataCorrupted
@@ -387,6 +959,10 @@ pub extern "C" fn trigger_event(handle: *mut PluginHandle, event: EventHandle) -
         return DataStoreReturnCode::NotAuthenticated;
     }
 
+    if !han.quota.take_message() {
+        return DataStoreReturnCode::QuotaExceeded;
+    }
+
     if han.event_channel.send(EventMessage::Trigger(event)).is_ok() {
         DataStoreReturnCode::Ok
     } else {
@@ -394,6 +970,47 @@ pub extern "C" fn trigger_event(handle: *mut PluginHandle, event: EventHandle) -
     }
 }
 
+/// Marks a lap, sector, stint or session boundary
+///
+/// Advances the core counters (datarace.session.lap, .sector and .stint) and triggers the core event
+/// for this marker (datarace.session.new_lap etc.).
+/// Markers are shared between all plugins, so only the plugin reading the game should set them.
+/// The marker is queued, so the counters change shortly after
+#[no_mangle]
+pub extern "C" fn mark_session(handle: *mut PluginHandle, marker: SessionMarker) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    queue_change(han, DatastoreChange::SessionMark(marker))
+}
+
+/// Returns the core clock, in microseconds since DataRace launched (same as datarace.clock, but exact)
+///
+/// The clock is monotonic and shared by all plugins, so timestamps taken with it can be passed to
+/// other plugins (or stored in Duration properties) and compared, unlike your own Instant
+#[no_mangle]
+pub extern "C" fn get_clock() -> i64 {
+    clock::now()
+}
+
+/// Returns the microseconds passed since the timestamp (taken with get_clock or read from datarace.clock),
+/// negative if the timestamp is in the future
+#[no_mangle]
+pub extern "C" fn clock_delta(since: i64) -> i64 {
+    clock::now() - since
+}
+
+/// Raises a notification, shown as a toast on all connected dashboards and listed on the
+/// notifications page (with your plugin name as source)
+///
+/// The text is a null terminated String, it is not deallocated, that is your job
+#[no_mangle]
+pub extern "C" fn raise_notification(handle: *mut PluginHandle, severity: NotificationSeverity, text: *mut c_char) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    let text = get_string!(text, DataStoreReturnCode::ParameterCorrupted, han, "raise_notification");
+
+    queue_change(han, DatastoreChange::Notification(severity.into(), text))
+}
+
 /// Logs a null terminated String as a Info
 /// String is not deallocated, that is your job
 #[no_mangle]
@@ -413,6 +1030,9 @@ fn log_plugin_msg(handle: *mut PluginHandle, message: *mut c_char, log_level: lo
 
     let msg = if let Some(message) = utils::get_string(message) {
         message
+    } else if is_strict() {
+        string_error(message, Some(han), "log_info/log_error");
+        return;
     } else {
         error!("Message was corrupted");
         return;
@@ -472,15 +1092,17 @@ pub extern "C" fn save_state_now(handle: *mut PluginHandle, state: *mut c_void)
 /// If the index is out of bounds returns a Property with Type None
 #[no_mangle]
 pub extern "C" fn get_array_value(array_handle: *mut ArrayValueHandle, index: usize) -> Property {
-    let arr = if let Some(arr) = unsafe {
-        array_handle.as_ref()  
-    } {
+    let arr = if let Some(arr) = get_array(array_handle, None, "get_array_value") {
         arr
     } else {
         return Property::default();
     };
 
-    arr.arr.read(index)
+    let prop = arr.arr.read(index);
+    if let Some(owner) = leaks::owner(array_handle) {
+        leaks::track_property(&prop, owner);
+    }
+    prop
 }
 
 /// Sets the Value at a certain index of an array.
@@ -493,9 +1115,7 @@ pub extern "C" fn get_array_value(array_handle: *mut ArrayValueHandle, index: us
 #[no_mangle]
 pub extern "C" fn set_array_value(handle: *mut PluginHandle, array_handle: *mut ArrayValueHandle, index: usize, value: Property) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
-    let arr = if let Some(arr) = unsafe {
-        array_handle.as_ref()  
-    } {
+    let arr = if let Some(arr) = get_array(array_handle, Some(han), "set_array_value") {
         arr
     } else {
         return DataStoreReturnCode::ParameterCorrupted;
@@ -508,12 +1128,43 @@ pub extern "C" fn set_array_value(handle: *mut PluginHandle, array_handle: *mut
     }
 }
 
+/// Sets count values of an array at once, starting at the index start, from values (pointing to count Properties).
+/// Meant for updating many entries (like a leaderboard) without a call per entry.
+///
+/// All values must be the same type as the array, and the range has to be within the array,
+/// otherwise nothing is written (TypeMissmatch and DoesNotExist).
+/// Strings (and array handles) in values are taken over the same as with set_array_value, even on failure,
+/// the memory of values itself remains yours.
+///
+/// You can only edit arrays you created.
+/// Trying to change value in Arrayhandles from properties of other plugin will return NotAuthenticated
+#[no_mangle]
+pub extern "C" fn set_array_values(handle: *mut PluginHandle, array_handle: *mut ArrayValueHandle, start: usize, values: *const Property, count: usize) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+    if values.is_null() && count != 0 {
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+
+    // Moving the values out, the plugin keeps the memory holding them
+    let values: Vec<Property> = (0..count).map(|i| unsafe { values.add(i).read() }).collect();
+
+    let code = match get_array(array_handle, Some(han), "set_array_values") {
+        Some(arr) if arr.allow_modify => return arr.arr.write_range(start, values, han),
+        Some(_) => DataStoreReturnCode::NotAuthenticated,
+        None => DataStoreReturnCode::ParameterCorrupted
+    };
+
+    // We took over the strings and array handles, so they have to be freed here
+    for value in values {
+        utils::release_value(value, han);
+    }
+    code
+}
+
 /// Returns the length of the array
 #[no_mangle]
 pub extern "C" fn get_array_length(array_handle: *mut ArrayValueHandle) -> usize {
-    let arr = if let Some(arr) = unsafe {
-        array_handle.as_ref()  
-    } {
+    let arr = if let Some(arr) = get_array(array_handle, None, "get_array_length") {
         arr
     } else {
         return 0;
@@ -525,9 +1176,7 @@ pub extern "C" fn get_array_length(array_handle: *mut ArrayValueHandle) -> usize
 /// Returns the type for the data stored in the array
 #[no_mangle]
 pub extern "C" fn get_array_type(array_handle: *mut ArrayValueHandle) -> PropertyType {
-    let arr = if let Some(arr) = unsafe {
-        array_handle.as_ref()  
-    } {
+    let arr = if let Some(arr) = get_array(array_handle, None, "get_array_type") {
         arr
     } else {
         return PropertyType::None;
@@ -538,7 +1187,7 @@ pub extern "C" fn get_array_type(array_handle: *mut ArrayValueHandle) -> Propert
 
 /// Creates a new Array and returns it's handle.
 ///
-/// Only Int, Float, Bool, String, Duration are accepted as types, others will fail.
+/// Only Int, Float, Bool, String, Duration, Timestamp are accepted as types, others will fail.
 /// This function will return null on fail.
 ///
 /// Size and type can not be changed later.
@@ -555,10 +1204,17 @@ pub extern "C" fn get_array_type(array_handle: *mut ArrayValueHandle) -> Propert
 pub extern "C" fn create_array(handle: *mut PluginHandle, size: usize, init_value: Property) -> *mut ArrayValueHandle {
     let han = get_handle!(handle, std::ptr::null_mut());
 
+    if !han.quota.allows_array_length(size) {
+        error!("Plugin {} failed to create array of size {}, exceeds max_array_length of its quota", han.name, size);
+        return std::ptr::null_mut();
+    }
+
     if let Some(arr) = utils::ArrayValueContainer::new(size, init_value, han) {
-        let arr_handle = ArrayValueHandle { arr: Arc::new(arr), allow_modify: true };
+        let arr_handle = ArrayValueHandle::new(Arc::new(arr), true);
 
-        Box::into_raw(Box::new(arr_handle))
+        let ptr = Box::into_raw(Box::new(arr_handle));
+        leaks::track(ptr, han.id, Allocation::Array);
+        ptr
     } else {
         std::ptr::null_mut()
     }
@@ -572,16 +1228,18 @@ pub extern "C" fn create_array(handle: *mut PluginHandle, size: usize, init_valu
 /// Be aware to call `drop_array_handle` precisely once on each handle
 #[no_mangle]
 pub extern "C" fn clone_array_handle(array_handle: *mut ArrayValueHandle) -> *mut ArrayValueHandle {
-    let arr = if let Some(arr) = unsafe {
-        array_handle.as_ref()  
-    } {
+    let arr = if let Some(arr) = get_array(array_handle, None, "clone_array_handle") {
         arr
     } else {
         return std::ptr::null_mut()
     };
 
-    let dub = ArrayValueHandle { arr: arr.arr.clone(), allow_modify: arr.allow_modify.clone() };
-    Box::into_raw(Box::new(dub))
+    let dub = ArrayValueHandle::new(arr.arr.clone(), arr.allow_modify);
+    let ptr = Box::into_raw(Box::new(dub));
+    if let Some(owner) = leaks::owner(array_handle) {
+        leaks::track(ptr, owner, Allocation::Array);
+    }
+    ptr
 }
 
 /// Drops the passed in ArrayHandle.
@@ -589,7 +1247,9 @@ pub extern "C" fn clone_array_handle(array_handle: *mut ArrayValueHandle) -> *mu
 /// This does not necessarily drop the array, only if this was the last handle holding it (and no property is holding it)
 #[no_mangle]
 pub extern "C" fn drop_array_handle(array_handle: *mut ArrayValueHandle) {
-    if !array_handle.is_null() {
+    if let Some(arr) = get_array(array_handle, None, "drop_array_handle") {
+        arr.tag.store(ARRAY_HANDLE_DROPPED, Ordering::Release);
+        leaks::release(array_handle);
         unsafe {
             array_handle.drop_in_place()
         }
@@ -605,6 +1265,10 @@ pub extern "C" fn drop_array_handle(array_handle: *mut ArrayValueHandle) {
 pub extern "C" fn send_internal_msg(handle: *mut PluginHandle, msg_code: i64) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
 
+    if !han.quota.take_message() {
+        return DataStoreReturnCode::QuotaExceeded;
+    }
+
     if let Err(e) = han.sender.send(LoaderMessage::InternalMessage(msg_code)) {
         error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
         DataStoreReturnCode::DataCorrupted
@@ -623,6 +1287,10 @@ pub extern "C" fn send_internal_msg(handle: *mut PluginHandle, msg_code: i64) ->
 pub extern "C" fn send_ptr_msg_to_plugin(handle: *mut PluginHandle, target: u64, ptr: *mut c_void, reason: i64) -> DataStoreReturnCode {
     let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
 
+    if !han.quota.take_message() {
+        return DataStoreReturnCode::QuotaExceeded;
+    }
+
     if let Err(e) = han.sender.send(LoaderMessage::SendPluginMessagePtr((target, VoidPtrWrapper { ptr }, reason))) {
         error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
         DataStoreReturnCode::DataCorrupted
@@ -631,6 +1299,43 @@ pub extern "C" fn send_ptr_msg_to_plugin(handle: *mut PluginHandle, target: u64,
     }
 }
 
+/// Sets which types of messages are passed to the update function of your plugin.  
+/// Each bit of filter stands for a MessageType (`1 << MessageType`), all bits set (the default)
+/// delivers all messages. Types that are not set are skipped by the pluginloader, for example to
+/// not receive Lock and Unlock.  
+///
+/// Shutdown, PluginMessagePtr and GroupUpdate are always delivered.
+/// Lock still applies to the handle even if you don't receive the messages, so worker threads reading
+/// from it can still be stalled.  
+/// Best called in init.
+#[no_mangle]
+pub extern "C" fn set_message_filter(handle: *mut PluginHandle, filter: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    han.set_message_filter(filter);
+    DataStoreReturnCode::Ok
+}
+
+/// Asks to be told when the plugin with this id (see get_foreign_plugin_id) starts and stops,
+/// through the WatchedPluginStarted and WatchedPluginStopped messages.  
+/// If the plugin is already running WatchedPluginStarted is delivered right away, else once it
+/// finished its startup (also if it is loaded later). The watch stays over restarts of the plugin.
+#[no_mangle]
+pub extern "C" fn watch_plugin(handle: *mut PluginHandle, plugin: u64) -> DataStoreReturnCode {
+    let han = get_handle!(handle, DataStoreReturnCode::DataCorrupted);
+
+    if plugin == han.id || plugin == crate::system::CORE_PLUGIN_ID {
+        return DataStoreReturnCode::ParameterCorrupted;
+    }
+
+    if let Err(e) = han.sender.send(LoaderMessage::WatchPlugin(plugin)) {
+        error!("Failed to send message in channel for Plugin {}: {}", han.name, e);
+        DataStoreReturnCode::DataCorrupted
+    } else {
+        DataStoreReturnCode::Ok
+    }
+}
+
 /// Allows you to optain the id of another plugin based on it's name. 
 /// This function is intended for runtime use, for compiletime macros use `compiletime_get_plugin_name_hash()`.
 ///
@@ -640,7 +1345,7 @@ pub extern "C" fn send_ptr_msg_to_plugin(handle: *mut PluginHandle, target: u64,
 /// but does not check if the plugin is loaded.
 #[no_mangle]
 pub extern "C" fn get_foreign_plugin_id(handle: *mut PluginHandle, name: *mut c_char) -> PluginNameHash {
-    let _han = get_handle!(handle, PluginNameHash { valid: false, id: 0 });
+    let han = get_handle!(handle, PluginNameHash { valid: false, id: 0 });
     // We only aquire a reference to stop people from passing in null
     
     if let Some(str) = utils::get_string(name) {
@@ -651,6 +1356,9 @@ pub extern "C" fn get_foreign_plugin_id(handle: *mut PluginHandle, name: *mut c_
             PluginNameHash { id: 0, valid: false }
         }
     } else {
+        if is_strict() {
+            string_error(name, Some(han), "get_foreign_plugin_id");
+        }
         PluginNameHash { id: 0, valid: false }
     }
 }
@@ -771,6 +1479,7 @@ pub extern "C" fn get_description(handle: *mut PluginHandle) -> PluginDescriptio
 /// So this function is provided to allow you to deallocate strings the API passed to you
 #[no_mangle]
 pub extern "C" fn deallocate_string(ptr: *mut libc::c_char) {
+    leaks::release(ptr);
     unsafe {
         drop(std::ffi::CString::from_raw(ptr))
     }
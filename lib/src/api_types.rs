@@ -16,7 +16,9 @@ pub struct PluginHandle {
     pub(crate) state_ptr: *mut libc::c_void,
     free_string: extern "C" fn(ptr: *mut libc::c_char),
     lock: std::sync::atomic::AtomicU32,
-    pub(crate) event_channel: kanal::Sender<crate::events::EventMessage>
+    message_filter: std::sync::atomic::AtomicU64,
+    pub(crate) event_channel: kanal::Sender<crate::events::EventMessage>,
+    pub(crate) quota: crate::quota::Quota
 }
 
 impl PluginHandle {
@@ -38,8 +40,10 @@ impl PluginHandle {
             sender,
             version,
             lock: std::sync::atomic::AtomicU32::new(0),
+            message_filter: std::sync::atomic::AtomicU64::new(u64::MAX),
             state_ptr: std::ptr::null_mut(),
-            event_channel
+            event_channel,
+            quota: crate::quota::Quota::default()
         }
     }
 
@@ -68,10 +72,46 @@ impl PluginHandle {
     pub(crate) fn is_locked(&self) -> bool {
         self.lock.load(std::sync::atomic::Ordering::Acquire) != 1
     }
+
+    pub(crate) fn set_message_filter(&self, filter: u64) {
+        self.message_filter.store(filter, std::sync::atomic::Ordering::Release);
+    }
+
+    /// If the plugin wants this type of message delivered (see set_message_filter).
+    /// Shutdown, PluginMessagePtr and GroupUpdate are always delivered, as the plugin has to
+    /// handle/deallocate them
+    pub(crate) fn wants_message(&self, sort: MessageType) -> bool {
+        match sort {
+            MessageType::Shutdown | MessageType::PluginMessagePtr | MessageType::GroupUpdate => true,
+            sort => self.message_filter.load(std::sync::atomic::Ordering::Acquire) & (1 << sort as u8) != 0
+        }
+    }
+}
+
+/// How urgent a notification is, see raise_notification
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum NotificationSeverity {
+    Info = 0,
+    Warning = 1,
+    Error = 2
+}
+
+/// Boundaries in a session, see mark_session
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum SessionMarker {
+    /// A new lap started, the sector goes back to 1
+    Lap = 0,
+    Sector = 1,
+    /// A new stint started (like after a driver change), the lap keeps counting
+    Stint = 2,
+    /// A new session started, all counters go back to 1
+    Session = 3
 }
 
 /// Return codes from operations like create_property, etc.
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 #[repr(u8)]
 pub enum DataStoreReturnCode {
     Ok = 0,
@@ -81,7 +121,12 @@ pub enum DataStoreReturnCode {
     TypeMissmatch = 5,
     NotImplemented = 6,
     ParameterCorrupted = 10, 
-    DataCorrupted = 11
+    DataCorrupted = 11,
+    /// A limit set in plugin_quota of the config was reached
+    QuotaExceeded = 12,
+    /// The datastore is locked right now (for example while plugins are starting), try again later.
+    /// Api functions never wait on it, as waiting inside init or update can deadlock
+    Busy = 13
 
 }
 
@@ -179,6 +224,18 @@ impl EventHandle {
     }
 }
 
+/// Reduces how often a subscriber is informed of an event (see subscribe_event_filtered),
+/// for noisy events that can trigger hundreds of times a second
+#[repr(C)]
+#[derive(Clone,Copy,Default,Debug)]
+pub struct EventFilter {
+    /// Only triggers after at least this many ms without a trigger are passed on, so one per burst.
+    /// 0 passes all
+    pub debounce_ms: u64,
+    /// Only every nth trigger (of those passing the debounce) is passed on, 0 and 1 pass all
+    pub every_nth: u64
+}
+
 /// The Type and Value of a Property
 #[repr(C)]
 pub struct Property {
@@ -188,14 +245,17 @@ pub struct Property {
 
 /// The type of this Property
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub enum PropertyType {
+    #[default]
     None = 0,
     Int = 1,
     Float = 2,
     Boolean = 3,
     Str = 4,
     Duration = 5,
+    Timestamp = 6,
+    Vector = 7,
 
     Array = 10
 }
@@ -206,6 +266,8 @@ pub enum PropertyType {
 /// boolean is a Boolean
 /// str is a pointer to a null terminating String
 /// dur is a Duration in micro seconds (1s = 1,000millis = 1,000,000 micros), signed
+/// timestamp is a point in time, in micro seconds since the unix epoch (1970-01-01 00:00:00 UTC), signed
+/// vector is a 3D Vector (for 2D z is 0)
 /// arr is a pointer to a ArrayValue
 #[repr(C)]
 pub union PropertyValue {
@@ -215,9 +277,21 @@ pub union PropertyValue {
     // this is the reason to not support clone
     pub str: *mut c_char,
     pub dur: i64,
+    pub timestamp: i64,
+    pub vector: Vector3,
     pub arr: *mut ArrayValueHandle,
 }
 
+/// Vector of 3 floats (like a world position or g-forces), updated as a unit so a reader never
+/// sees components of two different updates
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
 /// Handle to the array contained in a property.
 ///
 /// These are long lived references, values retrieved are always up to date.
@@ -228,7 +302,18 @@ pub union PropertyValue {
 /// You can produce a second handle to the same data via `clone_array_handle`.
 pub struct ArrayValueHandle {
     pub(crate) arr: Arc<utils::ArrayValueContainer>,
-    pub(crate) allow_modify: bool
+    pub(crate) allow_modify: bool,
+    /// ARRAY_HANDLE_ALIVE till dropped, used to detect double drops (see strict_ffi)
+    pub(crate) tag: std::sync::atomic::AtomicU32
+}
+
+pub(crate) const ARRAY_HANDLE_ALIVE: u32 = 0xA11A_E000;
+pub(crate) const ARRAY_HANDLE_DROPPED: u32 = 0xDEAD_A77A;
+
+impl ArrayValueHandle {
+    pub(crate) fn new(arr: Arc<utils::ArrayValueContainer>, allow_modify: bool) -> Self {
+        ArrayValueHandle { arr, allow_modify, tag: std::sync::atomic::AtomicU32::new(ARRAY_HANDLE_ALIVE) }
+    }
 }
 
 impl<T> ReturnValue<T> where T: Default {
@@ -258,6 +343,7 @@ pub struct Message {
     pub value: MessageValue
 }
 
+#[derive(Clone, Copy, PartialEq)]
 #[repr(u8)]
 pub enum MessageType {
     StartupFinished = 0,
@@ -268,6 +354,24 @@ pub enum MessageType {
     EventTriggered = 6,
     EventUnsubscribed = 7,
 
+    /// A subscription completed, the value is `update` with the current value of the property
+    PropertySubscribed = 8,
+    /// Values in a subscription group changed, the value is `group_update`
+    GroupUpdate = 9,
+    /// The owner of a subscribed property changed its type (with change_property_type),
+    /// the value is `update` with the current value in the new type
+    PropertyTypeChanged = 12,
+    /// Something the plugin queued failed after the call had already returned Ok,
+    /// the value is `operation_failed`
+    OperationFailed = 13,
+    /// A plugin watched with watch_plugin finished its startup (or was already running), the value is `plugin_id`
+    WatchedPluginStarted = 14,
+    /// A plugin watched with watch_plugin stopped, the value is `plugin_id`
+    WatchedPluginStopped = 15,
+    /// The owner of a subscribed property stopped, so the subscription was removed,
+    /// the value is `removed_property`
+    SubscriptionRemoved = 16,
+
     // Update = 0,
     // Removed = 1,
     Lock = 10,
@@ -275,6 +379,30 @@ pub enum MessageType {
     Shutdown = 20,
 }
 
+impl MessageType {
+    /// Name of the message, used for the profile and the message trace
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            MessageType::StartupFinished => "StartupFinished",
+            MessageType::OtherPluginStarted => "OtherPluginStarted",
+            MessageType::InternalMessage => "InternalMessage",
+            MessageType::PluginMessagePtr => "PluginMessagePtr",
+            MessageType::EventTriggered => "EventTriggered",
+            MessageType::EventUnsubscribed => "EventUnsubscribed",
+            MessageType::PropertySubscribed => "PropertySubscribed",
+            MessageType::GroupUpdate => "GroupUpdate",
+            MessageType::PropertyTypeChanged => "PropertyTypeChanged",
+            MessageType::OperationFailed => "OperationFailed",
+            MessageType::WatchedPluginStarted => "WatchedPluginStarted",
+            MessageType::WatchedPluginStopped => "WatchedPluginStopped",
+            MessageType::SubscriptionRemoved => "SubscriptionRemoved",
+            MessageType::Lock => "Lock",
+            MessageType::Unlock => "Unlock",
+            MessageType::Shutdown => "Shutdown"
+        }
+    }
+}
+
 #[repr(C)]
 pub union MessageValue {
     pub plugin_id: u64,
@@ -284,6 +412,57 @@ pub union MessageValue {
     pub removed_property: PropertyHandle,
     pub update: ManuallyDrop<UpdateValue>,
     pub event: EventHandle,
+    pub group_update: SubscriptionGroupUpdate,
+    pub operation_failed: FailedOperation,
+}
+
+/// Operations that are queued and carried out later by the pluginloader, see FailedOperation
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum Operation {
+    CreateProperty = 0,
+    ChangePropertyType = 1,
+    DeleteProperty = 2,
+    SubscribeProperty = 3,
+    UnsubscribeProperty = 4,
+    /// send_ptr_msg_to_plugin, the pointer was not delivered and is still yours (it is passed back in message_ptr)
+    SendPluginMessage = 5,
+    AddToSubscriptionGroup = 6,
+    RemoveFromSubscriptionGroup = 7,
+    DeleteSubscriptionGroup = 8
+}
+
+/// A queued operation that could not be carried out (like creating a property whose id is
+/// already taken), the call itself had returned Ok
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FailedOperation {
+    pub what: Operation,
+    pub code: DataStoreReturnCode,
+    /// The property of the operation, zero for SendPluginMessage and DeleteSubscriptionGroup
+    pub property: PropertyHandle,
+    /// The target plugin for SendPluginMessage, the group for subscription group operations, else zero
+    pub target: u64,
+    /// The pointer that was not delivered for SendPluginMessage (so it can be freed), else null
+    pub message_ptr: *mut libc::c_void,
+    /// The reason passed with the pointer for SendPluginMessage, else zero
+    pub reason: i64
+}
+
+// message_ptr is only handed back to the plugin that sent it, we never dereference it
+unsafe impl Send for FailedOperation {}
+unsafe impl Sync for FailedOperation {}
+
+/// All values of a subscription group that changed since the last GroupUpdate.
+///
+/// The list has to be deallocated with drop_group_update, the values in it (strings and arrays)
+/// have to be deallocated like any other value
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SubscriptionGroupUpdate {
+    pub group: u64,
+    pub updates: *mut UpdateValue,
+    pub len: usize
 }
 
 #[repr(C)]
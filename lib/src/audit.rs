@@ -0,0 +1,175 @@
+//! Audit log of the changes made from the web: property writes (through the api or a dashboard)
+//! and config reloads from the settings page (also after restoring a backup), as dashboards can
+//! act as control surfaces for the rig. Rejected writes are recorded too.
+//!
+//! The datastore keeps the last entries (queried through /api/audit), with audit_log in the config
+//! they are also appended to that file as json lines, so they outlive a restart.
+//! The file is written on its own thread, so recording never waits on the disk.
+
+use std::{collections::VecDeque, io::Write, path::{Path, PathBuf}, sync::{Mutex, OnceLock}, time::{SystemTime, UNIX_EPOCH}};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+/// How many entries are kept in memory
+const HISTORY_LENGTH: usize = 1000;
+/// Values longer than this (in characters) are cut off, writes can be up to the body limit of the web
+const MAX_VALUE_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditAction {
+    /// A property was written, target is the property name
+    Write,
+    /// A setting was changed by reloading the config, target is the setting
+    Setting
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AuditEntry {
+    /// Increasing, set when recorded
+    pub(crate) id: u64,
+    /// Milliseconds since the unix epoch, set when recorded
+    pub(crate) time: i64,
+    pub(crate) action: AuditAction,
+    /// Ip of the client
+    pub(crate) client: String,
+    /// `api`, `dashboard <name>`, `settings` or `restore`
+    pub(crate) source: String,
+    pub(crate) target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) old_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) new_value: Option<String>,
+    /// Ok, or why it was rejected (like NotAuthenticated)
+    pub(crate) result: String
+}
+
+impl AuditEntry {
+    pub(crate) fn new(action: AuditAction, client: String, source: String, target: String) -> Self {
+        AuditEntry { id: 0, time: 0, action, client, source, target, old_value: None, new_value: None, result: "Ok".to_string() }
+    }
+}
+
+/// Query parameters of /api/audit, all optional
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct AuditQuery {
+    /// Only entries with a higher id
+    since: Option<u64>,
+    action: Option<AuditAction>,
+    /// Only targets containing this text (case insensitive)
+    target: Option<String>,
+    client: Option<String>,
+    /// Number of entries returned at most (newest first)
+    limit: Option<usize>
+}
+
+/// Can be recorded to while only holding a read lock on the datastore
+#[derive(Debug, Default)]
+pub(crate) struct AuditLog {
+    entries: Mutex<(VecDeque<AuditEntry>, u64)>,
+    /// Lines for the file, started on the first entry written to a file
+    writer: OnceLock<Option<kanal::Sender<(PathBuf, String)>>>
+}
+
+impl AuditLog {
+    /// Records the entry, and queues appending it to the file (if set)
+    pub(crate) fn record(&self, file: Option<&Path>, mut entry: AuditEntry) {
+        entry.old_value = entry.old_value.map(truncate);
+        entry.new_value = entry.new_value.map(truncate);
+        entry.time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+
+        if let Ok(mut guard) = self.entries.lock() {
+            let (list, next_id) = &mut *guard;
+            *next_id += 1;
+            entry.id = *next_id;
+
+            list.push_back(entry.clone());
+            while list.len() > HISTORY_LENGTH {
+                list.pop_front();
+            }
+        }
+
+        if let Some(path) = file {
+            let line = match serde_json::to_string(&entry) {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("Unable to serialize audit log entry: {}", e);
+                    return;
+                }
+            };
+
+            if let Some(writer) = self.writer.get_or_init(start_writer) {
+                let _ = writer.send((path.to_path_buf(), line));
+            }
+        }
+    }
+
+    /// The entries matching the query, newest first
+    pub(crate) fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        let Ok(guard) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let target = query.target.as_ref().map(|t| t.to_lowercase());
+
+        guard.0.iter().rev()
+            .filter(|e| query.since.is_none_or(|id| e.id > id))
+            .filter(|e| query.action.is_none_or(|action| e.action == action))
+            .filter(|e| target.as_ref().is_none_or(|t| e.target.to_lowercase().contains(t.as_str())))
+            .filter(|e| query.client.as_ref().is_none_or(|c| &e.client == c))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+fn truncate(mut value: String) -> String {
+    if let Some((index, _)) = value.char_indices().nth(MAX_VALUE_LENGTH) {
+        value.truncate(index);
+        value.push_str("...");
+    }
+    value
+}
+
+/// Appends the queued lines in order, None if the thread could not be started (then nothing is written to the file)
+fn start_writer() -> Option<kanal::Sender<(PathBuf, String)>> {
+    let (sender, receiver) = kanal::unbounded::<(PathBuf, String)>();
+
+    let res = std::thread::Builder::new().name("audit-log".to_string()).spawn(move || {
+        while let Ok((path, line)) = receiver.recv() {
+            if let Err(e) = append(path.as_path(), line) {
+                error!("Unable to write to the audit log {}: {}", path.to_str().unwrap_or_default(), e);
+            }
+        }
+    });
+
+    match res {
+        Ok(_) => Some(sender),
+        Err(e) => {
+            error!("Unable to start writing the audit log: {}", e);
+            None
+        }
+    }
+}
+
+fn append(path: &Path, mut line: String) -> Result<(), std::io::Error> {
+    line.push('\n');
+
+    std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+/// The settings that differ between the two configs (as serialized, so hidden settings are never logged),
+/// with their old and new value
+pub(crate) fn diff_settings<T: Serialize>(old: &T, new: &T) -> Vec<(String, Option<String>, Option<String>)> {
+    let (Ok(toml::Value::Table(old)), Ok(toml::Value::Table(new))) = (toml::Value::try_from(old), toml::Value::try_from(new)) else {
+        return Vec::new();
+    };
+
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter().filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| (key.clone(), old.get(key).map(|v| v.to_string()), new.get(key).map(|v| v.to_string())))
+        .collect()
+}
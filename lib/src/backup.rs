@@ -0,0 +1,171 @@
+//! Backup and restore of the configuration state: the config file and all dashboards (including
+//! subfolders) bundled into a single json archive, to move them to another rig or to roll back
+//! after a bad change.
+//!
+//! Restoring overwrites the config and the dashboards contained in the archive, dashboards not in
+//! the archive are kept. From the web this is only possible on the host itself. Before that the current state is saved as a backup, so a restore can be
+//! undone too.
+
+use std::{collections::BTreeMap, io::Write, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{built_info, config};
+
+/// Version of the archive format, archives of newer versions are rejected
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Backup {
+    format: u32,
+    /// Version of DataRace that created the backup
+    datarace: String,
+    /// Milliseconds since the unix epoch
+    created: i64,
+    /// Text of the config file, empty if there was none
+    config: String,
+    /// Name of the dashboard (path without .json, see web::dashboard_file) -> content
+    dashboards: BTreeMap<String, String>
+}
+
+impl Backup {
+    /// Collects the current config file and the dashboards from the folder set in the config
+    pub(crate) fn create(config: &config::Config) -> Result<Backup, String> {
+        let path = config::get_config_path();
+        let text = if path.exists() {
+            std::fs::read_to_string(path.as_path()).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?
+        } else {
+            String::new()
+        };
+
+        let mut dashboards = BTreeMap::new();
+        let folder = config.get_dashboards_folder();
+        if folder.is_dir() {
+            collect_dashboards(folder.as_path(), &mut dashboards)?;
+        }
+
+        Ok(Backup {
+            format: FORMAT_VERSION,
+            datarace: built_info::PKG_VERSION.to_string(),
+            created: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64,
+            config: text,
+            dashboards
+        })
+    }
+
+    /// Parses an archive, checking the format version and the dashboard names
+    pub(crate) fn parse(text: &str) -> Result<Backup, String> {
+        let backup: Backup = serde_json::from_str(text).map_err(|e| format!("Not a valid backup: {}", e))?;
+
+        if backup.format > FORMAT_VERSION {
+            return Err(format!("Backup has format version {} (created by DataRace {}), but only up to {} is supported", backup.format, backup.datarace, FORMAT_VERSION));
+        }
+        if let Some(name) = backup.dashboards.keys().find(|name| crate::web::dashboard_file(PathBuf::new(), name).is_none()) {
+            return Err(format!("Backup contains an invalid dashboard name: {}", name));
+        }
+
+        Ok(backup)
+    }
+
+    /// Versioned filename, like datarace-backup-0.1.0-2024-05-01T18-30-00.json
+    pub(crate) fn file_name(&self) -> String {
+        let time = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_millis(self.created.max(0) as u64)).to_string();
+        format!("datarace-backup-{}-{}.json", self.datarace, time.trim_end_matches('Z').replace(':', "-"))
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize backup: {}", e))
+    }
+
+    /// Writes the config and the dashboards, after saving the current state into folder.
+    /// The config in the backup has to be valid.
+    /// The dashboards are written into the folder of the current config, the backup could point
+    /// its dashboards folder anywhere. current is None if the current config is broken (only
+    /// possible from the command line), then the folder set in the backup is used instead.
+    /// Returns the path of the backup of the previous state
+    pub(crate) fn restore(&self, current: Option<&config::Config>, folder: &Path) -> Result<PathBuf, String> {
+        let restored = config::parse_config(self.config.as_str()).map_err(|e| format!("Config in the backup is invalid: {}", e))?;
+        let dash_folder = current.unwrap_or(&restored).get_dashboards_folder();
+
+        let previous = write_backup(&Backup::create(current.unwrap_or(&restored))?, folder)?;
+
+        let path = config::get_config_path();
+        std::fs::write(path.as_path(), self.config.as_bytes()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+
+        for (name, content) in self.dashboards.iter() {
+            let file = crate::web::dashboard_file(dash_folder.clone(), name).ok_or(format!("Invalid dashboard name: {}", name))?;
+            if let Some(parent) = file.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Unable to create folder {}: {}", parent.display(), e))?;
+            }
+            std::fs::write(file.as_path(), content.as_bytes()).map_err(|e| format!("Unable to write {}: {}", file.display(), e))?;
+        }
+
+        info!("Restored backup from DataRace {} with {} dashboards, previous state saved to {}", self.datarace, self.dashboards.len(), previous.display());
+        Ok(previous)
+    }
+}
+
+/// Reads all dashboards in the folder, skipping hidden folders (like the cached previews) and symlinks
+fn collect_dashboards(folder: &Path, dashboards: &mut BTreeMap<String, String>) -> Result<(), String> {
+    let mut dirs = vec![(folder.to_path_buf(), String::new())];
+
+    while let Some((dir, prefix)) = dirs.pop() {
+        let iter = std::fs::read_dir(dir.as_path()).map_err(|e| format!("Unable to read {}: {}", dir.display(), e))?;
+
+        for item in iter.flatten() {
+            let path = item.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if item.file_type().is_ok_and(|sort| sort.is_dir()) {
+                if !name.starts_with('.') {
+                    dirs.push((path.clone(), format!("{}{}/", prefix, name)));
+                }
+            } else if let Some(stem) = name.strip_suffix(".json") {
+                // Names the web can not open are left out, restoring them would fail
+                let name = format!("{}{}", prefix, stem);
+                if crate::web::dashboard_file(PathBuf::new(), name.as_str()).is_none() {
+                    continue;
+                }
+
+                let content = std::fs::read_to_string(path.as_path()).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+                dashboards.insert(name, content);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the backup into the folder (with the versioned filename), returns the path.
+/// Existing files are never overwritten (like the backup being restored, when taken in the same
+/// second), instead a counter is added to the name
+pub(crate) fn write_backup(backup: &Backup, folder: &Path) -> Result<PathBuf, String> {
+    let json = backup.to_json()?;
+    let name = backup.file_name();
+    let stem = name.trim_end_matches(".json");
+
+    let mut path = folder.join(name.as_str());
+    let mut counter = 1;
+    loop {
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(path.as_path()) {
+            Ok(mut file) => {
+                file.write_all(json.as_bytes()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+                return Ok(path);
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                path = folder.join(format!("{}-{}.json", stem, counter));
+                counter += 1;
+            },
+            Err(e) => return Err(format!("Unable to write {}: {}", path.display(), e))
+        }
+    }
+}
+
+/// Reads and parses a backup file
+pub(crate) fn read_backup(path: &Path) -> Result<Backup, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    Backup::parse(text.as_str())
+}
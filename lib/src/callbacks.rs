@@ -0,0 +1,135 @@
+//! Callbacks registered by plugins, which are called directly when a property is updated
+//! (instead of the plugin having to read the value).
+//!
+//! The callback is run on the thread of the plugin updating the property. The registry lock is
+//! released before calling (so callbacks can subscribe and unsubscribe), instead removing a callback
+//! (unsubscribe or shutdown of the subscriber) waits for running calls of it to return, so the
+//! plugin library is not unloaded (or the user data freed) while it is still running.
+
+use std::{cell::RefCell, sync::{atomic::{AtomicUsize, Ordering}, Arc, RwLock, Weak}};
+
+use hashbrown::HashMap;
+
+use crate::{utils::VoidPtrWrapper, Property, PropertyHandle};
+
+/// Called with the handle of the property that was updated, the new value and the user data passed in on registering.
+/// Strings have to be deallocated and arrays dropped by the plugin, same as with get_property_value
+pub type PropertyCallback = extern "C" fn(prop_handle: PropertyHandle, value: Property, user_data: *mut libc::c_void);
+
+struct Callback {
+    subscriber: u64,
+    callback: PropertyCallback,
+    user_data: VoidPtrWrapper
+}
+
+/// Property -> callbacks of the subscribers
+type Registry = HashMap<PropertyHandle, Vec<Arc<Callback>>>;
+
+static CALLBACKS: RwLock<Option<Registry>> = RwLock::new(None);
+/// Number of callbacks registered, so updates can skip the lock when there are none
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Callbacks running on this thread (nested if a callback updates a property), removing them from within
+    /// has to not wait on the call itself
+    static RUNNING: RefCell<Vec<*const Callback>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn register(prop_handle: PropertyHandle, subscriber: u64, callback: PropertyCallback, user_data: *mut libc::c_void) {
+    let entry = Arc::new(Callback { subscriber, callback, user_data: VoidPtrWrapper { ptr: user_data } });
+
+    let replaced = if let Ok(mut map) = CALLBACKS.write() {
+        let list = map.get_or_insert_with(HashMap::default).entry(prop_handle).or_default();
+
+        // Registering again replaces the callback
+        if let Some(pos) = list.iter().position(|cb| cb.subscriber == subscriber) {
+            Some(std::mem::replace(&mut list[pos], entry))
+        } else {
+            list.push(entry);
+            COUNT.fetch_add(1, Ordering::Release);
+            None
+        }
+    } else {
+        None
+    };
+
+    if let Some(old) = replaced {
+        wait_for_calls(vec![old]);
+    }
+}
+
+/// Removes the callback of the subscriber for this property, after running calls of it returned
+pub(crate) fn remove(prop_handle: &PropertyHandle, subscriber: u64) {
+    let mut removed = Vec::new();
+    if let Ok(mut map) = CALLBACKS.write() {
+        if let Some(list) = map.as_mut().and_then(|map| map.get_mut(prop_handle)) {
+            take_subscriber(list, subscriber, &mut removed);
+        }
+    }
+
+    wait_for_calls(removed);
+}
+
+/// Removes all callbacks of this plugin, has to be called before the plugin is unloaded
+pub(crate) fn remove_plugin(subscriber: u64) {
+    let mut removed = Vec::new();
+    if let Ok(mut map) = CALLBACKS.write() {
+        if let Some(map) = map.as_mut() {
+            for list in map.values_mut() {
+                take_subscriber(list, subscriber, &mut removed);
+            }
+            map.retain(|_, list| !list.is_empty());
+        }
+    }
+
+    wait_for_calls(removed);
+}
+
+fn take_subscriber(list: &mut Vec<Arc<Callback>>, subscriber: u64, removed: &mut Vec<Arc<Callback>>) {
+    let mut index = 0;
+    while index < list.len() {
+        if list[index].subscriber == subscriber {
+            removed.push(list.remove(index));
+            COUNT.fetch_sub(1, Ordering::Release);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// Every running call holds a clone of the entry, so once we hold the last one no call is left.
+/// A callback removing itself does not wait on its own call
+fn wait_for_calls(removed: Vec<Arc<Callback>>) {
+    for entry in removed {
+        let own = RUNNING.with(|running| running.borrow().iter().filter(|ptr| std::ptr::eq(**ptr, Arc::as_ptr(&entry))).count());
+        while Arc::strong_count(&entry) > own + 1 {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// Calls all callbacks of this property, read is called to generate the value for each of them
+pub(crate) fn notify(prop_handle: &PropertyHandle, read: impl Fn() -> Property) {
+    if COUNT.load(Ordering::Acquire) == 0 {
+        return;
+    }
+
+    // Copying the list so the lock is not held while calling into other plugins.
+    // Only the running call holds the entry, so a callback can remove the ones after it
+    let list: Vec<Weak<Callback>> = match CALLBACKS.read() {
+        Ok(map) => match map.as_ref().and_then(|map| map.get(prop_handle)) {
+            Some(list) => list.iter().map(Arc::downgrade).collect(),
+            None => return
+        },
+        Err(_) => return
+    };
+
+    for entry in list.iter().filter_map(Weak::upgrade) {
+        let value = read();
+        crate::leaks::track_property(&value, entry.subscriber);
+
+        RUNNING.with(|running| running.borrow_mut().push(Arc::as_ptr(&entry)));
+        (entry.callback)(*prop_handle, value, entry.user_data.ptr);
+        RUNNING.with(|running| running.borrow_mut().pop());
+    }
+}
@@ -0,0 +1,397 @@
+use std::path::PathBuf;
+
+use log::error;
+use serde::Serialize;
+use tokio::time::{self, Duration, Instant};
+
+use crate::{backup, config::Config, datastore::DataStore, events, ipc, pluginloader, utils::{Value, ValueCache}, PropertyHandle};
+
+/// How long we wait for plugins to finish startup before dumping the properties
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) const USAGE: &str = "Usage: launch_datarace [OPTIONS]
+       launch_datarace new-plugin <NAME>
+       launch_datarace backup [FOLDER]
+       launch_datarace restore <FILE>
+       launch_datarace ctl <COMMAND>
+
+Commands:
+    new-plugin <NAME>                   Generates a new rust plugin project in the folder NAME
+    backup [FOLDER]                     Saves the config and all dashboards into a versioned archive in FOLDER
+                                        (default is the current folder)
+    restore <FILE>                      Restores the config and dashboards from the archive, the current
+                                        state is saved as a backup first
+    ctl plugins                         Lists the plugins of the running instance
+    ctl list                            Lists all properties of the running instance
+    ctl get <PLUGIN.PROPERTY>           Prints the value of the property
+    ctl action <PLUGIN.EVENT>           Triggers the event in the running instance
+    ctl set <PLUGIN.PROPERTY> <VALUE>   Sets the property in the running instance (durations in seconds)
+    ctl memory                          Lists the memory held by the properties of each plugin
+    ctl trace <PLUGIN> [FILE]           Logs every message the plugin receives (into FILE if set)
+    ctl trace-stop <PLUGIN>             Stops tracing the messages of the plugin
+    ctl restart <web|events>            Restarts the webserver or the event loop, the plugins keep running
+    ctl shutdown                        Shuts the running instance down
+
+Options:
+    --list-plugins      Lists all plugins in the plugin folder (without running them) and if they can be loaded
+    --dump-properties   Starts all plugins, prints all properties once startup finished, then shuts down
+    --doctor            Checks the config, folders, web port and plugins, and prints a report of the problems
+    --json              Print the output of --list-plugins, --dump-properties or --doctor as json
+    --service           Run as Windows service (only when launched by the service manager)
+    --profile <NAME>    Uses Config.NAME.toml instead of Config.toml (also applies to ctl)
+    -h, --help          Prints this help";
+
+/// What the launch was requested to do
+pub(crate) enum Mode {
+    Run,
+    ListPlugins { json: bool },
+    DumpProperties { json: bool },
+    Doctor { json: bool },
+    NewPlugin(String),
+    Backup(Option<String>),
+    Restore(String),
+    Ctl(CtlCommand),
+    Service,
+    Help
+}
+
+/// Commands send to a running instance via the control socket
+pub(crate) enum CtlCommand {
+    Plugins,
+    List,
+    Get(String),
+    Action(String),
+    Set(String, String),
+    Memory,
+    /// Plugin and the file to trace into
+    Trace(String, Option<String>),
+    TraceStop(String),
+    Restart(String),
+    Shutdown
+}
+
+/// Parses the command line arguments of the process
+/// Returns the Mode and the profile (if set)
+pub(crate) fn parse_args() -> Result<(Mode, Option<String>), String> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // The profile can be set with any mode, so we take it out first
+    let profile = if let Some(index) = args.iter().position(|a| a == "--profile") {
+        if index + 1 >= args.len() {
+            return Err("--profile requires a profile name".to_string());
+        }
+        let name = args.remove(index + 1);
+        args.remove(index);
+        Some(name)
+    } else {
+        None
+    };
+
+    Ok((parse_mode(args)?, profile))
+}
+
+fn parse_mode(args: Vec<String>) -> Result<Mode, String> {
+    let mut list_plugins = false;
+    let mut dump_properties = false;
+    let mut doctor = false;
+    let mut json = false;
+    let mut service = false;
+
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(|a| a.as_str()) == Some("new-plugin") {
+        args.next();
+        return match (args.next(), args.next()) {
+            (Some(name), None) => Ok(Mode::NewPlugin(name)),
+            (None, _) => Err("new-plugin requires a plugin name".to_string()),
+            (Some(_), Some(arg)) => Err(format!("Unknown argument: {}", arg))
+        };
+    }
+
+    if args.peek().map(|a| a.as_str()) == Some("backup") {
+        args.next();
+        return match (args.next(), args.next()) {
+            (folder, None) => Ok(Mode::Backup(folder)),
+            (_, Some(arg)) => Err(format!("Unknown argument: {}", arg))
+        };
+    }
+
+    if args.peek().map(|a| a.as_str()) == Some("restore") {
+        args.next();
+        return match (args.next(), args.next()) {
+            (Some(file), None) => Ok(Mode::Restore(file)),
+            (None, _) => Err("restore requires a backup file".to_string()),
+            (Some(_), Some(arg)) => Err(format!("Unknown argument: {}", arg))
+        };
+    }
+
+    if args.peek().map(|a| a.as_str()) == Some("ctl") {
+        args.next();
+        let cmd = match args.next().as_deref() {
+            Some("plugins") => CtlCommand::Plugins,
+            Some("list") => CtlCommand::List,
+            Some("memory") => CtlCommand::Memory,
+            Some("shutdown") => CtlCommand::Shutdown,
+            Some("restart") => match (args.next(), args.next()) {
+                (Some(subsystem), None) => CtlCommand::Restart(subsystem),
+                (None, _) => return Err("ctl restart requires a subsystem (web or events)".to_string()),
+                (Some(_), Some(arg)) => return Err(format!("Unknown argument: {}", arg))
+            },
+            Some("trace") => match (args.next(), args.next(), args.next()) {
+                (Some(plugin), file, None) => CtlCommand::Trace(plugin, file),
+                (None, _, _) => return Err("ctl trace requires a plugin name".to_string()),
+                (Some(_), _, Some(arg)) => return Err(format!("Unknown argument: {}", arg))
+            },
+            Some("trace-stop") => match (args.next(), args.next()) {
+                (Some(plugin), None) => CtlCommand::TraceStop(plugin),
+                (None, _) => return Err("ctl trace-stop requires a plugin name".to_string()),
+                (Some(_), Some(arg)) => return Err(format!("Unknown argument: {}", arg))
+            },
+            Some("get") => match (args.next(), args.next()) {
+                (Some(prop), None) => CtlCommand::Get(prop),
+                (None, _) => return Err("ctl get requires a property name".to_string()),
+                (Some(_), Some(arg)) => return Err(format!("Unknown argument: {}", arg))
+            },
+            Some("action") => match (args.next(), args.next()) {
+                (Some(event), None) => CtlCommand::Action(event),
+                (None, _) => return Err("ctl action requires an event name".to_string()),
+                (Some(_), Some(_)) => return Err("ctl action does not support parameters, events carry no data".to_string())
+            },
+            Some("set") => match (args.next(), args.next(), args.next()) {
+                (Some(prop), Some(value), None) => CtlCommand::Set(prop, value),
+                (Some(_), Some(_), Some(arg)) => return Err(format!("Unknown argument: {}", arg)),
+                _ => return Err("ctl set requires a property name and a value".to_string())
+            },
+            Some(cmd) => return Err(format!("Unknown ctl command: {}", cmd)),
+            None => return Err("ctl requires a command".to_string())
+        };
+        if let Some(arg) = args.next() {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+        return Ok(Mode::Ctl(cmd));
+    }
+
+    for arg in args {
+        match arg.as_str() {
+            "--list-plugins" => list_plugins = true,
+            "--dump-properties" => dump_properties = true,
+            "--doctor" => doctor = true,
+            "--json" => json = true,
+            "--service" => service = true,
+            "-h" | "--help" => return Ok(Mode::Help),
+            _ => return Err(format!("Unknown argument: {}", arg))
+        }
+    }
+
+    if service {
+        return if list_plugins || dump_properties || doctor || json {
+            Err("--service can not be combined with other options".to_string())
+        } else {
+            Ok(Mode::Service)
+        };
+    }
+
+    match (list_plugins, dump_properties, doctor) {
+        (true, false, false) => Ok(Mode::ListPlugins { json }),
+        (false, true, false) => Ok(Mode::DumpProperties { json }),
+        (false, false, true) => Ok(Mode::Doctor { json }),
+        (false, false, false) if json => Err("--json requires --list-plugins, --dump-properties or --doctor".to_string()),
+        (false, false, false) => Ok(Mode::Run),
+        _ => Err("--list-plugins, --dump-properties and --doctor can not be used together".to_string())
+    }
+}
+
+/// Prints all plugins found in the plugin folder, including why they would fail to load
+pub(crate) fn list_plugins(config: &Config, json: bool) {
+    let folder = config.get_plugin_folder();
+    let list = match pluginloader::read_all_plugin_descriptions(config) {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Unable to read plugin folder {}: {}", folder.to_str().unwrap_or_default(), e);
+            return;
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&list) {
+            Ok(text) => println!("{}", text),
+            Err(e) => error!("Unable to serialize plugin list: {}", e)
+        }
+        return;
+    }
+
+    if list.is_empty() {
+        println!("No plugins found in {}", folder.to_str().unwrap_or_default());
+    }
+
+    for info in list {
+        println!("{} {}.{}.{} (id {}, api {}) - {}",
+            info.name.as_deref().unwrap_or("<unknown>"),
+            info.version[0], info.version[1], info.version[2],
+            info.id, info.api_version, info.path.to_str().unwrap_or_default());
+
+        if let Some(reason) = info.error {
+            println!("    Will not load: {}", reason);
+        }
+    }
+}
+
+/// Creates a new plugin project from the template in assets/plugin_template
+pub(crate) fn new_plugin(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.starts_with(|c: char| c.is_ascii_lowercase())
+        || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        return Err(format!("Invalid plugin name {}: only lowercase letters, digits and _ are allowed, and it has to start with a letter", name));
+    }
+
+    let folder = PathBuf::from(name);
+    if folder.exists() {
+        return Err(format!("Unable to create plugin {}: {} already exists", name, folder.to_str().unwrap_or_default()));
+    }
+
+    let files = [
+        ("Cargo.toml", include_str!("../assets/plugin_template/Cargo.toml")),
+        ("src/lib.rs", include_str!("../assets/plugin_template/lib.rs"))
+    ];
+
+    for (file, template) in files {
+        let path = folder.join(file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Unable to create folder {}: {}", parent.to_str().unwrap_or_default(), e))?;
+        }
+
+        std::fs::write(path.as_path(), template.replace("{{name}}", name))
+            .map_err(|e| format!("Unable to write {}: {}", path.to_str().unwrap_or_default(), e))?;
+    }
+
+    println!("Created plugin {} in {}", name, folder.to_str().unwrap_or_default());
+    println!("Build it with `cargo build --release` and copy the library into the plugins folder");
+    Ok(())
+}
+
+/// Sends the command to the running instance
+pub(crate) fn run_ctl(config: &Config, cmd: CtlCommand) -> Result<(), String> {
+    let (method, params) = match &cmd {
+        CtlCommand::Plugins => ("list-plugins", serde_json::Value::Null),
+        CtlCommand::List => ("list-properties", serde_json::Value::Null),
+        CtlCommand::Get(property) => ("get", serde_json::json!({ "property": property })),
+        CtlCommand::Action(event) => ("trigger-event", serde_json::json!({ "event": event })),
+        CtlCommand::Set(property, value) => ("set", serde_json::json!({ "property": property, "value": value })),
+        CtlCommand::Memory => ("memory", serde_json::Value::Null),
+        CtlCommand::Trace(plugin, file) => {
+            // The running instance might be in another folder
+            let file = file.as_ref().map(|file| std::env::current_dir().map(|dir| dir.join(file)).unwrap_or_else(|_| PathBuf::from(file)));
+            ("trace-start", serde_json::json!({ "plugin": plugin, "file": file }))
+        },
+        CtlCommand::TraceStop(plugin) => ("trace-stop", serde_json::json!({ "plugin": plugin })),
+        CtlCommand::Restart(subsystem) => ("restart", serde_json::json!({ "subsystem": subsystem })),
+        CtlCommand::Shutdown => ("shutdown", serde_json::Value::Null)
+    };
+
+    let res = ipc::send_request(config.get_control_socket().as_path(), method, params)?;
+    if let Some(e) = res.error {
+        return Err(format!("Error {}: {}", e.code, e.message));
+    }
+    let result = res.result.unwrap_or_default();
+
+    match cmd {
+        CtlCommand::Plugins => {
+            for plugin in result.as_array().into_iter().flatten() {
+                let version: Vec<String> = plugin["version"].as_array().into_iter().flatten().map(|v| v.to_string()).collect();
+                println!("{} {} (id {}){}", plugin["name"].as_str().unwrap_or_default(), version.join("."), plugin["id"],
+                    if plugin["running"].as_bool().unwrap_or_default() { "" } else { " - starting" });
+            }
+        },
+        CtlCommand::List => {
+            for prop in result.as_array().into_iter().flatten() {
+                let value: Value = serde_json::from_value(prop["value"].clone()).unwrap_or(Value::None);
+                println!("{} - {}", prop["name"].as_str().unwrap_or_default(), value.to_display_string());
+            }
+        },
+        CtlCommand::Memory => {
+            for usage in result.as_array().into_iter().flatten() {
+                let bytes = |key: &str| crate::memory::format_bytes(usage[key].as_u64().unwrap_or_default() as usize);
+                println!("{} - {} (properties {} {}, arrays {} {}, strings {} {}, history {} {})", usage["plugin"].as_str().unwrap_or_default(), bytes("total_bytes"),
+                    usage["properties"], bytes("property_bytes"), usage["arrays"], bytes("array_bytes"),
+                    usage["strings"], bytes("string_bytes"), usage["history_samples"], bytes("history_bytes"));
+            }
+        },
+        CtlCommand::Get(_) => {
+            let value: Value = serde_json::from_value(result).map_err(|e| format!("Unable to parse value: {}", e))?;
+            println!("{}", value.to_display_string());
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DumpedProperty {
+    name: String,
+    handle: PropertyHandle,
+    value: Value
+}
+
+/// Runs the plugins until their startup is complete, prints all properties, and then shuts down
+pub(crate) async fn dump_properties(config: Config, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (event_loop, event_channel) = events::create_event_task();
+    let datastore: &'static tokio::sync::RwLock<DataStore> = Box::leak(Box::new(DataStore::new(event_channel, config)));
+
+    let mut plugin_set = pluginloader::load_all_plugins(datastore).await?;
+
+    let mut remaining = plugin_set.len();
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while datastore.read().await.count_plugins() < remaining && Instant::now() < deadline {
+        // Plugins that fail during startup exit, so we stop waiting on them
+        if let Ok(Some(_)) = time::timeout(Duration::from_millis(50), plugin_set.join_next()).await {
+            remaining -= 1;
+        }
+    }
+
+    let mut list = {
+        let ds_r = datastore.read().await;
+        let mut list = Vec::new();
+
+        for key in ds_r.iter_properties() {
+            if let (Some(name), Some(cont)) = (ds_r.read_property_name(key), ds_r.get_property_container(key)) {
+                let mut cache = ValueCache::default();
+                cont.read_web(&mut cache);
+                list.push(DumpedProperty { name, handle: *key, value: cache.value });
+            }
+        }
+
+        list
+    };
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+
+    datastore.write().await.start_shutdown().await;
+    while plugin_set.join_next().await.is_some() {}
+    let _ = event_loop.await;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&list)?);
+    } else {
+        for prop in list {
+            println!("{} - {}", prop.name, prop.value.to_display_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a backup of the config and dashboards into the folder
+pub(crate) fn backup(config: &Config, folder: Option<String>) -> Result<(), String> {
+    let folder = PathBuf::from(folder.unwrap_or(".".to_string()));
+    let path = backup::write_backup(&backup::Backup::create(config)?, folder.as_path())?;
+
+    println!("Saved backup to {}", path.display());
+    Ok(())
+}
+
+/// Restores the backup file, the current config can be broken (that is what the backup is for)
+pub(crate) fn restore(current: Option<&Config>, file: &str) -> Result<(), String> {
+    let backup = backup::read_backup(PathBuf::from(file).as_path())?;
+    let previous = backup.restore(current, PathBuf::from(".").as_path())?;
+
+    println!("Restored {}, the previous state was saved to {}", file, previous.display());
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! The core clock: a monotonic time in microseconds since launch, published as datarace.clock and
+//! readable by plugins through get_clock, so timestamps taken by different plugins can be compared
+//! (an Instant of one plugin is meaningless to another, and the system time can jump).
+
+use std::{sync::{atomic::{AtomicI64, Ordering}, Arc, OnceLock}, time::{Duration, Instant}};
+
+use log::debug;
+
+use crate::{datastore::DataStore, system, utils::ValueContainer};
+
+/// How often datarace.clock is updated, get_clock is always exact
+const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Microseconds since launch (the first call)
+pub(crate) fn now() -> i64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros() as i64
+}
+
+/// Creates datarace.clock (and starts the clock, if not already), has to be called before the
+/// plugins are loaded, so they can subscribe to it during their init
+pub(crate) async fn create_clock_property(datastore: &'static tokio::sync::RwLock<DataStore>) -> Arc<AtomicI64> {
+    let clock = Arc::new(AtomicI64::new(now()));
+
+    system::insert_property(&mut *datastore.write().await, "clock", ValueContainer::Dur(clock.clone()));
+    debug!("Clock created");
+
+    clock
+}
+
+/// Keeps datarace.clock updated till shutdown
+pub(crate) async fn run_clock(datastore: &'static tokio::sync::RwLock<DataStore>, clock: Arc<AtomicI64>) {
+    let mut interval = tokio::time::interval(UPDATE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        if datastore.read().await.get_shutdown_status() {
+            break;
+        }
+
+        clock.store(now(), Ordering::Release);
+    }
+
+    debug!("Clock stopped");
+}
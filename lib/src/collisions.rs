@@ -0,0 +1,65 @@
+//! Id collisions: plugin and property ids are hashes of their names, so two different names can
+//! end up with the same id. Those are rejected like any duplicate, but recorded here with both
+//! names so they can be told apart from registering the same name twice (see /collisions).
+//!
+//! The hash keys can not be changed, plugins compute their ids at compile time with them.
+
+use std::{sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use log::error;
+use serde::Serialize;
+
+/// Keeps memory bounded if a plugin keeps retrying
+const MAX_COLLISIONS: usize = 100;
+
+static COLLISIONS: Mutex<Vec<Collision>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Kind {
+    Plugin,
+    Property
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Collision {
+    pub(crate) kind: Kind,
+    pub(crate) id: u64,
+    /// Name that holds the id
+    pub(crate) existing: String,
+    /// Name that was rejected
+    pub(crate) rejected: String,
+    /// Unix time in ms of the first rejection
+    pub(crate) time: u64,
+    pub(crate) count: u64
+}
+
+/// Checks if the rejected name differs from the one holding the id (names are case insensitive),
+/// in which case it is logged and recorded. Returns true on a collision
+pub(crate) fn check(kind: Kind, id: u64, existing: &str, rejected: &str) -> bool {
+    if existing.eq_ignore_ascii_case(rejected) {
+        return false;
+    }
+
+    error!("Id collision: {:?} {} has the same id {} as {}, it was rejected", kind, rejected, id, existing);
+
+    let mut list = COLLISIONS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = list.iter_mut().find(|entry| entry.kind == kind && entry.id == id && entry.rejected.eq_ignore_ascii_case(rejected)) {
+        entry.count += 1;
+    } else if list.len() < MAX_COLLISIONS {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default();
+        list.push(Collision { kind, id, existing: existing.to_string(), rejected: rejected.to_string(), time, count: 1 });
+    }
+
+    true
+}
+
+/// Same as check for two properties of a plugin, which only hold their short name
+pub(crate) fn check_property(plugin: &str, id: u64, existing: &str, rejected: &str) -> bool {
+    check(Kind::Property, id, format!("{}.{}", plugin, existing).to_lowercase().as_str(), format!("{}.{}", plugin, rejected).to_lowercase().as_str())
+}
+
+/// All collisions since launch, oldest first
+pub(crate) fn list() -> Vec<Collision> {
+    COLLISIONS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
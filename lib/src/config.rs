@@ -0,0 +1,830 @@
+use std::{collections::HashMap, net::{IpAddr, Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, str::FromStr, sync::OnceLock, time::SystemTime};
+
+use log::{debug, error, info, warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+use tokio::time::{self, Duration};
+
+use crate::{aggregate::AggregateConfig, datastore::{DataStore, Subsystem}, event_stats, inputs::InputsConfig, pluginloader::DuplicatePlugins, quota::QuotaConfig, rules::RuleConfig, shutdown::ShutdownConfig, threads::ThreadConfig, web::i18n::Lang};
+
+/// Location of the config file, relative to the working directory
+const CONFIG_FILE: &str = "./Config.toml";
+
+/// Selected profile, which replaces the config file with Config.<profile>.toml
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// How often we check the config file for changes
+const CONFIG_POLL_RATE: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct Config {
+    plugin_location: PathString,
+    /// Names of the plugins to launch, None launches every plugin in the plugin_location
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plugins: Option<Vec<String>>,
+    /// SHA-256 hashes (hex) of the plugin libraries allowed to load, None allows every library
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plugin_hashes: Option<Vec<String>>,
+    /// Which plugin is launched when several share the same name: newest, oldest or none
+    duplicate_plugins: DuplicatePlugins,
+    dashboards_location: PathString,
+    log_level: String,
+    /// Language of the web ui, `auto` uses the language requested by the browser
+    language: String,
+    web_address: SocketAddr,
+    /// Clients allowed to access the webserver, None allows everyone (localhost is always allowed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web_whitelist: Option<Vec<IpMatcher>>,
+    /// Properties web clients may write (full names, or prefixes ending with *), none by default
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    web_writable: Vec<String>,
+    /// Blocks everything on the web that changes something (writing properties, editing dashboards,
+    /// reloading the config, restoring backups), regardless of the other settings
+    web_read_only: bool,
+    /// File the audit log of changes from the web is appended to, None only keeps it in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log: Option<PathString>,
+    /// Time between updates send to the dashboards in ms
+    websocket_update_rate: u64,
+    /// Up to which share of an array (of the values, or of the text for string arrays) only the changed
+    /// indices are send to the dashboards, above it the whole array
+    websocket_array_delta: f64,
+    /// Local socket for controlling this instance (see ipc)
+    control_socket: PathString,
+    /// Extra validation of the calls plugins make, for plugin development
+    strict_ffi: bool,
+    /// Folder of the lua automation scripts (requires the lua feature)
+    scripts_location: PathString,
+    /// Key for signing dashboard share links, None uses a random key (links only last till a restart).
+    /// Not serialized, so it is not shown on the settings page
+    #[serde(skip_serializing)]
+    share_secret: Option<String>,
+    /// Events whose trigger count and time are published as datarace.event.{event}.count/last
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    event_stats: Vec<String>,
+    // Tables from here on, toml can not serialize plain values after them
+    /// Limits on the resources of plugins, none by default
+    #[serde(skip_serializing_if = "QuotaConfig::is_unlimited")]
+    plugin_quota: QuotaConfig,
+    /// Plugins running on their own thread instead of the shared runtime
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    plugin_threads: HashMap<String, ThreadConfig>,
+    /// Statistics over properties, published as datarace.aggregate.{name}
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aggregates: Vec<AggregateConfig>,
+    /// Events/sets triggered when a property crosses a threshold
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<RuleConfig>,
+    /// Keys/buttons/axes of the host mapped to events and properties
+    inputs: InputsConfig,
+    /// Timeouts of the shutdown phases, after which remaining plugins are dropped
+    shutdown: ShutdownConfig
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            plugin_location: PathString::from("./plugins"),
+            plugins: None,
+            plugin_hashes: None,
+            duplicate_plugins: DuplicatePlugins::default(),
+            dashboards_location: PathString::from("./dashboards"),
+            log_level: "debug".to_string(),
+            language: "auto".to_string(),
+            web_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 3000),
+            web_whitelist: None,
+            web_writable: Vec::new(),
+            web_read_only: false,
+            audit_log: None,
+            websocket_update_rate: 10,
+            websocket_array_delta: crate::utils::ARRAY_DELTA,
+            control_socket: PathString::from("./datarace.sock"),
+            strict_ffi: false,
+            scripts_location: PathString::from("./scripts"),
+            share_secret: None,
+            event_stats: Vec::new(),
+            plugin_quota: QuotaConfig::default(),
+            plugin_threads: HashMap::new(),
+            aggregates: Vec::new(),
+            rules: Vec::new(),
+            inputs: InputsConfig::default(),
+            shutdown: ShutdownConfig::default()
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config from the given file.
+    /// If the file does not exist the default config is returned
+    fn load(path: &Path) -> Result<Config, ConfigError> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(content.as_str())?)
+    }
+
+    /// Overrides settings with the DATARACE_* environment variables (if set)
+    fn apply_env_overrides(&mut self) -> Result<(), ConfigError> {
+        fn env(name: &str) -> Option<String> {
+            std::env::var(name).ok().filter(|val| !val.is_empty())
+        }
+
+        fn parse<T: FromStr>(name: &str, val: String) -> Result<T, ConfigError> {
+            val.parse::<T>().map_err(|_| ConfigError::InvalidValue(format!("environment variable {} has invalid value {}", name, val)))
+        }
+
+        if let Some(val) = env("DATARACE_PLUGIN_LOCATION") {
+            self.plugin_location = PathString::from(val);
+        }
+        if let Some(val) = env("DATARACE_DUPLICATE_PLUGINS") {
+            self.duplicate_plugins = parse("DATARACE_DUPLICATE_PLUGINS", val)?;
+        }
+        if let Some(val) = env("DATARACE_DASHBOARDS_LOCATION") {
+            self.dashboards_location = PathString::from(val);
+        }
+        if let Some(val) = env("DATARACE_LOG_LEVEL") {
+            self.log_level = val;
+        }
+        if let Some(val) = env("DATARACE_LANGUAGE") {
+            self.language = val;
+        }
+        if let Some(val) = env("DATARACE_WEB_ADDRESS") {
+            self.web_address = parse("DATARACE_WEB_ADDRESS", val)?;
+        }
+        if let Some(val) = env("DATARACE_WEB_IP") {
+            self.web_address.set_ip(parse("DATARACE_WEB_IP", val)?);
+        }
+        if let Some(val) = env("DATARACE_WEB_PORT") {
+            self.web_address.set_port(parse("DATARACE_WEB_PORT", val)?);
+        }
+        if let Some(val) = env("DATARACE_WEB_WHITELIST") {
+            let list = val.split(',').map(|entry| entry.trim().parse::<IpMatcher>()).collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ConfigError::InvalidValue(format!("environment variable DATARACE_WEB_WHITELIST: {}", e)))?;
+            self.web_whitelist = Some(list);
+        }
+        if let Some(val) = env("DATARACE_WEB_WRITABLE") {
+            self.web_writable = val.split(',').map(|entry| entry.trim().to_string()).collect();
+        }
+        if let Some(val) = env("DATARACE_WEB_READ_ONLY") {
+            self.web_read_only = parse("DATARACE_WEB_READ_ONLY", val)?;
+        }
+        if let Some(val) = env("DATARACE_EVENT_STATS") {
+            self.event_stats = val.split(',').map(|entry| entry.trim().to_string()).collect();
+        }
+        if let Some(val) = env("DATARACE_AUDIT_LOG") {
+            self.audit_log = Some(PathString::from(val));
+        }
+        if let Some(val) = env("DATARACE_WEBSOCKET_UPDATE_RATE") {
+            self.websocket_update_rate = parse("DATARACE_WEBSOCKET_UPDATE_RATE", val)?;
+        }
+        if let Some(val) = env("DATARACE_WEBSOCKET_ARRAY_DELTA") {
+            self.websocket_array_delta = parse("DATARACE_WEBSOCKET_ARRAY_DELTA", val)?;
+        }
+        if let Some(val) = env("DATARACE_CONTROL_SOCKET") {
+            self.control_socket = PathString::from(val);
+        }
+        if let Some(val) = env("DATARACE_STRICT_FFI") {
+            self.strict_ffi = parse("DATARACE_STRICT_FFI", val)?;
+        }
+        if let Some(val) = env("DATARACE_SCRIPTS_LOCATION") {
+            self.scripts_location = PathString::from(val);
+        }
+        if let Some(val) = env("DATARACE_SHARE_SECRET") {
+            self.share_secret = Some(val);
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if LevelFilter::from_str(self.log_level.as_str()).is_err() {
+            return Err(ConfigError::InvalidValue(format!("log_level {} is not a valid level (off, error, warn, info, debug, trace)", self.log_level)));
+        }
+        if self.language != "auto" && Lang::from_code(self.language.as_str()).is_none() {
+            return Err(ConfigError::InvalidValue(format!("language {} is not supported (auto, en, de)", self.language)));
+        }
+        if self.share_secret.as_ref().is_some_and(|secret| secret.len() < 16) {
+            return Err(ConfigError::InvalidValue("share_secret has to be at least 16 characters".to_string()));
+        }
+        if self.websocket_update_rate == 0 {
+            return Err(ConfigError::InvalidValue("websocket_update_rate has to be at least 1ms".to_string()));
+        }
+        if !(0.0..=1.0).contains(&self.websocket_array_delta) {
+            return Err(ConfigError::InvalidValue(format!("websocket_array_delta {} has to be between 0 and 1", self.websocket_array_delta)));
+        }
+
+        for entry in self.web_writable.iter() {
+            let name = entry.strip_suffix('*').unwrap_or(entry.as_str());
+            let Some((plugin, _)) = name.split_once('.') else {
+                return Err(ConfigError::InvalidValue(format!("web_writable entry {} has no plugin (like plugin.property or plugin.*)", entry)));
+            };
+            if plugin.is_empty() || name.contains('*') {
+                return Err(ConfigError::InvalidValue(format!("web_writable entry {} is not a property name or prefix ending with *", entry)));
+            }
+            if plugin.eq_ignore_ascii_case(crate::system::CORE_PLUGIN_NAME) {
+                return Err(ConfigError::InvalidValue(format!("web_writable entry {}: core properties can not be written", entry)));
+            }
+        }
+
+        for hash in self.plugin_hashes.iter().flatten() {
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ConfigError::InvalidValue(format!("plugin_hashes entry {} is not a SHA-256 hash (64 hex characters)", hash)));
+            }
+        }
+
+        self.plugin_quota.validate().map_err(ConfigError::InvalidValue)?;
+        for (plugin, thread) in self.plugin_threads.iter() {
+            thread.validate(plugin.as_str()).map_err(ConfigError::InvalidValue)?;
+        }
+
+        crate::aggregate::validate_all(&self.aggregates).map_err(ConfigError::InvalidValue)?;
+        for rule in self.rules.iter() {
+            rule.validate().map_err(ConfigError::InvalidValue)?;
+        }
+        for name in self.event_stats.iter() {
+            event_stats::validate(name).map_err(ConfigError::InvalidValue)?;
+        }
+        self.inputs.validate().map_err(ConfigError::InvalidValue)?;
+
+        for (name, path) in [("plugin_location", &self.plugin_location), ("dashboards_location", &self.dashboards_location), ("control_socket", &self.control_socket), ("scripts_location", &self.scripts_location)] {
+            path.resolve().map_err(|e| ConfigError::InvalidValue(format!("{} {}: {}", name, path.0, e)))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_plugin_folder(&self) -> PathBuf {
+        self.plugin_location.to_path()
+    }
+
+    /// If the plugin should be launched, based on the plugins list
+    pub(crate) fn is_plugin_enabled(&self, name: &str) -> bool {
+        match &self.plugins {
+            Some(list) => list.iter().any(|p| p.eq_ignore_ascii_case(name)),
+            None => true
+        }
+    }
+
+    /// Checks the SHA-256 hash (lowercase hex) against the allow-list,
+    /// always true when no allow-list is configured
+    pub(crate) fn is_plugin_hash_allowed(&self, hash: &str) -> bool {
+        match &self.plugin_hashes {
+            Some(list) => list.iter().any(|h| h.eq_ignore_ascii_case(hash)),
+            None => true
+        }
+    }
+
+    pub(crate) fn has_plugin_hashes(&self) -> bool {
+        self.plugin_hashes.is_some()
+    }
+
+    pub(crate) fn get_duplicate_plugins(&self) -> DuplicatePlugins {
+        self.duplicate_plugins
+    }
+
+    pub(crate) fn get_dashboards_folder(&self) -> PathBuf {
+        self.dashboards_location.to_path()
+    }
+
+    pub(crate) fn get_log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(self.log_level.as_str()).unwrap_or(LevelFilter::Debug)
+    }
+
+    /// None if the language should be negotiated with the browser
+    pub(crate) fn get_language(&self) -> Option<Lang> {
+        Lang::from_code(self.language.as_str())
+    }
+
+    pub(crate) fn get_web_address(&self) -> SocketAddr {
+        self.web_address
+    }
+
+    /// If the web interface may not change anything
+    pub(crate) fn is_web_read_only(&self) -> bool {
+        self.web_read_only
+    }
+
+    /// If the client is allowed to access the webserver
+    pub(crate) fn is_web_client_allowed(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if ip.is_loopback() {
+            return true;
+        }
+
+        match &self.web_whitelist {
+            Some(list) => list.iter().any(|m| m.matches(ip)),
+            None => true
+        }
+    }
+
+    /// If web clients may write the property (by the full name), based on the web_writable list
+    pub(crate) fn is_web_writable(&self, name: &str) -> bool {
+        if self.web_read_only {
+            return false;
+        }
+
+        let name = name.to_lowercase();
+
+        self.web_writable.iter().any(|entry| {
+            let entry = entry.to_lowercase();
+            match entry.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == entry
+            }
+        })
+    }
+
+    pub(crate) fn get_websocket_update_rate(&self) -> Duration {
+        Duration::from_millis(self.websocket_update_rate)
+    }
+
+    pub(crate) fn get_websocket_array_delta(&self) -> f64 {
+        self.websocket_array_delta
+    }
+
+    pub(crate) fn get_audit_log(&self) -> Option<PathBuf> {
+        self.audit_log.as_ref().map(PathString::to_path)
+    }
+
+    pub(crate) fn get_control_socket(&self) -> PathBuf {
+        self.control_socket.to_path()
+    }
+
+    pub(crate) fn is_strict_ffi(&self) -> bool {
+        self.strict_ffi
+    }
+
+    #[cfg_attr(not(feature = "lua"), allow(dead_code))]
+    pub(crate) fn get_scripts_folder(&self) -> PathBuf {
+        self.scripts_location.to_path()
+    }
+
+    pub(crate) fn get_share_secret(&self) -> Option<&str> {
+        self.share_secret.as_deref()
+    }
+
+    pub(crate) fn get_plugin_quota(&self) -> &QuotaConfig {
+        &self.plugin_quota
+    }
+
+    /// The thread settings if the plugin runs on a dedicated thread
+    pub(crate) fn get_plugin_thread(&self, name: &str) -> Option<&ThreadConfig> {
+        self.plugin_threads.iter().find(|(plugin, _)| plugin.eq_ignore_ascii_case(name)).map(|(_, thread)| thread)
+    }
+
+    pub(crate) fn get_aggregates(&self) -> &[AggregateConfig] {
+        self.aggregates.as_slice()
+    }
+
+    pub(crate) fn get_rules(&self) -> &[RuleConfig] {
+        self.rules.as_slice()
+    }
+
+    pub(crate) fn get_event_stats(&self) -> &[String] {
+        self.event_stats.as_slice()
+    }
+
+    pub(crate) fn get_inputs(&self) -> &InputsConfig {
+        &self.inputs
+    }
+
+    pub(crate) fn get_shutdown(&self) -> &ShutdownConfig {
+        &self.shutdown
+    }
+
+    /// Returns the names of all settings that differ between the two configs,
+    /// but can not be applied without restarting
+    pub(crate) fn requires_restart(&self, other: &Config) -> Vec<&'static str> {
+        let mut list = Vec::new();
+
+        if self.plugin_location != other.plugin_location {
+            list.push("plugin_location");
+        }
+        if self.plugins != other.plugins {
+            list.push("plugins");
+        }
+        if self.plugin_hashes != other.plugin_hashes {
+            list.push("plugin_hashes");
+        }
+        if self.plugin_quota != other.plugin_quota {
+            list.push("plugin_quota");
+        }
+        if self.plugin_threads != other.plugin_threads {
+            list.push("plugin_threads");
+        }
+        if self.duplicate_plugins != other.duplicate_plugins {
+            list.push("duplicate_plugins");
+        }
+        if self.control_socket != other.control_socket {
+            list.push("control_socket");
+        }
+        if self.aggregates != other.aggregates {
+            list.push("aggregates");
+        }
+        if self.rules != other.rules {
+            list.push("rules");
+        }
+        if self.event_stats != other.event_stats {
+            list.push("event_stats");
+        }
+        if self.inputs != other.inputs {
+            list.push("inputs");
+        }
+        if self.shutdown != other.shutdown {
+            list.push("shutdown");
+        }
+
+        list
+    }
+
+    /// Takes over the settings that require a restart from the running config,
+    /// so the config only reflects what is actually in use
+    fn keep_restart_settings(&mut self, running: &Config) {
+        self.plugin_location = running.plugin_location.clone();
+        self.plugins = running.plugins.clone();
+        self.plugin_hashes = running.plugin_hashes.clone();
+        self.plugin_quota = running.plugin_quota.clone();
+        self.plugin_threads = running.plugin_threads.clone();
+        self.duplicate_plugins = running.duplicate_plugins;
+        self.control_socket = running.control_socket.clone();
+        self.aggregates = running.aggregates.clone();
+        self.rules = running.rules.clone();
+        self.event_stats = running.event_stats.clone();
+        self.inputs = running.inputs.clone();
+        self.shutdown = running.shutdown.clone();
+    }
+}
+
+/// A path as written in the config, which is resolved when used:
+/// - `~/` is the home folder of the user
+/// - `./` is the working directory
+/// - `:/` is the folder containing the executable
+/// - `{config}` is the folder containing the config file
+/// - `$VAR`, `${VAR}` and `%VAR%` are replaced with the environment variable
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub(crate) struct PathString(String);
+
+impl From<&str> for PathString {
+    fn from(value: &str) -> Self {
+        PathString(value.to_string())
+    }
+}
+
+impl From<String> for PathString {
+    fn from(value: String) -> Self {
+        PathString(value)
+    }
+}
+
+impl PathString {
+    /// Resolves the path, fails if a variable is not set or the home/executable folder can not be determined
+    pub(crate) fn resolve(&self) -> Result<PathBuf, String> {
+        let text = expand_variables(self.0.as_str())?;
+        let text = if text.contains("{config}") {
+            let config = get_config_path();
+            let folder = config.parent().and_then(|p| p.to_str()).unwrap_or(".");
+            text.replace("{config}", if folder.is_empty() { "." } else { folder })
+        } else {
+            text
+        };
+
+        if text == "~" {
+            return home_folder();
+        }
+        if let Some(rest) = text.strip_prefix("~/").or_else(|| text.strip_prefix("~\\")) {
+            return Ok(home_folder()?.join(rest));
+        }
+        if let Some(rest) = text.strip_prefix(":/").or_else(|| text.strip_prefix(":\\")) {
+            let exe = std::env::current_exe().map_err(|e| format!("unable to locate executable: {}", e))?;
+            let folder = exe.parent().ok_or("unable to locate executable folder".to_string())?;
+            return Ok(folder.join(rest));
+        }
+
+        Ok(PathBuf::from(text))
+    }
+
+    /// Resolves the path, falling back to the unexpanded text (validate has already reported any errors)
+    fn to_path(&self) -> PathBuf {
+        self.resolve().unwrap_or_else(|_| PathBuf::from(self.0.as_str()))
+    }
+}
+
+fn home_folder() -> Result<PathBuf, String> {
+    std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+        .ok_or("unable to determine home folder".to_string())
+}
+
+/// Replaces `$VAR`, `${VAR}` and `%VAR%` with the value of the environment variable.
+/// A `$` or `%` that does not start a variable name is kept as is
+fn expand_variables(text: &str) -> Result<String, String> {
+    fn is_name(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    fn lookup(name: &str) -> Result<String, String> {
+        std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find(['$', '%']) {
+        output.push_str(&rest[..pos]);
+        let sign = &rest[pos..pos + 1];
+        let after = &rest[pos + 1..];
+
+        let (name, remaining) = if sign == "$" {
+            if let Some(braced) = after.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => (&braced[..end], &braced[end + 1..]),
+                    None => return Err("missing closing } for ${".to_string())
+                }
+            } else {
+                let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        } else {
+            match after.find('%') {
+                Some(end) if end > 0 && after[..end].chars().all(is_name) => (&after[..end], &after[end + 1..]),
+                _ => ("", after)
+            }
+        };
+
+        if name.is_empty() {
+            output.push_str(sign);
+        } else {
+            output.push_str(lookup(name)?.as_str());
+        }
+        rest = remaining;
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// An entry of the web whitelist, parsed from one of:
+/// - a single address: `192.168.1.20` or `fe80::1`
+/// - CIDR notation: `192.168.1.0/24` or `fd00::/8`
+/// - a range (inclusive): `192.168.1.10-192.168.1.50`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum IpMatcher {
+    Single(IpAddr),
+    Cidr(IpAddr, u8),
+    Range(IpAddr, IpAddr)
+}
+
+impl IpMatcher {
+    pub(crate) fn matches(&self, ip: IpAddr) -> bool {
+        match self {
+            Self::Single(addr) => *addr == ip,
+            Self::Cidr(net, prefix) => match (net, ip) {
+                (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                    u32::from(*net) & mask == u32::from(ip) & mask
+                },
+                (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                    u128::from(*net) & mask == u128::from(ip) & mask
+                },
+                _ => false
+            },
+            Self::Range(start, end) => match (start, end, ip) {
+                (IpAddr::V4(start), IpAddr::V4(end), IpAddr::V4(ip)) => (*start..=*end).contains(&ip),
+                (IpAddr::V6(start), IpAddr::V6(end), IpAddr::V6(ip)) => (*start..=*end).contains(&ip),
+                _ => false
+            }
+        }
+    }
+}
+
+impl FromStr for IpMatcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse_ip(text: &str) -> Result<IpAddr, String> {
+            // Addresses of clients are compared in canonical form, so ::ffff:a.b.c.d has to be too
+            text.trim().parse::<IpAddr>().map(|ip| ip.to_canonical()).map_err(|_| format!("{} is not a valid ip address", text.trim()))
+        }
+
+        if let Some((net, prefix)) = s.split_once('/') {
+            let net = parse_ip(net)?;
+            let max = if net.is_ipv4() { 32 } else { 128 };
+            let prefix = prefix.trim().parse::<u8>().ok().filter(|p| *p <= max)
+                .ok_or(format!("{} has an invalid prefix length (0 to {})", s, max))?;
+            Ok(Self::Cidr(net, prefix))
+        } else if let Some((start, end)) = s.split_once('-') {
+            let (start, end) = (parse_ip(start)?, parse_ip(end)?);
+            if start.is_ipv4() != end.is_ipv4() {
+                return Err(format!("{} mixes ipv4 and ipv6", s));
+            }
+            if start > end {
+                return Err(format!("{} has the start of the range after the end", s));
+            }
+            Ok(Self::Range(start, end))
+        } else {
+            Ok(Self::Single(parse_ip(s)?))
+        }
+    }
+}
+
+impl TryFrom<String> for IpMatcher {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for IpMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single(addr) => write!(f, "{}", addr),
+            Self::Cidr(net, prefix) => write!(f, "{}/{}", net, prefix),
+            Self::Range(start, end) => write!(f, "{}-{}", start, end)
+        }
+    }
+}
+
+impl From<IpMatcher> for String {
+    fn from(value: IpMatcher) -> Self {
+        value.to_string()
+    }
+}
+
+/// Selects the profile, has to be called before the config is read (and can only be set once)
+pub(crate) fn set_profile(name: String) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("Invalid profile name {}: only letters, digits, _ and - are allowed", name));
+    }
+
+    PROFILE.set(name).map_err(|_| "Profile was already set".to_string())
+}
+
+/// Returns the path of the config file, depending on the selected profile
+pub(crate) fn get_config_path() -> PathBuf {
+    match PROFILE.get() {
+        Some(profile) => PathBuf::from(format!("./Config.{}.toml", profile)),
+        None => PathBuf::from(CONFIG_FILE)
+    }
+}
+
+/// Reads the config file (if it exists), applies the environment variable overrides and validates the result
+pub(crate) fn read_config(path: &Path) -> Result<Config, ConfigError> {
+    let mut config = if PROFILE.get().is_some() && !path.exists() {
+        // A missing default config is fine, but a missing profile is most likely a typo
+        return Err(ConfigError::FSError(std::io::Error::new(std::io::ErrorKind::NotFound, format!("profile {} does not exist", path.to_str().unwrap_or_default()))));
+    } else {
+        Config::load(path)?
+    };
+    config.apply_env_overrides()?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// Parses the text of a config file (like from a backup), with the same checks as read_config
+pub(crate) fn parse_config(text: &str) -> Result<Config, ConfigError> {
+    let mut config: Config = toml::from_str(text)?;
+    config.apply_env_overrides()?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+    FSError(std::io::Error),
+    ParseError(toml::de::Error),
+    InvalidValue(String)
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        Self::FSError(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::ParseError(value)
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FSError(e) => write!(f, "Failed to open file: {}", e),
+            Self::ParseError(e) => write!(f, "Unable to parse: {}", e),
+            Self::InvalidValue(text) => write!(f, "Invalid value: {}", text)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Rereads the config file and applies the new config to the datastore.
+/// Returns the settings that were changed, but require a restart
+pub(crate) async fn reload_config(datastore: &'static tokio::sync::RwLock<DataStore>) -> Result<Vec<&'static str>, ConfigError> {
+    let mut config = read_config(get_config_path().as_path())?;
+
+    let mut ds_w = datastore.write().await;
+    let restart = ds_w.get_config().requires_restart(&config);
+    config.keep_restart_settings(ds_w.get_config());
+    log::set_max_level(config.get_log_level());
+    crate::api_func::set_strict_ffi(config.is_strict_ffi());
+    let web_moved = ds_w.get_config().get_web_address() != config.get_web_address();
+    ds_w.set_config(config);
+    if web_moved {
+        ds_w.restart_subsystem(Subsystem::Web);
+    }
+    drop(ds_w);
+
+    if restart.is_empty() {
+        info!("Config reloaded");
+    } else {
+        warn!("Config reloaded, but the following settings require a restart to apply: {}", restart.join(", "));
+    }
+
+    Ok(restart)
+}
+
+/// Watches the config file for changes (and listens to SIGHUP on unix), reloading it when necessary
+/// This task exits once shutdown has begun
+pub(crate) async fn run_config_watcher(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let mut hangup = HangupListener::new();
+    let mut last_modified = get_config_modified();
+
+    loop {
+        let forced = tokio::select! {
+            _ = time::sleep(CONFIG_POLL_RATE) => false,
+            _ = hangup.recv() => true
+        };
+
+        if datastore.read().await.get_shutdown_status() {
+            break;
+        }
+
+        let modified = get_config_modified();
+        if forced || modified != last_modified {
+            last_modified = modified;
+
+            if let Err(e) = reload_config(datastore).await {
+                error!("Failed to reload config, keeping previous config: {}", e);
+            }
+        }
+    }
+
+    debug!("Config watcher stopped");
+}
+
+fn get_config_modified() -> Option<SystemTime> {
+    std::fs::metadata(get_config_path()).ok()?.modified().ok()
+}
+
+#[cfg(unix)]
+struct HangupListener(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl HangupListener {
+    fn new() -> Self {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(sig) => HangupListener(Some(sig)),
+            Err(e) => {
+                error!("Unable to listen for SIGHUP, config reload only possible through file changes: {}", e);
+                HangupListener(None)
+            }
+        }
+    }
+
+    async fn recv(&mut self) {
+        if let Some(sig) = self.0.as_mut() {
+            if sig.recv().await.is_some() {
+                debug!("Received SIGHUP");
+                return;
+            }
+        }
+
+        std::future::pending::<()>().await
+    }
+}
+
+/// There is no SIGHUP outside of unix, so we only rely on file changes
+#[cfg(not(unix))]
+struct HangupListener;
+
+#[cfg(not(unix))]
+impl HangupListener {
+    fn new() -> Self {
+        HangupListener
+    }
+
+    async fn recv(&mut self) {
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,177 @@
+//! Crash reports: on a panic in the core (or when DataRace exits with an error) a report with the
+//! reason, the backtrace, the running plugins with their queues, the config and the last log lines
+//! is written into the working directory, so crashes on other machines can be reported.
+//!
+//! Panics in plugin libraries are caught by the plugin macros, the ones of built-in and script plugins by catch_panic.
+//! The panic hook runs before it is known if a panic is caught, so it only keeps the panic, the
+//! report is written by report_panics once the panic reached the top of the main thread.
+
+use std::{any::Any, backtrace::Backtrace, cell::{Cell, RefCell}, collections::VecDeque, fmt::Write, panic::{AssertUnwindSafe, PanicHookInfo}, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Mutex, OnceLock, TryLockError}, time::SystemTime};
+
+use log::{Log, Metadata, Record};
+
+use crate::{built_info, config, datastore::DataStore};
+
+/// How many log lines are kept for the report
+const LOG_LINES: usize = 200;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static DATASTORE: OnceLock<&'static tokio::sync::RwLock<DataStore>> = OnceLock::new();
+/// Only the first crash is written, the panics after it are usually caused by it
+static WRITTEN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The last panic on this thread with its backtrace, taken by report_panics
+    static LAST_PANIC: RefCell<Option<(String, Backtrace)>> = const { RefCell::new(None) };
+    /// Set while catch_panic runs, the panics within are recovered and not worth a backtrace
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Forwards to env_logger, keeping the last lines for the report
+struct CrashLogger {
+    inner: env_logger::Logger
+}
+
+impl Log for CrashLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("{} {:<5} {}] {}", humantime::format_rfc3339_seconds(SystemTime::now()), record.level(), record.target(), record.args());
+        let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+        if log.len() >= LOG_LINES {
+            log.pop_front();
+        }
+        log.push_back(line);
+        drop(log);
+
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets env_logger as the logger, wrapped so the last lines end up in the report
+pub(crate) fn init_logger(builder: &mut env_logger::Builder) {
+    let logger = CrashLogger { inner: builder.build() };
+    let max_level = logger.inner.filter();
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Installs the panic hook, the previous hook still prints the panic afterwards
+pub(crate) fn install_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !CATCHING.get() {
+            let panic = (panic_reason(info), Backtrace::force_capture());
+            LAST_PANIC.with(|last| if let Ok(mut last) = last.try_borrow_mut() {
+                *last = Some(panic);
+            });
+        }
+        previous(info);
+    }));
+}
+
+/// Runs the function, writing the report if it panics before continuing to unwind.
+/// Panics caught further down (tasks, plugins) never get here, so they don't count as a crash
+pub(crate) fn report_panics<R>(func: impl FnOnce() -> R) -> R {
+    match std::panic::catch_unwind(AssertUnwindSafe(func)) {
+        Ok(res) => res,
+        Err(payload) => {
+            match LAST_PANIC.take() {
+                Some((reason, backtrace)) => write_report(reason.as_str(), Some(backtrace)),
+                None => write_report(format!("Panicked: {}", payload_message(payload.as_ref())).as_str(), None)
+            }
+            std::panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// The report includes the plugins once the datastore is set
+pub(crate) fn set_datastore(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let _ = DATASTORE.set(datastore);
+}
+
+/// Runs the function and catches a panic, returning the panic message.
+/// Used for built-in and script plugins, so a panic in them only stops the plugin
+pub(crate) fn catch_panic<R>(func: impl FnOnce() -> R) -> Result<R, String> {
+    // Restoring the previous value, as a plugin can be called from within another one
+    let outer = CATCHING.replace(true);
+    let res = std::panic::catch_unwind(AssertUnwindSafe(func));
+    CATCHING.set(outer);
+
+    res.map_err(|payload| payload_message(payload.as_ref()))
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|text| text.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+fn panic_reason(info: &PanicHookInfo) -> String {
+    let msg = payload_message(info.payload());
+    let location = info.location().map(|loc| format!(" at {}:{}:{}", loc.file(), loc.line(), loc.column())).unwrap_or_default();
+
+    format!("Thread {} panicked{}: {}", std::thread::current().name().unwrap_or("<unnamed>"), location, msg)
+}
+
+/// Writes the report, unless one was already written. This runs while unwinding a panic, so
+/// nothing here may block or panic (locks are only tried)
+pub(crate) fn write_report(reason: &str, backtrace: Option<Backtrace>) {
+    if WRITTEN.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let now = SystemTime::now();
+    let mut report = String::new();
+    let _ = writeln!(report, "DataRace {} (api {}) on {} {}", built_info::PKG_VERSION, crate::API_VERSION, std::env::consts::OS, std::env::consts::ARCH);
+    let _ = writeln!(report, "Time: {}", humantime::format_rfc3339_seconds(now));
+    let _ = writeln!(report, "Reason: {}", reason);
+
+    if let Some(backtrace) = backtrace {
+        let _ = writeln!(report, "\nBacktrace:\n{}", backtrace);
+    }
+
+    let _ = writeln!(report, "\nPlugins:");
+    match DATASTORE.get().map(|datastore| datastore.try_read()) {
+        Some(Ok(ds_r)) => {
+            let queues = ds_r.list_queue_lengths();
+            for (id, name, version, running) in ds_r.list_plugins() {
+                let queue = queues.iter().find(|(other, _, _)| *other == id).map(|(_, _, len)| *len).unwrap_or_default();
+                let _ = writeln!(report, "  {} {}.{}.{} (id {}){}, queue {}", name, version[0], version[1], version[2], id,
+                    if running { "" } else { " - starting" }, queue);
+            }
+
+            let _ = writeln!(report, "\nConfig ({}):", config::get_config_path().display());
+            let _ = writeln!(report, "{}", toml::to_string(ds_r.get_config()).unwrap_or_else(|e| format!("Unable to serialize: {}", e)));
+        },
+        Some(Err(_)) => { let _ = writeln!(report, "  Unavailable, the datastore is locked"); },
+        None => { let _ = writeln!(report, "  None, crashed before launch"); }
+    }
+
+    let _ = writeln!(report, "\nLast log lines:");
+    match LOG.try_lock() {
+        Ok(log) => log.iter().for_each(|line| { let _ = writeln!(report, "{}", line); }),
+        Err(TryLockError::Poisoned(e)) => e.into_inner().iter().for_each(|line| { let _ = writeln!(report, "{}", line); }),
+        // The panic happened while logging on this thread
+        Err(TryLockError::WouldBlock) => { let _ = writeln!(report, "Unavailable, the log is locked"); }
+    }
+
+    let time = humantime::format_rfc3339_seconds(now).to_string();
+    let path = PathBuf::from(format!("datarace-crash-{}-{}.txt", built_info::PKG_VERSION, time.trim_end_matches('Z').replace(':', "-")));
+    match std::fs::write(path.as_path(), report) {
+        Ok(()) => eprintln!("Crash report written to {}, please attach it when reporting this", path.display()),
+        Err(e) => eprintln!("Unable to write crash report {}: {}", path.display(), e)
+    }
+}
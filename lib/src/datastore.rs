@@ -1,40 +1,69 @@
-use std::{path::PathBuf, str::FromStr};
-
 use log::info;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tokio::sync::RwLock;
 use kanal::{AsyncSender, Sender};
 use hashbrown::HashMap;
 
-use crate::{events::EventMessage, pluginloader::LoaderMessage, utils::{PluginStatus, ValueContainer}, DataStoreReturnCode, PluginHandle, PropertyHandle};
+use crate::{aliases::PropertyAliases, audit::AuditLog, config::Config, events::EventMessage, notifications::{Notifications, Severity}, pluginloader::{DuplicatePlugin, LoaderMessage}, session::SessionCounters, utils::{PluginStatus, ValueContainer}, DataStoreReturnCode, PluginHandle, PropertyHandle};
+
+/// Parts of DataRace that can be restarted on their own, without the plugins
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Subsystem {
+    Web,
+    Events
+}
 
 /// This is our centralized State
 pub(crate) struct DataStore {
     plugins: HashMap<u64, Plugin>,
+    /// Plugin id -> the plugins watching it (see watch_plugin), kept when the plugin stops
+    watchers: HashMap<u64, Vec<u64>>,
     // Serves for access by the websocket
     properties: HashMap<PropertyHandle, ValueContainer>,
     // As the hash is not reversible, but for certain opertations we need the name...
+    // Filled on creation, names are kept after the property is deleted (so logs stay readable)
     prop_names: HashMap<PropertyHandle, String>,
+    /// Old names of renamed properties (see aliases)
+    aliases: PropertyAliases,
     
     config: Config,
     
     // task_map: HashMap<tokio::task::Id, (u64, String)>,
     
     shutdown: bool,
+    /// Set till the webserver picks the restart up
+    web_restart: bool,
+
+    event_channel: kanal::Sender<EventMessage>,
+
+    notifications: Notifications,
 
-    event_channel: kanal::Sender<EventMessage>
+    audit: AuditLog,
+
+    session: SessionCounters,
+
+    /// Plugins found multiple times at launch (see duplicate_plugins)
+    duplicate_plugins: Vec<DuplicatePlugin>
 }
 
 impl DataStore {
-    pub fn new(event_channel: kanal::Sender<EventMessage>) -> RwLock<DataStore> {
+    pub fn new(event_channel: kanal::Sender<EventMessage>, config: Config) -> RwLock<DataStore> {
         RwLock::new(DataStore {
             plugins: HashMap::default(),
+            watchers: HashMap::default(),
             properties: HashMap::default(),
             prop_names: HashMap::default(),
-            config: Config::default(),
+            aliases: PropertyAliases::default(),
+            config,
             // task_map: HashMap::default(),
             shutdown: false,
-            event_channel
+            web_restart: false,
+            event_channel,
+            notifications: Notifications::default(),
+            audit: AuditLog::default(),
+            session: SessionCounters::default(),
+            duplicate_plugins: Vec::new()
         })
     }
 
@@ -43,7 +72,7 @@ impl DataStore {
             return None;
         }
         
-        if self.plugins.contains_key(&id) {
+        if self.plugins.contains_key(&id) || crate::system::is_reserved_id(id) {
             return None;
         } 
 
@@ -54,6 +83,7 @@ impl DataStore {
     pub(crate) async fn delete_plugin(&mut self, id: u64, safe_shutdown: bool) -> DataStoreReturnCode {
         if self.plugins.contains_key(&id) {
             let handle = self.plugins[&id].handle;
+            let was_running = self.plugins[&id].plugin_status == PluginStatus::Running;
             
             self.plugins.remove(&id);
 
@@ -74,12 +104,25 @@ impl DataStore {
             // Deletes the properties of this plugin from the datastore,
             // so they won't be available to the web endpoint anymore
             self.properties.retain(|&k, _| k.plugin != id );
+            self.aliases.remove_plugin(id);
 
             let _ = self.event_channel.as_async().send(EventMessage::RemovePlugin(id));
 
-            // TODO send a message to all other plugins so they can remove leftover
-            // properties/subscriptions from
-            // this plugin
+            for watchers in self.watchers.values_mut() {
+                watchers.retain(|watcher| *watcher != id);
+            }
+            if was_running {
+                if let Some(watchers) = self.watchers.get(&id) {
+                    for watcher in watchers {
+                        self.send_message_to_plugin(*watcher, LoaderMessage::WatchedPluginStopped(id)).await;
+                    }
+                }
+            }
+
+            // The other plugins remove their subscriptions to this plugin (and this plugin as subscriber)
+            for other in self.plugins.values() {
+                let _ = other.channel.send(LoaderMessage::PluginRemoved(id)).await;
+            }
 
             DataStoreReturnCode::Ok
         } else {
@@ -92,14 +135,45 @@ impl DataStore {
         self.event_channel.clone()
     }
 
+    /// Lists id, name, version and if the plugin is running of all plugins
+    pub(crate) fn list_plugins(&self) -> Vec<(u64, String, [u16;3], bool)> {
+        self.plugins.iter().filter_map(|(id, p)| {
+            // The handle is only deallocated after removal from this map, and name/version are never changed
+            let han = unsafe { p.handle.as_ref() }?;
+            Some((*id, han.name.clone(), han.version, p.plugin_status == PluginStatus::Running))
+        }).collect()
+    }
+
+    /// Lists id, name and the number of messages waiting in the queue of all plugins
+    pub(crate) fn list_queue_lengths(&self) -> Vec<(u64, String, usize)> {
+        self.plugins.iter().filter_map(|(id, p)| {
+            let han = unsafe { p.handle.as_ref() }?;
+            Some((*id, han.name.clone(), p.channel.len()))
+        }).collect()
+    }
+
+    pub(crate) fn has_plugin(&self, id: u64) -> bool {
+        self.plugins.contains_key(&id)
+    }
+
     pub(crate) fn count_plugins(&self) -> usize {
         self.plugins.iter().filter(|(_,p)|p.plugin_status == PluginStatus::Running).count()
     }
 
     pub(crate) async fn start_shutdown(&mut self) {
+        self.begin_shutdown();
+        self.deliver_shutdown().await;
+    }
+
+    /// Marks the shutdown, which stops the webserver and the other tasks and rejects new plugins
+    pub(crate) fn begin_shutdown(&mut self) {
         info!("Beginning Shutdown... ");
         self.shutdown = true;
+        crate::service::notify("STOPPING=1");
+    }
 
+    /// Sends Shutdown to all plugins and the event loop, can block when their queues are full
+    pub(crate) async fn deliver_shutdown(&self) {
         for (_,plugin) in self.plugins.iter() {
             let _ = plugin.channel.send(LoaderMessage::Shutdown).await;
         }
@@ -111,6 +185,30 @@ impl DataStore {
         self.shutdown
     }
 
+    /// Requests a restart of the subsystem, the plugins keep running.
+    /// Returns false if shutdown has begun
+    pub(crate) fn restart_subsystem(&mut self, subsystem: Subsystem) -> bool {
+        if self.shutdown {
+            return false;
+        }
+
+        info!("Restart of the {:?} subsystem requested", subsystem);
+        match subsystem {
+            Subsystem::Web => self.web_restart = true,
+            // Queued behind the messages already send, so none of them get lost
+            Subsystem::Events => { let _ = self.event_channel.send(EventMessage::Restart); }
+        }
+        true
+    }
+
+    pub(crate) fn is_web_restart_requested(&self) -> bool {
+        self.web_restart
+    }
+
+    pub(crate) fn finish_web_restart(&mut self) {
+        self.web_restart = false;
+    }
+
     pub(crate) async fn send_message_to_plugin(&self, id: u64, msg: LoaderMessage) -> bool {
         if let Some(plugin) = self.plugins.get(&id) {
             plugin.channel.send(msg).await.is_ok()
@@ -130,15 +228,37 @@ impl DataStore {
         self.prop_names.insert(handle, name);
     }
 
-    /// Retrieves the property name
+    /// Retrieves the property name (or the name of the alias)
     pub(crate) fn read_property_name(&self, handle: &PropertyHandle) -> Option<String> {
-        Some(self.prop_names.get(handle)?.clone())
+        self.prop_names.get(handle).map(String::as_str).or_else(|| self.aliases.get_name(handle)).map(str::to_string)
+    }
+
+    /// Name of the property for logs and traces, the ids (plugin|property) if it is unknown
+    pub(crate) fn property_label(&self, handle: &PropertyHandle) -> String {
+        self.read_property_name(handle).unwrap_or_else(|| format!("{}|{}", handle.plugin, handle.property))
+    }
+
+    pub(crate) fn get_aliases(&self) -> &PropertyAliases {
+        &self.aliases
+    }
+
+    pub(crate) fn get_aliases_mut(&mut self) -> &mut PropertyAliases {
+        &mut self.aliases
     }
 
     /// Retrieves a reference to the valuecontainer (if present)
     /// There are again no checks, you should only read the values contained
     pub(crate) fn get_property_container<'a>(&'a self, handle: &PropertyHandle) -> Option<&'a ValueContainer> {
-        self.properties.get(handle)
+        // Properties take priority over an alias with the same name
+        self.properties.get(handle).or_else(|| self.properties.get(&self.aliases.resolve(handle)?))
+    }
+
+    /// The property get_property_container would return, resolving old names of renamed properties
+    pub(crate) fn resolve_property(&self, handle: &PropertyHandle) -> Option<PropertyHandle> {
+        if self.properties.contains_key(handle) {
+            return Some(*handle);
+        }
+        self.aliases.resolve(handle).filter(|target| self.properties.contains_key(target))
     }
 
     /// Deletes the Property (only if it exists) with no further checks
@@ -155,10 +275,41 @@ impl DataStore {
         &self.config
     }
 
+    /// Replaces the config, settings that are read on use apply immediatly
+    pub(crate) fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
     pub(crate) fn iter_properties<'a>(&'a self) -> hashbrown::hash_map::Keys<'a, PropertyHandle, ValueContainer> {
         self.properties.keys()
     }
 
+    /// Adds the notification, it is send out to the dashboards with the next update of the websocket
+    pub(crate) fn raise_notification(&mut self, severity: Severity, source: String, text: String) {
+        self.notifications.push(severity, source, text);
+    }
+
+    pub(crate) fn set_duplicate_plugins(&mut self, duplicates: Vec<DuplicatePlugin>) {
+        self.duplicate_plugins = duplicates;
+    }
+
+    pub(crate) fn get_duplicate_plugins(&self) -> &[DuplicatePlugin] {
+        &self.duplicate_plugins
+    }
+
+    pub(crate) fn get_notifications(&self) -> &Notifications {
+        &self.notifications
+    }
+
+    /// Records while only holding a read lock
+    pub(crate) fn get_audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    pub(crate) fn get_session_counters(&self) -> &SessionCounters {
+        &self.session
+    }
+
     pub(crate) async fn set_plugin_ready(&mut self, id: u64) {
         if let Some(p) = self.plugins.get_mut(&id) {
             p.plugin_status = PluginStatus::Running;
@@ -174,6 +325,22 @@ impl DataStore {
             // Inform our plugin of the plugin that is already running
             self.send_message_to_plugin(id, LoaderMessage::OtherPluginStartup(*other_id)).await;
         }
+
+        if let Some(watchers) = self.watchers.get(&id) {
+            for watcher in watchers {
+                self.send_message_to_plugin(*watcher, LoaderMessage::WatchedPluginStarted(id)).await;
+            }
+        }
+    }
+
+    /// Adds the watcher to the plugin (see watch_plugin), returns if the plugin is already running
+    pub(crate) fn watch_plugin(&mut self, watcher: u64, plugin: u64) -> bool {
+        let watchers = self.watchers.entry(plugin).or_default();
+        if !watchers.contains(&watcher) {
+            watchers.push(watcher);
+        }
+
+        self.plugins.get(&plugin).is_some_and(|p| p.plugin_status == PluginStatus::Running)
     }
 }
 
@@ -185,38 +352,3 @@ pub(crate) struct Plugin {
 
 unsafe impl Send for Plugin {}
 unsafe impl Sync for Plugin {}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub(crate) struct Config {
-    plugin_location: PathBuf,
-    dashboards_location: PathBuf
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        let base = PathBuf::from_str(".").expect("Current folder dereference should always work");
-        Config {
-            plugin_location: {
-                let mut plugin = base.clone();
-                plugin.push("plugins");
-                plugin
-            },
-            dashboards_location: {
-                let mut dash = base.clone();
-                dash.push("dashboards");
-                dash
-            },
-        }
-    }
-}
-
-impl Config {
-    pub(crate) fn get_plugin_folder(&self) -> PathBuf {
-        self.plugin_location.clone()
-    }
-
-
-    pub(crate) fn get_dashboards_folder(&self) -> PathBuf {
-        self.dashboards_location.clone()
-    }
-}
@@ -0,0 +1,176 @@
+//! Self-test for `--doctor`: checks everything DataRace needs to start (config, folders, the web
+//! port, the plugins) without starting it, and prints a report that can be pasted into a bug report.
+
+use std::{fs, net::TcpListener, path::Path};
+
+use serde::Serialize;
+
+use crate::{built_info, config::{self, Config, ConfigError}, ipc, pluginloader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warning,
+    Error
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    /// What was checked, like `config` or `plugin sample_plugin`
+    name: String,
+    status: Status,
+    detail: String
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    version: &'static str,
+    api_version: u64,
+    os: &'static str,
+    checks: Vec<Check>
+}
+
+impl Report {
+    fn push(&mut self, name: impl ToString, status: Status, detail: impl ToString) {
+        self.checks.push(Check { name: name.to_string(), status, detail: detail.to_string() });
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.checks.iter().filter(|check| check.status == status).count()
+    }
+}
+
+/// Runs all checks and prints the report. A broken config is reported, the remaining checks then
+/// run with the default config. Returns false if any check failed
+pub(crate) fn run_doctor(config: Result<&Config, &ConfigError>, json: bool) -> bool {
+    let mut report = Report { version: built_info::PKG_VERSION, api_version: crate::API_VERSION, os: std::env::consts::OS, checks: Vec::new() };
+
+    let path = config::get_config_path();
+    let default = Config::default();
+    let config = match config {
+        Ok(config) if path.exists() => {
+            report.push("config", Status::Ok, format!("{} is valid", path.display()));
+            config
+        },
+        Ok(config) => {
+            report.push("config", Status::Ok, format!("{} does not exist, using the defaults", path.display()));
+            config
+        },
+        Err(e) => {
+            report.push("config", Status::Error, format!("{}: {} (continuing with the defaults)", path.display(), e));
+            &default
+        }
+    };
+
+    // A running instance holds the port and the socket, so those failures are expected then
+    let running = ipc::send_request(config.get_control_socket().as_path(), "list-plugins", serde_json::Value::Null).is_ok();
+    if running {
+        report.push("instance", Status::Warning, "DataRace is already running, the web port and control socket are in use by it");
+    }
+
+    check_folder(&mut report, "plugin folder", config.get_plugin_folder().as_path(), false);
+    check_folder(&mut report, "dashboards folder", config.get_dashboards_folder().as_path(), true);
+    #[cfg(feature = "lua")]
+    check_folder(&mut report, "scripts folder", config.get_scripts_folder().as_path(), false);
+    if let Some(parent) = config.get_audit_log().as_deref().and_then(Path::parent) {
+        check_folder(&mut report, "audit log folder", parent, true);
+    }
+    #[cfg(unix)]
+    if !running {
+        let socket = config.get_control_socket();
+        if let Some(parent) = socket.parent() {
+            check_folder(&mut report, "control socket folder", parent, true);
+        }
+    }
+
+    let address = config.get_web_address();
+    match TcpListener::bind(address) {
+        Ok(_) => report.push("web port", Status::Ok, format!("{} is available", address)),
+        Err(e) => report.push("web port", if running { Status::Warning } else { Status::Error },
+            format!("Unable to bind {}: {}", address, e))
+    }
+
+    match pluginloader::read_all_plugin_descriptions(config) {
+        Ok(list) if list.is_empty() => report.push("plugins", Status::Warning, format!("No plugins found in {}", config.get_plugin_folder().display())),
+        Ok(list) => for info in list {
+            let name = format!("plugin {}", info.name.as_deref().unwrap_or("<unknown>"));
+            let detail = format!("{}.{}.{} (api {}) - {}", info.version[0], info.version[1], info.version[2], info.api_version, info.path.display());
+
+            match info.error {
+                None => report.push(name, Status::Ok, detail),
+                // Disabled on purpose (or another copy is launched), not a problem
+                Some(reason) if info.duplicate || info.name.as_deref().is_some_and(|name| !config.is_plugin_enabled(name)) =>
+                    report.push(name, Status::Warning, format!("{}: {}", detail, reason)),
+                Some(reason) => report.push(name, Status::Error, format!("{}: {}", detail, reason))
+            }
+        },
+        // Missing is fine, the folder is created at launch (and reported above)
+        Err(_) if !config.get_plugin_folder().exists() => (),
+        Err(e) => report.push("plugins", Status::Error, format!("Unable to read {}: {}", config.get_plugin_folder().display(), e))
+    }
+
+    let errors = report.count(Status::Error);
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("Unable to serialize report: {}", e)
+        }
+    } else {
+        println!("DataRace {} (api {}) on {}", report.version, report.api_version, report.os);
+        for check in report.checks.iter() {
+            let status = match check.status {
+                Status::Ok => " OK ",
+                Status::Warning => "WARN",
+                Status::Error => "FAIL"
+            };
+            println!("[{}] {}: {}", status, check.name, check.detail);
+        }
+        println!("{} errors, {} warnings", errors, report.count(Status::Warning));
+    }
+
+    errors == 0
+}
+
+/// Checks the folder can be read, and written if required. A missing folder is only a warning,
+/// as it is created at launch (which is then checked on the parent)
+fn check_folder(report: &mut Report, name: &str, folder: &Path, write: bool) {
+    // An empty parent is the working directory
+    let folder = if folder.as_os_str().is_empty() { Path::new(".") } else { folder };
+
+    if !folder.exists() {
+        let parent = folder.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        match writable(parent) {
+            Ok(()) => report.push(name, Status::Warning, format!("{} does not exist, it will be created", folder.display())),
+            Err(e) => report.push(name, Status::Error, format!("{} does not exist and can not be created in {}: {}", folder.display(), parent.display(), e))
+        }
+        return;
+    }
+
+    if !folder.is_dir() {
+        report.push(name, Status::Error, format!("{} is not a folder", folder.display()));
+        return;
+    }
+
+    if let Err(e) = fs::read_dir(folder) {
+        report.push(name, Status::Error, format!("{} can not be read: {}", folder.display(), e));
+        return;
+    }
+
+    if !write {
+        report.push(name, Status::Ok, format!("{} is readable", folder.display()));
+        return;
+    }
+
+    match writable(folder) {
+        Ok(()) => report.push(name, Status::Ok, format!("{} is writable", folder.display())),
+        Err(e) => report.push(name, Status::Error, format!("{} is not writable: {}", folder.display(), e))
+    }
+}
+
+/// Permissions are platform specific (and ACLs on top), so we just try to create a file
+fn writable(folder: &Path) -> Result<(), std::io::Error> {
+    let file = folder.join(format!(".datarace-doctor-{}", std::process::id()));
+    fs::write(file.as_path(), [])?;
+    fs::remove_file(file)
+}
@@ -0,0 +1,111 @@
+//! Trigger statistics of the events listed in event_stats of the config, published as core
+//! properties datarace.event.{event}.count and datarace.event.{event}.last, so dashboards can
+//! show things like how long ago the last pit call was without a plugin mirroring the event.
+
+use std::{sync::{atomic::{AtomicI64, Ordering}, Arc}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use hashbrown::HashMap;
+use kanal::{AsyncReceiver, AsyncSender};
+use log::{debug, error};
+
+use crate::{datastore::DataStore, events::EventMessage, pluginloader::LoaderMessage, system, utils::ValueContainer, EventFilter, EventHandle};
+
+/// How often we check for shutdown while no event is triggered
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Stat {
+    count: Arc<AtomicI64>,
+    /// Micro seconds since the unix epoch, 0 till the first trigger
+    last: Arc<AtomicI64>
+}
+
+pub(crate) struct EventStats {
+    list: HashMap<EventHandle, Stat>,
+    sender: AsyncSender<LoaderMessage>,
+    receiver: AsyncReceiver<LoaderMessage>
+}
+
+pub(crate) fn validate(name: &str) -> Result<(), String> {
+    if EventHandle::new(name).is_none() {
+        return Err(format!("event_stats entry {} is not a valid event name", name));
+    }
+
+    Ok(())
+}
+
+/// Subscribes us to the event, this also works if the event was not created yet
+async fn subscribe(datastore: &'static tokio::sync::RwLock<DataStore>, ev: EventHandle, sender: &AsyncSender<LoaderMessage>) {
+    let channel = datastore.read().await.get_event_channel();
+    if channel.as_async().send(EventMessage::Subscribe(ev, system::EVENT_STATS_LISTENER_ID, sender.clone(), EventFilter::default())).await.is_err() {
+        error!("Unable to subscribe to event {}|{} for its statistics", ev.plugin, ev.event);
+    }
+}
+
+/// Creates the properties of the events in the config, has to be called before the plugins
+/// are loaded, so they can subscribe to them during their init
+pub(crate) async fn create_event_stats(datastore: &'static tokio::sync::RwLock<DataStore>) -> EventStats {
+    let (sender, receiver) = kanal::unbounded_async();
+    let mut list = HashMap::new();
+
+    {
+        let mut ds_w = datastore.write().await;
+        for name in ds_w.get_config().get_event_stats().to_vec() {
+            let Some(ev) = EventHandle::new(name.as_str()) else {
+                continue;
+            };
+            if list.contains_key(&ev) {
+                continue;
+            }
+
+            let stat = Stat { count: Arc::default(), last: Arc::default() };
+            let name = name.trim().to_lowercase();
+            system::insert_property(&mut ds_w, format!("event.{}.count", name).as_str(), ValueContainer::Int(stat.count.clone()));
+            system::insert_property(&mut ds_w, format!("event.{}.last", name).as_str(), ValueContainer::Time(stat.last.clone()));
+            list.insert(ev, stat);
+        }
+    }
+
+    for ev in list.keys() {
+        subscribe(datastore, *ev, &sender).await;
+    }
+
+    debug!("Tracking {} events", list.len());
+    EventStats { list, sender, receiver }
+}
+
+/// Counts the triggers till shutdown
+pub(crate) async fn run_event_stats(datastore: &'static tokio::sync::RwLock<DataStore>, stats: EventStats) {
+    if stats.list.is_empty() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(SHUTDOWN_CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if datastore.read().await.get_shutdown_status() {
+                    break;
+                }
+            },
+            msg = stats.receiver.recv() => match msg {
+                Ok(LoaderMessage::EventTriggered(ev)) => {
+                    if let Some(stat) = stats.list.get(&ev) {
+                        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as i64).unwrap_or_default();
+                        stat.count.fetch_add(1, Ordering::AcqRel);
+                        stat.last.store(now, Ordering::Release);
+                    }
+                },
+                Ok(LoaderMessage::EventUnsubscribed(ev)) => {
+                    // The event was deleted (like the plugin shutting down), we subscribe again
+                    // so we keep counting once it is created again
+                    subscribe(datastore, ev, &stats.sender).await;
+                },
+                Ok(_) => (),
+                Err(_) => break
+            }
+        }
+    }
+
+    debug!("Event statistics stopped");
+}
@@ -1,33 +1,105 @@
+use std::time::{Duration, Instant};
+
 use hashbrown::HashMap;
 use kanal::{AsyncReceiver, AsyncSender, Sender};
-use log::{debug, error};
+use log::{debug, error, info};
 use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::{pluginloader::LoaderMessage, EventFilter, EventHandle};
+
+/// The boolean serves to declare if the event has been created, or if there are only
+/// subscribers waiting for creation
+type Mappings = HashMap<EventHandle, (bool, HashMap<u64, Listener>)>;
+
+/// A subscriber of an event, with the state of its filter
+#[derive(Debug)]
+struct Listener {
+    channel: AsyncSender<LoaderMessage>,
+    filter: EventFilter,
+    count: u64,
+    last_trigger: Option<Instant>
+}
+
+impl Listener {
+    fn new(channel: AsyncSender<LoaderMessage>, filter: EventFilter) -> Self {
+        Listener { channel, filter, count: 0, last_trigger: None }
+    }
 
-use crate::{pluginloader::LoaderMessage, EventHandle};
+    /// Counts the trigger, returns if it passes the filter
+    fn pass(&mut self, now: Instant) -> bool {
+        if self.filter.debounce_ms != 0 {
+            let quiet = self.last_trigger.is_none_or(|last| now.duration_since(last) >= Duration::from_millis(self.filter.debounce_ms));
+            self.last_trigger = Some(now);
+
+            if !quiet {
+                return false;
+            }
+        }
+
+        if self.filter.every_nth > 1 {
+            self.count += 1;
+            if self.count < self.filter.every_nth {
+                return false;
+            }
+            self.count = 0;
+        }
+
+        true
+    }
+}
 
 pub(crate) fn create_event_task() -> (JoinHandle<()>, Sender<EventMessage>) {
     let (s, r) = kanal::unbounded();
 
-    (tokio::spawn(event_loop(r.to_async(), s.clone().to_async())),s)
+    (tokio::spawn(supervise_event_loop(r.to_async(), s.clone().to_async())),s)
 }
 
-async fn event_loop(recv: AsyncReceiver<EventMessage>, sender: AsyncSender<EventMessage>) {
-    debug!("Starting EventHandler Loop");
+/// Runs the event loop till shutdown, restarting it when requested (keeping the subscriptions)
+/// or after it crashed (losing them, as they are part of the crashed task)
+async fn supervise_event_loop(recv: AsyncReceiver<EventMessage>, sender: AsyncSender<EventMessage>) {
+    let mut mappings = Mappings::new();
 
-    // The boolean serves to declare if the event has been created, or if there are only
-    // subscribers waiting for creation
-    let mut mappings = HashMap::<EventHandle, (bool, HashMap<u64, AsyncSender<LoaderMessage>>)>::new();
+    loop {
+        match tokio::spawn(event_loop(recv.clone(), sender.clone(), mappings)).await {
+            Ok(Some(kept)) => {
+                info!("Restarting EventHandler");
+                mappings = kept;
+            },
+            Ok(None) => break,
+            Err(e) => {
+                error!("EventHandler crashed, restarting it without the subscriptions: {}", e);
+                mappings = Mappings::new();
+            }
+        }
+    }
+
+    debug!("EventHandler shutdown");
+}
+
+/// Returns the mappings when a restart was requested, None on shutdown
+async fn event_loop(recv: AsyncReceiver<EventMessage>, sender: AsyncSender<EventMessage>, mut mappings: Mappings) -> Option<Mappings> {
+    debug!("Starting EventHandler Loop");
 
     while let Ok(msg) = recv.recv().await {
         match msg {
             EventMessage::Shutdown => { break; },
+            EventMessage::Restart => { return Some(mappings); },
             EventMessage::Trigger(ev) => {
-                if let Some((_,listeners)) = mappings.get(&ev) {
-                    for (plugin, sender) in listeners.iter() {
-                        if let Err(e) = sender.send(LoaderMessage::EventTriggered(ev)).await {
-                            error!("Unable to inform plugin {plugin} of the event {}|{} triggering: {e}", ev.plugin, ev.event);
+                if let Some((_,listeners)) = mappings.get_mut(&ev) {
+                    let span = tracing::trace_span!("event_trigger", plugin = ev.plugin, event = ev.event, listeners = listeners.len());
+                    let now = Instant::now();
+                    async {
+                        for (plugin, listener) in listeners.iter_mut() {
+                            if !listener.pass(now) {
+                                continue;
+                            }
+
+                            if let Err(e) = listener.channel.send(LoaderMessage::EventTriggered(ev)).await {
+                                error!("Unable to inform plugin {plugin} of the event {}|{} triggering: {e}", ev.plugin, ev.event);
+                            }
                         }
-                    }
+                    }.instrument(span).await;
                 }
             },
             EventMessage::Create(ev) => {
@@ -39,27 +111,28 @@ async fn event_loop(recv: AsyncReceiver<EventMessage>, sender: AsyncSender<Event
             },
             EventMessage::Remove(ev) => {
                 if let Some((_,listeners)) = mappings.remove(&ev) {
-                    for (plugin, sender) in listeners.iter() {
-                        if let Err(e) = sender.send(LoaderMessage::EventUnsubscribed(ev)).await {
+                    for (plugin, listener) in listeners.iter() {
+                        if let Err(e) = listener.channel.send(LoaderMessage::EventUnsubscribed(ev)).await {
                             error!("Unable to inform plugin {plugin} of event {}|{} being deleted: {e}", ev.plugin, ev.event);
                         }
                     }
                 }
             },
-            EventMessage::Subscribe(ev, plugin, channel) => {
+            EventMessage::Subscribe(ev, plugin, channel, filter) => {
+                // Subscribing again replaces the filter
                 if let Some((_, listeners)) = mappings.get_mut(&ev) {
-                    listeners.insert(plugin, channel);
+                    listeners.insert(plugin, Listener::new(channel, filter));
                 } else {
                     // If the event already exists we allow pre subscribing
                     let mut listeners = HashMap::new();
-                    listeners.insert(plugin, channel);
+                    listeners.insert(plugin, Listener::new(channel, filter));
                     mappings.insert(ev, (false, listeners));
                 }
             },
             EventMessage::Unsubscribe(ev, plugin) => {
                 if let Some((_, listeners)) = mappings.get_mut(&ev) {
-                    if let Some(channel) = listeners.remove(&plugin) {
-                        if let Err(e) = channel.send(LoaderMessage::EventUnsubscribed(ev)).await {
+                    if let Some(listener) = listeners.remove(&plugin) {
+                        if let Err(e) = listener.channel.send(LoaderMessage::EventUnsubscribed(ev)).await {
                             error!("Unable to inform plugin {plugin} of event {}|{} was unsubscribed: {e}", ev.plugin, ev.event);
                         }
                     }
@@ -76,19 +149,21 @@ async fn event_loop(recv: AsyncReceiver<EventMessage>, sender: AsyncSender<Event
             }
         }
     }
-    
-    debug!("EventHandler shutdown");
+
+    None
 }
 
 #[derive(Debug)]
 pub(crate) enum EventMessage {
     Create(EventHandle),
     Remove(EventHandle),
-    Subscribe(EventHandle, u64, AsyncSender<LoaderMessage>),
+    Subscribe(EventHandle, u64, AsyncSender<LoaderMessage>, EventFilter),
     Unsubscribe(EventHandle, u64),
 
     Trigger(EventHandle),
 
     Shutdown,
+    /// Restarts the event loop, keeping the subscriptions
+    Restart,
     RemovePlugin(u64)
 }
@@ -0,0 +1,200 @@
+//! Frame groups: properties of a plugin that are published together. The plugin writes the values
+//! between begin_frame and commit_frame, and readers (the dashboard socket, subscription groups and
+//! the property stream) only read a group while no frame is being written, so a dashboard never
+//! shows the speed of one frame together with the gear of the next.
+//!
+//! Readers do not wait, a group in the middle of a frame is skipped and picked up on the next
+//! pass. begin_frame instead waits for the readers of the group to finish their pass.
+
+use std::sync::{atomic::{AtomicU32, AtomicUsize, Ordering}, Arc, Mutex, RwLock};
+
+use hashbrown::HashMap;
+
+use crate::PropertyHandle;
+
+/// Set in the state while a frame is written, the rest is the number of readers
+const WRITING: u32 = 1 << 31;
+
+#[derive(Debug, Default)]
+pub(crate) struct FrameGate {
+    state: AtomicU32,
+    /// Properties updated during the frame, their callbacks are called on commit
+    pending: Mutex<Vec<PropertyHandle>>
+}
+
+impl FrameGate {
+    fn try_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            if state & WRITING != 0 {
+                return false;
+            }
+
+            match self.state.compare_exchange_weak(state, state + 1, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return true,
+                Err(current) => state = current
+            }
+        }
+    }
+
+    fn release_read(&self) {
+        if self.state.fetch_sub(1, Ordering::AcqRel) == WRITING | 1 {
+            // The last reader, the writer is waiting for us
+            atomic_wait::wake_all(&self.state);
+        }
+    }
+
+    /// False if a frame was already begun
+    fn begin(&self) -> bool {
+        if self.state.fetch_or(WRITING, Ordering::AcqRel) & WRITING != 0 {
+            return false;
+        }
+
+        loop {
+            let state = self.state.load(Ordering::Acquire);
+            if state == WRITING {
+                return true;
+            }
+            atomic_wait::wait(&self.state, state);
+        }
+    }
+
+    /// False if no frame was begun
+    fn commit(&self) -> bool {
+        self.state.fetch_and(!WRITING, Ordering::AcqRel) & WRITING != 0
+    }
+
+    fn is_writing(&self) -> bool {
+        self.state.load(Ordering::Acquire) & WRITING != 0
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// (plugin id, group id) -> gate
+    groups: HashMap<(u64, u64), Arc<FrameGate>>,
+    /// Property -> gate of its group
+    members: HashMap<PropertyHandle, Arc<FrameGate>>
+}
+
+static FRAMES: RwLock<Option<Registry>> = RwLock::new(None);
+/// Number of properties in groups, so readers can skip the lock when there are none
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn get_gate(prop_handle: &PropertyHandle) -> Option<Arc<FrameGate>> {
+    if COUNT.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+
+    FRAMES.read().ok()?.as_ref()?.members.get(prop_handle).cloned()
+}
+
+fn get_group(plugin: u64, group: u64) -> Option<Arc<FrameGate>> {
+    FRAMES.read().ok()?.as_ref()?.groups.get(&(plugin, group)).cloned()
+}
+
+/// Creating a group that already exists does nothing
+pub(crate) fn create_group(plugin: u64, group: u64) {
+    if let Ok(mut map) = FRAMES.write() {
+        map.get_or_insert_with(Registry::default).groups.entry((plugin, group)).or_default();
+    }
+}
+
+/// Adds the property to the group (moving it out of its previous group), false if the group does not exist
+pub(crate) fn add_to_group(plugin: u64, group: u64, prop_handle: PropertyHandle) -> bool {
+    let Ok(mut map) = FRAMES.write() else {
+        return false;
+    };
+    let Some(registry) = map.as_mut() else {
+        return false;
+    };
+    let Some(gate) = registry.groups.get(&(plugin, group)).cloned() else {
+        return false;
+    };
+
+    if registry.members.insert(prop_handle, gate).is_none() {
+        COUNT.fetch_add(1, Ordering::Release);
+    }
+    true
+}
+
+/// Removes all groups of this plugin, at shutdown
+pub(crate) fn remove_plugin(plugin: u64) {
+    if let Ok(mut map) = FRAMES.write() {
+        if let Some(registry) = map.as_mut() {
+            registry.groups.retain(|(id, _), _| *id != plugin);
+
+            let before = registry.members.len();
+            registry.members.retain(|prop, _| prop.plugin != plugin);
+            COUNT.fetch_sub(before - registry.members.len(), Ordering::Release);
+        }
+    }
+}
+
+/// Begins a frame, waiting for running readers of the group to finish.
+/// None if the group does not exist, false if a frame was already begun
+pub(crate) fn begin(plugin: u64, group: u64) -> Option<bool> {
+    Some(get_group(plugin, group)?.begin())
+}
+
+/// Commits the frame, returning the properties updated during it (for their callbacks).
+/// None if the group does not exist or no frame was begun
+pub(crate) fn commit(plugin: u64, group: u64) -> Option<Vec<PropertyHandle>> {
+    let gate = get_group(plugin, group)?;
+    let pending = std::mem::take(&mut *gate.pending.lock().unwrap_or_else(|e| e.into_inner()));
+
+    gate.commit().then_some(pending)
+}
+
+/// If the property is part of a frame being written, it is noted for commit and true returned,
+/// then the callbacks have to wait till the frame is committed
+pub(crate) fn defer_callbacks(prop_handle: &PropertyHandle) -> bool {
+    match get_gate(prop_handle) {
+        Some(gate) if gate.is_writing() => {
+            let mut pending = gate.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if !pending.contains(prop_handle) {
+                pending.push(*prop_handle);
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+/// Used for one pass over properties: every group read in the pass can not begin a new frame till
+/// the reader is dropped, so all values of a group come from the same frame.
+/// Do not hold it over an await, the plugin writing the group waits for it
+#[derive(Default)]
+pub(crate) struct FrameReader {
+    held: Vec<Arc<FrameGate>>,
+    skipped: Vec<Arc<FrameGate>>
+}
+
+impl FrameReader {
+    /// If the property can be read now, false if its group is in the middle of a frame
+    pub(crate) fn can_read(&mut self, prop_handle: &PropertyHandle) -> bool {
+        let Some(gate) = get_gate(prop_handle) else {
+            return true;
+        };
+
+        if self.held.iter().any(|held| Arc::ptr_eq(held, &gate)) {
+            true
+        } else if self.skipped.iter().any(|skipped| Arc::ptr_eq(skipped, &gate)) {
+            false
+        } else if gate.try_read() {
+            self.held.push(gate);
+            true
+        } else {
+            self.skipped.push(gate);
+            false
+        }
+    }
+}
+
+impl Drop for FrameReader {
+    fn drop(&mut self) {
+        for gate in self.held.drain(..) {
+            gate.release_read();
+        }
+    }
+}
@@ -0,0 +1,588 @@
+//! Maps inputs of the host (keyboard keys, gamepad buttons and axes) to events and properties,
+//! configured in inputs of the config, so a button box can flip a dashboard page without a plugin.
+//!
+//! Reading the devices blocks (or has to be polled), so it runs on threads of its own, which send
+//! every change to the task applying the mappings. The last value of each mapped input is kept for the
+//! inputs page. Other inputs are only kept while learning (to find the name of an input for the config),
+//! which can only be started on the host itself, as this would list every key typed.
+//!
+//! Properties are written through the plugin owning them (see pluginloader::write_property_text).
+
+use std::{fmt::Display, str::FromStr, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use hashbrown::HashMap;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use crate::{datastore::DataStore, events::EventMessage, pluginloader, rules::SetAction, system, utils::{ValueCache, ValueContainer}, DataStoreReturnCode, EventHandle, PropertyHandle, PropertyType};
+
+/// How often we check for shutdown while no input changes
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keys, buttons and axes count as pressed at or above this value
+const PRESS_THRESHOLD: f64 = 0.5;
+
+/// Set on shutdown, so the reader threads stop
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// How long inputs without a mapping are listed after learning was started
+const LEARN_DURATION: Duration = Duration::from_secs(30);
+
+/// Last value, change (ms since the unix epoch) and if it is mapped, of the inputs seen
+type Seen = HashMap<InputId, (f64, u64, bool)>;
+
+/// For the inputs page
+static SEEN: Mutex<Option<Seen>> = Mutex::new(None);
+/// Till when (ms since the unix epoch) inputs without a mapping are recorded
+static LEARN_UNTIL: AtomicU64 = AtomicU64::new(0);
+
+/// inputs in the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub(crate) struct InputsConfig {
+    /// Reads the devices, off by default as this includes every key pressed on the host
+    enabled: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    map: Vec<InputMapping>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct InputMapping {
+    /// Like key:30, button:0:1 or axis:0:2 (see InputId)
+    input: String,
+    /// Full name of the event triggered on press
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    event: Option<String>,
+    /// Property set on press
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set: Option<SetAction>,
+    /// Full name of a boolean property flipped on every press
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    toggle: Option<String>,
+    /// Full name of a property that follows the input (0/1 for keys and buttons, -1 to 1 for sticks)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>
+}
+
+impl InputsConfig {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn get_map(&self) -> &[InputMapping] {
+        self.map.as_slice()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for mapping in self.map.iter() {
+            mapping.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl InputMapping {
+    pub(crate) fn get_input(&self) -> &str {
+        self.input.as_str()
+    }
+
+    /// The actions, as shown on the inputs page
+    pub(crate) fn describe(&self) -> String {
+        let mut list = Vec::new();
+        if let Some(event) = self.event.as_ref() {
+            list.push(format!("event {}", event));
+        }
+        if let Some(set) = self.set.as_ref() {
+            list.push(format!("set {} = {}", set.property, set.value));
+        }
+        if let Some(prop) = self.toggle.as_ref() {
+            list.push(format!("toggle {}", prop));
+        }
+        if let Some(prop) = self.value.as_ref() {
+            list.push(format!("value {}", prop));
+        }
+        list.join(", ")
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if InputId::from_str(self.input.as_str()).is_err() {
+            return Err(format!("input {} is not an input (like key:30, button:0:1 or axis:0:2)", self.input));
+        }
+        if self.event.is_none() && self.set.is_none() && self.toggle.is_none() && self.value.is_none() {
+            return Err(format!("input {}: has neither an event, a set, a toggle nor a value", self.input));
+        }
+        if let Some(event) = self.event.as_ref() {
+            if EventHandle::new(event.as_str()).is_none() {
+                return Err(format!("input {}: event {} is not a valid event name", self.input, event));
+            }
+        }
+        let set = self.set.as_ref().map(|set| &set.property);
+        for (name, prop) in [("set", set), ("toggle", self.toggle.as_ref()), ("value", self.value.as_ref())] {
+            if let Some(prop) = prop {
+                match PropertyHandle::new(prop.as_str()) {
+                    None => return Err(format!("input {}: {} property {} is not a valid property name", self.input, name, prop)),
+                    Some(handle) if handle.plugin == system::CORE_PLUGIN_ID => return Err(format!("input {}: core property {} can not be set", self.input, prop)),
+                    Some(_) => ()
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An input of a device, the device is the number of the gamepad (in the order the os lists them),
+/// key codes are the ones of the os (evdev on linux, virtual key codes on windows)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum InputId {
+    Key(u16),
+    Button(u8, u16),
+    Axis(u8, u16)
+}
+
+impl Display for InputId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputId::Key(code) => write!(f, "key:{}", code),
+            InputId::Button(device, number) => write!(f, "button:{}:{}", device, number),
+            InputId::Axis(device, number) => write!(f, "axis:{}:{}", device, number)
+        }
+    }
+}
+
+impl FromStr for InputId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+
+        match parts.as_slice() {
+            ["key", code] => Ok(InputId::Key(code.parse().map_err(|_| ())?)),
+            ["button", device, number] => Ok(InputId::Button(device.parse().map_err(|_| ())?, number.parse().map_err(|_| ())?)),
+            ["axis", device, number] => Ok(InputId::Axis(device.parse().map_err(|_| ())?, number.parse().map_err(|_| ())?)),
+            _ => Err(())
+        }
+    }
+}
+
+/// An input seen since launch, listed on the inputs page
+#[derive(Debug, Serialize)]
+pub(crate) struct SeenInput {
+    input: String,
+    value: f64,
+    /// ms since the unix epoch
+    changed: u64
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Records inputs without a mapping for the next LEARN_DURATION, only call this for clients on the host itself
+pub(crate) fn start_learning() {
+    LEARN_UNTIL.store(now_ms() + LEARN_DURATION.as_millis() as u64, Ordering::Release);
+}
+
+fn is_learning() -> bool {
+    LEARN_UNTIL.load(Ordering::Acquire) > now_ms()
+}
+
+/// The inputs seen since launch, last changed first.
+/// Inputs without a mapping are only included for clients on the host, and are forgotten once learning ended
+pub(crate) fn seen_inputs(local: bool) -> Vec<SeenInput> {
+    let Ok(mut lock) = SEEN.lock() else {
+        return Vec::new();
+    };
+
+    if !is_learning() {
+        if let Some(map) = lock.as_mut() {
+            map.retain(|_, (_, _, mapped)| *mapped);
+        }
+    }
+
+    let mut list: Vec<SeenInput> = lock.iter().flatten()
+        .filter(|(_, (_, _, mapped))| *mapped || local)
+        .map(|(input, (value, changed, _))| SeenInput { input: input.to_string(), value: *value, changed: *changed })
+        .collect();
+    list.sort_by_key(|input| std::cmp::Reverse(input.changed));
+    list
+}
+
+fn record(input: InputId, value: f64, mapped: bool) {
+    if !mapped && !is_learning() {
+        return;
+    }
+
+    if let Ok(mut lock) = SEEN.lock() {
+        lock.get_or_insert_with(HashMap::new).insert(input, (value, now_ms(), mapped));
+    }
+}
+
+struct Mapping {
+    event: Option<EventHandle>,
+    set: Option<(PropertyHandle, String)>,
+    toggle: Option<PropertyHandle>,
+    value: Option<PropertyHandle>
+}
+
+impl Mapping {
+    fn new(config: &InputMapping) -> Option<(InputId, Self)> {
+        Some((InputId::from_str(config.input.as_str()).ok()?, Mapping {
+            event: config.event.as_ref().and_then(|ev| EventHandle::new(ev.as_str())),
+            set: match config.set.as_ref() {
+                Some(set) => Some((PropertyHandle::new(set.property.as_str())?, set.value.clone())),
+                None => None
+            },
+            toggle: config.toggle.as_ref().and_then(|prop| PropertyHandle::new(prop.as_str())),
+            value: config.value.as_ref().and_then(|prop| PropertyHandle::new(prop.as_str()))
+        }))
+    }
+}
+
+async fn write(datastore: &'static tokio::sync::RwLock<DataStore>, handle: &PropertyHandle, text: String, action: &str) {
+    let code = pluginloader::write_property_text(datastore, *handle, text.clone()).await;
+    if code != DataStoreReturnCode::Ok {
+        error!("Input failed to {} property {} to {}: {:?}", action, datastore.read().await.property_label(handle), text, code);
+    }
+}
+
+/// Formats the value of the input for the type of the property
+fn value_text(sort: PropertyType, value: f64) -> String {
+    match sort {
+        PropertyType::Boolean => (value >= PRESS_THRESHOLD).to_string(),
+        PropertyType::Int => (value.round() as i64).to_string(),
+        _ => value.to_string()
+    }
+}
+
+/// The datastore is only locked for reading the current values, as the owner of the property needs it for the write
+async fn apply(datastore: &'static tokio::sync::RwLock<DataStore>, mapping: &Mapping, pressed: bool, value: f64) {
+    if let Some(handle) = mapping.value.as_ref() {
+        let sort = datastore.read().await.get_property_container(handle).map(ValueContainer::get_type).unwrap_or_default();
+        write(datastore, handle, value_text(sort, value), "set").await;
+    }
+
+    if !pressed {
+        return;
+    }
+
+    if let Some(ev) = mapping.event {
+        let channel = datastore.read().await.get_event_channel();
+        if channel.as_async().send(EventMessage::Trigger(ev)).await.is_err() {
+            error!("Input failed to trigger event {}|{}", ev.plugin, ev.event);
+        }
+    }
+
+    if let Some((handle, text)) = mapping.set.as_ref() {
+        write(datastore, handle, text.clone(), "set").await;
+    }
+
+    if let Some(handle) = mapping.toggle.as_ref() {
+        let current = datastore.read().await.get_property_container(handle).and_then(|cont| {
+            let mut cache = ValueCache::default();
+            cont.read_web(&mut cache);
+            cache.value.to_number()
+        }).unwrap_or_default();
+
+        write(datastore, handle, (current < PRESS_THRESHOLD).to_string(), "toggle").await;
+    }
+}
+
+/// Reads the devices and applies the mappings till shutdown
+pub(crate) async fn run_inputs(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let config = datastore.read().await.get_config().get_inputs().clone();
+    if !config.enabled {
+        return;
+    }
+
+    let mut mappings: HashMap<InputId, Vec<Mapping>> = HashMap::new();
+    for (input, mapping) in config.map.iter().filter_map(Mapping::new) {
+        mappings.entry(input).or_default().push(mapping);
+    }
+
+    let (sender, receiver) = kanal::unbounded();
+    if !platform::start(sender) {
+        error!("Reading inputs is not supported on this platform");
+        return;
+    }
+    let receiver = receiver.to_async();
+    debug!("Reading inputs with {} mappings", config.map.len());
+
+    let mut pressed: HashMap<InputId, bool> = HashMap::new();
+    let mut interval = tokio::time::interval(SHUTDOWN_CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if datastore.read().await.get_shutdown_status() {
+                    break;
+                }
+            },
+            msg = receiver.recv() => {
+                let Ok((input, value)) = msg else {
+                    break;
+                };
+                let list = mappings.get(&input);
+                record(input, value, list.is_some());
+                let Some(list) = list else {
+                    continue;
+                };
+
+                // Only the change to pressed fires, holding (or a key repeating) does not
+                let now = value >= PRESS_THRESHOLD;
+                let before = pressed.insert(input, now).unwrap_or_default();
+
+                for mapping in list {
+                    apply(datastore, mapping, now && !before, value).await;
+                }
+            }
+        }
+    }
+
+    STOPPED.store(true, Ordering::Release);
+    debug!("Inputs stopped");
+}
+
+/// Reads the joysticks (/dev/input/js*) and keyboards (/dev/input/event*), which requires the user
+/// to be able to read them (usually by being in the input group)
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::{io::Read, path::PathBuf, sync::{atomic::Ordering, Arc, Mutex}, time::Duration};
+
+    use hashbrown::HashSet;
+    use kanal::Sender;
+    use log::debug;
+
+    use super::{InputId, STOPPED};
+
+    /// How often we look for new devices
+    const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+    /// How long a reader waits for input before checking for shutdown
+    const READ_TIMEOUT_MS: i32 = 1000;
+
+    const JS_EVENT_BUTTON: u8 = 0x01;
+    const JS_EVENT_AXIS: u8 = 0x02;
+    const JS_EVENT_INIT: u8 = 0x80;
+
+    const EV_KEY: u16 = 0x01;
+    /// Codes from here on are buttons of mice and gamepads, which we read through js instead
+    const BTN_MISC: u16 = 0x100;
+
+    pub(super) fn start(sender: Sender<(InputId, f64)>) -> bool {
+        let open: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+
+        std::thread::spawn(move || {
+            while !STOPPED.load(Ordering::Acquire) {
+                scan(&sender, &open);
+                std::thread::sleep(SCAN_INTERVAL);
+            }
+        });
+
+        true
+    }
+
+    fn scan(sender: &Sender<(InputId, f64)>, open: &Arc<Mutex<HashSet<PathBuf>>>) {
+        let Ok(dir) = std::fs::read_dir("/dev/input") else {
+            return;
+        };
+
+        for entry in dir.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            let reader: fn(std::fs::File, u8, &Sender<(InputId, f64)>) = if name.starts_with("js") {
+                read_joystick
+            } else if name.starts_with("event") {
+                read_keyboard
+            } else {
+                continue;
+            };
+            let Some(device) = name.trim_start_matches(char::is_alphabetic).parse::<u8>().ok() else {
+                continue;
+            };
+
+            let Ok(mut lock) = open.lock() else {
+                return;
+            };
+            if lock.contains(&path) {
+                continue;
+            }
+            let Ok(file) = std::fs::File::open(&path) else {
+                // No permission, or not a device we can read
+                continue;
+            };
+
+            debug!("Reading inputs of {}", path.display());
+            lock.insert(path.clone());
+            drop(lock);
+
+            let sender = sender.clone();
+            let open = open.clone();
+            std::thread::spawn(move || {
+                reader(file, device, &sender);
+
+                // The device was unplugged, so we open it again once it returns
+                if let Ok(mut lock) = open.lock() {
+                    lock.remove(&path);
+                }
+            });
+        }
+    }
+
+    /// Waits till the device can be read, false on shutdown or if the device was removed.
+    /// Reading directly would block till the next input, so the thread could never stop
+    fn wait_readable(file: &std::fs::File) -> bool {
+        use std::os::fd::AsRawFd;
+
+        while !STOPPED.load(Ordering::Acquire) {
+            let mut fd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut fd, 1, READ_TIMEOUT_MS) } {
+                0 => continue,
+                res if res > 0 => return fd.revents & libc::POLLIN != 0,
+                _ if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => continue,
+                _ => return false
+            }
+        }
+
+        false
+    }
+
+    /// Reads js_event (u32 time, i16 value, u8 type, u8 number) till the device is removed
+    fn read_joystick(mut file: std::fs::File, device: u8, sender: &Sender<(InputId, f64)>) {
+        let mut buf = [0_u8; 8];
+
+        while wait_readable(&file) && file.read_exact(&mut buf).is_ok() {
+            let value = i16::from_ne_bytes([buf[4], buf[5]]);
+            let number = buf[7] as u16;
+
+            let msg = match buf[6] & !JS_EVENT_INIT {
+                JS_EVENT_BUTTON => (InputId::Button(device, number), value as f64),
+                JS_EVENT_AXIS => (InputId::Axis(device, number), value as f64 / i16::MAX as f64),
+                _ => continue
+            };
+
+            if sender.send(msg).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Reads input_event (timeval time, u16 type, u16 code, i32 value) till the device is removed,
+    /// only keys of keyboards are used
+    fn read_keyboard(mut file: std::fs::File, _device: u8, sender: &Sender<(InputId, f64)>) {
+        let time_len = std::mem::size_of::<libc::timeval>();
+        let mut buf = vec![0_u8; std::mem::size_of::<libc::input_event>()];
+
+        while wait_readable(&file) && file.read_exact(&mut buf).is_ok() {
+            let sort = u16::from_ne_bytes([buf[time_len], buf[time_len + 1]]);
+            let code = u16::from_ne_bytes([buf[time_len + 2], buf[time_len + 3]]);
+            let value = i32::from_ne_bytes([buf[time_len + 4], buf[time_len + 5], buf[time_len + 6], buf[time_len + 7]]);
+
+            // value 2 is the key repeating
+            if sort != EV_KEY || code >= BTN_MISC || value > 1 {
+                continue;
+            }
+
+            if sender.send((InputId::Key(code), value as f64)).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Polls the xinput gamepads and the keyboard state
+#[cfg(windows)]
+mod platform {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use hashbrown::HashMap;
+    use kanal::Sender;
+    use windows_sys::Win32::UI::Input::{KeyboardAndMouse::GetAsyncKeyState, XboxController::{XInputGetState, XINPUT_STATE}};
+
+    use super::{InputId, STOPPED};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    const MAX_CONTROLLERS: u32 = 4;
+    /// Smaller changes of axes are not send, so a resting stick does not flood the mappings
+    const AXIS_DEADBAND: f64 = 0.01;
+
+    pub(super) fn start(sender: Sender<(InputId, f64)>) -> bool {
+        std::thread::spawn(move || {
+            let mut last: HashMap<InputId, f64> = HashMap::new();
+
+            while !STOPPED.load(Ordering::Acquire) {
+                let mut changes = Vec::new();
+                poll_controllers(&mut changes);
+                poll_keys(&mut changes);
+
+                for (input, value) in changes {
+                    // Compared to the last value send, so slow stick movements add up past the deadband
+                    let previous = last.get(&input).copied().unwrap_or_default();
+                    let changed = match input {
+                        InputId::Axis(..) => (value - previous).abs() >= AXIS_DEADBAND,
+                        _ => value != previous
+                    };
+                    if !changed {
+                        continue;
+                    }
+
+                    last.insert(input, value);
+                    if sender.send((input, value)).is_err() {
+                        return;
+                    }
+                }
+
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        true
+    }
+
+    fn poll_controllers(changes: &mut Vec<(InputId, f64)>) {
+        for index in 0..MAX_CONTROLLERS {
+            let mut state: XINPUT_STATE = unsafe { std::mem::zeroed() };
+            if unsafe { XInputGetState(index, &mut state) } != 0 {
+                continue;
+            }
+
+            let device = index as u8;
+            let pad = state.Gamepad;
+            for bit in 0..16 {
+                changes.push((InputId::Button(device, bit), if pad.wButtons & (1 << bit) != 0 { 1.0 } else { 0.0 }));
+            }
+
+            let axes = [
+                pad.sThumbLX as f64 / i16::MAX as f64,
+                pad.sThumbLY as f64 / i16::MAX as f64,
+                pad.sThumbRX as f64 / i16::MAX as f64,
+                pad.sThumbRY as f64 / i16::MAX as f64,
+                pad.bLeftTrigger as f64 / u8::MAX as f64,
+                pad.bRightTrigger as f64 / u8::MAX as f64
+            ];
+            for (number, value) in axes.into_iter().enumerate() {
+                changes.push((InputId::Axis(device, number as u16), value.clamp(-1.0, 1.0)));
+            }
+        }
+    }
+
+    fn poll_keys(changes: &mut Vec<(InputId, f64)>) {
+        // 0x01 to 0x07 are mouse buttons
+        for code in 0x08..=0xFE_u16 {
+            let state = unsafe { GetAsyncKeyState(code as i32) } as u16;
+            changes.push((InputId::Key(code), if state & 0x8000 != 0 { 1.0 } else { 0.0 }));
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use kanal::Sender;
+
+    use super::InputId;
+
+    pub(super) fn start(_sender: Sender<(InputId, f64)>) -> bool {
+        false
+    }
+}
@@ -0,0 +1,416 @@
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader}, time::{self, Duration}};
+
+use crate::{datastore::{DataStore, Subsystem}, events::EventMessage, utils::{self, Value, ValueCache}, DataStoreReturnCode, EventHandle, PropertyHandle};
+
+/// How often the listener checks if shutdown has begun
+const SHUTDOWN_POLL_RATE: Duration = Duration::from_secs(1);
+
+/// A request on the control socket, following JSON-RPC 2.0.
+/// Every request (and response) is a single line of json
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Request {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: serde_json::Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Response {
+    pub jsonrpc: String,
+    pub id: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RpcError {
+    pub code: i64,
+    pub message: String
+}
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// DataStoreReturnCodes are reported as this minus the code
+const RETURN_CODE_ERROR: i64 = -32000;
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError { code, message: message.to_string() }
+    }
+}
+
+impl From<DataStoreReturnCode> for RpcError {
+    fn from(value: DataStoreReturnCode) -> Self {
+        let message = format!("{:?}", value);
+        RpcError { code: RETURN_CODE_ERROR - (value as u8 as i64), message }
+    }
+}
+
+#[derive(Deserialize)]
+struct PropertyParams {
+    property: String
+}
+
+#[derive(Deserialize)]
+struct SetParams {
+    property: String,
+    value: String
+}
+
+#[derive(Deserialize)]
+struct TriggerParams {
+    event: String
+}
+
+#[derive(Deserialize)]
+struct TraceParams {
+    plugin: String,
+    /// Written into the log if not set
+    #[serde(default)]
+    file: Option<std::path::PathBuf>
+}
+
+#[derive(Deserialize)]
+struct RestartParams {
+    subsystem: Subsystem
+}
+
+#[derive(Serialize)]
+struct PluginEntry {
+    id: u64,
+    name: String,
+    version: [u16;3],
+    running: bool
+}
+
+#[derive(Serialize)]
+struct PropertyEntry {
+    name: String,
+    handle: PropertyHandle,
+    value: Value
+}
+
+/// Executes a single request
+///
+/// Available methods:
+/// - `list-plugins`: All loaded plugins
+/// - `list-properties`: All properties with their values
+/// - `get` {property}: Value of a single property
+/// - `set` {property, value}: Sets the value (as text, parsed to the type of the property)
+/// - `trigger-event` {event}: Triggers the event
+/// - `memory`: Memory held by the properties of each plugin
+/// - `trace-start` {plugin, file}: Traces the messages of the plugin into the file (or the log)
+/// - `trace-stop` {plugin}: Stops tracing the plugin
+/// - `restart` {subsystem}: Restarts `web` or `events`, without the plugins
+/// - `shutdown`: Shuts DataRace down
+async fn process_request(datastore: &'static tokio::sync::RwLock<DataStore>, req: Request) -> Result<serde_json::Value, RpcError> {
+    fn to_json(val: impl Serialize) -> Result<serde_json::Value, RpcError> {
+        serde_json::to_value(val).map_err(|e| RpcError::new(INTERNAL_ERROR, e))
+    }
+
+    match req.method.as_str() {
+        "list-plugins" => {
+            let list: Vec<PluginEntry> = datastore.read().await.list_plugins().into_iter()
+                .map(|(id, name, version, running)| PluginEntry { id, name, version, running })
+                .collect();
+
+            to_json(list)
+        },
+        "list-properties" => {
+            let mut list = {
+                let ds_r = datastore.read().await;
+                let mut list = Vec::new();
+
+                for key in ds_r.iter_properties() {
+                    if let (Some(name), Some(cont)) = (ds_r.read_property_name(key), ds_r.get_property_container(key)) {
+                        let mut cache = ValueCache::default();
+                        cont.read_web(&mut cache);
+                        list.push(PropertyEntry { name, handle: *key, value: cache.value });
+                    }
+                }
+
+                list
+            };
+            list.sort_by(|a, b| a.name.cmp(&b.name));
+
+            to_json(list)
+        },
+        "memory" => to_json(crate::memory::collect(&*datastore.read().await)),
+        "get" => {
+            let params: PropertyParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let handle = PropertyHandle::new(params.property.as_str()).ok_or(RpcError::new(INVALID_PARAMS, "invalid property name"))?;
+
+            let mut cache = ValueCache::default();
+            datastore.read().await.get_property_container(&handle).ok_or(RpcError::from(DataStoreReturnCode::DoesNotExist))?
+                .read_web(&mut cache);
+
+            to_json(cache.value)
+        },
+        "set" => {
+            let params: SetParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let handle = PropertyHandle::new(params.property.as_str()).ok_or(RpcError::new(INVALID_PARAMS, "invalid property name"))?;
+            if handle.plugin == crate::system::CORE_PLUGIN_ID {
+                // They would be overwritten on the next update anyway
+                return Err(RpcError::from(DataStoreReturnCode::NotAuthenticated));
+            }
+
+            // Through the owner, like writes from the web
+            match crate::pluginloader::write_property_text(datastore, handle, params.value).await {
+                DataStoreReturnCode::Ok => Ok(serde_json::Value::Null),
+                code => Err(RpcError::from(code))
+            }
+        },
+        "trigger-event" => {
+            let params: TriggerParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let event = EventHandle::new(params.event.as_str()).ok_or(RpcError::new(INVALID_PARAMS, "invalid event name"))?;
+
+            let channel = datastore.read().await.get_event_channel();
+            if channel.as_async().send(EventMessage::Trigger(event)).await.is_ok() {
+                Ok(serde_json::Value::Null)
+            } else {
+                Err(RpcError::from(DataStoreReturnCode::DataCorrupted))
+            }
+        },
+        "trace-start" => {
+            let params: TraceParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let id = utils::generate_plugin_name_hash(params.plugin.as_str()).ok_or(RpcError::new(INVALID_PARAMS, "invalid plugin name"))?;
+
+            crate::message_trace::start(id, params.plugin.to_lowercase().as_str(), params.file.as_deref()).map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+            Ok(serde_json::Value::Null)
+        },
+        "trace-stop" => {
+            let params: TraceParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let id = utils::generate_plugin_name_hash(params.plugin.as_str()).ok_or(RpcError::new(INVALID_PARAMS, "invalid plugin name"))?;
+
+            if crate::message_trace::stop(id) {
+                Ok(serde_json::Value::Null)
+            } else {
+                Err(RpcError::from(DataStoreReturnCode::DoesNotExist))
+            }
+        },
+        "restart" => {
+            let params: RestartParams = serde_json::from_value(req.params).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+
+            if datastore.write().await.restart_subsystem(params.subsystem) {
+                Ok(serde_json::Value::Null)
+            } else {
+                Err(RpcError::new(INTERNAL_ERROR, "Shutdown has begun"))
+            }
+        },
+        "shutdown" => {
+            let mut ds_w = datastore.write().await;
+            if !ds_w.get_shutdown_status() {
+                info!("Shutdown requested via control socket");
+                ds_w.start_shutdown().await;
+            }
+
+            Ok(serde_json::Value::Null)
+        },
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}", req.method)))
+    }
+}
+
+/// Parses a single line and generates the response
+async fn process_line(datastore: &'static tokio::sync::RwLock<DataStore>, line: &str) -> Response {
+    let req: Request = match serde_json::from_str(line) {
+        Ok(req) => req,
+        Err(e) => return Response { jsonrpc: "2.0".to_string(), id: serde_json::Value::Null, result: None, error: Some(RpcError::new(PARSE_ERROR, e)) }
+    };
+
+    let id = req.id.clone();
+    match process_request(datastore, req).await {
+        Ok(result) => Response { jsonrpc: "2.0".to_string(), id, result: Some(result), error: None },
+        Err(e) => Response { jsonrpc: "2.0".to_string(), id, result: None, error: Some(e) }
+    }
+}
+
+/// Handles a single client connection, processing line by line
+async fn handle_connection<T>(datastore: &'static tokio::sync::RwLock<DataStore>, stream: T) where T: AsyncRead + AsyncWrite {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let res = process_line(datastore, line.as_str()).await;
+        let Ok(mut text) = serde_json::to_string(&res) else {
+            break;
+        };
+        text.push('\n');
+
+        if write.write_all(text.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs the control socket (a unix domain socket), till shutdown
+///
+/// Access is limited through file permissions, so only the user running DataRace can connect
+#[cfg(unix)]
+pub(crate) async fn run_control_socket(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let path = datastore.read().await.get_config().get_control_socket();
+
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path.as_path()).is_ok() {
+            error!("Control socket {} is in use by another instance, control socket disabled", path.to_str().unwrap_or_default());
+            return;
+        }
+
+        // Leftover from a crash
+        let _ = std::fs::remove_file(path.as_path());
+    }
+
+    // The socket is created with the umask, so other users could connect before the permissions are
+    // restricted. It is bound in a private folder instead, and only moved to its path once restricted
+    let private = path.with_file_name(format!(".{}-{}", path.file_name().and_then(|n| n.to_str()).unwrap_or_default(), std::process::id()));
+    let _ = std::fs::remove_dir_all(private.as_path());
+    let res = bind_private(path.as_path(), private.as_path());
+    let _ = std::fs::remove_dir_all(private.as_path());
+
+    let listener = match res {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to create control socket {}, control socket disabled: {}", path.to_str().unwrap_or_default(), e);
+            return;
+        }
+    };
+    info!("Control socket listening on {}", path.to_str().unwrap_or_default());
+
+    loop {
+        let stream = tokio::select! {
+            res = listener.accept() => match res {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    error!("Failed to accept connection on control socket: {}", e);
+                    continue;
+                }
+            },
+            _ = time::sleep(SHUTDOWN_POLL_RATE) => {
+                if datastore.read().await.get_shutdown_status() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        tokio::spawn(handle_connection(datastore, stream));
+    }
+
+    let _ = std::fs::remove_file(path.as_path());
+    debug!("Control socket closed");
+}
+
+/// Binds the socket within the private folder (only accessible by us), restricts it and moves it to path
+#[cfg(unix)]
+fn bind_private(path: &std::path::Path, private: &std::path::Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new().mode(0o700).create(private)?;
+    let socket = private.join("socket");
+    let listener = tokio::net::UnixListener::bind(socket.as_path())?;
+    std::fs::set_permissions(socket.as_path(), std::fs::Permissions::from_mode(0o600))?;
+    std::fs::rename(socket.as_path(), path)?;
+
+    Ok(listener)
+}
+
+/// Runs the control socket (a named pipe), till shutdown
+///
+/// The pipe name is generated from the file name of the control_socket setting,
+/// and remote clients are rejected
+#[cfg(windows)]
+pub(crate) async fn run_control_socket(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = pipe_name(datastore.read().await.get_config().get_control_socket().as_path());
+
+    let mut server = match ServerOptions::new().first_pipe_instance(true).reject_remote_clients(true).create(name.as_str()) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Unable to create control pipe {} (is another instance running?): {}", name, e);
+            return;
+        }
+    };
+    info!("Control pipe listening on {}", name);
+
+    loop {
+        tokio::select! {
+            res = server.connect() => {
+                if let Err(e) = res {
+                    error!("Failed to accept connection on control pipe: {}", e);
+                    continue;
+                }
+            },
+            _ = time::sleep(SHUTDOWN_POLL_RATE) => {
+                if datastore.read().await.get_shutdown_status() {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        // The connected instance is handed off, and a new one is created for the next client
+        let next = match ServerOptions::new().reject_remote_clients(true).create(name.as_str()) {
+            Ok(next) => next,
+            Err(e) => {
+                error!("Unable to create next control pipe instance, control pipe disabled: {}", e);
+                break;
+            }
+        };
+        let connected = std::mem::replace(&mut server, next);
+
+        tokio::spawn(handle_connection(datastore, connected));
+    }
+
+    debug!("Control pipe closed");
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) async fn run_control_socket(_datastore: &'static tokio::sync::RwLock<DataStore>) {
+    log::warn!("The control socket is not supported on this platform");
+}
+
+#[cfg(windows)]
+fn pipe_name(path: &std::path::Path) -> String {
+    format!(r"\\.\pipe\{}", path.file_name().and_then(|n| n.to_str()).unwrap_or("datarace.sock"))
+}
+
+/// Sends a single request to the control socket of a running instance and returns the response
+pub(crate) fn send_request(path: &std::path::Path, method: &str, params: serde_json::Value) -> Result<Response, String> {
+    use std::io::{BufRead, Write};
+
+    #[cfg(unix)]
+    let mut stream = std::os::unix::net::UnixStream::connect(path)
+        .map_err(|e| format!("Unable to connect to control socket {} (is DataRace running?): {}", path.to_str().unwrap_or_default(), e))?;
+
+    #[cfg(windows)]
+    let mut stream = {
+        let name = pipe_name(path);
+        std::fs::OpenOptions::new().read(true).write(true).open(name.as_str())
+            .map_err(|e| format!("Unable to connect to control pipe {} (is DataRace running?): {}", name, e))?
+    };
+
+    #[cfg(not(any(unix, windows)))]
+    let mut stream: std::fs::File = {
+        let _ = path;
+        return Err("The control socket is not supported on this platform".to_string());
+    };
+
+    let req = Request { jsonrpc: "2.0".to_string(), id: serde_json::Value::from(1), method: method.to_string(), params };
+    let mut text = serde_json::to_string(&req).map_err(|e| e.to_string())?;
+    text.push('\n');
+    stream.write_all(text.as_bytes()).map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let mut line = String::new();
+    std::io::BufReader::new(stream).read_line(&mut line).map_err(|e| format!("Failed to read response: {}", e))?;
+
+    serde_json::from_str(line.as_str()).map_err(|e| format!("Unable to parse response: {}", e))
+}
@@ -0,0 +1,108 @@
+//! Accounting of the strings, array handles and group updates handed to plugins, so plugins that
+//! forget to call `deallocate_string`, `drop_array_handle` or `drop_group_update` are told at shutdown.
+//!
+//! Only active in debug builds, in release builds these functions do nothing.
+
+#[cfg(debug_assertions)]
+use std::sync::Mutex;
+
+#[cfg(debug_assertions)]
+use hashbrown::HashMap;
+
+use crate::{Property, PropertyType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Allocation {
+    Str,
+    Array,
+    GroupUpdate
+}
+
+/// Pointer -> (plugin id, type) of everything currently handed out
+#[cfg(debug_assertions)]
+static OUTSTANDING: Mutex<Option<HashMap<usize, (u64, Allocation)>>> = Mutex::new(None);
+
+/// Records that the plugin received this allocation
+pub(crate) fn track<T>(ptr: *mut T, plugin: u64, kind: Allocation) {
+    #[cfg(debug_assertions)]
+    if !ptr.is_null() {
+        if let Ok(mut map) = OUTSTANDING.lock() {
+            map.get_or_insert_with(HashMap::default).insert(ptr as usize, (plugin, kind));
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = (ptr, plugin, kind);
+}
+
+/// Strings and arrays in properties handed out have to be given back by the plugin too
+pub(crate) fn track_property(prop: &Property, plugin: u64) {
+    match prop.sort {
+        PropertyType::Str => track(unsafe { prop.value.str }, plugin, Allocation::Str),
+        PropertyType::Array => track(unsafe { prop.value.arr }, plugin, Allocation::Array),
+        _ => ()
+    }
+}
+
+/// Records that the allocation was given back (deallocated, or consumed by a property)
+pub(crate) fn release<T>(ptr: *mut T) {
+    #[cfg(debug_assertions)]
+    if let Ok(mut map) = OUTSTANDING.lock() {
+        if let Some(map) = map.as_mut() {
+            map.remove(&(ptr as usize));
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = ptr;
+}
+
+/// The plugin that received this allocation
+pub(crate) fn owner<T>(ptr: *mut T) -> Option<u64> {
+    #[cfg(debug_assertions)]
+    return OUTSTANDING.lock().ok()?.as_ref()?.get(&(ptr as usize)).map(|(plugin, _)| *plugin);
+
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = ptr;
+        None
+    }
+}
+
+/// Logs what the plugin never gave back, called when the plugin shuts down
+pub(crate) fn report(plugin: u64, name: &str) {
+    #[cfg(debug_assertions)]
+    {
+        let (mut strings, mut arrays, mut group_updates) = (0, 0, 0);
+
+        if let Ok(mut map) = OUTSTANDING.lock() {
+            if let Some(map) = map.as_mut() {
+                map.retain(|_, (owner, kind)| {
+                    if *owner != plugin {
+                        return true;
+                    }
+
+                    match kind {
+                        Allocation::Str => strings += 1,
+                        Allocation::Array => arrays += 1,
+                        Allocation::GroupUpdate => group_updates += 1
+                    }
+                    false
+                });
+            }
+        }
+
+        if strings > 0 {
+            log::warn!("Plugin {} leaked {} string(s), call deallocate_string on strings received from DataRace", name, strings);
+        }
+        if arrays > 0 {
+            log::warn!("Plugin {} leaked {} array handle(s), call drop_array_handle when done with them", name, arrays);
+        }
+        if group_updates > 0 {
+            log::warn!("Plugin {} leaked {} group update(s), call drop_group_update when done with them", name, group_updates);
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = (plugin, name);
+}
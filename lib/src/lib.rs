@@ -3,7 +3,9 @@ use std::sync::{atomic::AtomicBool, Arc};
 use log::{info, error, debug};
 use tokio::runtime::Builder;
 
-pub(crate) const API_VERSION: u64 = 0;
+/// Has to be raised on every change to the layout of the api types (PropertyValue grew to 24 bytes with
+/// Vector3 in 1), plugins built against another version are not loaded
+pub(crate) const API_VERSION: u64 = 1;
 
 mod built_info {
     include!(concat!(env!("OUT_DIR"), "/built.rs"));
@@ -11,12 +13,63 @@ mod built_info {
 
 mod datastore;
 
+mod config;
+
+mod cli;
+
+mod backup;
+
+mod ipc;
+
+mod service;
+
 mod web;
 
 mod events;
 
+mod system;
+
+mod aggregate;
+
+mod event_stats;
+
+mod rules;
+
+mod inputs;
+
+mod notifications;
+
+mod session;
+
+mod clock;
+
 mod pluginloader;
+pub use pluginloader::{DataRacePlugin, register_static_plugin};
+#[cfg(feature = "lua")]
+mod lua;
+#[cfg(feature = "lua")]
+mod scripts;
+#[cfg(feature = "python")]
+mod python;
 pub(crate) mod utils;
+mod leaks;
+mod memory;
+mod audit;
+mod quota;
+mod threads;
+mod shutdown;
+#[cfg(feature = "trace")]
+mod trace;
+mod callbacks;
+pub use callbacks::PropertyCallback;
+mod frames;
+mod profile;
+mod message_trace;
+mod doctor;
+mod crash;
+mod collisions;
+mod aliases;
+mod stale;
 
 static mut IS_RUNTIME: bool = false;
 
@@ -33,14 +86,119 @@ pub extern "C" fn run() {
     }
 
 
-    let log_level = log::LevelFilter::Debug;
-    env_logger::builder().filter_level(log_level).init();
+    let (mode, profile) = match cli::parse_args() {
+        Ok((cli::Mode::Help, _)) => {
+            println!("{}", cli::USAGE);
+            return;
+        },
+        Ok((cli::Mode::NewPlugin(name), _)) => {
+            if let Err(e) = cli::new_plugin(name.as_str()) {
+                eprintln!("{}", e);
+            }
+            return;
+        },
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            return;
+        }
+    };
+
+    if let Some(profile) = profile {
+        if let Err(e) = config::set_profile(profile) {
+            eprintln!("{}", e);
+            return;
+        }
+    }
+
+    if let cli::Mode::Service = mode {
+        // Services are launched in the system folder, so we move to the folder of the executable
+        if let Some(folder) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.to_path_buf())) {
+            let _ = std::env::set_current_dir(folder);
+        }
+    }
+
+    let config = config::read_config(config::get_config_path().as_path());
+
+    // The logger lets everything through, the actual level is set via max_level,
+    // as this can be changed during runtime when the config is reloaded
+    crash::init_logger(env_logger::builder().filter_level(log::LevelFilter::Trace));
+
+    // Restoring has to work with a broken config, as rolling that back is the point
+    if let cli::Mode::Restore(file) = &mode {
+        if let Err(e) = cli::restore(config.as_ref().ok(), file.as_str()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Same for the doctor, a broken config is one of the things it reports
+    if let cli::Mode::Doctor { json } = mode {
+        if !doctor::run_doctor(config.as_ref(), json) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Unable to load {}, aborting launch: {}", config::get_config_path().to_str().unwrap_or_default(), e);
+            return;
+        }
+    };
+    log::set_max_level(config.get_log_level());
+    api_func::set_strict_ffi(config.is_strict_ffi());
+
+    let json = match mode {
+        cli::Mode::ListPlugins { json } => {
+            cli::list_plugins(&config, json);
+            return;
+        },
+        cli::Mode::Backup(folder) => {
+            if let Err(e) = cli::backup(&config, folder) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        },
+        cli::Mode::Ctl(cmd) => {
+            if let Err(e) = cli::run_ctl(&config, cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        },
+        cli::Mode::Service => {
+            service::run_service(config);
+            return;
+        },
+        cli::Mode::DumpProperties { json } => Some(json),
+        _ => None
+    };
+
+    launch(config, json);
+}
+
+/// Starts the async runtime and runs DataRace till shutdown.
+/// json is only set when the properties should be dumped (see cli)
+pub(crate) fn launch(config: config::Config, json: Option<bool>) {
+    #[cfg(feature = "trace")]
+    let _trace = trace::init();
+    crash::install_hook();
 
     if let Ok(rt) = Builder::new_multi_thread().enable_all().build() {
-        let res = rt.block_on(internal_main());
+        // Within the runtime, so the report is written before it shuts down
+        let res = crash::report_panics(|| if let Some(json) = json {
+            rt.block_on(cli::dump_properties(config, json))
+        } else {
+            rt.block_on(internal_main(config))
+        });
 
         if let Err(e) = res {
             error!("DataRace crashed: {}", e);
+            crash::write_report(format!("DataRace crashed: {}", e).as_str(), None);
         } else {
             info!("Shutting down...");
         }
@@ -49,19 +207,22 @@ pub extern "C" fn run() {
     } else {
         error!("Unable to launch tokio async runtime, aborting launch")
     }
-
 }
 
-async fn internal_main() -> Result<(), Box<dyn std::error::Error> > {
+async fn internal_main(config: config::Config) -> Result<(), Box<dyn std::error::Error> > {
     info!("Launching DataRace version {}.{}.{} (apiversion: {})...", built_info::PKG_VERSION_MAJOR, built_info::PKG_VERSION_MINOR, built_info::PKG_VERSION_PATCH, API_VERSION);
 
     let (event_loop, event_channel) = events::create_event_task();
-    let datastore: &'static tokio::sync::RwLock<datastore::DataStore>  = Box::leak(Box::new(datastore::DataStore::new(event_channel)));
+    let shutdown_config = config.get_shutdown().clone();
+    let datastore: &'static tokio::sync::RwLock<datastore::DataStore>  = Box::leak(Box::new(datastore::DataStore::new(event_channel, config)));
+    crash::set_datastore(datastore);
 
+    // Handles Ctrl+C, SIGTERM (like from systemd) and stop requests of the Windows service manager
     let shutdown = Arc::new(AtomicBool::new(false));
-    let sh_clone = shutdown.clone();
-    ctrlc::set_handler(move || {
-        futures_lite::future::block_on(async {
+    let runtime = tokio::runtime::Handle::current();
+    let stop_config = shutdown_config.clone();
+    let stop = move || {
+        runtime.block_on(async {
             if shutdown.load(std::sync::atomic::Ordering::Acquire) {
                 // we are already in a shutdown
                 error!("Stop requested a second time, so we are now hard exiting");
@@ -69,14 +230,19 @@ async fn internal_main() -> Result<(), Box<dyn std::error::Error> > {
             }
 
             // We shut down everything
-            let mut ds = datastore.write().await;
-            ds.start_shutdown().await;
-            drop(ds);
+            shutdown::request_shutdown(datastore, &stop_config).await;
 
             shutdown.store(true, std::sync::atomic::Ordering::Release);
         });
-    })?;
-
+    };
+    service::set_stop_handler(Box::new(stop.clone()));
+    ctrlc::set_handler(stop)?;
+
+    let clock = clock::create_clock_property(datastore).await;
+    let system_properties = system::create_system_properties(datastore).await;
+    session::create_session_properties(datastore).await;
+    let aggregates = aggregate::create_aggregates(datastore).await;
+    let event_stats = event_stats::create_event_stats(datastore).await;
     let mut plugin_set = pluginloader::load_all_plugins(datastore).await?;
 
     // Handles closing the plugin tasks
@@ -99,11 +265,41 @@ async fn internal_main() -> Result<(), Box<dyn std::error::Error> > {
         debug!("All Plugins have shut down");
     });
 
-    web::run_webserver(datastore, sh_clone).await?;
+    let config_watcher = tokio::spawn(config::run_config_watcher(datastore));
+    let control_socket = tokio::spawn(ipc::run_control_socket(datastore));
+    let watchdog = tokio::spawn(service::run_watchdog(datastore));
+    let clock = tokio::spawn(clock::run_clock(datastore, clock));
+    let system_properties = tokio::spawn(system::run_system_properties(datastore, system_properties));
+    let aggregates = tokio::spawn(aggregate::run_aggregates(datastore, aggregates));
+    let event_stats = tokio::spawn(event_stats::run_event_stats(datastore, event_stats));
+    let rules = tokio::spawn(rules::run_rules(datastore));
+    let inputs = tokio::spawn(inputs::run_inputs(datastore));
+    let stale = tokio::spawn(stale::run_stale_monitor(datastore));
+    #[cfg(feature = "lua")]
+    let scripts = tokio::spawn(scripts::run_script_watcher(datastore));
+
+    tokio::select! {
+        res = web::run_webserver(datastore) => res?,
+        _ = shutdown::forced_teardown() => ()
+    }
 
-    // Stops the Runtime from closing when plugins are still running
-    let _ = handle.await;
-    let _ = event_loop.await;
+    // Stops the Runtime from closing when plugins are still running (up to the join_timeout)
+    let tasks = vec![
+        event_loop,
+        config_watcher,
+        control_socket,
+        watchdog,
+        clock,
+        system_properties,
+        aggregates,
+        event_stats,
+        rules,
+        inputs,
+        stale,
+        #[cfg(feature = "lua")]
+        scripts
+    ];
+    shutdown::join(datastore, &shutdown_config, handle, tasks).await;
 
     Ok(())
 }
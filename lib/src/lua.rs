@@ -0,0 +1,343 @@
+use std::{ffi::CString, path::Path, sync::Mutex, time::Duration};
+
+use hashbrown::HashMap;
+use mlua::{Function, Lua, Table, Value as LuaValue};
+
+use crate::{events::EventMessage, notifications, pluginloader::{DataRacePlugin, LoaderMessage}, utils, ArrayValueHandle, DataStoreReturnCode, EventFilter, EventHandle, Message, MessageType, PluginHandle, Property, PropertyHandle, PropertyType, PropertyValue};
+
+/// A .lua script out of the plugin folder, run as a built-in plugin.
+///
+/// The script can define the global functions `init()` and `update(msg)`,
+/// and uses the `datarace` table to interact with properties and events.
+pub(crate) struct LuaPlugin {
+    name: String,
+    lua: Mutex<Lua>
+}
+
+/// The PluginHandle of the running script, set on init
+struct HandlePtr(usize);
+
+/// The id of the plugin and the names of the events and properties subscribed to, so we can pass the name in update
+struct ScriptInfo {
+    name: String,
+    id: u64,
+    events: HashMap<EventHandle, String>,
+    properties: HashMap<PropertyHandle, String>,
+    /// Running timers, delivered as internal messages with the id
+    timers: HashMap<i64, String>,
+    next_timer: i64
+}
+
+impl LuaPlugin {
+    /// Runs the script once (so it defines its functions), the name of the plugin is the file name
+    /// The code is passed in, as it was read when checking the hash (see read_plugin_file)
+    pub(crate) fn load(path: &Path, code: String) -> Result<LuaPlugin, String> {
+        let name = crate::pluginloader::script_name(path);
+        let id = utils::generate_plugin_name_hash(name.as_str()).ok_or(format!("Plugin name {} does not comply with naming schema", name))?;
+
+        let lua = Lua::new();
+        lua.set_app_data(ScriptInfo { name: name.clone(), id, events: HashMap::default(), properties: HashMap::default(), timers: HashMap::default(), next_timer: 0 });
+        register_api(&lua).map_err(|e| format!("Unable to set up api: {}", e))?;
+        lua.load(code.as_str()).set_name(name.as_str()).exec().map_err(|e| format!("Script failed: {}", e))?;
+
+        Ok(LuaPlugin { name, lua: Mutex::new(lua) })
+    }
+}
+
+impl DataRacePlugin for LuaPlugin {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn version(&self) -> [u16;3] {
+        [0, 0, 0]
+    }
+
+    fn init(&self, handle: *mut PluginHandle) -> Result<(), String> {
+        let lua = self.lua.lock().map_err(|_| "Lua runtime poisoned".to_string())?;
+        lua.set_app_data(HandlePtr(handle as usize));
+
+        if let Ok(func) = lua.globals().get::<_, Function>("init") {
+            func.call::<_, ()>(()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn update(&self, _handle: *mut PluginHandle, msg: Message) -> Result<(), String> {
+        let lua = self.lua.lock().map_err(|_| "Lua runtime poisoned".to_string())?;
+
+        let table = lua.create_table().map_err(|e| e.to_string())?;
+        match msg.sort {
+            MessageType::StartupFinished => table.set("type", "startup_finished"),
+            MessageType::OtherPluginStarted => table.set("type", "other_plugin_started")
+                .and_then(|_| table.set("plugin_id", unsafe { msg.value.plugin_id })),
+            MessageType::EventTriggered | MessageType::EventUnsubscribed => {
+                let event = unsafe { msg.value.event };
+                let name = lua.app_data_ref::<ScriptInfo>().and_then(|info| info.events.get(&event).cloned());
+                table.set("type", if matches!(msg.sort, MessageType::EventTriggered) { "event_triggered" } else { "event_unsubscribed" })
+                    .and_then(|_| table.set("event", name))
+            },
+            MessageType::PropertySubscribed | MessageType::PropertyTypeChanged => {
+                // Converting the value also deallocates it, so this is done even without an update function
+                let update = unsafe { std::mem::ManuallyDrop::into_inner(msg.value.update) };
+                let name = lua.app_data_ref::<ScriptInfo>().and_then(|info| info.properties.get(&update.handle).cloned());
+                let value = from_property(&lua, update.value).map_err(|e| e.to_string())?;
+                table.set("type", if matches!(msg.sort, MessageType::PropertySubscribed) { "property_subscribed" } else { "property_type_changed" })
+                    .and_then(|_| table.set("property", name))
+                    .and_then(|_| table.set("value", value))
+            },
+            MessageType::OperationFailed => {
+                let failed = unsafe { msg.value.operation_failed };
+                let name = lua.app_data_ref::<ScriptInfo>().and_then(|info| info.properties.get(&failed.property).cloned());
+                table.set("type", "operation_failed")
+                    .and_then(|_| table.set("operation", format!("{:?}", failed.what)))
+                    .and_then(|_| table.set("code", format!("{:?}", failed.code)))
+                    .and_then(|_| table.set("property", name))
+            },
+            MessageType::SubscriptionRemoved => {
+                let prop = unsafe { msg.value.removed_property };
+                let name = lua.app_data_ref::<ScriptInfo>().and_then(|info| info.properties.get(&prop).cloned());
+                table.set("type", "subscription_removed")
+                    .and_then(|_| table.set("property", name))
+            },
+            MessageType::InternalMessage => {
+                let timer = unsafe { msg.value.internal_msg };
+                let Some(name) = lua.app_data_mut::<ScriptInfo>().and_then(|mut info| info.timers.remove(&timer)) else {
+                    return Ok(());
+                };
+                table.set("type", "timer")
+                    .and_then(|_| table.set("name", name))
+            },
+            MessageType::Shutdown => table.set("type", "shutdown"),
+            // Scripts are run one message at a time, so they have no use for lock/unlock or pointers
+            _ => return Ok(())
+        }.map_err(|e| e.to_string())?;
+
+        let Ok(func) = lua.globals().get::<_, Function>("update") else {
+            return Ok(());
+        };
+        func.call::<_, ()>(table).map_err(|e| e.to_string())
+    }
+}
+
+/// Creates the `datarace` table of functions available to scripts
+fn register_api(lua: &Lua) -> mlua::Result<()> {
+    let api = lua.create_table()?;
+
+    api.set("create_property", lua.create_function(|lua, (name, value): (String, LuaValue)| {
+        let (handle, prop_handle) = own_property(lua, name.as_str())?;
+        let value = to_property(value)?;
+        let name = CString::new(name).map_err(mlua::Error::external)?.into_raw();
+
+        let res = crate::create_property(handle, name, prop_handle, value);
+        crate::deallocate_string(name);
+        check(res)
+    })?)?;
+
+    api.set("update_property", lua.create_function(|lua, (name, value): (String, LuaValue)| {
+        let (handle, prop_handle) = own_property(lua, name.as_str())?;
+        check(crate::update_property(handle, prop_handle, to_property(value)?))
+    })?)?;
+
+    api.set("delete_property", lua.create_function(|lua, name: String| {
+        let (handle, prop_handle) = own_property(lua, name.as_str())?;
+        check(crate::delete_property(handle, prop_handle))
+    })?)?;
+
+    api.set("get_property", lua.create_function(|lua, name: String| {
+        let handle = get_handle(lua)?;
+        let prop_handle = PropertyHandle::new(name.as_str()).ok_or(mlua::Error::runtime(format!("Invalid property name {}", name)))?;
+
+        let res = crate::get_property_value(handle, prop_handle);
+        check(res.code)?;
+        from_property(lua, res.value)
+    })?)?;
+
+    api.set("subscribe_property", lua.create_function(|lua, name: String| {
+        let handle = get_handle(lua)?;
+        let prop_handle = PropertyHandle::new(name.as_str()).ok_or(mlua::Error::runtime(format!("Invalid property name {}", name)))?;
+
+        if let Some(mut info) = lua.app_data_mut::<ScriptInfo>() {
+            info.properties.insert(prop_handle, name);
+        }
+        check(crate::subscribe_property(handle, prop_handle))
+    })?)?;
+
+    api.set("create_event", lua.create_function(|lua, name: String| {
+        let (handle, event) = own_event(lua, name.as_str())?;
+        check(crate::create_event(handle, event))
+    })?)?;
+
+    api.set("trigger_event", lua.create_function(|lua, name: String| {
+        let (handle, event) = own_event(lua, name.as_str())?;
+        check(crate::trigger_event(handle, event))
+    })?)?;
+
+    api.set("subscribe_event", lua.create_function(|lua, (name, debounce_ms, every_nth): (String, Option<u64>, Option<u64>)| {
+        let handle = get_handle(lua)?;
+        let event = EventHandle::new(name.as_str()).ok_or(mlua::Error::runtime(format!("Invalid event name {}", name)))?;
+
+        if let Some(mut info) = lua.app_data_mut::<ScriptInfo>() {
+            info.events.insert(event, name);
+        }
+        let filter = EventFilter { debounce_ms: debounce_ms.unwrap_or_default(), every_nth: every_nth.unwrap_or_default() };
+        check(crate::subscribe_event_filtered(handle, event, filter))
+    })?)?;
+
+    api.set("read_property", lua.create_function(|lua, name: String| {
+        let handle = get_handle(lua)?;
+        let prop_handle = PropertyHandle::new(name.as_str()).ok_or(mlua::Error::runtime(format!("Invalid property name {}", name)))?;
+
+        // Same as lookup_property_type, waiting on the datastore here could deadlock
+        let han = unsafe { handle.as_ref() }.ok_or(mlua::Error::runtime("Plugin handle missing"))?;
+        let value = {
+            let ds = han.datastore.try_read().map_err(|_| mlua::Error::runtime(format!("DataRace returned {:?}", DataStoreReturnCode::Busy)))?;
+            ds.get_property_container(&prop_handle).map(|cont| cont.read(false))
+        };
+        let value = value.ok_or(mlua::Error::runtime(format!("DataRace returned {:?}", DataStoreReturnCode::DoesNotExist)))?;
+        crate::leaks::track_property(&value, han.id);
+        from_property(lua, value)
+    })?)?;
+
+    api.set("trigger_action", lua.create_function(|lua, name: String| {
+        let handle = get_handle(lua)?;
+        let event = EventHandle::new(name.as_str()).ok_or(mlua::Error::runtime(format!("Invalid event name {}", name)))?;
+
+        let han = unsafe { handle.as_ref() }.ok_or(mlua::Error::runtime("Plugin handle missing"))?;
+        han.event_channel.send(EventMessage::Trigger(event)).map_err(mlua::Error::external)
+    })?)?;
+
+    api.set("set_timer", lua.create_function(|lua, (seconds, name): (f64, String)| {
+        let handle = get_handle(lua)?;
+        let han = unsafe { handle.as_ref() }.ok_or(mlua::Error::runtime("Plugin handle missing"))?;
+        let delay = Duration::try_from_secs_f64(seconds).map_err(mlua::Error::external)?;
+        let runtime = tokio::runtime::Handle::try_current().map_err(mlua::Error::external)?;
+
+        let timer = {
+            let mut info = lua.app_data_mut::<ScriptInfo>().ok_or(mlua::Error::runtime("Script info missing"))?;
+            let timer = info.next_timer;
+            info.next_timer += 1;
+            info.timers.insert(timer, name);
+            timer
+        };
+
+        // If the script was shut down in the mean time sending fails, which is fine
+        let sender = han.sender.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = sender.send(LoaderMessage::InternalMessage(timer));
+        });
+        Ok(())
+    })?)?;
+
+    api.set("notify", lua.create_function(|lua, (severity, text): (String, String)| {
+        let handle = get_handle(lua)?;
+        let severity = notifications::parse_severity(severity.as_str())
+            .ok_or(mlua::Error::runtime(format!("Invalid severity {}, has to be info, warning or error", severity)))?;
+        let text = CString::new(text).map_err(mlua::Error::external)?.into_raw();
+
+        let res = crate::raise_notification(handle, severity, text);
+        crate::deallocate_string(text);
+        check(res)
+    })?)?;
+
+    api.set("log_info", lua.create_function(|lua, msg: String| {
+        log::info!("[{}] {}", script_name(lua), msg);
+        Ok(())
+    })?)?;
+
+    api.set("log_error", lua.create_function(|lua, msg: String| {
+        log::error!("[{}] {}", script_name(lua), msg);
+        Ok(())
+    })?)?;
+
+    lua.globals().set("datarace", api)
+}
+
+fn get_handle(lua: &Lua) -> mlua::Result<*mut PluginHandle> {
+    lua.app_data_ref::<HandlePtr>()
+        .map(|ptr| ptr.0 as *mut PluginHandle)
+        .ok_or(mlua::Error::runtime("The datarace api is only available from init onwards"))
+}
+
+fn script_name(lua: &Lua) -> String {
+    lua.app_data_ref::<ScriptInfo>().map(|info| info.name.clone()).unwrap_or_default()
+}
+
+/// Properties of the script itself are addressed without the plugin name
+fn own_property(lua: &Lua, name: &str) -> mlua::Result<(*mut PluginHandle, PropertyHandle)> {
+    let handle = get_handle(lua)?;
+    let id = lua.app_data_ref::<ScriptInfo>().map(|info| info.id).unwrap_or_default();
+    let property = utils::generate_property_name_hash(name).ok_or(mlua::Error::runtime(format!("Invalid property name {}", name)))?;
+
+    Ok((handle, PropertyHandle { plugin: id, property }))
+}
+
+/// Events of the script itself are addressed without the plugin name
+fn own_event(lua: &Lua, name: &str) -> mlua::Result<(*mut PluginHandle, EventHandle)> {
+    let handle = get_handle(lua)?;
+    let id = lua.app_data_ref::<ScriptInfo>().map(|info| info.id).unwrap_or_default();
+    let event = utils::generate_event_name_hash(name).ok_or(mlua::Error::runtime(format!("Invalid event name {}", name)))?;
+
+    Ok((handle, EventHandle { plugin: id, event }))
+}
+
+fn check(code: DataStoreReturnCode) -> mlua::Result<()> {
+    if code == DataStoreReturnCode::Ok {
+        Ok(())
+    } else {
+        Err(mlua::Error::runtime(format!("DataRace returned {:?}", code)))
+    }
+}
+
+/// Converts a lua value into a Property, strings are freed by the property (see PropertyContainer::update)
+fn to_property(value: LuaValue) -> mlua::Result<Property> {
+    Ok(match value {
+        LuaValue::Nil => Property::default(),
+        LuaValue::Integer(i) => Property { sort: PropertyType::Int, value: PropertyValue { integer: i } },
+        LuaValue::Number(f) => Property { sort: PropertyType::Float, value: PropertyValue { decimal: f } },
+        LuaValue::Boolean(b) => Property { sort: PropertyType::Boolean, value: PropertyValue { boolean: b } },
+        LuaValue::String(s) => {
+            let ptr = CString::new(s.as_bytes()).map_err(mlua::Error::external)?.into_raw();
+            Property { sort: PropertyType::Str, value: PropertyValue { str: ptr } }
+        },
+        other => return Err(mlua::Error::runtime(format!("Unable to store a {} in a property", other.type_name())))
+    })
+}
+
+/// Converts a Property into a lua value, durations are in seconds, vectors and arrays become tables
+fn from_property(lua: &Lua, prop: Property) -> mlua::Result<LuaValue<'_>> {
+    Ok(match prop.sort {
+        PropertyType::None => LuaValue::Nil,
+        PropertyType::Int => LuaValue::Integer(unsafe { prop.value.integer }),
+        PropertyType::Float => LuaValue::Number(unsafe { prop.value.decimal }),
+        PropertyType::Boolean => LuaValue::Boolean(unsafe { prop.value.boolean }),
+        PropertyType::Duration => LuaValue::Number(unsafe { prop.value.dur } as f64 / 1_000_000.0),
+        PropertyType::Timestamp => LuaValue::Number(unsafe { prop.value.timestamp } as f64 / 1_000_000.0),
+        PropertyType::Vector => {
+            let v = unsafe { prop.value.vector };
+            let table: Table = lua.create_table()?;
+            table.set("x", v.x)?;
+            table.set("y", v.y)?;
+            table.set("z", v.z)?;
+            LuaValue::Table(table)
+        },
+        PropertyType::Str => {
+            let ptr = unsafe { prop.value.str };
+            let text = utils::get_string(ptr).unwrap_or_default();
+            crate::deallocate_string(ptr);
+            LuaValue::String(lua.create_string(text.as_str())?)
+        },
+        PropertyType::Array => {
+            let arr: *mut ArrayValueHandle = unsafe { prop.value.arr };
+            let table: Table = lua.create_table()?;
+            for index in 0..crate::get_array_length(arr) {
+                // lua arrays start at 1
+                table.set(index + 1, from_property(lua, crate::get_array_value(arr, index))?)?;
+            }
+            crate::drop_array_handle(arr);
+            LuaValue::Table(table)
+        }
+    })
+}
+
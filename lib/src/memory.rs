@@ -0,0 +1,105 @@
+//! Memory held by the datastore per plugin: the properties with their strings and arrays,
+//! and the history buffers of the aggregates (counted for the core plugin).
+//!
+//! Sizes are estimates of the containers and the heap allocations behind them, allocator overhead is not included.
+//! Arrays and strings a plugin holds outside of its properties are not part of it.
+
+use std::mem::size_of;
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::{aggregate, datastore::DataStore, system};
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub(crate) struct MemoryUsage {
+    pub(crate) plugin: String,
+    pub(crate) total_bytes: usize,
+    pub(crate) properties: usize,
+    /// The containers of the properties and their names, without strings and arrays
+    pub(crate) property_bytes: usize,
+    pub(crate) arrays: usize,
+    pub(crate) array_bytes: usize,
+    pub(crate) strings: usize,
+    pub(crate) string_bytes: usize,
+    /// Samples kept by aggregates with a window
+    pub(crate) history_samples: usize,
+    pub(crate) history_bytes: usize
+}
+
+impl MemoryUsage {
+    pub(crate) fn add_property(&mut self, bytes: usize) {
+        self.properties += 1;
+        self.property_bytes += bytes;
+    }
+
+    pub(crate) fn add_array(&mut self, bytes: usize) {
+        self.arrays += 1;
+        self.array_bytes += bytes;
+    }
+
+    /// Takes the capacity of the string, which is what it actually allocated
+    pub(crate) fn add_string(&mut self, capacity: usize) {
+        self.strings += 1;
+        self.string_bytes += capacity;
+    }
+}
+
+/// Formats bytes for display, like 12.3 KiB
+pub(crate) fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Size of an Arc holding a T (the value and the two reference counts)
+pub(crate) fn arc_size<T>() -> usize {
+    size_of::<T>() + 2 * size_of::<usize>()
+}
+
+/// Collects the usage of all plugins with properties, sorted by the total (largest first)
+pub(crate) fn collect(ds: &DataStore) -> Vec<MemoryUsage> {
+    let mut plugins: HashMap<u64, MemoryUsage> = HashMap::new();
+
+    for handle in ds.iter_properties() {
+        let Some(cont) = ds.get_property_container(handle) else {
+            continue;
+        };
+        let name = ds.read_property_name(handle).unwrap_or_default();
+
+        let usage = plugins.entry(handle.plugin).or_default();
+        if usage.plugin.is_empty() {
+            usage.plugin = name.split_once('.').map(|(plugin, _)| plugin.to_string()).unwrap_or_else(|| handle.plugin.to_string());
+        }
+
+        cont.add_memory_usage(usage);
+        usage.property_bytes += size_of::<String>() + name.capacity();
+    }
+
+    let (samples, bytes) = aggregate::history_usage();
+    if samples > 0 {
+        let core = plugins.entry(system::CORE_PLUGIN_ID).or_default();
+        core.plugin = system::CORE_PLUGIN_NAME.to_string();
+        core.history_samples = samples;
+        core.history_bytes = bytes;
+    }
+
+    let mut list: Vec<MemoryUsage> = plugins.into_values().map(|mut usage| {
+        usage.total_bytes = usage.property_bytes + usage.array_bytes + usage.string_bytes + usage.history_bytes;
+        usage
+    }).collect();
+    list.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then_with(|| a.plugin.cmp(&b.plugin)));
+
+    list
+}
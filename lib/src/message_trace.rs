@@ -0,0 +1,116 @@
+//! Message tracing of single plugins, for debugging the order messages arrive in (like the Lock
+//! that comes in between the steps of a subscription in the sample_plugin). Every message taken
+//! from the queue of a traced plugin and every message handed to its update function (or filtered
+//! out by the plugin) is written with the timestamp of the core clock to the log or to a file.
+//!
+//! Toggled at runtime over the control socket. Tracing is kept by plugin id, so a plugin can be
+//! traced before it is loaded (or across a restart) to also catch its startup.
+
+use std::{fs::File, io::{LineWriter, Write}, path::Path, sync::{atomic::{AtomicBool, Ordering}, Mutex}};
+
+use hashbrown::HashMap;
+use log::{error, info};
+
+use crate::{clock, datastore::DataStore, Message, MessageType, PropertyHandle};
+
+enum Output {
+    Log,
+    /// Flushed on every line, so the trace is complete even if DataRace crashes
+    File(LineWriter<File>)
+}
+
+struct Target {
+    name: String,
+    output: Output
+}
+
+/// Set while any plugin is traced, so the untraced plugins do not have to take the lock
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Plugin id -> where its trace goes
+static TARGETS: Mutex<Option<HashMap<u64, Target>>> = Mutex::new(None);
+
+fn with_targets<R>(func: impl FnOnce(&mut HashMap<u64, Target>) -> R) -> R {
+    let mut guard = TARGETS.lock().unwrap_or_else(|e| e.into_inner());
+    let targets = guard.get_or_insert_with(HashMap::default);
+    let res = func(targets);
+    ACTIVE.store(!targets.is_empty(), Ordering::Release);
+    res
+}
+
+/// Starts tracing the plugin, into the file if set (overwriting it) otherwise into the log.
+/// Replaces the previous trace of this plugin
+pub(crate) fn start(id: u64, name: &str, file: Option<&Path>) -> Result<(), String> {
+    let output = match file {
+        Some(path) => Output::File(LineWriter::new(File::create(path)
+            .map_err(|e| format!("Unable to create trace file {}: {}", path.to_str().unwrap_or_default(), e))?)),
+        None => Output::Log
+    };
+
+    info!("Tracing messages of plugin {}{}", name, file.and_then(|path| path.to_str()).map(|path| format!(" into {}", path)).unwrap_or_default());
+    with_targets(|targets| targets.insert(id, Target { name: name.to_string(), output }));
+    Ok(())
+}
+
+/// Stops tracing the plugin, false if it was not traced
+pub(crate) fn stop(id: u64) -> bool {
+    match with_targets(|targets| targets.remove(&id)) {
+        Some(target) => {
+            info!("Stopped tracing messages of plugin {}", target.name);
+            true
+        },
+        None => false
+    }
+}
+
+/// Writes a line to the trace, if the plugin is traced. The summary is only generated then
+pub(crate) fn record(id: u64, stage: &str, name: &str, summary: impl FnOnce() -> String) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+
+    with_targets(|targets| {
+        let Some(target) = targets.get_mut(&id) else {
+            return;
+        };
+
+        let line = format!("{} {} {} {}", clock::now(), stage, name, summary());
+        match &mut target.output {
+            Output::Log => info!("Trace {}: {}", target.name, line.trim_end()),
+            Output::File(writer) => if let Err(e) = writeln!(writer, "{}", line.trim_end()) {
+                error!("Unable to write trace of plugin {}, continuing in the log: {}", target.name, e);
+                target.output = Output::Log;
+            }
+        }
+    });
+}
+
+/// Name of the property for the trace. The datastore is only tried, as the trace is written while
+/// the loader may hold the lock, falling back to the ids
+pub(crate) fn property_label(datastore: &tokio::sync::RwLock<DataStore>, handle: &PropertyHandle) -> String {
+    match datastore.try_read() {
+        Ok(ds_r) => ds_r.property_label(handle),
+        Err(_) => format!("{}|{}", handle.plugin, handle.property)
+    }
+}
+
+/// Payload of the message handed to the update function of the plugin
+pub(crate) fn summarize(msg: &Message, datastore: &tokio::sync::RwLock<DataStore>) -> String {
+    // The sort determines which field of the union is set
+    unsafe {
+        match msg.sort {
+            MessageType::StartupFinished | MessageType::Lock | MessageType::Unlock | MessageType::Shutdown => String::new(),
+            MessageType::OtherPluginStarted | MessageType::WatchedPluginStarted | MessageType::WatchedPluginStopped => format!("plugin {}", msg.value.plugin_id),
+            MessageType::InternalMessage => format!("value {}", msg.value.internal_msg),
+            MessageType::PluginMessagePtr => format!("from {} reason {}", msg.value.message_ptr.origin, msg.value.message_ptr.reason),
+            MessageType::EventTriggered | MessageType::EventUnsubscribed => format!("event {}|{}", msg.value.event.plugin, msg.value.event.event),
+            MessageType::PropertySubscribed | MessageType::PropertyTypeChanged => format!("property {} ({:?})",
+                property_label(datastore, &msg.value.update.handle), msg.value.update.value.sort),
+            MessageType::SubscriptionRemoved => format!("property {}", property_label(datastore, &msg.value.removed_property)),
+            MessageType::GroupUpdate => format!("group {} with {} updates", msg.value.group_update.group, msg.value.group_update.len),
+            MessageType::OperationFailed => {
+                let failed = &msg.value.operation_failed;
+                format!("{:?} failed with {:?} (property {}, target {})", failed.what, failed.code, property_label(datastore, &failed.property), failed.target)
+            }
+        }
+    }
+}
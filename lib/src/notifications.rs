@@ -0,0 +1,105 @@
+//! Alerts raised by plugins (and the rules), shown as toasts on all connected dashboards
+//! and listed on the notifications page of the web ui.
+//!
+//! The datastore keeps the last notifications, the socket updater sends out everything newer
+//! then what it has send last cycle.
+
+use std::{collections::VecDeque, time::{SystemTime, UNIX_EPOCH}};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::NotificationSeverity;
+
+/// How many notifications are kept for the notifications page
+const HISTORY_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Notification {
+    /// Increasing, used by the clients to tell notifications apart
+    pub(crate) id: u64,
+    /// Milliseconds since the unix epoch
+    pub(crate) time: i64,
+    pub(crate) severity: Severity,
+    /// Name of the plugin that raised it (or rules)
+    pub(crate) source: String,
+    pub(crate) text: String
+}
+
+/// NotificationSeverity, but serializable (and used in the config)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Error
+}
+
+impl Severity {
+    /// Same as the serialized name
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        }
+    }
+}
+
+impl From<NotificationSeverity> for Severity {
+    fn from(value: NotificationSeverity) -> Self {
+        match value {
+            NotificationSeverity::Info => Severity::Info,
+            NotificationSeverity::Warning => Severity::Warning,
+            NotificationSeverity::Error => Severity::Error
+        }
+    }
+}
+
+/// Parses the severity passed by scripts as a string
+#[cfg(any(feature = "lua", feature = "python"))]
+pub(crate) fn parse_severity(name: &str) -> Option<NotificationSeverity> {
+    match name {
+        "info" => Some(NotificationSeverity::Info),
+        "warning" => Some(NotificationSeverity::Warning),
+        "error" => Some(NotificationSeverity::Error),
+        _ => None
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Notifications {
+    list: VecDeque<Notification>,
+    next_id: u64
+}
+
+impl Notifications {
+    pub(crate) fn push(&mut self, severity: Severity, source: String, text: String) {
+        // Also logged, so they are not lost when nobody is looking at a dashboard
+        match severity {
+            Severity::Info => info!("Notification from {}: {}", source, text),
+            Severity::Warning => warn!("Notification from {}: {}", source, text),
+            Severity::Error => error!("Notification from {}: {}", source, text)
+        }
+
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+
+        self.next_id += 1;
+        self.list.push_back(Notification { id: self.next_id, time, severity, source, text });
+
+        while self.list.len() > HISTORY_LENGTH {
+            self.list.pop_front();
+        }
+    }
+
+    /// All notifications with an id higher then the given one, oldest first
+    pub(crate) fn since(&self, id: u64) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.list.iter().filter(move |n| n.id > id)
+    }
+
+    /// Id of the newest notification (0 if none were raised yet)
+    pub(crate) fn last_id(&self) -> u64 {
+        self.next_id
+    }
+}
@@ -1,12 +1,15 @@
-use std::{path::PathBuf, fs};
+use std::{path::{Path, PathBuf}, fs, mem::ManuallyDrop, str::FromStr, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
 
 use dlopen2::wrapper::{WrapperApi, Container};
-use hashbrown::HashMap;
-use log::{error, info, debug};
+use hashbrown::{HashMap, HashSet};
+use log::{error, info, debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use tokio::task::JoinSet;
+use tracing::Instrument;
 
-use crate::{api_types, datastore::DataStore, events::EventMessage, utils::{self, VoidPtrWrapper}, DataStoreReturnCode, EventHandle, Message, MessagePtr, MessageType, MessageValue, PluginHandle, PropertyHandle};
+use crate::{api_types, collisions, config::Config, datastore::DataStore, events::EventMessage, quota::Quota, threads, utils::{self, VoidPtrWrapper}, DataStoreReturnCode, EventHandle, Message, MessagePtr, MessageType, MessageValue, FailedOperation, Operation, PluginHandle, PropertyHandle, SubscriptionGroupUpdate, UpdateValue};
 
 
 
@@ -33,206 +36,728 @@ pub(crate) async fn load_all_plugins(datastore: &'static tokio::sync::RwLock<Dat
 
     let mut plugin_task_handles = JoinSet::<Result<(), String>>::new();
 
+    // Plugins sharing an id would fail to register depending on the load order, so we pick one ahead.
+    // This loads the libraries, which already executes code in them, so it is blocking
+    let config = datastore.read().await.get_config().clone();
+    let scan = tokio::task::spawn_blocking(move || scan_plugins(&config)).await
+        .map_err(|e| e.to_string())
+        .and_then(|res| res.map_err(|e| e.to_string()));
+    let (list, mut libraries, duplicates) = match scan {
+        Ok(res) => res,
+        Err(e) => {
+            error!("Unable to check the plugins for duplicates: {}", e);
+            (Vec::new(), Libraries::new(), Vec::new())
+        }
+    };
+    let skipped = list.iter().filter(|info| info.duplicate && info.path != Path::new(BUILT_IN)).map(|info| info.path.clone()).collect::<HashSet<_>>();
+    let skipped_built_in = list.iter().filter(|info| info.path == Path::new(BUILT_IN)).map(|info| info.duplicate).collect::<Vec<_>>();
+    report_duplicates(datastore, duplicates).await;
 
     if let Ok(mut res) = fs::read_dir(plugin_folder) {
         while let Some(Ok(item)) = res.next() {
             debug!("Found {} in plugin folder", item.path().to_str().unwrap());
+            if skipped.contains(&item.path()) {
+                continue;
+            }
             if item.path().extension().unwrap().to_str().unwrap() == ending {
                 let event_c = event_channel.clone();
-                plugin_task_handles.spawn(run_plugin(item.path(), datastore, event_c));
+                // Reusing the library loaded by the scan, only the ones it failed on are opened again (reporting why)
+                match libraries.remove(&item.path()) {
+                    Some(wrapper) => plugin_task_handles.spawn(run_library(item.path(), wrapper, datastore, event_c)),
+                    None => plugin_task_handles.spawn(run_plugin(item.path(), datastore, event_c))
+                };
+            }
+
+            let (script, enabled) = {
+                let ds_r = datastore.read().await;
+                let config = ds_r.get_config();
+                (read_script_plugin(config, item.path().as_path()), config.is_plugin_enabled(script_name(item.path().as_path()).as_str()))
+            };
+            match script {
+                // Loading already runs the script, so this can not wait for run_loaded_plugin to check
+                Some(Ok(_)) if !enabled => info!("Plugin {} is not in the plugins list of the config, skipping", script_name(item.path().as_path())),
+                Some(Ok((load, code))) => match load_script_plugin(load, item.path(), code).await {
+                    Ok(plugin) => { plugin_task_handles.spawn(run_static_plugin(plugin, datastore, event_channel.clone())); },
+                    Err(e) => error!("Unable to load script {}: {}", item.path().to_str().unwrap_or_default(), e)
+                },
+                Some(Err(e)) => error!("Unable to load script {}: {}", item.path().to_str().unwrap_or_default(), e),
+                None => ()
             }
         }
 
     }
 
+    for (index, plugin) in get_static_plugins().into_iter().enumerate() {
+        debug!("Found built-in plugin {}", plugin.name());
+        if skipped_built_in.get(index).copied().unwrap_or_default() {
+            continue;
+        }
+        plugin_task_handles.spawn(run_static_plugin(plugin, datastore, event_channel.clone()));
+    }
+
  
     Ok(plugin_task_handles)
 }
 
 async fn run_plugin(path: PathBuf, datastore: &'static tokio::sync::RwLock<DataStore>, event_channel: kanal::Sender<EventMessage>) -> Result<(), String> {
-    if let Ok(wrapper) = unsafe { Container::<PluginWrapper>::load(path.to_str().unwrap()) } {
-        // Preperations
-        let desc = wrapper.get_plugin_description();
+    // Hashing reads the whole file and loading already executes code in the library, so both are blocking
+    let config = datastore.read().await.get_config().clone();
+    let lib_path = path.clone();
+    let wrapper = match tokio::task::spawn_blocking(move || open_library(&config, lib_path.as_path())).await {
+        Ok(Ok(wrapper)) => wrapper,
+        Ok(Err(reason)) => {
+            error!("{}", reason);
+            return Err(path.to_str().unwrap_or_default().to_string());
+        },
+        Err(e) => {
+            error!("Loading {} failed: {}", path.to_str().unwrap_or_default(), e);
+            return Err(path.to_str().unwrap_or_default().to_string());
+        }
+    };
 
-        let name = if let Some(n) = utils::get_string(desc.name) {
-            wrapper.free_string(desc.name);
-            n
-        } else {
-            error!("Unable to parse plugin name, id {}", desc.id);
-            wrapper.free_string(desc.name);
-            return Err(path.file_name().unwrap_or_default().to_str().unwrap_or_default().to_string());
-        };
+    run_library(path, wrapper, datastore, event_channel).await
+}
 
-        if desc.api_version == u64::MAX {
-            // Missmatched API Version
-            error!("API version must be set at compiletime of your plugin ({}), requesting a API Version from the library during runtime will only return u64::MAX (DataRace is running on api version {})",
-                name.as_str(), crate::API_VERSION);
-            return Err(name);
-        }
-        if desc.api_version != crate::API_VERSION {
-            // Missmatched API Version
-            error!("Missmatched api version for plugin {}, will not be launched: Build for api {} (DataRace is running on {})", name.as_str(), desc.api_version, crate::API_VERSION);
-            return Err(name);
+/// Runs a plugin library that is already loaded (with its hash checked)
+async fn run_library(path: PathBuf, wrapper: Container<PluginWrapper>, datastore: &'static tokio::sync::RwLock<DataStore>, event_channel: kanal::Sender<EventMessage>) -> Result<(), String> {
+    // Preperations
+    let desc = wrapper.get_plugin_description();
+    let version = desc.version;
+
+    // desc is consumed, as the name ptr is pointing at freed memory afterwards
+    let (name, id) = match verify_description(&wrapper, desc) {
+        Ok(res) => res,
+        Err((name, reason)) => {
+            error!("{}", reason);
+            return Err(name.unwrap_or(path.file_name().unwrap_or_default().to_str().unwrap_or_default().to_string()));
         }
+    };
 
-        // Verifying ID is generated correctly with hash
-        let id = if let Some(id) = utils::generate_plugin_name_hash(name.as_str()) {
-            if id != desc.id {
-                error!("Plugin id set by plugin {} does not match the id generated by the name: Given {}, Expected: {}", name.as_str(), desc.id, id);
-                return Err(name);
-            }
-            id
-        } else {
-            error!("Unable to verify plugin id set by plugin {}: plugin name does not comply with naming schema", name.as_str());
-            return Err(name);
-        };
+    let free_string = wrapper.free_string;
+    run_loaded_plugin(&*wrapper, name, id, version, free_string, datastore, event_channel).await
+}
 
-        // Creates PluginHandle
-        let (sender, receiver) = utils::get_message_channel();
-        let handle = PluginHandle::new(name, id, datastore, sender.clone(), wrapper.free_string.clone(), desc.version, event_channel);
-        let mut ptr_h = PtrWrapper { ptr: Box::into_raw(Box::new(handle)), is_locked: false, subscribers: HashMap::default() };
-        drop(desc); // drop is importantent, name ptr is pointing at freed memory
+/// Runs a plugin compiled into the binary (see register_static_plugin)
+pub(crate) async fn run_static_plugin(plugin: Arc<dyn DataRacePlugin>, datastore: &'static tokio::sync::RwLock<DataStore>, event_channel: kanal::Sender<EventMessage>) -> Result<(), String> {
+    let name = plugin.name().to_lowercase();
+    let Some(id) = utils::generate_plugin_name_hash(name.as_str()) else {
+        error!("Unable to generate id for built-in plugin {}: plugin name does not comply with naming schema", name);
+        return Err(name);
+    };
+    let version = plugin.version();
 
-        let mut w_store = datastore.write().await;
-        if w_store.register_plugin(id, sender.clone(), ptr_h.ptr).is_none() {
-            let name = get_plugin_name(&ptr_h);
+    // Strings are allocated by the same allocator, so we can free them like our own
+    run_loaded_plugin(&StaticPlugin(plugin), name, id, version, crate::deallocate_string, datastore, event_channel).await
+}
 
-            // We can drop the pointer with no risk, as nothing can access it
-            unsafe {
-                drop(Box::from_raw(ptr_h.ptr));
-            }
+type ScriptLoader = fn(&Path, String) -> Result<Arc<dyn DataRacePlugin>, String>;
 
-            if w_store.get_shutdown_status() {
-                error!("Unable to register Plugin {}, shut down already in progress", name.as_str());
-                return Ok(());
-            }
+/// Scripts are named after their file, so the name is known without running them
+pub(crate) fn script_name(path: &Path) -> String {
+    path.file_stem().and_then(|n| n.to_str()).unwrap_or_default().to_lowercase()
+}
+
+/// Reads a script (lua or python, depending on the enabled features) and returns it with its loader,
+/// None if the file is not a supported script.
+/// The hash is checked like for libraries
+fn read_script_plugin(config: &Config, path: &Path) -> Option<Result<(ScriptLoader, String), String>> {
+    let scripts: &[(&str, ScriptLoader)] = &[
+        #[cfg(feature = "lua")]
+        ("lua", |path, code| crate::lua::LuaPlugin::load(path, code).map(|p| Arc::new(p) as Arc<dyn DataRacePlugin>)),
+        #[cfg(feature = "python")]
+        ("py", |path, code| crate::python::PythonPlugin::load(path, code).map(|p| Arc::new(p) as Arc<dyn DataRacePlugin>)),
+    ];
+
+    let ending = path.extension().and_then(|e| e.to_str())?;
+    let (_, load) = scripts.iter().find(|(script_ending, _)| *script_ending == ending)?;
+
+    Some(read_plugin_file(config, path)
+        .and_then(|content| String::from_utf8(content).map_err(|_| format!("Script {} is not valid utf8", path.to_str().unwrap_or_default())))
+        .map(|code| (*load, code)))
+}
 
-            error!("Unable to register Plugin {} (id {}), name/id collision", name.as_str(), id);
-            return Err(name);
+/// Loads the script read with read_script_plugin as built-in plugin.
+/// The script runs once while loading, which takes as long as it wants, so this is done on the blocking pool
+async fn load_script_plugin(load: ScriptLoader, path: PathBuf, code: String) -> Result<Arc<dyn DataRacePlugin>, String> {
+    tokio::task::spawn_blocking(move || load(path.as_path(), code)).await
+        .map_err(|e| format!("Loading the script failed: {}", e))?
+}
+
+/// Runs the plugin if it is enabled, on a dedicated thread if it is in plugin_threads of the config
+async fn run_loaded_plugin(wrapper: &dyn PluginCalls,
+    name: String,
+    id: u64,
+    version: [u16;3],
+    free_string: extern "C" fn(ptr: *mut libc::c_char),
+    datastore: &'static tokio::sync::RwLock<DataStore>,
+    event_channel: kanal::Sender<EventMessage>
+) -> Result<(), String> {
+    let thread = {
+        let ds_r = datastore.read().await;
+        if !ds_r.get_config().is_plugin_enabled(name.as_str()) {
+            info!("Plugin {} is not in the plugins list of the config, skipping", name);
+            return Ok(());
+        }
+        ds_r.get_config().get_plugin_thread(name.as_str()).cloned()
+    };
+
+    match thread {
+        Some(thread) => {
+            let plugin = name.clone();
+            threads::run_dedicated(plugin.as_str(), &thread, run_registered_plugin(wrapper, name, id, version, free_string, datastore, event_channel))
+        },
+        None => run_registered_plugin(wrapper, name, id, version, free_string, datastore, event_channel).await
+    }
+}
+
+/// Registers the plugin with the datastore and processes its messages till it shuts down
+async fn run_registered_plugin(wrapper: &dyn PluginCalls,
+    name: String,
+    id: u64,
+    version: [u16;3],
+    free_string: extern "C" fn(ptr: *mut libc::c_char),
+    datastore: &'static tokio::sync::RwLock<DataStore>,
+    event_channel: kanal::Sender<EventMessage>
+) -> Result<(), String> {
+    // Creates PluginHandle
+    let (sender, receiver) = utils::get_message_channel();
+    let quota = datastore.read().await.get_config().get_plugin_quota().for_plugin(name.as_str());
+    let mut handle = PluginHandle::new(name, id, datastore, sender.clone(), free_string, version, event_channel);
+    handle.quota = Quota::new(quota);
+    let mut ptr_h = PtrWrapper { ptr: Box::into_raw(Box::new(handle)), is_locked: false, subscribers: HashMap::default(), groups: HashMap::default(), profiler: Default::default() };
+
+    let mut w_store = datastore.write().await;
+    if w_store.register_plugin(id, sender.clone(), ptr_h.ptr).is_none() {
+        let name = get_plugin_name(&ptr_h);
+
+        // We can drop the pointer with no risk, as nothing can access it
+        unsafe {
+            drop(Box::from_raw(ptr_h.ptr));
+        }
+
+        if w_store.get_shutdown_status() {
+            error!("Unable to register Plugin {}, shut down already in progress", name.as_str());
+            return Ok(());
         }
-        drop(w_store);
 
-        if let Some(han) = unsafe {
-            ptr_h.ptr.as_ref()    
-        } {
-            info!("Plugin {} (version {}.{}.{}) loaded", han.name, han.version[0], han.version[1], han.version[2]);
-            debug!("Plugin {} has id {}", han.name, id);
+        let existing = w_store.list_plugins().into_iter().find(|(other, ..)| *other == id).map(|(_, existing, ..)| existing)
+            .unwrap_or_else(|| "datarace (reserved)".to_string());
+        if !collisions::check(collisions::Kind::Plugin, id, existing.as_str(), name.as_str()) {
+            error!("Unable to register Plugin {} (id {}), it is already loaded", name.as_str(), id);
         }
+        return Err(name);
+    }
+    drop(w_store);
+
+    if let Some(han) = unsafe {
+        ptr_h.ptr.as_ref()    
+    } {
+        info!("Plugin {} (version {}.{}.{}) loaded", han.name, han.version[0], han.version[1], han.version[2]);
+        debug!("Plugin {} has id {}", han.name, id);
+        crate::profile::register(id, han.name.as_str(), &ptr_h.profiler);
+    }
+
+    // Safe shutdown is a flag to secure if we can be reasonable sure no other resource is
+    // accessing the pluginhandle right now (like a thread spun up by the plugin)
+    let mut safe_shutdown = false;
+
+    // Initializing
+    let code = wrapper.init(ptr_h.ptr);
+    if code != 0 {
+        // None Zero Error Code, shut down
+        let name = get_plugin_name(&ptr_h);
+        error!("Plugin {} failed to initialize (return code {})", name.as_str(), code);
+        crate::callbacks::remove_plugin(id);
+        crate::frames::remove_plugin(id);
+        crate::profile::remove_plugin(id);
+        crate::stale::remove_plugin(id);
+        
+        let mut w_store = datastore.write().await;
+        let _ = w_store.delete_plugin(id, safe_shutdown).await;
+        drop(w_store);
 
-        // Safe shutdown is a flag to secure if we can be reasonable sure no other resource is
-        // accessing the pluginhandle right now (like a thread spun up by the plugin)
-        let mut safe_shutdown = false;
+        return Err(name);
+    } else if let Some(han) = unsafe { ptr_h.ptr.as_ref() } {
+        let _ = han.sender.as_async().send(LoaderMessage::StartupFinished).await;
+    }
 
-        // Initializing
-        if wrapper.init(ptr_h.ptr) != 0 {
-            // None Zero Error Code, shut down
-            let name = get_plugin_name(&ptr_h);
-            error!("Plugin {} failed to initialize", name.as_str());
+    let async_rec = receiver.to_async();
+
+    // let _ = sender.as_async().send(Message::Polled).await;
+    let plugin_name = get_plugin_name(&ptr_h);
+    while let Ok(msg) = async_rec.recv().await {
+        // dbg!(&msg);
+        let span = tracing::trace_span!("plugin_message", plugin = plugin_name.as_str(), message = msg.name());
+        crate::message_trace::record(id, "queue", msg.name(), || msg.summary(datastore));
+        if let Err(e) = async { match msg {
+            LoaderMessage::PropertyCreate(id, container) => create_property(wrapper, &mut ptr_h, id, container).await,
+            LoaderMessage::PropertyTypeChange(id, val_container, allow_modify) => property_type_change(wrapper, &mut ptr_h, id, val_container, allow_modify).await,
+            LoaderMessage::PropertyDelete(id) => delete_property(wrapper, &mut ptr_h, id).await,
+            LoaderMessage::Shutdown => shutdown(wrapper, &mut ptr_h),
+            LoaderMessage::Subscribe(prop_handle) => subscribe_property_start(wrapper, &mut ptr_h, prop_handle).await,
+            LoaderMessage::GenerateSubscribtion(id, prop_handle) => generate_subcription(wrapper, &mut ptr_h, id, prop_handle).await,
+            LoaderMessage::UpdateSubscription(prop_handle, val_container) => update_subscription(wrapper, &mut ptr_h, prop_handle, val_container),
+            LoaderMessage::Unsubscribe(prop_handle) => unsubscribe(wrapper, &mut ptr_h, prop_handle).await,
+            LoaderMessage::HasUnsubscribed(id, prop_handle) => has_unsubscribed(wrapper, &mut ptr_h, prop_handle, id),
+            LoaderMessage::GroupCreate(group, interval) => create_group(&mut ptr_h, group, interval),
+            LoaderMessage::GroupAdd(group, prop_handle) => add_to_group(wrapper, &mut ptr_h, group, prop_handle),
+            LoaderMessage::GroupRemove(group, prop_handle) => remove_from_group(wrapper, &mut ptr_h, group, prop_handle),
+            LoaderMessage::GroupDelete(group) => delete_group(wrapper, &mut ptr_h, group),
+            LoaderMessage::GroupTick(group) => group_tick(wrapper, &mut ptr_h, group),
             
-            let mut w_store = datastore.write().await;
-            let _ = w_store.delete_plugin(id, safe_shutdown).await;
-            drop(w_store);
-
-            return Err(name);
-        } else if let Some(han) = unsafe { ptr_h.ptr.as_ref() } {
-            let _ = han.sender.as_async().send(LoaderMessage::StartupFinished).await;
-        }
-
-        let async_rec = receiver.to_async();
-
-        // let _ = sender.as_async().send(Message::Polled).await;
-        while let Ok(msg) = async_rec.recv().await {
-            // dbg!(&msg);
-            if let Err(e) = match msg {
-                LoaderMessage::PropertyCreate(id, container) => create_property(&wrapper, &mut ptr_h, id, container).await,
-                LoaderMessage::PropertyTypeChange(id, val_container, allow_modify) => property_type_change(&wrapper, &mut ptr_h, id, val_container, allow_modify).await,
-                LoaderMessage::PropertyDelete(id) => delete_property(&wrapper, &mut ptr_h, id).await,
-                LoaderMessage::Shutdown => shutdown(&wrapper, &mut ptr_h),
-                LoaderMessage::Subscribe(prop_handle) => subscribe_property_start(&wrapper, &mut ptr_h, prop_handle).await,
-                LoaderMessage::GenerateSubscribtion(id, prop_handle) => generate_subcription(&wrapper, &mut ptr_h, id, prop_handle).await,
-                LoaderMessage::UpdateSubscription(prop_handle, val_container) => update_subscription(&wrapper, &mut ptr_h, prop_handle, val_container),
-                LoaderMessage::Unsubscribe(prop_handle) => unsubscribe(&wrapper, &mut ptr_h, prop_handle).await,
-                LoaderMessage::HasUnsubscribed(id, prop_handle) => has_unsubscribed(&wrapper, &mut ptr_h, prop_handle, id),
-                
-                LoaderMessage::StartupFinished => startup_complete(&wrapper, &mut ptr_h).await,
-                LoaderMessage::OtherPluginStartup(id) => send_simple_message(&wrapper, &mut ptr_h,
-                    Message { sort: MessageType::OtherPluginStarted, value: MessageValue { plugin_id: id }}, "Failed on informing about other plugin"),
-                LoaderMessage::InternalMessage(msg) => send_simple_message(&wrapper, &mut ptr_h,
-                    Message { sort: MessageType::InternalMessage, value: MessageValue { internal_msg: msg }}, "Failed on processing plugin internal message"),
-                LoaderMessage::PluginMessagePtr((origin, ptr, reason)) => send_simple_message(&wrapper, &mut ptr_h,
-                    Message { sort: MessageType::PluginMessagePtr, value: MessageValue { message_ptr: MessagePtr { origin, message_ptr: ptr.ptr, reason } }},
-                    "Failed to process PluginMessagePtr"),
-                LoaderMessage::SendPluginMessagePtr((target, ptr, reason)) => {
-                    send_plugin_message(&ptr_h, target, LoaderMessage::PluginMessagePtr((id, ptr, reason))).await.map(|okay| if !okay {
+            LoaderMessage::StartupFinished => startup_complete(wrapper, &mut ptr_h).await,
+            LoaderMessage::OtherPluginStartup(id) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::OtherPluginStarted, value: MessageValue { plugin_id: id }}, "Failed on informing about other plugin"),
+            LoaderMessage::InternalMessage(msg) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::InternalMessage, value: MessageValue { internal_msg: msg }}, "Failed on processing plugin internal message"),
+            LoaderMessage::PluginMessagePtr((origin, ptr, reason)) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::PluginMessagePtr, value: MessageValue { message_ptr: MessagePtr { origin, message_ptr: ptr.ptr, reason } }},
+                "Failed to process PluginMessagePtr"),
+            LoaderMessage::SendPluginMessagePtr((target, ptr, reason)) => {
+                // Handed back to the sender if the target is not running
+                let unsent = VoidPtrWrapper { ptr: ptr.ptr };
+                match send_plugin_message(&ptr_h, target, LoaderMessage::PluginMessagePtr((id, ptr, reason))).await {
+                    Ok(false) => {
                         error!("Plugin {} failed to send message with ptr to plugin {}", get_plugin_name(&ptr_h), target);
-                    })
-                },
-
-                LoaderMessage::EventTriggered(ev) => send_simple_message(&wrapper, &mut ptr_h,
-                    Message { sort: MessageType::EventTriggered, value: MessageValue { event: ev } }, "Failed to pass in event trigger"),
-                LoaderMessage::EventUnsubscribed(ev) => send_simple_message(&wrapper, &mut ptr_h,
-                    Message { sort: MessageType::EventUnsubscribed, value: MessageValue { event: ev } }, "Failed to inform of event unsubscribe"),
-                
-
-                // LoaderMessage::Update(prop_handle, value) => {
-                //     let msg = LoaderMessage::Update(prop_handle, value);
-                //     send_update!(wrapper, ptr_h, msg);
-                //     Ok(())
-                // },
-                // LoaderMessage::Removed(prop_handle) => {
-                //     let msg = LoaderMessage::Removed(prop_handle);
-                //     send_update!(wrapper, ptr_h, msg);
-                //     Ok(())
-                // }
-            } {
-                // log out the error and exit loop
-                match e {
-                    MsgProcessingError::Shutdown => {
-                        debug!("Plugin {} received shutdown, exiting loop", get_plugin_name(&ptr_h));
-                        safe_shutdown = !ptr_h.ptr.is_null();
+                        send_failed_operation(wrapper, &mut ptr_h, FailedOperation {
+                            what: Operation::SendPluginMessage,
+                            code: DataStoreReturnCode::DoesNotExist,
+                            property: PropertyHandle { plugin: 0, property: 0 },
+                            target,
+                            message_ptr: unsent.ptr,
+                            reason
+                        })
                     },
-                    MsgProcessingError::NoneZeroReturnCode(str) => {
-                        error!("Plugin {} failed, none zero return code received when executing {}", get_plugin_name(&ptr_h), str);
-                    },
-                    MsgProcessingError::NullPtr => {
-                        error!("A Plugin could not dereference the pluginhandle due to null pointer");
-                    }
+                    res => res.map(|_| ())
                 }
-                break;
-            }
+            },
+            LoaderMessage::WatchPlugin(plugin) => watch_plugin(wrapper, &mut ptr_h, plugin).await,
+            LoaderMessage::Datastore(change) => apply_datastore_change(&ptr_h, change).await,
+            LoaderMessage::ExternalWrite(id, text, reply) => external_write(wrapper, &mut ptr_h, id, text, reply),
+            LoaderMessage::PluginRemoved(plugin) => plugin_removed(wrapper, &mut ptr_h, plugin),
+            LoaderMessage::WatchedPluginStarted(plugin) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::WatchedPluginStarted, value: MessageValue { plugin_id: plugin }}, "Failed on informing about watched plugin start"),
+            LoaderMessage::WatchedPluginStopped(plugin) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::WatchedPluginStopped, value: MessageValue { plugin_id: plugin }}, "Failed on informing about watched plugin stop"),
+            LoaderMessage::OperationFailed(failed) => send_failed_operation(wrapper, &mut ptr_h, failed),
+
+            LoaderMessage::EventTriggered(ev) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::EventTriggered, value: MessageValue { event: ev } }, "Failed to pass in event trigger"),
+            LoaderMessage::EventUnsubscribed(ev) => send_simple_message(wrapper, &mut ptr_h,
+                Message { sort: MessageType::EventUnsubscribed, value: MessageValue { event: ev } }, "Failed to inform of event unsubscribe"),
+            
 
-            if async_rec.is_empty() && ptr_h.is_locked {
-                // debug!("Unlock triggered");
-                send_unlock(&wrapper, &mut ptr_h).unwrap();
+            // LoaderMessage::Update(prop_handle, value) => {
+            //     let msg = LoaderMessage::Update(prop_handle, value);
+            //     send_update!(wrapper, ptr_h, msg);
+            //     Ok(())
+            // },
+            // LoaderMessage::Removed(prop_handle) => {
+            //     let msg = LoaderMessage::Removed(prop_handle);
+            //     send_update!(wrapper, ptr_h, msg);
+            //     Ok(())
+            // }
+        }}.instrument(span).await {
+            // log out the error and exit loop
+            match e {
+                MsgProcessingError::Shutdown => {
+                    debug!("Plugin {} received shutdown, exiting loop", get_plugin_name(&ptr_h));
+                    safe_shutdown = !ptr_h.ptr.is_null();
+                },
+                MsgProcessingError::NoneZeroReturnCode(str, code) => {
+                    error!("Plugin {} failed, none zero return code {} received when executing {}", get_plugin_name(&ptr_h), code, str);
+                },
+                MsgProcessingError::NullPtr => {
+                    error!("A Plugin could not dereference the pluginhandle due to null pointer");
+                }
             }
+            break;
+        }
+
+        if async_rec.is_empty() && ptr_h.is_locked {
+            // debug!("Unlock triggered");
+            send_unlock(wrapper, &mut ptr_h).unwrap();
         }
+    }
 
 
 
-        // End of life
-        let name = get_plugin_name(&ptr_h);
-        let mut w_store = datastore.write().await;
-        if DataStoreReturnCode::Ok != w_store.delete_plugin(id, safe_shutdown).await {
-            error!("Plugin {} failed to shutdown properly", name.as_str());
-            drop(w_store);
-            return Err(name);
-        } else {
-            info!("Plugin {} stopped", name);
-        }
+    // End of life
+    let name = get_plugin_name(&ptr_h);
+    for group in ptr_h.groups.values() {
+        group.timer.abort();
+    }
+    // Has to happen before the plugin library is unloaded
+    crate::callbacks::remove_plugin(id);
+    crate::frames::remove_plugin(id);
+    crate::profile::remove_plugin(id);
+    crate::stale::remove_plugin(id);
+    let mut w_store = datastore.write().await;
+    if DataStoreReturnCode::Ok != w_store.delete_plugin(id, safe_shutdown).await {
+        error!("Plugin {} failed to shutdown properly", name.as_str());
         drop(w_store);
+        return Err(name);
+    } else {
+        info!("Plugin {} stopped", name);
+    }
+    crate::leaks::report(id, name.as_str());
+    drop(w_store);
+
+    Ok(())
+}
+
+/// Reads the name out of the description and verifies api version and id.
+/// This takes care of freeing the name string.
+///
+/// Returns name and id, or the name (if it was readable) and the reason the plugin can not be loaded
+fn verify_description(wrapper: &Container<PluginWrapper>, desc: api_types::PluginDescription) -> Result<(String, u64), (Option<String>, String)> {
+    let name = if let Some(n) = utils::get_string(desc.name) {
+        wrapper.free_string(desc.name);
+        n
+    } else {
+        wrapper.free_string(desc.name);
+        return Err((None, format!("Unable to parse plugin name, id {}", desc.id)));
+    };
+
+    if desc.api_version == u64::MAX {
+        // Missmatched API Version
+        return Err((Some(name.clone()), format!("API version must be set at compiletime of your plugin ({}), requesting a API Version from the library during runtime will only return u64::MAX (DataRace is running on api version {})",
+            name.as_str(), crate::API_VERSION)));
+    }
+    if desc.api_version != crate::API_VERSION {
+        // Missmatched API Version
+        return Err((Some(name.clone()), format!("Missmatched api version for plugin {}, will not be launched: Build for api {} (DataRace is running on {})", name.as_str(), desc.api_version, crate::API_VERSION)));
+    }
+
+    // Verifying ID is generated correctly with hash
+    if let Some(id) = utils::generate_plugin_name_hash(name.as_str()) {
+        if id != desc.id {
+            return Err((Some(name.clone()), format!("Plugin id set by plugin {} does not match the id generated by the name: Given {}, Expected: {}", name.as_str(), desc.id, id)));
+        }
+        Ok((name, id))
+    } else {
+        Err((Some(name.clone()), format!("Unable to verify plugin id set by plugin {}: plugin name does not comply with naming schema", name.as_str())))
+    }
+}
+
+/// Verifies the content of the file against the plugin_hashes allow-list of the config (if set).
+/// Returns the reason when the file is not allowed to load
+fn check_plugin_hash(config: &Config, path: &Path, content: &[u8]) -> Result<(), String> {
+    if !config.has_plugin_hashes() {
+        return Ok(());
+    }
 
+    let hash = Sha256::digest(content).iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    if config.is_plugin_hash_allowed(hash.as_str()) {
         Ok(())
     } else {
-        error!("Unable to load {} as a plugin (file could be damaged or missing necessary functions)", path.to_str().unwrap_or_default());
-        Err(path.to_str().unwrap_or_default().to_string())
+        Err(format!("Plugin library {} is not in the plugin_hashes allow-list (unknown or modified), refusing to load: SHA-256 {}", path.to_str().unwrap_or_default(), hash))
+    }
+}
+
+/// Reads the file and verifies it against the plugin_hashes allow-list (if set).
+/// What was read has to be loaded (not the path again), as the file could be swapped after the check
+pub(crate) fn read_plugin_file(config: &Config, path: &Path) -> Result<Vec<u8>, String> {
+    let content = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.to_str().unwrap_or_default(), e))?;
+    check_plugin_hash(config, path, content.as_slice())?;
+    Ok(content)
+}
+
+/// Loads the library, after verifying its hash (if plugin_hashes is set). This is blocking
+fn open_library(config: &Config, path: &Path) -> Result<Container<PluginWrapper>, String> {
+    let res = if config.has_plugin_hashes() {
+        open_verified_library(config, path)?
+    } else {
+        unsafe { Container::<PluginWrapper>::load(path) }
+    };
+
+    res.map_err(|_| format!("Unable to load {} as a plugin (file could be damaged or missing necessary functions)", path.to_str().unwrap_or_default()))
+}
+
+/// Loads an in memory copy of the checked content, which only we hold
+#[cfg(target_os = "linux")]
+fn open_verified_library(config: &Config, path: &Path) -> Result<Result<Container<PluginWrapper>, dlopen2::Error>, String> {
+    use std::{io::Write, os::fd::FromRawFd};
+
+    let content = read_plugin_file(config, path)?;
+    let copy_failed = |e: std::io::Error| format!("Unable to copy {} for loading: {}", path.to_str().unwrap_or_default(), e);
+
+    let name = std::ffi::CString::new("datarace-plugin").expect("string is string");
+    let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(copy_failed(std::io::Error::last_os_error()));
+    }
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    file.write_all(content.as_slice()).map_err(copy_failed)?;
+
+    // The library stays mapped after the file is closed
+    Ok(unsafe { Container::<PluginWrapper>::load(format!("/proc/self/fd/{}", fd)) })
+}
+
+/// Holds the file open without sharing write or delete access while checking and loading it,
+/// so it can not be modified or swapped in between
+#[cfg(windows)]
+fn open_verified_library(config: &Config, path: &Path) -> Result<Result<Container<PluginWrapper>, dlopen2::Error>, String> {
+    use std::{io::Read, os::windows::fs::OpenOptionsExt};
+
+    // FILE_SHARE_READ
+    const SHARE_READ: u32 = 0x1;
+    let read_failed = |e: std::io::Error| format!("Unable to read {}: {}", path.to_str().unwrap_or_default(), e);
+
+    let mut file = fs::OpenOptions::new().read(true).share_mode(SHARE_READ).open(path).map_err(read_failed)?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).map_err(read_failed)?;
+    check_plugin_hash(config, path, content.as_slice())?;
+
+    let res = unsafe { Container::<PluginWrapper>::load(path) };
+    drop(file);
+    Ok(res)
+}
+
+/// Loads a private copy of the checked content, which is removed again after loading
+#[cfg(not(any(target_os = "linux", windows)))]
+fn open_verified_library(config: &Config, path: &Path) -> Result<Result<Container<PluginWrapper>, dlopen2::Error>, String> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    let content = read_plugin_file(config, path)?;
+    let copy_failed = |e: std::io::Error| format!("Unable to copy {} for loading: {}", path.to_str().unwrap_or_default(), e);
+
+    let copy = std::env::temp_dir().join(format!("datarace-{}-{}", std::process::id(), path.file_name().and_then(|n| n.to_str()).unwrap_or_default()));
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(copy.as_path()).map_err(copy_failed)?;
+    let res = file.write_all(content.as_slice()).map_err(copy_failed)
+        .map(|_| unsafe { Container::<PluginWrapper>::load(copy.as_path()) });
+    let _ = fs::remove_file(copy.as_path());
+    res
+}
+
+/// Result of inspecting a single file in the plugin folder without running it
+#[derive(Debug, Serialize)]
+pub(crate) struct PluginInfo {
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub id: u64,
+    pub version: [u16;3],
+    pub api_version: u64,
+    /// None if the plugin would be launched, otherwise the reason why it won't
+    pub error: Option<String>,
+    /// Skipped as another plugin with the same id is launched instead (see duplicate_plugins)
+    pub duplicate: bool
+}
+
+/// Path of the built-in plugins in the PluginInfo
+pub(crate) const BUILT_IN: &str = "<built-in>";
+
+/// Which of the plugins sharing an id (so the same name) is launched
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DuplicatePlugins {
+    /// Highest version, the first found if they are the same
+    #[default]
+    Newest,
+    /// Lowest version, the first found if they are the same
+    Oldest,
+    /// None of them, till the duplicates are removed
+    None
+}
+
+impl FromStr for DuplicatePlugins {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "newest" => Ok(DuplicatePlugins::Newest),
+            "oldest" => Ok(DuplicatePlugins::Oldest),
+            "none" => Ok(DuplicatePlugins::None),
+            _ => Err(())
+        }
+    }
+}
+
+/// Plugins found with the same id, of which only one (or none) was launched
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DuplicatePlugin {
+    pub name: String,
+    pub id: u64,
+    /// None if none of them was launched
+    pub launched: Option<PluginCopy>,
+    pub skipped: Vec<PluginCopy>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PluginCopy {
+    pub path: String,
+    pub version: [u16;3]
+}
+
+impl PluginCopy {
+    fn new(info: &PluginInfo) -> PluginCopy {
+        PluginCopy { path: info.path.display().to_string(), version: info.version }
+    }
+}
+
+impl std::fmt::Display for PluginCopy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}.{}.{})", self.path, self.version[0], self.version[1], self.version[2])
+    }
+}
+
+/// Loads the descriptions of all plugins in the plugin folder, but does not initialize them.
+/// Duplicates that would not be launched are marked with the reason
+pub(crate) fn read_all_plugin_descriptions(config: &Config) -> Result<Vec<PluginInfo>, std::io::Error> {
+    scan_plugins(config).map(|(list, _, _)| list)
+}
+
+/// Libraries loaded while reading their description, by path
+type Libraries = HashMap<PathBuf, Container<PluginWrapper>>;
+
+/// Same as read_all_plugin_descriptions, also returning the loaded libraries and the duplicates.
+/// Scripts are not run, only checked against the plugin_hashes. This is blocking
+fn scan_plugins(config: &Config) -> Result<(Vec<PluginInfo>, Libraries, Vec<DuplicatePlugin>), std::io::Error> {
+    let ending = if cfg!(target_os = "linux") {
+        "so"
+    } else {
+        "dll"
+    };
+
+    let mut list = Vec::new();
+    let mut libraries = Libraries::new();
+    for item in fs::read_dir(config.get_plugin_folder())?.flatten() {
+        let path = item.path();
+
+        if let Some(res) = read_script_plugin(config, path.as_path()) {
+            let name = script_name(path.as_path());
+            let id = utils::generate_plugin_name_hash(name.as_str());
+            let error = match (res, id) {
+                (Err(reason), _) => Some(reason),
+                (Ok(_), None) => Some(format!("Plugin name {} does not comply with naming schema", name)),
+                (Ok(_), Some(_)) if !config.is_plugin_enabled(name.as_str()) => Some("Not in the plugins list of the config".to_string()),
+                (Ok(_), Some(_)) => None
+            };
+
+            // Scripts have no version, same as they report once loaded
+            list.push(PluginInfo { path, name: Some(name), id: id.unwrap_or_default(), version: [0;3], api_version: crate::API_VERSION, error, duplicate: false });
+            continue;
+        }
+
+        if path.extension().and_then(|e| e.to_str()) != Some(ending) {
+            continue;
+        }
+
+        let info = match open_library(config, path.as_path()) {
+            Err(reason) => PluginInfo { path, name: None, id: 0, version: [0;3], api_version: 0, error: Some(reason), duplicate: false },
+            Ok(wrapper) => {
+                let desc = wrapper.get_plugin_description();
+                let (id, version, api_version) = (desc.id, desc.version, desc.api_version);
+
+                let info = match verify_description(&wrapper, desc) {
+                    Ok((name, _)) if !config.is_plugin_enabled(name.as_str()) => PluginInfo { path, name: Some(name), id, version, api_version,
+                        error: Some("Not in the plugins list of the config".to_string()), duplicate: false },
+                    Ok((name, _)) => PluginInfo { path, name: Some(name), id, version, api_version, error: None, duplicate: false },
+                    Err((name, reason)) => PluginInfo { path, name, id, version, api_version, error: Some(reason), duplicate: false }
+                };
+                libraries.insert(info.path.clone(), wrapper);
+                info
+            }
+        };
+
+        list.push(info);
+    }
+
+    for plugin in get_static_plugins() {
+        let name = plugin.name().to_lowercase();
+        let id = utils::generate_plugin_name_hash(name.as_str());
+        let error = if id.is_none() {
+            Some(format!("Unable to generate id for built-in plugin {}: plugin name does not comply with naming schema", name))
+        } else if !config.is_plugin_enabled(name.as_str()) {
+            Some("Not in the plugins list of the config".to_string())
+        } else {
+            None
+        };
+
+        list.push(PluginInfo { path: PathBuf::from(BUILT_IN), name: Some(name), id: id.unwrap_or_default(), version: plugin.version(), api_version: crate::API_VERSION, error, duplicate: false });
+    }
+
+    let duplicates = mark_duplicates(&mut list, config.get_duplicate_plugins());
+    Ok((list, libraries, duplicates))
+}
+
+/// Picks which of the launchable plugins sharing an id is launched, the others are marked as duplicate
+fn mark_duplicates(list: &mut [PluginInfo], policy: DuplicatePlugins) -> Vec<DuplicatePlugin> {
+    let mut ids = HashMap::<u64, Vec<usize>>::new();
+    for (index, info) in list.iter().enumerate() {
+        if info.error.is_none() {
+            ids.entry(info.id).or_default().push(index);
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for (id, indices) in ids {
+        if indices.len() < 2 {
+            continue;
+        }
+
+        // On equal versions the first found wins, as reduce keeps the earlier one
+        let launched = match policy {
+            DuplicatePlugins::Newest => indices.iter().copied().reduce(|a, b| if list[b].version > list[a].version { b } else { a }),
+            DuplicatePlugins::Oldest => indices.iter().copied().reduce(|a, b| if list[b].version < list[a].version { b } else { a }),
+            DuplicatePlugins::None => None
+        };
+
+        let reason = match launched {
+            Some(index) => format!("Duplicate of {}, which is launched instead (see duplicate_plugins)", PluginCopy::new(&list[index])),
+            None => "Duplicate plugin, none of them are launched (see duplicate_plugins)".to_string()
+        };
+
+        let mut skipped = Vec::new();
+        for index in indices.iter().copied().filter(|index| Some(*index) != launched) {
+            list[index].error = Some(reason.clone());
+            list[index].duplicate = true;
+            skipped.push(PluginCopy::new(&list[index]));
+        }
+
+        duplicates.push(DuplicatePlugin {
+            name: list[indices[0]].name.clone().unwrap_or_default(),
+            id,
+            launched: launched.map(|index| PluginCopy::new(&list[index])),
+            skipped
+        });
+    }
+
+    duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+    duplicates
+}
+
+/// Logs the duplicates and raises a notification for each, they are also listed on the home page
+async fn report_duplicates(datastore: &'static tokio::sync::RwLock<DataStore>, duplicates: Vec<DuplicatePlugin>) {
+    let mut ds_w = datastore.write().await;
+    for dup in duplicates.iter() {
+        let skipped = dup.skipped.iter().map(PluginCopy::to_string).collect::<Vec<_>>().join(", ");
+        let text = match &dup.launched {
+            Some(launched) => format!("Plugin {} was found multiple times, launching {} and skipping {}", dup.name, launched, skipped),
+            None => format!("Plugin {} was found multiple times, launching none of {}", dup.name, skipped)
+        };
+
+        warn!("{}", text);
+        ds_w.raise_notification(crate::notifications::Severity::Warning, crate::system::CORE_PLUGIN_NAME.to_string(), text);
     }
+    ds_w.set_duplicate_plugins(duplicates);
 }
 
 // We have to do this, as you can otherwise not await anything
 struct PtrWrapper {
     ptr: *mut PluginHandle,
     is_locked: bool,
-    subscribers: HashMap<u64, Vec<u64>>
+    subscribers: HashMap<u64, Vec<u64>>,
+    groups: HashMap<u64, SubscriptionGroup>,
+    profiler: crate::profile::Profiler
+}
+
+/// Properties whose changes are collected and delivered in one message every interval
+struct SubscriptionGroup {
+    properties: Vec<(PropertyHandle, utils::ValueCache)>,
+    // Set while a tick is queued, so a slow plugin does not get flooded with ticks
+    pending: Arc<AtomicBool>,
+    timer: tokio::task::JoinHandle<()>
 }
 
 unsafe impl Send for PtrWrapper { }
@@ -256,6 +781,87 @@ pub struct PluginWrapper {
     update: extern "C" fn(handle: *mut PluginHandle, msg: api_types::Message) -> libc::c_int,
 }
 
+/// The calls into a running plugin, either loaded from a library or compiled in (StaticPlugin)
+trait PluginCalls: Sync {
+    fn init(&self, handle: *mut PluginHandle) -> libc::c_int;
+    fn update(&self, handle: *mut PluginHandle, msg: api_types::Message) -> libc::c_int;
+}
+
+impl PluginCalls for PluginWrapper {
+    fn init(&self, handle: *mut PluginHandle) -> libc::c_int {
+        PluginWrapper::init(self, handle)
+    }
+
+    fn update(&self, handle: *mut PluginHandle, msg: api_types::Message) -> libc::c_int {
+        PluginWrapper::update(self, handle, msg)
+    }
+}
+
+/// A plugin compiled directly into a custom DataRace binary, instead of being loaded from the plugin folder.
+///
+/// Register it with [`register_static_plugin`] before calling [`run`](crate::run).
+/// The plugin uses the same functions to interact with DataRace as a plugin library
+/// (like [`create_property`](crate::create_property)), just without going through dynamic linking.
+pub trait DataRacePlugin: Send + Sync {
+    /// Name of the plugin, the plugin id is generated from it (so it has to follow the same naming rules)
+    fn name(&self) -> &str;
+
+    fn version(&self) -> [u16;3];
+
+    /// Called once on startup, an Err aborts the plugin
+    fn init(&self, handle: *mut PluginHandle) -> Result<(), String>;
+
+    /// Called for every message, an Err shuts the plugin down
+    fn update(&self, handle: *mut PluginHandle, msg: Message) -> Result<(), String>;
+}
+
+/// Plugins registered by register_static_plugin, launched together with the plugins from the plugin folder
+static STATIC_PLUGINS: Mutex<Vec<Arc<dyn DataRacePlugin>>> = Mutex::new(Vec::new());
+
+/// Registers a plugin compiled into the binary, has to be called before [`run`](crate::run)
+pub fn register_static_plugin(plugin: impl DataRacePlugin + 'static) {
+    if let Ok(mut list) = STATIC_PLUGINS.lock() {
+        list.push(Arc::new(plugin));
+    }
+}
+
+fn get_static_plugins() -> Vec<Arc<dyn DataRacePlugin>> {
+    STATIC_PLUGINS.lock().map(|list| list.clone()).unwrap_or_default()
+}
+
+struct StaticPlugin(Arc<dyn DataRacePlugin>);
+
+// Panics are caught like the plugin macros do, so they stop the plugin instead of the loader task
+impl PluginCalls for StaticPlugin {
+    fn init(&self, handle: *mut PluginHandle) -> libc::c_int {
+        match crate::crash::catch_panic(|| self.0.init(handle)) {
+            Ok(Ok(())) => 0,
+            Ok(Err(e)) => {
+                error!("Plugin {} failed to initialize: {}", self.0.name(), e);
+                1
+            },
+            Err(panic) => {
+                error!("Plugin {} paniced during init: {}", self.0.name(), panic);
+                10
+            }
+        }
+    }
+
+    fn update(&self, handle: *mut PluginHandle, msg: api_types::Message) -> libc::c_int {
+        match crate::crash::catch_panic(|| self.0.update(handle, msg)) {
+            Ok(Ok(())) => 0,
+            Ok(Err(e)) => {
+                error!("Plugin {} failed to process message: {}", self.0.name(), e);
+                1
+            },
+            Err(panic) => {
+                error!("Plugin {} paniced during update: {}", self.0.name(), panic);
+                10
+            }
+        }
+    }
+}
+
 // Sketchup of what Message will internally become
 #[derive(Debug)]
 pub(crate) enum LoaderMessage {
@@ -267,15 +873,31 @@ pub(crate) enum LoaderMessage {
     UpdateSubscription(PropertyHandle, utils::ValueContainer),
     Unsubscribe(PropertyHandle),
     HasUnsubscribed(u64, PropertyHandle),
+    GroupCreate(u64, u64),
+    GroupAdd(u64, PropertyHandle),
+    GroupRemove(u64, PropertyHandle),
+    GroupDelete(u64),
+    GroupTick(u64),
     
     InternalMessage(i64),
     StartupFinished,
     SendPluginMessagePtr((u64, VoidPtrWrapper, i64)),
     PluginMessagePtr((u64, VoidPtrWrapper, i64)),
     OtherPluginStartup(u64),
+    WatchPlugin(u64),
+    WatchedPluginStarted(u64),
+    WatchedPluginStopped(u64),
+    /// Another plugin stopped, leftover subscriptions to it (or from it) are removed
+    PluginRemoved(u64),
 
     EventTriggered(EventHandle),
     EventUnsubscribed(EventHandle),
+    /// Something the plugin queued failed in the loader of another plugin (like the owner of a subscribed property)
+    OperationFailed(FailedOperation),
+    /// Queued by an api function, see DatastoreChange
+    Datastore(DatastoreChange),
+    /// A value written from outside (like the web), parsed by write_text, the result is send back
+    ExternalWrite(u64, String, tokio::sync::oneshot::Sender<DataStoreReturnCode>),
     
 
     // Update(PropertyHandle, Value),
@@ -284,9 +906,161 @@ pub(crate) enum LoaderMessage {
 
 }
 
+impl LoaderMessage {
+    /// Name of the message, used for the tracing spans
+    fn name(&self) -> &'static str {
+        match self {
+            LoaderMessage::PropertyCreate(..) => "PropertyCreate",
+            LoaderMessage::PropertyTypeChange(..) => "PropertyTypeChange",
+            LoaderMessage::PropertyDelete(..) => "PropertyDelete",
+            LoaderMessage::Subscribe(..) => "Subscribe",
+            LoaderMessage::GenerateSubscribtion(..) => "GenerateSubscribtion",
+            LoaderMessage::UpdateSubscription(..) => "UpdateSubscription",
+            LoaderMessage::Unsubscribe(..) => "Unsubscribe",
+            LoaderMessage::HasUnsubscribed(..) => "HasUnsubscribed",
+            LoaderMessage::GroupCreate(..) => "GroupCreate",
+            LoaderMessage::GroupAdd(..) => "GroupAdd",
+            LoaderMessage::GroupRemove(..) => "GroupRemove",
+            LoaderMessage::GroupDelete(..) => "GroupDelete",
+            LoaderMessage::GroupTick(..) => "GroupTick",
+            LoaderMessage::InternalMessage(..) => "InternalMessage",
+            LoaderMessage::StartupFinished => "StartupFinished",
+            LoaderMessage::SendPluginMessagePtr(..) => "SendPluginMessagePtr",
+            LoaderMessage::PluginMessagePtr(..) => "PluginMessagePtr",
+            LoaderMessage::OtherPluginStartup(..) => "OtherPluginStartup",
+            LoaderMessage::WatchPlugin(..) => "WatchPlugin",
+            LoaderMessage::WatchedPluginStarted(..) => "WatchedPluginStarted",
+            LoaderMessage::WatchedPluginStopped(..) => "WatchedPluginStopped",
+            LoaderMessage::PluginRemoved(..) => "PluginRemoved",
+            LoaderMessage::EventTriggered(..) => "EventTriggered",
+            LoaderMessage::EventUnsubscribed(..) => "EventUnsubscribed",
+            LoaderMessage::OperationFailed(..) => "OperationFailed",
+            LoaderMessage::Datastore(..) => "Datastore",
+            LoaderMessage::ExternalWrite(..) => "ExternalWrite",
+            LoaderMessage::Shutdown => "Shutdown"
+        }
+    }
+
+    /// Ids of the message (without values, properties by name), used for the message trace
+    fn summary(&self, datastore: &tokio::sync::RwLock<DataStore>) -> String {
+        let label = |handle: &PropertyHandle| crate::message_trace::property_label(datastore, handle);
+
+        match self {
+            LoaderMessage::PropertyCreate(prop, _) | LoaderMessage::PropertyDelete(prop) => format!("property {}", prop),
+            LoaderMessage::PropertyTypeChange(prop, _, allow_modify) => format!("property {} allow_modify {}", prop, allow_modify),
+            LoaderMessage::Subscribe(handle) | LoaderMessage::Unsubscribe(handle) | LoaderMessage::UpdateSubscription(handle, _) => format!("property {}", label(handle)),
+            LoaderMessage::GenerateSubscribtion(plugin, handle) | LoaderMessage::HasUnsubscribed(plugin, handle) => format!("property {} for {}", label(handle), plugin),
+            LoaderMessage::GroupCreate(group, interval) => format!("group {} interval {}", group, interval),
+            LoaderMessage::GroupAdd(group, handle) | LoaderMessage::GroupRemove(group, handle) => format!("group {} property {}", group, label(handle)),
+            LoaderMessage::GroupDelete(group) | LoaderMessage::GroupTick(group) => format!("group {}", group),
+            LoaderMessage::InternalMessage(msg) => format!("value {}", msg),
+            LoaderMessage::SendPluginMessagePtr((plugin, _, reason)) => format!("to {} reason {}", plugin, reason),
+            LoaderMessage::PluginMessagePtr((plugin, _, reason)) => format!("from {} reason {}", plugin, reason),
+            LoaderMessage::OtherPluginStartup(plugin) | LoaderMessage::WatchPlugin(plugin)
+                | LoaderMessage::WatchedPluginStarted(plugin) | LoaderMessage::WatchedPluginStopped(plugin)
+                | LoaderMessage::PluginRemoved(plugin) => format!("plugin {}", plugin),
+            LoaderMessage::EventTriggered(ev) | LoaderMessage::EventUnsubscribed(ev) => format!("event {}|{}", ev.plugin, ev.event),
+            LoaderMessage::OperationFailed(failed) => format!("{:?} failed with {:?}", failed.what, failed.code),
+            LoaderMessage::Datastore(change) => change.summary(),
+            LoaderMessage::ExternalWrite(id, text, _) => format!("property {} = {}", id, text),
+            LoaderMessage::StartupFinished | LoaderMessage::Shutdown => String::new()
+        }
+    }
+}
+
+/// Changes to the datastore requested through the api. The api functions can not wait on the datastore lock
+/// (they are called inside init and update, waiting there can deadlock the runtime), so the loader applies them
+#[derive(Debug)]
+pub(crate) enum DatastoreChange {
+    NamespaceVersion(u32),
+    Alias { alias: PropertyHandle, name: String, target: PropertyHandle, version: u32 },
+    Notification(crate::notifications::Severity, String),
+    SessionMark(api_types::SessionMarker)
+}
+
+impl DatastoreChange {
+    fn summary(&self) -> String {
+        match self {
+            DatastoreChange::NamespaceVersion(version) => format!("namespace version {}", version),
+            DatastoreChange::Alias { name, version, .. } => format!("alias {} version {}", name, version),
+            DatastoreChange::Notification(severity, _) => format!("notification {:?}", severity),
+            DatastoreChange::SessionMark(marker) => format!("session marker {:?}", marker)
+        }
+    }
+}
+
+async fn apply_datastore_change(ptr: &PtrWrapper, change: DatastoreChange) -> Result<(), MsgProcessingError> {
+    let (datastore, event_channel, id, name) = {
+        let han = get_handle(ptr)?;
+        (han.datastore, han.event_channel.clone(), han.id, han.name.clone())
+    };
+
+    match change {
+        DatastoreChange::NamespaceVersion(version) => datastore.write().await.get_aliases_mut().set_namespace_version(id, version),
+        DatastoreChange::Alias { alias, name: alias_name, target, version } => {
+            let code = datastore.write().await.get_aliases_mut().add(alias, alias_name.clone(), target, version);
+            if code != DataStoreReturnCode::Ok {
+                error!("Plugin {} failed to create property alias {}: {:?}", name, alias_name, code);
+            }
+        },
+        DatastoreChange::Notification(severity, text) => datastore.write().await.raise_notification(severity, name, text),
+        DatastoreChange::SessionMark(marker) => {
+            let event = datastore.read().await.get_session_counters().mark(marker);
+            if let Some(ev) = event {
+                let _ = event_channel.send(EventMessage::Trigger(ev));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a value from outside (web, inputs, control socket, rules) to a property through its owner, so the write is ordered
+/// with the changes of the plugin (like type changes), and callbacks and max ages see it like an update.
+/// The datastore is not locked while waiting for the owner
+pub(crate) async fn write_property_text(datastore: &'static tokio::sync::RwLock<DataStore>, prop_handle: PropertyHandle, text: String) -> DataStoreReturnCode {
+    let reply = {
+        let ds_r = datastore.read().await;
+        let Some(target) = ds_r.resolve_property(&prop_handle) else {
+            return DataStoreReturnCode::DoesNotExist;
+        };
+
+        let (sender, reply) = tokio::sync::oneshot::channel();
+        if !ds_r.send_message_to_plugin(target.plugin, LoaderMessage::ExternalWrite(target.property, text, sender)).await {
+            // Core properties have no loader
+            return DataStoreReturnCode::NotAuthenticated;
+        }
+        reply
+    };
+
+    reply.await.unwrap_or(DataStoreReturnCode::DoesNotExist)
+}
+
+fn external_write(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64, text: String, reply: tokio::sync::oneshot::Sender<DataStoreReturnCode>) -> Result<(), MsgProcessingError> {
+    send_unlock(wrapper, ptr)?;
+    let handle = get_handle(ptr)?;
+
+    let code = match handle.properties.get(&id) {
+        Some(cont) => {
+            let code = cont.write_text(text.as_str());
+            if code == DataStoreReturnCode::Ok {
+                let prop_handle = PropertyHandle { plugin: handle.id, property: id };
+                crate::stale::touch(&prop_handle);
+                crate::callbacks::notify(&prop_handle, || cont.read_foreign());
+            }
+            code
+        },
+        None => DataStoreReturnCode::DoesNotExist
+    };
+
+    // The writer might have given up waiting
+    let _ = reply.send(code);
+    Ok(())
+}
+
 #[derive(Debug)]
 enum MsgProcessingError {
-    NoneZeroReturnCode(&'static str),
+    NoneZeroReturnCode(&'static str, libc::c_int),
     Shutdown,
     NullPtr,
 
@@ -338,22 +1112,55 @@ where
     Ok(())
 }
 
-fn send_update(wrapper: &PluginWrapper, ptr: &PtrWrapper, msg: Message, fail_error: &'static str) -> Result<(), MsgProcessingError> {
-    if wrapper.update(ptr.ptr, msg) != 0 {
-        return Err(MsgProcessingError::NoneZeroReturnCode(fail_error));
+fn send_update(wrapper: &dyn PluginCalls, ptr: &PtrWrapper, msg: Message, fail_error: &'static str) -> Result<(), MsgProcessingError> {
+    let handle = get_handle(ptr)?;
+    if !handle.wants_message(msg.sort) {
+        // Filtered out by the plugin
+        crate::message_trace::record(handle.id, "filtered", msg.sort.name(), || crate::message_trace::summarize(&msg, handle.datastore));
+        return Ok(());
+    }
+    crate::message_trace::record(handle.id, "update", msg.sort.name(), || crate::message_trace::summarize(&msg, handle.datastore));
+
+    // The call into the plugin, from handing over the message till it returns
+    let span = tracing::trace_span!("plugin_update", message = msg.sort as u8, reason = fail_error);
+    let sort = msg.sort;
+    let start = Instant::now();
+    let code = span.in_scope(|| wrapper.update(ptr.ptr, msg));
+    ptr.profiler.record(sort, start.elapsed());
+    if code < 0 {
+        // Negative codes are recoverable, the plugin only failed to process this message
+        warn!("Plugin {} failed to process a message (return code {}) when executing {}, skipping it", get_plugin_name(ptr), code, fail_error);
+    } else if code != 0 {
+        return Err(MsgProcessingError::NoneZeroReturnCode(fail_error, code));
     }
 
     Ok(())
 }
 
-fn send_simple_message(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, msg: Message, fail_error: &'static str) -> Result<(), MsgProcessingError> {
+fn send_simple_message(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, msg: Message, fail_error: &'static str) -> Result<(), MsgProcessingError> {
     send_unlock(wrapper, ptr)?;
 
     send_update(wrapper, ptr, msg, fail_error)
 }
 
+/// Informs the plugin that an operation it queued failed (the reason is logged by the caller)
+fn operation_failed(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, what: Operation, code: DataStoreReturnCode, property: PropertyHandle, target: u64) -> Result<(), MsgProcessingError> {
+    send_failed_operation(wrapper, ptr, FailedOperation { what, code, property, target, message_ptr: std::ptr::null_mut(), reason: 0 })
+}
+
+fn send_failed_operation(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, failed: FailedOperation) -> Result<(), MsgProcessingError> {
+    if failed.what == Operation::SubscribeProperty {
+        // A callback registered with the subscription would otherwise stay around
+        crate::callbacks::remove(&failed.property, get_handle(ptr)?.id);
+    }
+
+    send_simple_message(wrapper, ptr,
+        Message { sort: MessageType::OperationFailed, value: MessageValue { operation_failed: failed } },
+        "Failed on informing about a failed operation")
+}
+
 /// Serves to check if the handle is locked, if not change that
-fn send_lock(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
+fn send_lock(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
     if !ptr.is_locked {
         // We lock the plugin, then actually secure write lock
         // This is to prevent a lock trap from calls during the lock update
@@ -369,7 +1176,7 @@ fn send_lock(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgPro
     Ok(())
 }
 
-fn send_unlock(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
+fn send_unlock(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
     if ptr.is_locked {
         let han = get_handle(ptr)?;
         han.unlock();
@@ -383,15 +1190,19 @@ fn send_unlock(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgP
     Ok(())
 }
 
-async fn create_property(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64, container: utils::PropertyContainer) -> Result<(), MsgProcessingError> {
+async fn create_property(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64, container: utils::PropertyContainer) -> Result<(), MsgProcessingError> {
     send_lock(wrapper, ptr)?;
 
     let handle = get_mut_handle(ptr)?;
     
-    if handle.properties.contains_key(&id) {
+    if let Some(existing) = handle.properties.get(&id) {
         // We will not create the property, instead log an error
-        error!("Plugin {} failed to add property {}, id collision {}", handle.name, container.short_name, id);
-        return Ok(());
+        if !collisions::check_property(handle.name.as_str(), id, existing.short_name.as_str(), container.short_name.as_str()) {
+            error!("Plugin {} failed to add property {}, it already exists", handle.name, container.short_name);
+        }
+        handle.quota.release_property();
+        let prop = PropertyHandle { plugin: handle.id, property: id };
+        return operation_failed(wrapper, ptr, Operation::CreateProperty, DataStoreReturnCode::AlreadyExists, prop, 0);
     }
     let val_container = container.clone_container();
     let prop_name = format!("{}.{}", handle.name.to_lowercase(), container.short_name.to_lowercase());
@@ -407,7 +1218,7 @@ async fn create_property(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64,
     Ok(())
 }
 
-async fn property_type_change(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64, val_container: utils::ValueContainer, allow_modify: bool) -> Result<(), MsgProcessingError> {
+async fn property_type_change(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64, val_container: utils::ValueContainer, allow_modify: bool) -> Result<(), MsgProcessingError> {
     send_lock(wrapper, ptr)?;
     
     let handle = get_mut_handle(ptr)?;
@@ -428,25 +1239,29 @@ async fn property_type_change(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id:
         }).await?;
     } else {
         error!("Plugin {} failed to change type of property of id {}, it does not exist", handle.name, id);
-        return Ok(());
+        let prop = PropertyHandle { plugin: handle.id, property: id };
+        return operation_failed(wrapper, ptr, Operation::ChangePropertyType, DataStoreReturnCode::DoesNotExist, prop, 0);
     }
 
     Ok(())
 }
 
-async fn delete_property(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64) -> Result<(), MsgProcessingError> {
+async fn delete_property(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64) -> Result<(), MsgProcessingError> {
     send_lock(wrapper, ptr)?;
     let handle = get_mut_handle(ptr)?;
     
     if !handle.properties.contains_key(&id) {
-        // We will not create the property, instead log an error
+        // We will not delete the property, instead log an error
         error!("Plugin {} failed to delete property of id {}, not found", handle.name, id);
-        return Ok(());
+        let prop = PropertyHandle { plugin: handle.id, property: id };
+        return operation_failed(wrapper, ptr, Operation::DeleteProperty, DataStoreReturnCode::DoesNotExist, prop, 0);
     }
     handle.properties.remove(&id);
+    handle.quota.release_property();
 
     // Technically we can unlock while sending messages, practically we have to see if there is any gain
     let prop = PropertyHandle { plugin: handle.id, property: id };
+    crate::stale::remove(&prop);
     let mut ds_w = handle.datastore.write().await;
     ds_w.delete_property(&prop);
     drop(ds_w); // We could rewrite send_message to take the mutexguard... or not
@@ -461,25 +1276,39 @@ async fn delete_property(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64)
     Ok(())
 }
 
-fn shutdown(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
+fn shutdown(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
     send_unlock(wrapper, ptr)?;
     
-    if wrapper.update(ptr.ptr, Message { sort: MessageType::Shutdown, value: MessageValue { flag: true }}) != 0 {
-        return Err(MsgProcessingError::NoneZeroReturnCode("Failed on shutdown message"));
-    }
+    send_update(wrapper, ptr, Message { sort: MessageType::Shutdown, value: MessageValue { flag: true }}, "Failed on shutdown message")?;
 
     Err(MsgProcessingError::Shutdown)
 }
 
 /// Subscribing is a 3 step process, this is done by the sub, first we send a message to the property owner
-async fn subscribe_property_start(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
+async fn subscribe_property_start(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
     send_unlock(wrapper, ptr)?;
 
     // debug!("Entered Step 1");
 
+    if prop_handle.plugin == crate::system::CORE_PLUGIN_ID {
+        // Core properties have no loader, so we skip straight to Step 3
+        let val_container = {
+            let ds_r = get_handle(ptr)?.datastore.read().await;
+            ds_r.get_property_container(&prop_handle).map(|cont| cont.shallow_clone())
+        };
+
+        if let Some(val_container) = val_container {
+            return update_subscription(wrapper, ptr, prop_handle, val_container);
+        }
+
+        let name = get_handle(ptr)?.datastore.read().await.property_label(&prop_handle);
+        error!("Plugin {} failed to subscribe to core property {}: it does not exist", get_plugin_name(ptr), name);
+        return operation_failed(wrapper, ptr, Operation::SubscribeProperty, DataStoreReturnCode::DoesNotExist, prop_handle, 0);
+    }
+
     if !send_plugin_message(ptr, prop_handle.plugin, LoaderMessage::GenerateSubscribtion(get_handle(ptr)?.id, prop_handle)).await? {
         error!("Plugin {} failed to send message to generate subscription to plugin of id {} (likely plugin does not exist)", get_plugin_name(ptr), prop_handle.plugin);
-        return Ok(());
+        return operation_failed(wrapper, ptr, Operation::SubscribeProperty, DataStoreReturnCode::DoesNotExist, prop_handle, 0);
     }
 
     Ok(())
@@ -487,7 +1316,7 @@ async fn subscribe_property_start(wrapper: &PluginWrapper, ptr: &mut PtrWrapper,
 
 /// This is Step 2, this is run by the owner, generates a shallow copy of the ValueContainer and
 /// sends it back
-async fn generate_subcription(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id: u64, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
+async fn generate_subcription(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
     send_unlock(wrapper, ptr)?;
 
     // debug!("Entered Step 2");
@@ -502,6 +1331,8 @@ async fn generate_subcription(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id:
        cont.clone_container() 
     } else {
         error!("Plugin {} was requested property of id {} by plugin of id {}, but it does not exist", handle.name, prop_handle.property, id);
+        let failed = FailedOperation { what: Operation::SubscribeProperty, code: DataStoreReturnCode::DoesNotExist, property: prop_handle, target: 0, message_ptr: std::ptr::null_mut(), reason: 0 };
+        send_plugin_message(ptr, id, LoaderMessage::OperationFailed(failed)).await?;
         return Ok(());
     };
 
@@ -523,32 +1354,58 @@ async fn generate_subcription(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, id:
 }
 
 /// This is Step 3, run by the sub, we add the value container to our subscription list (for which
-/// we need to lock)
-/// This is also used to update the subscription, for example when the owner changed type
-fn update_subscription(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle: PropertyHandle, val_container: utils::ValueContainer) -> Result<(), MsgProcessingError> {
+/// we need to lock), and on a new subscription we deliver the current value
+/// This is also used to update the subscription when the owner changed type, which we deliver as
+/// PropertyTypeChanged
+fn update_subscription(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, prop_handle: PropertyHandle, val_container: utils::ValueContainer) -> Result<(), MsgProcessingError> {
     send_lock(wrapper, ptr)?;
 
     // debug!("Entered Step 3");
 
     let handle = get_mut_handle(ptr)?;
+    // A new container for an existing subscription means the owner changed the type
+    let sort = match handle.subscriptions.get(&prop_handle) {
+        None => Some(MessageType::PropertySubscribed),
+        Some(old) if !old.same_container(&val_container) => Some(MessageType::PropertyTypeChanged),
+        // Subscribed again, we already have the value
+        Some(_) => None
+    }.filter(|sort| handle.wants_message(*sort));
+    let msg = sort.map(|sort| {
+        let value = val_container.read(false);
+        crate::leaks::track_property(&value, handle.id);
+        (sort, value)
+    });
+
     // We do in this to allow overrides
     handle.subscriptions.insert(prop_handle, val_container);
 
+    if let Some((sort, value)) = msg {
+        send_simple_message(wrapper, ptr,
+            Message { sort, value: MessageValue { update: ManuallyDrop::new(UpdateValue { handle: prop_handle, value }) }},
+            "Failed on delivering subscribed property")?;
+    }
+
     Ok(())
 }
 
-async fn unsubscribe(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
+async fn unsubscribe(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
     send_lock(wrapper, ptr)?;
     
     let handle = get_mut_handle(ptr)?;
 
     if !handle.subscriptions.contains_key(&prop_handle) {
-        error!("Plugin {} failed to unsubscribe from property of plugin id {} property id {}: we weren't subscribed", get_plugin_name(ptr), prop_handle.plugin, prop_handle.property);
-        return Ok(());
+        let name = handle.datastore.read().await.property_label(&prop_handle);
+        error!("Plugin {} failed to unsubscribe from property {}: we weren't subscribed", get_plugin_name(ptr), name);
+        return operation_failed(wrapper, ptr, Operation::UnsubscribeProperty, DataStoreReturnCode::DoesNotExist, prop_handle, 0);
     }
 
     handle.subscriptions.remove(&prop_handle);
 
+    if prop_handle.plugin == crate::system::CORE_PLUGIN_ID {
+        // There is no owner to inform
+        return Ok(());
+    }
+
     if !send_plugin_message(ptr, prop_handle.plugin, LoaderMessage::HasUnsubscribed(handle.id, prop_handle)).await? {
         error!("Plugin {} failed to send reply message to containing subscription to plugin of id {}", get_plugin_name(ptr), prop_handle.plugin);
         return Ok(());
@@ -558,7 +1415,40 @@ async fn unsubscribe(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle:
     Ok(())
 }
 
-fn has_unsubscribed(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle: PropertyHandle, id: u64) -> Result<(), MsgProcessingError> {
+/// Another plugin stopped: our subscriptions to its properties are removed (releasing the
+/// containers, which would otherwise stay around till we stop), and we are informed for each.
+/// Also drops the plugin from the subscribers of our properties
+fn plugin_removed(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, id: u64) -> Result<(), MsgProcessingError> {
+    for subs in ptr.subscribers.values_mut() {
+        subs.retain(|sub| *sub != id);
+    }
+    ptr.subscribers.retain(|_, subs| !subs.is_empty());
+
+    let orphaned: Vec<PropertyHandle> = get_handle(ptr)?.subscriptions.keys().filter(|prop| prop.plugin == id).copied().collect();
+    if orphaned.is_empty() {
+        return Ok(());
+    }
+
+    send_lock(wrapper, ptr)?;
+
+    let handle = get_mut_handle(ptr)?;
+    for prop in orphaned.iter() {
+        handle.subscriptions.remove(prop);
+    }
+    for group in ptr.groups.values_mut() {
+        group.properties.retain(|(prop, _)| prop.plugin != id);
+    }
+    debug!("Plugin {} lost {} subscriptions to the stopped plugin of id {}", get_plugin_name(ptr), orphaned.len(), id);
+
+    for prop in orphaned {
+        send_simple_message(wrapper, ptr, Message { sort: MessageType::SubscriptionRemoved, value: MessageValue { removed_property: prop } },
+            "Failed on informing about a removed subscription")?;
+    }
+
+    Ok(())
+}
+
+fn has_unsubscribed(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, prop_handle: PropertyHandle, id: u64) -> Result<(), MsgProcessingError> {
     send_unlock(wrapper, ptr)?;
 
     let handle = get_handle(ptr)?;
@@ -577,8 +1467,132 @@ fn has_unsubscribed(wrapper: &PluginWrapper, ptr: &mut PtrWrapper, prop_handle:
     Ok(())
 }
 
-async fn startup_complete(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
-    send_unlock(&wrapper, ptr)?;
+/// Starts the timer of the group, the group is then filled through add_to_group
+fn create_group(ptr: &mut PtrWrapper, group: u64, interval: u64) -> Result<(), MsgProcessingError> {
+    if ptr.groups.contains_key(&group) {
+        debug!("Plugin {} created subscription group {} again, ignoring", get_plugin_name(ptr), group);
+        return Ok(());
+    }
+
+    let sender = get_handle(ptr)?.sender.as_async().clone();
+    let pending = Arc::new(AtomicBool::new(false));
+    let flag = pending.clone();
+
+    let timer = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(interval));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            if !flag.swap(true, Ordering::AcqRel) && sender.send(LoaderMessage::GroupTick(group)).await.is_err() {
+                // Plugin has shut down
+                break;
+            }
+        }
+    });
+
+    ptr.groups.insert(group, SubscriptionGroup { properties: Vec::new(), pending, timer });
+    Ok(())
+}
+
+fn add_to_group(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, group: u64, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
+    if let Some(grp) = ptr.groups.get_mut(&group) {
+        if !grp.properties.iter().any(|(prop, _)| *prop == prop_handle) {
+            grp.properties.push((prop_handle, utils::ValueCache::default()));
+        }
+    } else {
+        error!("Plugin {} failed to add property to subscription group {}, it does not exist", get_plugin_name(ptr), group);
+        return operation_failed(wrapper, ptr, Operation::AddToSubscriptionGroup, DataStoreReturnCode::DoesNotExist, prop_handle, group);
+    }
+
+    Ok(())
+}
+
+fn remove_from_group(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, group: u64, prop_handle: PropertyHandle) -> Result<(), MsgProcessingError> {
+    if let Some(grp) = ptr.groups.get_mut(&group) {
+        grp.properties.retain(|(prop, _)| *prop != prop_handle);
+    } else {
+        error!("Plugin {} failed to remove property from subscription group {}, it does not exist", get_plugin_name(ptr), group);
+        return operation_failed(wrapper, ptr, Operation::RemoveFromSubscriptionGroup, DataStoreReturnCode::DoesNotExist, prop_handle, group);
+    }
+
+    Ok(())
+}
+
+fn delete_group(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, group: u64) -> Result<(), MsgProcessingError> {
+    if let Some(grp) = ptr.groups.remove(&group) {
+        grp.timer.abort();
+    } else {
+        error!("Plugin {} failed to delete subscription group {}, it does not exist", get_plugin_name(ptr), group);
+        return operation_failed(wrapper, ptr, Operation::DeleteSubscriptionGroup, DataStoreReturnCode::DoesNotExist, PropertyHandle { plugin: 0, property: 0 }, group);
+    }
+
+    Ok(())
+}
+
+/// Collects all changed values of the group and sends them in one message
+fn group_tick(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, group: u64) -> Result<(), MsgProcessingError> {
+    let handle = if let Some(handle) = unsafe { ptr.ptr.as_ref() } {
+        handle
+    } else {
+        return Err(MsgProcessingError::NullPtr);
+    };
+
+    let grp = if let Some(grp) = ptr.groups.get_mut(&group) {
+        grp
+    } else {
+        // Deleted while the tick was queued
+        return Ok(());
+    };
+    grp.pending.store(false, Ordering::Release);
+
+    let mut updates = Vec::new();
+    // Released before the update is delivered, as the plugin could begin a frame while handling it
+    let mut frames = crate::frames::FrameReader::default();
+    for (prop_handle, cache) in grp.properties.iter_mut() {
+        // Not subscribed (yet), it will be delivered once it is.
+        // Groups in the middle of a frame are delivered on the next tick
+        if let Some(cont) = handle.subscriptions.get(prop_handle).filter(|_| frames.can_read(prop_handle)) {
+            if cont.read_web(cache) {
+                let value = cont.read(false);
+                crate::leaks::track_property(&value, handle.id);
+                updates.push(UpdateValue { handle: *prop_handle, value });
+            }
+        }
+    }
+    drop(frames);
+
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let len = updates.len();
+    let updates = Box::into_raw(updates.into_boxed_slice()) as *mut UpdateValue;
+    crate::leaks::track(updates, handle.id, crate::leaks::Allocation::GroupUpdate);
+
+    send_simple_message(wrapper, ptr,
+        Message { sort: MessageType::GroupUpdate, value: MessageValue { group_update: SubscriptionGroupUpdate { group, updates, len } }},
+        "Failed on delivering subscription group update")
+}
+
+/// Registers the watch, if the plugin is already running we tell the plugin right away
+async fn watch_plugin(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper, plugin: u64) -> Result<(), MsgProcessingError> {
+    let (datastore, id) = {
+        let handle = get_handle(ptr)?;
+        (handle.datastore, handle.id)
+    };
+    let running = datastore.write().await.watch_plugin(id, plugin);
+
+    if running {
+        send_simple_message(wrapper, ptr,
+            Message { sort: MessageType::WatchedPluginStarted, value: MessageValue { plugin_id: plugin }}, "Failed on informing about watched plugin start")
+    } else {
+        Ok(())
+    }
+}
+
+async fn startup_complete(wrapper: &dyn PluginCalls, ptr: &mut PtrWrapper) -> Result<(), MsgProcessingError> {
+    send_unlock(wrapper, ptr)?;
 
     let han = get_handle(ptr)?;
     let id = han.id.clone();
@@ -588,5 +1602,5 @@ async fn startup_complete(wrapper: &PluginWrapper, ptr: &mut PtrWrapper) -> Resu
     
     drop(ds_w);
 
-    send_update(&wrapper, ptr, Message { sort: MessageType::StartupFinished, value: MessageValue { flag: true } }, "Failed on informing about finshed startup")   
+    send_update(wrapper, ptr, Message { sort: MessageType::StartupFinished, value: MessageValue { flag: true } }, "Failed on informing about finshed startup")   
 }
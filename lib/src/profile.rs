@@ -0,0 +1,204 @@
+//! Time the plugins spend in their update function, per plugin and message type, so the plugin
+//! causing stutter can be found without a trace (see trace for the full timeline).
+//!
+//! Every call is recorded into a histogram (8 buckets per power of two, so percentiles are
+//! within 12.5%), kept from the start of the plugin till it stops. The longest call of the last
+//! second is also published as the core property datarace.update_time.{plugin}.
+
+use std::{sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::MessageType;
+
+/// Buckets per power of two, as bits
+const SUB_BITS: u32 = 3;
+const SUB_BUCKETS: u64 = 1 << SUB_BITS;
+/// Enough to cover every u64
+const BUCKETS: usize = ((64 - SUB_BITS + 1) as usize) * SUB_BUCKETS as usize;
+
+/// Call durations in nano seconds
+struct Histogram {
+    buckets: Box<[u64; BUCKETS]>,
+    count: u64,
+    total: u64,
+    max: u64
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram { buckets: Box::new([0; BUCKETS]), count: 0, total: 0, max: 0 }
+    }
+
+    fn bucket(value: u64) -> usize {
+        if value < SUB_BUCKETS {
+            return value as usize;
+        }
+
+        let power = 63 - value.leading_zeros();
+        let sub = (value >> (power - SUB_BITS)) & (SUB_BUCKETS - 1);
+        ((power - SUB_BITS + 1) as u64 * SUB_BUCKETS + sub) as usize
+    }
+
+    /// Highest value that falls into this bucket
+    fn upper_bound(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+        if bucket < SUB_BUCKETS {
+            return bucket;
+        }
+
+        let power = (bucket / SUB_BUCKETS) as u32 + SUB_BITS - 1;
+        let lower = (SUB_BUCKETS + bucket % SUB_BUCKETS) << (power - SUB_BITS);
+        lower.saturating_add((1 << (power - SUB_BITS)) - 1)
+    }
+
+    fn record(&mut self, value: u64) {
+        self.buckets[Histogram::bucket(value)] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    /// The value below which the given fraction of the calls fall, 0 without calls
+    fn percentile(&self, fraction: f64) -> u64 {
+        let target = ((self.count as f64 * fraction).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Histogram::upper_bound(bucket).min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    fn times(&self, message: &'static str) -> UpdateTimes {
+        let micros = |nanos: u64| nanos as f64 / 1000.0;
+
+        UpdateTimes {
+            message,
+            calls: self.count,
+            mean_us: self.total.checked_div(self.count).map(micros).unwrap_or_default(),
+            p50_us: micros(self.percentile(0.5)),
+            p90_us: micros(self.percentile(0.9)),
+            p99_us: micros(self.percentile(0.99)),
+            max_us: micros(self.max)
+        }
+    }
+}
+
+#[derive(Default)]
+struct PluginProfile {
+    /// By MessageType
+    messages: HashMap<u8, (&'static str, Histogram)>
+}
+
+/// Records the calls of one plugin, owned by its loader
+#[derive(Default)]
+pub(crate) struct Profiler {
+    profile: Arc<Mutex<PluginProfile>>,
+    /// Longest call since the core properties last read it, in micro seconds
+    peak: Arc<AtomicU64>
+}
+
+impl Profiler {
+    pub(crate) fn record(&self, sort: MessageType, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.peak.fetch_max(nanos / 1000, Ordering::AcqRel);
+
+        let mut profile = self.profile.lock().unwrap_or_else(|e| e.into_inner());
+        profile.messages.entry(sort as u8)
+            .or_insert_with(|| (sort.name(), Histogram::new()))
+            .1.record(nanos);
+    }
+}
+
+struct Entry {
+    name: String,
+    profile: Arc<Mutex<PluginProfile>>,
+    peak: Arc<AtomicU64>
+}
+
+/// Plugin id -> profile
+static PROFILES: Mutex<Option<HashMap<u64, Entry>>> = Mutex::new(None);
+
+fn with_profiles<R>(func: impl FnOnce(&mut HashMap<u64, Entry>) -> R) -> R {
+    let mut guard = PROFILES.lock().unwrap_or_else(|e| e.into_inner());
+    func(guard.get_or_insert_with(HashMap::default))
+}
+
+/// Publishes the profile of a plugin once it is registered, a restarted plugin starts with an empty one
+pub(crate) fn register(id: u64, name: &str, profiler: &Profiler) {
+    let entry = Entry { name: name.to_string(), profile: profiler.profile.clone(), peak: profiler.peak.clone() };
+    with_profiles(|profiles| profiles.insert(id, entry));
+}
+
+pub(crate) fn remove_plugin(id: u64) {
+    with_profiles(|profiles| profiles.remove(&id));
+}
+
+/// Longest call since the last time this was called for the plugin, in micro seconds
+pub(crate) fn take_peak(id: u64) -> Option<u64> {
+    with_profiles(|profiles| profiles.get(&id).map(|entry| entry.peak.swap(0, Ordering::AcqRel)))
+}
+
+/// Formats micro seconds for display, like 12.3 µs or 4.56 ms
+pub(crate) fn format_micros(micros: f64) -> String {
+    if micros < 1000.0 {
+        format!("{:.1} µs", micros)
+    } else {
+        format!("{:.2} ms", micros / 1000.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UpdateTimes {
+    pub(crate) message: &'static str,
+    pub(crate) calls: u64,
+    pub(crate) mean_us: f64,
+    pub(crate) p50_us: f64,
+    pub(crate) p90_us: f64,
+    pub(crate) p99_us: f64,
+    pub(crate) max_us: f64
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PluginUpdateTimes {
+    pub(crate) plugin: String,
+    /// All messages together
+    pub(crate) total: UpdateTimes,
+    /// Slowest (by p99) first
+    pub(crate) messages: Vec<UpdateTimes>
+}
+
+/// The profiles of all running plugins, slowest (by p99 over all messages) first
+pub(crate) fn collect() -> Vec<PluginUpdateTimes> {
+    let profiles: Vec<(String, Arc<Mutex<PluginProfile>>)> = with_profiles(|profiles| profiles.values()
+        .map(|entry| (entry.name.clone(), entry.profile.clone()))
+        .collect());
+
+    let mut list: Vec<PluginUpdateTimes> = profiles.into_iter().map(|(plugin, profile)| {
+        let profile = profile.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut total = Histogram::new();
+        for (_, hist) in profile.messages.values() {
+            for (bucket, count) in hist.buckets.iter().enumerate() {
+                total.buckets[bucket] += count;
+            }
+            total.count += hist.count;
+            total.total = total.total.saturating_add(hist.total);
+            total.max = total.max.max(hist.max);
+        }
+
+        let mut messages: Vec<UpdateTimes> = profile.messages.values().map(|(name, hist)| hist.times(name)).collect();
+        messages.sort_by(|a, b| b.p99_us.total_cmp(&a.p99_us));
+
+        PluginUpdateTimes { plugin, total: total.times("all"), messages }
+    }).collect();
+
+    list.sort_by(|a, b| b.total.p99_us.total_cmp(&a.total.p99_us));
+    list
+}
@@ -0,0 +1,337 @@
+// The pyfunction macro triggers this on every function
+#![allow(clippy::useless_conversion)]
+
+use std::{cell::RefCell, ffi::CString, path::Path, sync::{Arc, Mutex}};
+
+use hashbrown::HashMap;
+use pyo3::{exceptions::PyRuntimeError, prelude::*, types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString}};
+
+use crate::{notifications, pluginloader::DataRacePlugin, utils, ArrayValueHandle, DataStoreReturnCode, EventFilter, EventHandle, Message, MessageType, PluginHandle, Property, PropertyHandle, PropertyType, PropertyValue};
+
+/// A .py script out of the plugin folder, run as a built-in plugin.
+///
+/// All scripts share one interpreter, each script is its own module.
+/// The script can define the functions `init()` and `update(msg)`,
+/// and imports the `datarace` module to interact with properties and events.
+pub(crate) struct PythonPlugin {
+    name: String,
+    id: u64,
+    module: Py<PyModule>,
+    events: Arc<Mutex<HashMap<EventHandle, String>>>,
+    properties: Arc<Mutex<HashMap<PropertyHandle, String>>>
+}
+
+/// The plugin currently calling into python, so the datarace module knows who is calling
+struct Context {
+    handle: *mut PluginHandle,
+    name: String,
+    id: u64,
+    events: Arc<Mutex<HashMap<EventHandle, String>>>,
+    properties: Arc<Mutex<HashMap<PropertyHandle, String>>>
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+impl PythonPlugin {
+    /// Runs the script once (so it defines its functions), the name of the plugin is the file name
+    /// The code is passed in, as it was read when checking the hash (see read_plugin_file)
+    pub(crate) fn load(path: &Path, code: String) -> Result<PythonPlugin, String> {
+        let name = crate::pluginloader::script_name(path);
+        let id = utils::generate_plugin_name_hash(name.as_str()).ok_or(format!("Plugin name {} does not comply with naming schema", name))?;
+
+        let module = Python::with_gil(|py| {
+            register_api(py)?;
+            PyModule::from_code_bound(py, code.as_str(), path.to_str().unwrap_or_default(), name.as_str()).map(|m| m.unbind())
+        }).map_err(|e| format!("Script failed: {}", e))?;
+
+        Ok(PythonPlugin { name, id, module, events: Arc::default(), properties: Arc::default() })
+    }
+
+    /// Calls the function of the script (if it is defined)
+    fn call(&self, handle: *mut PluginHandle, func: &str, args: impl FnOnce(Python) -> PyResult<Option<PyObject>>) -> Result<(), String> {
+        CONTEXT.with_borrow_mut(|ctx| *ctx = Some(Context { handle, name: self.name.clone(), id: self.id, events: self.events.clone(), properties: self.properties.clone() }));
+
+        let res = Python::with_gil(|py| {
+            let module = self.module.bind(py);
+            // Arguments are converted first, as this deallocates values passed in
+            let args = args(py)?;
+            if !module.hasattr(func)? {
+                return Ok(());
+            }
+
+            match args {
+                Some(arg) => module.getattr(func)?.call1((arg,))?,
+                None => module.getattr(func)?.call0()?
+            };
+            Ok(())
+        }).map_err(|e: PyErr| e.to_string());
+
+        CONTEXT.with_borrow_mut(|ctx| *ctx = None);
+        res
+    }
+}
+
+impl DataRacePlugin for PythonPlugin {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn version(&self) -> [u16;3] {
+        [0, 0, 0]
+    }
+
+    fn init(&self, handle: *mut PluginHandle) -> Result<(), String> {
+        self.call(handle, "init", |_| Ok(None))
+    }
+
+    fn update(&self, handle: *mut PluginHandle, msg: Message) -> Result<(), String> {
+        let sort = match msg.sort {
+            MessageType::StartupFinished => "startup_finished",
+            MessageType::OtherPluginStarted => "other_plugin_started",
+            MessageType::EventTriggered => "event_triggered",
+            MessageType::EventUnsubscribed => "event_unsubscribed",
+            MessageType::PropertySubscribed => "property_subscribed",
+            MessageType::PropertyTypeChanged => "property_type_changed",
+            MessageType::OperationFailed => "operation_failed",
+            MessageType::SubscriptionRemoved => "subscription_removed",
+            MessageType::Shutdown => "shutdown",
+            // Scripts are run one message at a time, so they have no use for lock/unlock or pointers
+            _ => return Ok(())
+        };
+
+        self.call(handle, "update", |py| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("type", sort)?;
+            match msg.sort {
+                MessageType::OtherPluginStarted => dict.set_item("plugin_id", unsafe { msg.value.plugin_id })?,
+                MessageType::EventTriggered | MessageType::EventUnsubscribed => {
+                    let event = unsafe { msg.value.event };
+                    let name = self.events.lock().ok().and_then(|events| events.get(&event).cloned());
+                    dict.set_item("event", name)?
+                },
+                MessageType::PropertySubscribed | MessageType::PropertyTypeChanged => {
+                    let update = unsafe { std::mem::ManuallyDrop::into_inner(msg.value.update) };
+                    let name = self.properties.lock().ok().and_then(|properties| properties.get(&update.handle).cloned());
+                    dict.set_item("property", name)?;
+                    dict.set_item("value", from_property(py, update.value)?)?
+                },
+                MessageType::OperationFailed => {
+                    let failed = unsafe { msg.value.operation_failed };
+                    let name = self.properties.lock().ok().and_then(|properties| properties.get(&failed.property).cloned());
+                    dict.set_item("operation", format!("{:?}", failed.what))?;
+                    dict.set_item("code", format!("{:?}", failed.code))?;
+                    dict.set_item("property", name)?
+                },
+                MessageType::SubscriptionRemoved => {
+                    let prop = unsafe { msg.value.removed_property };
+                    let name = self.properties.lock().ok().and_then(|properties| properties.get(&prop).cloned());
+                    dict.set_item("property", name)?
+                },
+                _ => ()
+            }
+            Ok(Some(dict.into_any().unbind()))
+        })
+    }
+}
+
+/// Adds the `datarace` module to the interpreter (once)
+fn register_api(py: Python) -> PyResult<()> {
+    let modules = py.import_bound("sys")?.getattr("modules")?;
+    if modules.contains("datarace")? {
+        return Ok(());
+    }
+
+    let module = PyModule::new_bound(py, "datarace")?;
+    module.add_function(wrap_pyfunction!(create_property, &module)?)?;
+    module.add_function(wrap_pyfunction!(update_property, &module)?)?;
+    module.add_function(wrap_pyfunction!(delete_property, &module)?)?;
+    module.add_function(wrap_pyfunction!(get_property, &module)?)?;
+    module.add_function(wrap_pyfunction!(subscribe_property, &module)?)?;
+    module.add_function(wrap_pyfunction!(create_event, &module)?)?;
+    module.add_function(wrap_pyfunction!(trigger_event, &module)?)?;
+    module.add_function(wrap_pyfunction!(subscribe_event, &module)?)?;
+    module.add_function(wrap_pyfunction!(notify, &module)?)?;
+    module.add_function(wrap_pyfunction!(log_info, &module)?)?;
+    module.add_function(wrap_pyfunction!(log_error, &module)?)?;
+
+    modules.set_item("datarace", module)
+}
+
+/// Runs the closure with the context of the calling plugin
+fn with_context<T>(func: impl FnOnce(&Context) -> PyResult<T>) -> PyResult<T> {
+    CONTEXT.with_borrow(|ctx| match ctx {
+        Some(ctx) => func(ctx),
+        None => Err(PyRuntimeError::new_err("The datarace api is only available from init and update"))
+    })
+}
+
+fn check(code: DataStoreReturnCode) -> PyResult<()> {
+    if code == DataStoreReturnCode::Ok {
+        Ok(())
+    } else {
+        Err(PyRuntimeError::new_err(format!("DataRace returned {:?}", code)))
+    }
+}
+
+/// Properties of the script itself are addressed without the plugin name
+fn own_property(ctx: &Context, name: &str) -> PyResult<PropertyHandle> {
+    let property = utils::generate_property_name_hash(name).ok_or(PyRuntimeError::new_err(format!("Invalid property name {}", name)))?;
+    Ok(PropertyHandle { plugin: ctx.id, property })
+}
+
+/// Events of the script itself are addressed without the plugin name
+fn own_event(ctx: &Context, name: &str) -> PyResult<EventHandle> {
+    let event = utils::generate_event_name_hash(name).ok_or(PyRuntimeError::new_err(format!("Invalid event name {}", name)))?;
+    Ok(EventHandle { plugin: ctx.id, event })
+}
+
+#[pyfunction]
+fn create_property(name: String, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    with_context(|ctx| {
+        let prop_handle = own_property(ctx, name.as_str())?;
+        let value = to_property(value)?;
+        let name = CString::new(name).map_err(|e| PyRuntimeError::new_err(e.to_string()))?.into_raw();
+
+        let res = crate::create_property(ctx.handle, name, prop_handle, value);
+        crate::deallocate_string(name);
+        check(res)
+    })
+}
+
+#[pyfunction]
+fn update_property(name: String, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    with_context(|ctx| check(crate::update_property(ctx.handle, own_property(ctx, name.as_str())?, to_property(value)?)))
+}
+
+#[pyfunction]
+fn delete_property(name: String) -> PyResult<()> {
+    with_context(|ctx| check(crate::delete_property(ctx.handle, own_property(ctx, name.as_str())?)))
+}
+
+#[pyfunction]
+fn get_property(py: Python, name: String) -> PyResult<PyObject> {
+    with_context(|ctx| {
+        let prop_handle = PropertyHandle::new(name.as_str()).ok_or(PyRuntimeError::new_err(format!("Invalid property name {}", name)))?;
+
+        let res = crate::get_property_value(ctx.handle, prop_handle);
+        check(res.code)?;
+        from_property(py, res.value)
+    })
+}
+
+#[pyfunction]
+fn subscribe_property(name: String) -> PyResult<()> {
+    with_context(|ctx| {
+        let prop_handle = PropertyHandle::new(name.as_str()).ok_or(PyRuntimeError::new_err(format!("Invalid property name {}", name)))?;
+
+        if let Ok(mut properties) = ctx.properties.lock() {
+            properties.insert(prop_handle, name);
+        }
+        check(crate::subscribe_property(ctx.handle, prop_handle))
+    })
+}
+
+#[pyfunction]
+fn create_event(name: String) -> PyResult<()> {
+    with_context(|ctx| check(crate::create_event(ctx.handle, own_event(ctx, name.as_str())?)))
+}
+
+#[pyfunction]
+fn trigger_event(name: String) -> PyResult<()> {
+    with_context(|ctx| check(crate::trigger_event(ctx.handle, own_event(ctx, name.as_str())?)))
+}
+
+#[pyfunction]
+#[pyo3(signature = (name, debounce_ms = 0, every_nth = 0))]
+fn subscribe_event(name: String, debounce_ms: u64, every_nth: u64) -> PyResult<()> {
+    with_context(|ctx| {
+        let event = EventHandle::new(name.as_str()).ok_or(PyRuntimeError::new_err(format!("Invalid event name {}", name)))?;
+
+        if let Ok(mut events) = ctx.events.lock() {
+            events.insert(event, name);
+        }
+        check(crate::subscribe_event_filtered(ctx.handle, event, EventFilter { debounce_ms, every_nth }))
+    })
+}
+
+#[pyfunction]
+fn notify(severity: String, text: String) -> PyResult<()> {
+    with_context(|ctx| {
+        let severity = notifications::parse_severity(severity.as_str())
+            .ok_or(PyRuntimeError::new_err(format!("Invalid severity {}, has to be info, warning or error", severity)))?;
+        let text = CString::new(text).map_err(|e| PyRuntimeError::new_err(e.to_string()))?.into_raw();
+
+        let res = crate::raise_notification(ctx.handle, severity, text);
+        crate::deallocate_string(text);
+        check(res)
+    })
+}
+
+#[pyfunction]
+fn log_info(text: String) -> PyResult<()> {
+    with_context(|ctx| {
+        log::info!("[{}] {}", ctx.name, text);
+        Ok(())
+    })
+}
+
+#[pyfunction]
+fn log_error(text: String) -> PyResult<()> {
+    with_context(|ctx| {
+        log::error!("[{}] {}", ctx.name, text);
+        Ok(())
+    })
+}
+
+/// Converts a python value into a Property, strings are freed by the property (see PropertyContainer::update)
+fn to_property(value: &Bound<'_, PyAny>) -> PyResult<Property> {
+    // bool is a subclass of int, so it has to be checked first
+    Ok(if value.is_none() {
+        Property::default()
+    } else if value.is_instance_of::<PyBool>() {
+        Property { sort: PropertyType::Boolean, value: PropertyValue { boolean: value.extract()? } }
+    } else if value.is_instance_of::<PyInt>() {
+        Property { sort: PropertyType::Int, value: PropertyValue { integer: value.extract()? } }
+    } else if value.is_instance_of::<PyFloat>() {
+        Property { sort: PropertyType::Float, value: PropertyValue { decimal: value.extract()? } }
+    } else if value.is_instance_of::<PyString>() {
+        let text: String = value.extract()?;
+        let ptr = CString::new(text).map_err(|e| PyRuntimeError::new_err(e.to_string()))?.into_raw();
+        Property { sort: PropertyType::Str, value: PropertyValue { str: ptr } }
+    } else {
+        return Err(PyRuntimeError::new_err(format!("Unable to store a {} in a property", value.get_type())));
+    })
+}
+
+/// Converts a Property into a python value, durations are in seconds, vectors become (x, y, z) tuples and arrays lists
+fn from_property(py: Python, prop: Property) -> PyResult<PyObject> {
+    Ok(match prop.sort {
+        PropertyType::None => py.None(),
+        PropertyType::Int => unsafe { prop.value.integer }.into_py(py),
+        PropertyType::Float => unsafe { prop.value.decimal }.into_py(py),
+        PropertyType::Boolean => unsafe { prop.value.boolean }.into_py(py),
+        PropertyType::Duration => (unsafe { prop.value.dur } as f64 / 1_000_000.0).into_py(py),
+        PropertyType::Timestamp => (unsafe { prop.value.timestamp } as f64 / 1_000_000.0).into_py(py),
+        PropertyType::Vector => {
+            let v = unsafe { prop.value.vector };
+            (v.x, v.y, v.z).into_py(py)
+        },
+        PropertyType::Str => {
+            let ptr = unsafe { prop.value.str };
+            let text = utils::get_string(ptr).unwrap_or_default();
+            crate::deallocate_string(ptr);
+            text.into_py(py)
+        },
+        PropertyType::Array => {
+            let arr: *mut ArrayValueHandle = unsafe { prop.value.arr };
+            let list = PyList::empty_bound(py);
+            for index in 0..crate::get_array_length(arr) {
+                list.append(from_property(py, crate::get_array_value(arr, index))?)?;
+            }
+            crate::drop_array_handle(arr);
+            list.into_any().unbind()
+        }
+    })
+}
@@ -0,0 +1,120 @@
+//! Limits per plugin (plugin_quota in the config), so a buggy plugin on a shared install can not
+//! take everything down by allocating millions of properties or flooding the message queues.
+//! Calls exceeding them return QuotaExceeded.
+//!
+//! The limits are resolved when the plugin is loaded and kept in its PluginHandle, so changing them requires a restart.
+
+use std::{collections::HashMap, sync::{atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering}, OnceLock}, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// An unset limit is unlimited
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct Limits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_properties: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_array_length: Option<usize>,
+    /// Messages (internal, to other plugins, reenqueued) and triggered events per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_message_rate: Option<u32>
+}
+
+impl Limits {
+    /// Takes the limits set in other, keeps the rest
+    fn overridden_by(self, other: &Limits) -> Limits {
+        Limits {
+            max_properties: other.max_properties.or(self.max_properties),
+            max_array_length: other.max_array_length.or(self.max_array_length),
+            max_message_rate: other.max_message_rate.or(self.max_message_rate)
+        }
+    }
+}
+
+/// plugin_quota of the config: limits for all plugins, overridden per plugin in plugins
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct QuotaConfig {
+    #[serde(flatten)]
+    limits: Limits,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    plugins: HashMap<String, Limits>
+}
+
+impl QuotaConfig {
+    pub(crate) fn is_unlimited(&self) -> bool {
+        *self == QuotaConfig::default()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for (name, limits) in std::iter::once(("all plugins", &self.limits)).chain(self.plugins.iter().map(|(n, l)| (n.as_str(), l))) {
+            if limits.max_message_rate == Some(0) {
+                return Err(format!("plugin_quota for {}: max_message_rate has to be at least 1", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The limits applying to the plugin
+    pub(crate) fn for_plugin(&self, name: &str) -> Limits {
+        match self.plugins.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            Some((_, limits)) => self.limits.overridden_by(limits),
+            None => self.limits
+        }
+    }
+}
+
+/// Time the message rate is measured against
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Kept in the PluginHandle, tracks the usage against the limits
+#[derive(Debug, Default)]
+pub(crate) struct Quota {
+    limits: Limits,
+    properties: AtomicUsize,
+    /// Second (since START) the message count is for
+    message_second: AtomicU64,
+    messages: AtomicU32
+}
+
+impl Quota {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Quota { limits, ..Default::default() }
+    }
+
+    /// Reserves a property, false if the plugin has reached max_properties.
+    /// Counted when queued, so a plugin can not get around it by creating them faster than the loader processes them
+    pub(crate) fn take_property(&self) -> bool {
+        let Some(max) = self.limits.max_properties else {
+            self.properties.fetch_add(1, Ordering::AcqRel);
+            return true;
+        };
+
+        self.properties.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| (count < max).then_some(count + 1)).is_ok()
+    }
+
+    /// Frees the reservation of a property (deleted, or it failed to create)
+    pub(crate) fn release_property(&self) {
+        let _ = self.properties.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| count.checked_sub(1));
+    }
+
+    pub(crate) fn allows_array_length(&self, length: usize) -> bool {
+        self.limits.max_array_length.is_none_or(|max| length <= max)
+    }
+
+    /// Counts a message, false if the plugin has send max_message_rate messages this second
+    pub(crate) fn take_message(&self) -> bool {
+        let Some(max) = self.limits.max_message_rate else {
+            return true;
+        };
+
+        let second = START.get_or_init(Instant::now).elapsed().as_secs();
+        if self.message_second.swap(second, Ordering::AcqRel) != second {
+            self.messages.store(0, Ordering::Release);
+        }
+
+        self.messages.fetch_add(1, Ordering::AcqRel) < max
+    }
+}
@@ -0,0 +1,200 @@
+//! Rules configured by the user: when a property crosses a threshold an event is triggered,
+//! a property is set and/or a notification is raised, so simple glue (like a low fuel warning) needs no plugin.
+//!
+//! Same as the aggregates the properties are sampled at a fixed rate.
+
+use std::time::Duration;
+
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use crate::{datastore::DataStore, events::EventMessage, notifications::Severity, system, utils::ValueCache, DataStoreReturnCode, EventHandle, PropertyHandle};
+
+/// How often the properties are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An entry of rules in the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct RuleConfig {
+    /// Full name of the property that is watched
+    property: String,
+    threshold: f64,
+    #[serde(default)]
+    direction: Direction,
+    /// Full name of the event triggered on crossing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    event: Option<String>,
+    /// Property set on crossing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set: Option<SetAction>,
+    /// Notification raised on crossing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify: Option<NotifyAction>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct SetAction {
+    pub(crate) property: String,
+    /// Parsed to the type of the property, same as the set of the control socket
+    pub(crate) value: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct NotifyAction {
+    #[serde(default)]
+    severity: Severity,
+    text: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Direction {
+    /// Going from below to at or above the threshold
+    #[default]
+    Rising,
+    /// Going from above to at or below the threshold
+    Falling,
+    Both
+}
+
+impl RuleConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if PropertyHandle::new(self.property.as_str()).is_none() {
+            return Err(format!("rule property {} is not a valid property name", self.property));
+        }
+        if self.threshold.is_nan() {
+            return Err(format!("rule on {}: threshold is not a number", self.property));
+        }
+        if self.event.is_none() && self.set.is_none() && self.notify.is_none() {
+            return Err(format!("rule on {}: has neither an event, a set nor a notify action", self.property));
+        }
+        if let Some(event) = self.event.as_ref() {
+            if EventHandle::new(event.as_str()).is_none() {
+                return Err(format!("rule on {}: event {} is not a valid event name", self.property, event));
+            }
+        }
+        if let Some(set) = self.set.as_ref() {
+            match PropertyHandle::new(set.property.as_str()) {
+                None => return Err(format!("rule on {}: set property {} is not a valid property name", self.property, set.property)),
+                Some(handle) if handle.plugin == system::CORE_PLUGIN_ID => return Err(format!("rule on {}: core property {} can not be set", self.property, set.property)),
+                Some(_) => ()
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Rule {
+    property: PropertyHandle,
+    threshold: f64,
+    direction: Direction,
+    event: Option<EventHandle>,
+    set: Option<(PropertyHandle, String)>,
+    notify: Option<NotifyAction>,
+    /// Value of the last sample, None before the first sample (or when the property is missing)
+    last: Option<f64>
+}
+
+impl Rule {
+    fn new(config: &RuleConfig) -> Option<Self> {
+        Some(Rule {
+            property: PropertyHandle::new(config.property.as_str())?,
+            threshold: config.threshold,
+            direction: config.direction,
+            event: config.event.as_ref().and_then(|ev| EventHandle::new(ev.as_str())),
+            set: match config.set.as_ref() {
+                Some(set) => Some((PropertyHandle::new(set.property.as_str())?, set.value.clone())),
+                None => None
+            },
+            notify: config.notify.clone(),
+            last: None
+        })
+    }
+
+    /// Stores the new value, returns true if the threshold was crossed
+    fn crossed(&mut self, value: Option<f64>) -> bool {
+        let last = std::mem::replace(&mut self.last, value);
+        let (Some(last), Some(value)) = (last, value) else {
+            return false;
+        };
+
+        let rising = last < self.threshold && value >= self.threshold;
+        let falling = last > self.threshold && value <= self.threshold;
+
+        match self.direction {
+            Direction::Rising => rising,
+            Direction::Falling => falling,
+            Direction::Both => rising || falling
+        }
+    }
+}
+
+/// Evaluates the rules of the config till shutdown
+pub(crate) async fn run_rules(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let mut rules: Vec<Rule> = datastore.read().await.get_config().get_rules().iter().filter_map(Rule::new).collect();
+    if rules.is_empty() {
+        return;
+    }
+    debug!("Evaluating {} rules", rules.len());
+
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        let ds_r = datastore.read().await;
+        if ds_r.get_shutdown_status() {
+            break;
+        }
+
+        // Raising needs the write lock and writes go through the owner (which needs the lock too),
+        // so they are collected till we are done reading
+        let mut notifications = Vec::new();
+        let mut writes = Vec::new();
+
+        for rule in rules.iter_mut() {
+            let value = ds_r.get_property_container(&rule.property).and_then(|cont| {
+                let mut cache = ValueCache::default();
+                cont.read_web(&mut cache);
+                cache.value.to_number()
+            });
+
+            if !rule.crossed(value) {
+                continue;
+            }
+
+            if let Some(ev) = rule.event {
+                if ds_r.get_event_channel().as_async().send(EventMessage::Trigger(ev)).await.is_err() {
+                    error!("Rule failed to trigger event {}|{}", ev.plugin, ev.event);
+                }
+            }
+
+            if let Some(set) = rule.set.clone() {
+                writes.push(set);
+            }
+
+            if let Some(notify) = rule.notify.as_ref() {
+                notifications.push(notify.clone());
+            }
+        }
+        drop(ds_r);
+
+        for (handle, value) in writes {
+            let code = crate::pluginloader::write_property_text(datastore, handle, value.clone()).await;
+            if code != DataStoreReturnCode::Ok {
+                error!("Rule failed to set property {}|{} to {}: {:?}", handle.plugin, handle.property, value, code);
+            }
+        }
+
+        if !notifications.is_empty() {
+            let mut ds_w = datastore.write().await;
+            for notify in notifications {
+                ds_w.raise_notification(notify.severity, "rules".to_string(), notify.text);
+            }
+        }
+    }
+
+    debug!("Rules stopped");
+}
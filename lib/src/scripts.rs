@@ -0,0 +1,124 @@
+//! Automation scripts: .lua files in the scripts folder, run the same way as lua plugins,
+//! but restarted when the file changes and stopped when it is deleted.
+
+use std::{path::{Path, PathBuf}, sync::Arc, time::SystemTime};
+
+use hashbrown::HashMap;
+use log::{debug, error, info};
+use tokio::time::{self, Duration, Instant};
+
+use crate::{datastore::DataStore, lua::LuaPlugin, pluginloader::{self, DataRacePlugin, LoaderMessage}};
+
+/// How often the scripts folder is checked for changes
+const SCRIPT_POLL_RATE: Duration = Duration::from_secs(2);
+
+/// How long we wait for the old version of a script to stop before giving up on it
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A script we have seen, id is None if it failed to load
+struct Script {
+    modified: Option<SystemTime>,
+    id: Option<u64>
+}
+
+/// Lists the .lua files in the folder with their last modification
+fn list_scripts(folder: &Path) -> HashMap<PathBuf, Option<SystemTime>> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return HashMap::default();
+    };
+
+    entries.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("lua") {
+            return None;
+        }
+
+        let modified = std::fs::metadata(path.as_path()).ok().and_then(|meta| meta.modified().ok());
+        Some((path, modified))
+    }).collect()
+}
+
+async fn start_script(datastore: &'static tokio::sync::RwLock<DataStore>, path: &Path) -> Option<u64> {
+    let (code, event_channel) = {
+        let ds_r = datastore.read().await;
+        let code = match pluginloader::read_plugin_file(ds_r.get_config(), path) {
+            Ok(content) => String::from_utf8(content).ok()?,
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        };
+        (code, ds_r.get_event_channel())
+    };
+
+    // The script runs once while loading, which takes as long as it wants
+    let owned_path = path.to_path_buf();
+    let plugin = match tokio::task::spawn_blocking(move || LuaPlugin::load(owned_path.as_path(), code)).await.map_err(|e| e.to_string()).and_then(|res| res) {
+        Ok(plugin) => plugin,
+        Err(e) => {
+            error!("Unable to load script {}: {}", path.to_str().unwrap_or_default(), e);
+            return None;
+        }
+    };
+    let id = crate::utils::generate_plugin_name_hash(plugin.name())?;
+
+    info!("Starting script {}", plugin.name());
+    tokio::spawn(pluginloader::run_static_plugin(Arc::new(plugin), datastore, event_channel));
+    Some(id)
+}
+
+/// Shuts the script down and waits till it has been removed, so the id is free again
+async fn stop_script(datastore: &'static tokio::sync::RwLock<DataStore>, id: u64) {
+    if !datastore.read().await.send_message_to_plugin(id, LoaderMessage::Shutdown).await {
+        return;
+    }
+
+    let start = Instant::now();
+    while datastore.read().await.has_plugin(id) {
+        if start.elapsed() > STOP_TIMEOUT {
+            error!("Script of id {} did not stop in time", id);
+            return;
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Starts the scripts and restarts them when they change, till shutdown
+pub(crate) async fn run_script_watcher(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let mut scripts = HashMap::<PathBuf, Script>::default();
+
+    loop {
+        let folder = {
+            let ds_r = datastore.read().await;
+            if ds_r.get_shutdown_status() {
+                break;
+            }
+            ds_r.get_config().get_scripts_folder()
+        };
+
+        let found = list_scripts(&folder);
+
+        // Changed scripts are stopped here and started again below
+        let outdated: Vec<PathBuf> = scripts.iter()
+            .filter(|(path, script)| found.get(*path) != Some(&script.modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in outdated {
+            if let Some(Script { id: Some(id), .. }) = scripts.remove(&path) {
+                debug!("Script {} changed or was removed, stopping it", path.to_str().unwrap_or_default());
+                stop_script(datastore, id).await;
+            }
+        }
+
+        for (path, modified) in found {
+            if !scripts.contains_key(&path) {
+                let id = start_script(datastore, &path).await;
+                scripts.insert(path, Script { modified, id });
+            }
+        }
+
+        time::sleep(SCRIPT_POLL_RATE).await;
+    }
+
+    debug!("Script watcher stopped");
+}
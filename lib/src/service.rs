@@ -0,0 +1,149 @@
+use std::sync::Mutex;
+
+use log::error;
+
+use crate::datastore::DataStore;
+
+/// Called when the service manager requests us to stop (Windows only, on Linux systemd sends SIGTERM)
+static STOP_HANDLER: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+
+/// Sets the function triggered when the service manager requests a stop
+pub(crate) fn set_stop_handler(handler: Box<dyn Fn() + Send>) {
+    if let Ok(mut lock) = STOP_HANDLER.lock() {
+        *lock = Some(handler);
+    }
+}
+
+/// Sends a state notification to systemd (like READY=1), if we were launched as notify service.
+/// Does nothing on other platforms or when not running under systemd
+#[cfg(target_os = "linux")]
+pub(crate) fn notify(state: &str) {
+    use std::os::{linux::net::SocketAddrExt, unix::net::{SocketAddr, UnixDatagram}};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy().to_string();
+
+    let addr = if let Some(name) = path.strip_prefix('@') {
+        SocketAddr::from_abstract_name(name.as_bytes())
+    } else {
+        SocketAddr::from_pathname(path.as_str())
+    };
+
+    let res = addr.and_then(|addr| {
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &addr)
+    });
+
+    if let Err(e) = res {
+        error!("Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn notify(_state: &str) {}
+
+/// Sends watchdog pings to systemd at half the interval systemd requested, till shutdown
+/// Exits immediatly if no watchdog is configured
+#[cfg(target_os = "linux")]
+pub(crate) async fn run_watchdog(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let Some(usec) = std::env::var("WATCHDOG_USEC").ok().and_then(|v| v.parse::<u64>().ok()) else {
+        return;
+    };
+
+    // The watchdog is meant for our process, not for a child
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|v| v.parse::<u32>().ok()) {
+        if pid != std::process::id() {
+            return;
+        }
+    }
+
+    let interval = tokio::time::Duration::from_micros(usec / 2);
+    log::debug!("Systemd watchdog enabled, pinging every {}ms", interval.as_millis());
+
+    while !datastore.read().await.get_shutdown_status() {
+        notify("WATCHDOG=1");
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) async fn run_watchdog(_datastore: &'static tokio::sync::RwLock<DataStore>) {}
+
+#[cfg(windows)]
+const SERVICE_NAME: &str = "DataRace";
+
+/// Config handed from the launch into the service main
+#[cfg(windows)]
+static SERVICE_CONFIG: Mutex<Option<crate::config::Config>> = Mutex::new(None);
+
+#[cfg(windows)]
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+/// Runs DataRace as Windows service, this blocks till the service stops
+#[cfg(windows)]
+pub(crate) fn run_service(config: crate::config::Config) {
+    if let Ok(mut lock) = SERVICE_CONFIG.lock() {
+        *lock = Some(config);
+    }
+
+    if let Err(e) = windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+        error!("Unable to start as service (this has to be launched by the service manager): {}", e);
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn run_service(_config: crate::config::Config) {
+    error!("--service is only supported on Windows, on Linux launch normally through a systemd unit with Type=notify");
+}
+
+#[cfg(windows)]
+fn service_main(_arguments: Vec<std::ffi::OsString>) {
+    use windows_service::{service::{ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType}, service_control_handler::{self, ServiceControlHandlerResult}};
+
+    let Some(config) = SERVICE_CONFIG.lock().ok().and_then(|mut lock| lock.take()) else {
+        error!("Service was started without config");
+        return;
+    };
+
+    let handler = move |control| -> ServiceControlHandlerResult {
+        match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                log::info!("Stop requested by service manager");
+                if let Ok(lock) = STOP_HANDLER.lock() {
+                    if let Some(stop) = lock.as_ref() {
+                        stop();
+                    }
+                }
+                ServiceControlHandlerResult::NoError
+            },
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented
+        }
+    };
+
+    let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+        Ok(handle) => handle,
+        Err(e) => {
+            error!("Unable to register service control handler: {}", e);
+            return;
+        }
+    };
+
+    let status = |state: ServiceState, controls: ServiceControlAccept| ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: controls,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: std::time::Duration::default(),
+        process_id: None
+    };
+
+    let _ = status_handle.set_service_status(status(ServiceState::Running, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN));
+
+    crate::launch(config, None);
+
+    let _ = status_handle.set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty()));
+}
@@ -0,0 +1,91 @@
+//! Session markers: plugins mark lap, sector and stint boundaries through mark_session,
+//! the core counts them as core properties (datarace.session.lap etc.) and triggers a core event
+//! for every marker, so dashboards, aggregates and analysis can split data without heuristics.
+
+use std::sync::{atomic::{AtomicI64, Ordering}, Arc};
+
+use log::{debug, error};
+
+use crate::{datastore::DataStore, events::EventMessage, system, utils::{self, ValueContainer}, EventHandle, SessionMarker};
+
+/// Shares the atomics with the containers in the datastore
+#[derive(Debug)]
+pub(crate) struct SessionCounters {
+    lap: Arc<AtomicI64>,
+    sector: Arc<AtomicI64>,
+    stint: Arc<AtomicI64>
+}
+
+impl Default for SessionCounters {
+    fn default() -> Self {
+        // Counting starts with the first lap/sector/stint, not 0
+        SessionCounters {
+            lap: Arc::new(AtomicI64::new(1)),
+            sector: Arc::new(AtomicI64::new(1)),
+            stint: Arc::new(AtomicI64::new(1))
+        }
+    }
+}
+
+fn core_event_handle(name: &str) -> Option<EventHandle> {
+    Some(EventHandle { plugin: system::CORE_PLUGIN_ID, event: utils::generate_event_name_hash(name)? })
+}
+
+/// Name of the core event triggered for the marker
+fn event_name(marker: SessionMarker) -> &'static str {
+    match marker {
+        SessionMarker::Lap => "session.new_lap",
+        SessionMarker::Sector => "session.new_sector",
+        SessionMarker::Stint => "session.new_stint",
+        SessionMarker::Session => "session.new_session"
+    }
+}
+
+impl SessionCounters {
+    /// Advances the counters, returns the event that has to be triggered for this marker
+    pub(crate) fn mark(&self, marker: SessionMarker) -> Option<EventHandle> {
+        match marker {
+            SessionMarker::Lap => {
+                self.lap.fetch_add(1, Ordering::AcqRel);
+                self.sector.store(1, Ordering::Release);
+            },
+            SessionMarker::Sector => {
+                self.sector.fetch_add(1, Ordering::AcqRel);
+            },
+            SessionMarker::Stint => {
+                self.stint.fetch_add(1, Ordering::AcqRel);
+            },
+            SessionMarker::Session => {
+                self.lap.store(1, Ordering::Release);
+                self.sector.store(1, Ordering::Release);
+                self.stint.store(1, Ordering::Release);
+            }
+        }
+
+        core_event_handle(event_name(marker))
+    }
+}
+
+/// Creates the counter properties and the marker events, has to be called before the plugins are loaded,
+/// so they can subscribe to them during their init
+pub(crate) async fn create_session_properties(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let mut ds_w = datastore.write().await;
+
+    let (lap, sector, stint) = {
+        let counters = ds_w.get_session_counters();
+        (counters.lap.clone(), counters.sector.clone(), counters.stint.clone())
+    };
+    system::insert_property(&mut ds_w, "session.lap", ValueContainer::Int(lap));
+    system::insert_property(&mut ds_w, "session.sector", ValueContainer::Int(sector));
+    system::insert_property(&mut ds_w, "session.stint", ValueContainer::Int(stint));
+
+    let channel = ds_w.get_event_channel();
+    for marker in [SessionMarker::Lap, SessionMarker::Sector, SessionMarker::Stint, SessionMarker::Session] {
+        if let Some(ev) = core_event_handle(event_name(marker)) {
+            if channel.as_async().send(EventMessage::Create(ev)).await.is_err() {
+                error!("Unable to create core event {}.{}", system::CORE_PLUGIN_NAME, event_name(marker));
+            }
+        }
+    }
+    debug!("Session counters created");
+}
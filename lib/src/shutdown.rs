@@ -0,0 +1,83 @@
+//! Shutdown in phases, each bounded by a timeout (shutdown in the config), so a stop request
+//! ends DataRace in bounded time even when plugins misbehave:
+//! 1. stop: the datastore is marked as shutting down, the webserver and the other tasks stop and no more plugins are registered
+//! 2. deliver: Shutdown is send to every plugin and the event loop
+//! 3. join: waiting for the plugins (and the other tasks) to finish
+//!
+//! Plugins still running after the join phase are dropped (their tasks aborted) and listed in the log.
+//! Their PluginHandle is never deallocated, as the plugin could still be using it.
+
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Notify, task::JoinHandle, time::{self, Duration, Instant}};
+
+use crate::datastore::DataStore;
+
+/// Notified when the stop phase failed, so we tear down without the datastore being marked
+static FORCE_TEARDOWN: Notify = Notify::const_new();
+
+/// shutdown in the config, timeouts of the phases in ms
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct ShutdownConfig {
+    stop_timeout: u64,
+    deliver_timeout: u64,
+    join_timeout: u64
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { stop_timeout: 2000, deliver_timeout: 2000, join_timeout: 10000 }
+    }
+}
+
+/// Runs the stop and deliver phase
+pub(crate) async fn request_shutdown(datastore: &'static tokio::sync::RwLock<DataStore>, config: &ShutdownConfig) {
+    let Ok(mut ds_w) = time::timeout(Duration::from_millis(config.stop_timeout), datastore.write()).await else {
+        error!("Unable to lock the datastore within {}ms to begin the shutdown, forcing the teardown", config.stop_timeout);
+        FORCE_TEARDOWN.notify_one();
+        return;
+    };
+    ds_w.begin_shutdown();
+
+    // Still holding the lock, so no plugin can register in between and miss the Shutdown
+    if time::timeout(Duration::from_millis(config.deliver_timeout), ds_w.deliver_shutdown()).await.is_err() {
+        error!("Delivering Shutdown to the plugins took longer than {}ms, continuing with the remaining plugins", config.deliver_timeout);
+    }
+}
+
+/// Completes when the teardown has to be forced (the stop phase failed)
+pub(crate) async fn forced_teardown() {
+    FORCE_TEARDOWN.notified().await;
+}
+
+/// Runs the join phase: waits for the plugins and then the other tasks, till the join_timeout.
+/// Everything still running afterwards is aborted
+pub(crate) async fn join(datastore: &'static tokio::sync::RwLock<DataStore>, config: &ShutdownConfig, mut plugins: JoinHandle<()>, tasks: Vec<JoinHandle<()>>) {
+    let deadline = Instant::now() + Duration::from_millis(config.join_timeout);
+
+    if time::timeout_at(deadline, &mut plugins).await.is_err() {
+        // Dropping the JoinSet inside aborts the tasks of the plugins
+        plugins.abort();
+        report_remaining(datastore, config);
+    }
+
+    for mut task in tasks {
+        if time::timeout_at(deadline, &mut task).await.is_err() {
+            task.abort();
+        }
+    }
+
+    debug!("Shutdown complete");
+}
+
+fn report_remaining(datastore: &'static tokio::sync::RwLock<DataStore>, config: &ShutdownConfig) {
+    let Ok(ds_r) = datastore.try_read() else {
+        error!("Plugins did not shut down within {}ms, dropping them (the datastore is locked, so they can not be listed)", config.join_timeout);
+        return;
+    };
+
+    for (id, name, _, _) in ds_r.list_plugins() {
+        error!("Plugin {} (id {}) did not shut down within {}ms, dropping it", name, id, config.join_timeout);
+    }
+}
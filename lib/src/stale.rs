@@ -0,0 +1,162 @@
+//! Stale properties: a plugin can give its property a max age, and if it is not updated within it
+//! (for example because the game stopped sending data) the property is marked stale until the next
+//! update. Dashboards grey out the elements using it instead of freezing on the last value, plugins
+//! can check the flag with is_property_stale.
+//!
+//! Updates through update_property mark the property as updated directly, arrays are written through
+//! their handle, so for them the monitor checks if the array was written since the last check.
+
+use std::{sync::{atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering}, RwLock}, time::Duration};
+
+use hashbrown::HashMap;
+use log::debug;
+
+use crate::{clock, datastore::DataStore, PropertyHandle};
+
+/// How often the monitor checks the max ages, so a property turns stale up to this much late
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Entry {
+    /// In micro seconds, same as the clock
+    max_age: i64,
+    last_update: AtomicI64,
+    stale: AtomicBool,
+    /// Writes to the array seen on the last check, unused for other types
+    array_changes: AtomicUsize
+}
+
+type Registry = HashMap<PropertyHandle, Entry>;
+
+static STALE: RwLock<Option<Registry>> = RwLock::new(None);
+/// Number of properties with a max age, so updates can skip the lock when there are none
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the max age of the property in ms, 0 removes it (and the property is never stale).
+/// The age starts counting now, setting it again keeps the current state
+pub(crate) fn set_max_age(prop_handle: PropertyHandle, max_age_ms: u64) {
+    if max_age_ms == 0 {
+        remove(&prop_handle);
+        return;
+    }
+
+    if let Ok(mut map) = STALE.write() {
+        let max_age = (max_age_ms as i64).saturating_mul(1000);
+        let map = map.get_or_insert_with(HashMap::default);
+
+        if let Some(entry) = map.get_mut(&prop_handle) {
+            entry.max_age = max_age;
+        } else {
+            map.insert(prop_handle, Entry {
+                max_age,
+                last_update: AtomicI64::new(clock::now()),
+                stale: AtomicBool::new(false),
+                array_changes: AtomicUsize::new(0)
+            });
+            COUNT.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+/// Marks the property as updated, clearing the stale flag
+pub(crate) fn touch(prop_handle: &PropertyHandle) {
+    if COUNT.load(Ordering::Acquire) == 0 {
+        return;
+    }
+
+    if let Ok(map) = STALE.read() {
+        if let Some(entry) = map.as_ref().and_then(|map| map.get(prop_handle)) {
+            entry.last_update.store(clock::now(), Ordering::Release);
+            entry.stale.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Properties without a max age are never stale
+pub(crate) fn is_stale(prop_handle: &PropertyHandle) -> bool {
+    if COUNT.load(Ordering::Acquire) == 0 {
+        return false;
+    }
+
+    STALE.read().ok()
+        .and_then(|map| map.as_ref().and_then(|map| map.get(prop_handle)).map(|entry| entry.stale.load(Ordering::Acquire)))
+        .unwrap_or_default()
+}
+
+/// All properties that are stale right now
+pub(crate) fn list() -> Vec<PropertyHandle> {
+    if COUNT.load(Ordering::Acquire) == 0 {
+        return Vec::new();
+    }
+
+    STALE.read().ok()
+        .and_then(|map| map.as_ref().map(|map| map.iter()
+            .filter(|(_, entry)| entry.stale.load(Ordering::Acquire))
+            .map(|(handle, _)| *handle)
+            .collect()))
+        .unwrap_or_default()
+}
+
+pub(crate) fn remove(prop_handle: &PropertyHandle) {
+    if let Ok(mut map) = STALE.write() {
+        if map.as_mut().and_then(|map| map.remove(prop_handle)).is_some() {
+            COUNT.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
+
+/// Removes the max ages of all properties of this plugin
+pub(crate) fn remove_plugin(plugin: u64) {
+    if let Ok(mut map) = STALE.write() {
+        if let Some(map) = map.as_mut() {
+            let before = map.len();
+            map.retain(|handle, _| handle.plugin != plugin);
+            COUNT.fetch_sub(before - map.len(), Ordering::Release);
+        }
+    }
+}
+
+/// Marks every property not updated within its max age as stale
+fn check(datastore: &DataStore) {
+    let Ok(map) = STALE.read() else {
+        return;
+    };
+    let Some(map) = map.as_ref() else {
+        return;
+    };
+
+    let now = clock::now();
+    for (handle, entry) in map.iter() {
+        // Arrays count as updated if any value was written since the last check
+        if let Some(changes) = datastore.get_property_container(handle).and_then(|cont| cont.array_changes()) {
+            if entry.array_changes.swap(changes, Ordering::AcqRel) != changes {
+                entry.last_update.store(now, Ordering::Release);
+                entry.stale.store(false, Ordering::Release);
+                continue;
+            }
+        }
+
+        if now - entry.last_update.load(Ordering::Acquire) > entry.max_age && !entry.stale.swap(true, Ordering::AcqRel) {
+            debug!("Property {} turned stale", datastore.property_label(handle));
+        }
+    }
+}
+
+/// Checks the max ages till shutdown
+pub(crate) async fn run_stale_monitor(datastore: &'static tokio::sync::RwLock<DataStore>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        let ds_r = datastore.read().await;
+        if ds_r.get_shutdown_status() {
+            break;
+        }
+
+        if COUNT.load(Ordering::Acquire) != 0 {
+            check(&ds_r);
+        }
+    }
+
+    debug!("Stale monitor stopped");
+}
@@ -0,0 +1,175 @@
+//! Properties published by DataRace itself (plugin name datarace, id 0), so dashboards and
+//! plugins can monitor the host without needing an external plugin.
+//!
+//! There is no loader behind these properties, the values are written directly into the
+//! containers in the datastore, subscriptions are served straight from there.
+
+use std::sync::{atomic::{AtomicI64, AtomicU64, Ordering}, Arc};
+use std::time::Duration;
+
+use hashbrown::HashMap;
+use log::{debug, error};
+use sysinfo::{Pid, ProcessRefreshKind, System};
+
+use crate::{clock, datastore::DataStore, utils::{self, ValueContainer}, PropertyHandle};
+
+/// Name under which the core properties are published (datarace.uptime etc.),
+/// plugins can not use this name
+pub(crate) const CORE_PLUGIN_NAME: &str = "datarace";
+pub(crate) const CORE_PLUGIN_ID: u64 = 0;
+/// Id the dashboards subscribe to events with, reserved like the core plugin id
+pub(crate) const WEB_LISTENER_ID: u64 = 1;
+/// Id the event statistics subscribe with, as the aggregates use the core plugin id (a listener has one subscription per event)
+pub(crate) const EVENT_STATS_LISTENER_ID: u64 = 2;
+
+/// Ids no plugin can register with
+pub(crate) fn is_reserved_id(id: u64) -> bool {
+    id == CORE_PLUGIN_ID || id == WEB_LISTENER_ID || id == EVENT_STATS_LISTENER_ID
+}
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shares the atomics with the containers in the datastore
+pub(crate) struct SystemProperties {
+    uptime: Arc<AtomicI64>,
+    plugins: Arc<AtomicI64>,
+    web_clients: Arc<AtomicI64>,
+    cpu: Arc<AtomicU64>,
+    memory: Arc<AtomicI64>,
+    /// Plugin id -> properties of that plugin
+    queues: HashMap<u64, PluginProperties>
+}
+
+struct PluginProperties {
+    name: String,
+    /// Length of the message queue
+    queue: Arc<AtomicI64>,
+    /// Longest call of the update function in the last interval
+    update_time: Arc<AtomicI64>
+}
+
+fn core_property_handle(name: &str) -> Option<PropertyHandle> {
+    Some(PropertyHandle { plugin: CORE_PLUGIN_ID, property: utils::generate_property_name_hash(name)? })
+}
+
+pub(crate) fn insert_property(ds: &mut DataStore, name: &str, cont: ValueContainer) {
+    if let Some(handle) = core_property_handle(name) {
+        ds.set_property(handle, cont);
+        ds.register_property_name(handle, format!("{}.{}", CORE_PLUGIN_NAME, name));
+    } else {
+        error!("Unable to create core property {}, name does not comply with naming schema", name);
+    }
+}
+
+/// Creates the core properties, has to be called before the plugins are loaded,
+/// so they can subscribe to them during their init
+pub(crate) async fn create_system_properties(datastore: &'static tokio::sync::RwLock<DataStore>) -> SystemProperties {
+    let props = SystemProperties {
+        uptime: Arc::default(),
+        plugins: Arc::default(),
+        web_clients: Arc::default(),
+        cpu: Arc::default(),
+        memory: Arc::default(),
+        queues: HashMap::default()
+    };
+
+    {
+        let mut ds_w = datastore.write().await;
+        insert_property(&mut ds_w, "uptime", ValueContainer::Dur(props.uptime.clone()));
+        insert_property(&mut ds_w, "plugin_count", ValueContainer::Int(props.plugins.clone()));
+        insert_property(&mut ds_w, "web_clients", ValueContainer::Int(props.web_clients.clone()));
+        insert_property(&mut ds_w, "cpu_usage", ValueContainer::Float(props.cpu.clone()));
+        insert_property(&mut ds_w, "memory", ValueContainer::Int(props.memory.clone()));
+    }
+    debug!("Core properties created");
+
+    props
+}
+
+/// Keeps the core properties updated till shutdown
+pub(crate) async fn run_system_properties(datastore: &'static tokio::sync::RwLock<DataStore>, mut props: SystemProperties) {
+    let pid = match sysinfo::get_current_pid() {
+        Ok(pid) => Some(pid),
+        Err(e) => {
+            error!("Unable to get the process id, cpu_usage and memory will not be updated: {}", e);
+            None
+        }
+    };
+    let mut system = System::new();
+
+    loop {
+        let queues = {
+            let ds_r = datastore.read().await;
+            if ds_r.get_shutdown_status() {
+                break;
+            }
+
+            props.plugins.store(ds_r.count_plugins() as i64, Ordering::Release);
+            ds_r.list_queue_lengths()
+        };
+
+        // Same time base as datarace.clock
+        props.uptime.store(clock::now(), Ordering::Release);
+        props.web_clients.store(crate::web::count_clients() as i64, Ordering::Release);
+
+        if let Some(pid) = pid {
+            update_process_usage(&mut system, pid, &props);
+        }
+
+        update_queues(datastore, &mut props, queues).await;
+
+        tokio::time::sleep(UPDATE_INTERVAL).await;
+    }
+
+    debug!("Core properties stopped");
+}
+
+fn update_process_usage(system: &mut System, pid: Pid, props: &SystemProperties) {
+    system.refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu().with_memory());
+
+    if let Some(process) = system.process(pid) {
+        // In percent of a single core, so this can go above 100 with multiple cores in use
+        let cpu = process.cpu_usage() as f64;
+        props.cpu.store(u64::from_be_bytes(cpu.to_be_bytes()), Ordering::Release);
+        props.memory.store(process.memory() as i64, Ordering::Release);
+    }
+}
+
+/// Updates the datarace.queue.{plugin} and datarace.update_time.{plugin} properties, creating them
+/// for new plugins and removing them for plugins that were removed
+async fn update_queues(datastore: &'static tokio::sync::RwLock<DataStore>, props: &mut SystemProperties, queues: Vec<(u64, String, usize)>) {
+    let changed = queues.len() != props.queues.len() || queues.iter().any(|(id, _, _)| !props.queues.contains_key(id));
+
+    if changed {
+        let mut ds_w = datastore.write().await;
+
+        props.queues.retain(|id, plugin| {
+            let keep = queues.iter().any(|(other, _, _)| other == id);
+            if !keep {
+                for prefix in ["queue", "update_time"] {
+                    if let Some(handle) = core_property_handle(format!("{}.{}", prefix, plugin.name).as_str()) {
+                        ds_w.delete_property(&handle);
+                    }
+                }
+            }
+            keep
+        });
+
+        for (id, name, _) in queues.iter() {
+            if !props.queues.contains_key(id) {
+                let plugin = PluginProperties { name: name.clone(), queue: Arc::default(), update_time: Arc::default() };
+                insert_property(&mut ds_w, format!("queue.{}", name).as_str(), ValueContainer::Int(plugin.queue.clone()));
+                insert_property(&mut ds_w, format!("update_time.{}", name).as_str(), ValueContainer::Dur(plugin.update_time.clone()));
+                props.queues.insert(*id, plugin);
+            }
+        }
+    }
+
+    for (id, _, len) in queues {
+        if let Some(plugin) = props.queues.get(&id) {
+            plugin.queue.store(len as i64, Ordering::Release);
+            let peak = crate::profile::take_peak(id).unwrap_or_default();
+            plugin.update_time.store(peak.min(i64::MAX as u64) as i64, Ordering::Release);
+        }
+    }
+}
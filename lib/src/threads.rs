@@ -0,0 +1,172 @@
+//! Dedicated OS threads for plugins (plugin_threads in the config).
+//!
+//! Normally all plugins share the worker threads of the tokio runtime with the webserver,
+//! a plugin listed in plugin_threads instead processes its messages on its own thread
+//! (with its own single threaded runtime), optionally with a higher priority and pinned to cores.
+//! Meant for plugins processing physics data at a high rate, where scheduling delays show.
+
+use std::future::Future;
+
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ThreadPriority {
+    #[default]
+    Normal,
+    High,
+    /// Realtime scheduling, on Linux this requires CAP_SYS_NICE (or root)
+    Realtime
+}
+
+/// An entry of plugin_threads in the config
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct ThreadConfig {
+    priority: ThreadPriority,
+    /// Cores the thread may run on, empty allows all
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cores: Vec<usize>
+}
+
+impl ThreadConfig {
+    pub(crate) fn validate(&self, plugin: &str) -> Result<(), String> {
+        let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        if let Some(core) = self.cores.iter().find(|core| **core >= available) {
+            return Err(format!("plugin_threads of {}: core {} does not exist (this system has {})", plugin, core, available));
+        }
+
+        Ok(())
+    }
+
+    /// Applies priority and affinity to the current thread. Failing is not fatal,
+    /// the plugin still runs on its own thread
+    fn apply(&self, plugin: &str) {
+        if self.priority != ThreadPriority::Normal {
+            if let Err(e) = set_priority(self.priority) {
+                warn!("Unable to set the priority of the thread of plugin {}: {}", plugin, e);
+            }
+        }
+        if !self.cores.is_empty() {
+            if let Err(e) = set_affinity(self.cores.as_slice()) {
+                warn!("Unable to pin the thread of plugin {} to cores {:?}: {}", plugin, self.cores, e);
+            }
+        }
+    }
+}
+
+/// Runs the future (the message loop of the plugin) to completion on a new thread.
+/// The calling task blocks till then, so the runtime moves its other tasks to another worker
+pub(crate) fn run_dedicated<F: Future<Output = Result<(), String>> + Send>(plugin: &str, config: &ThreadConfig, fut: F) -> Result<(), String> {
+    tokio::task::block_in_place(|| std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new().name(format!("plugin-{}", plugin)).spawn_scoped(scope, || {
+            config.apply(plugin);
+            debug!("Plugin {} running on a dedicated thread", plugin);
+
+            match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(rt) => rt.block_on(fut),
+                Err(e) => {
+                    error!("Unable to create the runtime for the thread of plugin {}: {}", plugin, e);
+                    Err(plugin.to_string())
+                }
+            }
+        });
+
+        match thread {
+            Ok(handle) => handle.join().unwrap_or_else(|_| {
+                error!("The thread of plugin {} panicked", plugin);
+                Err(plugin.to_string())
+            }),
+            Err(e) => {
+                error!("Unable to start a thread for plugin {}: {}", plugin, e);
+                Err(plugin.to_string())
+            }
+        }
+    }))
+}
+
+#[cfg(unix)]
+fn set_priority(priority: ThreadPriority) -> Result<(), std::io::Error> {
+    let res = unsafe {
+        match priority {
+            ThreadPriority::Normal => 0,
+            // On Linux the nice value applies to the thread, not the process
+            ThreadPriority::High => libc::setpriority(libc::PRIO_PROCESS, 0, -10),
+            ThreadPriority::Realtime => {
+                let param = libc::sched_param { sched_priority: 50 };
+                libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+            }
+        }
+    };
+
+    match (priority, res) {
+        (_, 0) => Ok(()),
+        // pthread_setschedparam returns the error instead of setting errno
+        (ThreadPriority::Realtime, code) => Err(std::io::Error::from_raw_os_error(code)),
+        _ => Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cores: &[usize]) -> Result<(), std::io::Error> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in cores {
+            libc::CPU_SET(*core, &mut set);
+        }
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_affinity(_cores: &[usize]) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "pinning threads to cores is not supported on this system"))
+}
+
+#[cfg(windows)]
+mod win {
+    pub(super) const THREAD_PRIORITY_HIGHEST: i32 = 2;
+    pub(super) const THREAD_PRIORITY_TIME_CRITICAL: i32 = 15;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub(super) fn GetCurrentThread() -> *mut libc::c_void;
+        pub(super) fn SetThreadPriority(thread: *mut libc::c_void, priority: i32) -> i32;
+        pub(super) fn SetThreadAffinityMask(thread: *mut libc::c_void, mask: usize) -> usize;
+    }
+}
+
+#[cfg(windows)]
+fn set_priority(priority: ThreadPriority) -> Result<(), std::io::Error> {
+    let level = match priority {
+        ThreadPriority::Normal => return Ok(()),
+        ThreadPriority::High => win::THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::Realtime => win::THREAD_PRIORITY_TIME_CRITICAL
+    };
+
+    if unsafe { win::SetThreadPriority(win::GetCurrentThread(), level) } != 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+fn set_affinity(cores: &[usize]) -> Result<(), std::io::Error> {
+    if let Some(core) = cores.iter().find(|core| **core >= usize::BITS as usize) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("core {} is outside the first processor group", core)));
+    }
+
+    let mask = cores.iter().fold(0usize, |mask, core| mask | (1 << core));
+    if unsafe { win::SetThreadAffinityMask(win::GetCurrentThread(), mask) } != 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
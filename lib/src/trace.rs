@@ -0,0 +1,33 @@
+//! Export of the tracing spans, so latency problems can be profiled in a running install:
+//! - `plugin_message` for every message a plugin processes
+//! - `plugin_update` for every call into the update function of a plugin
+//! - `event_trigger` for passing a triggered event to its listeners
+//! - `socket_update_cycle` for every update cycle of the dashboards (without the sleep)
+//!
+//! Requires the trace feature, and is enabled by setting DATARACE_TRACE to the output file.
+//! The file is in the chrome trace format, viewable in chrome://tracing or https://ui.perfetto.dev
+
+use log::{error, info};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Name of the environment variable with the output file
+const TRACE_ENV: &str = "DATARACE_TRACE";
+
+/// Starts writing the trace if DATARACE_TRACE is set, the file is completed when the guard is dropped
+pub(crate) fn init() -> Option<tracing_chrome::FlushGuard> {
+    let path = std::env::var(TRACE_ENV).ok().filter(|path| !path.is_empty())?;
+
+    let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+        .file(path.as_str())
+        .include_args(true)
+        .build();
+
+    // Not using init of tracing_subscriber, as that would try to replace env_logger as the logger
+    if let Err(e) = tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)) {
+        error!("Unable to start writing the trace to {}: {}", path, e);
+        return None;
+    }
+
+    info!("Writing trace to {}", path);
+    Some(guard)
+}
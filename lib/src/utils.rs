@@ -4,7 +4,7 @@ use std::{ffi::{CStr, CString}, fmt::Debug, sync::{atomic::{AtomicBool, AtomicI6
 use kanal::{Sender, Receiver};
 use highway::{HighwayHash, HighwayHasher, Key};
 
-use crate::{pluginloader::LoaderMessage, DataStoreReturnCode, PluginHandle, Property, PropertyType, PropertyValue};
+use crate::{memory::{arc_size, MemoryUsage}, pluginloader::LoaderMessage, DataStoreReturnCode, PluginHandle, Property, PropertyType, PropertyValue, Vector3};
 
 /// Simple way to aquire a String for a null terminating c_char ptr
 /// We do not optain ownership of the String, the owner has to deallocate it
@@ -12,6 +12,10 @@ use crate::{pluginloader::LoaderMessage, DataStoreReturnCode, PluginHandle, Prop
 /// But does to_string clone the bytes? If you need to store this data longer then the API call
 /// then clone it
 pub fn get_string(ptr: *mut c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
     Some(unsafe {
         let c_str = CStr::from_ptr(ptr);
 
@@ -52,6 +56,19 @@ pub(crate) struct PropertyContainer {
 }
 
 impl PropertyContainer {
+    /// A string property only accepting the allowed values, None if the initial value is not one of them
+    pub(crate) fn new_enum(short_name: String, allowed: Vec<String>, value: String) -> Option<Self> {
+        if !allowed.contains(&value) {
+            return None;
+        }
+
+        Some(Self {
+            short_name,
+            allow_modify: true,
+            value: ValueContainer::Enum(Arc::new((RwLock::new(value), AtomicUsize::new(1))), allowed.into())
+        })
+    }
+
     pub(crate) fn new(short_name: String, value: Property, plugin_handle: &PluginHandle) -> Self {
         Self {
             short_name,
@@ -72,6 +89,7 @@ impl PropertyContainer {
                 PropertyType::Array => {
                     unsafe {
                         if !val.value.arr.is_null() {
+                            crate::leaks::release(val.value.arr);
                             val.value.arr.drop_in_place()
                         }
                     }
@@ -89,6 +107,20 @@ impl PropertyContainer {
         self.value.read(self.allow_modify)
     }
 
+    pub(crate) fn allowed_values(&self) -> Option<&[String]> {
+        self.value.allowed_values()
+    }
+
+    /// See ValueContainer::write_text
+    pub(crate) fn write_text(&self, text: &str) -> DataStoreReturnCode {
+        self.value.write_text(text)
+    }
+
+    /// Reads the value the way a subscriber would (arrays can not be modified)
+    pub(crate) fn read_foreign(&self) -> Property {
+        self.value.read(false)
+    }
+
     pub(crate) fn swap_container(&mut self, container: ValueContainer, allow_modify: bool) {
         self.value = container;
         self.allow_modify = allow_modify;
@@ -98,6 +130,10 @@ impl PropertyContainer {
     pub(crate) fn clone_container(&self) -> ValueContainer {
         self.value.shallow_clone()
     }
+
+    pub(crate) fn get_type(&self) -> PropertyType {
+        self.value.get_type()
+    }
 }
 
 #[derive(Debug)]
@@ -107,13 +143,23 @@ pub(crate) enum ValueContainer {
     Float(Arc<AtomicU64>),
     Bool(Arc<AtomicBool>),
     Str(Arc<(RwLock<String>,AtomicUsize)>),
+    /// A Str that only accepts the allowed values
+    Enum(Arc<(RwLock<String>,AtomicUsize)>, Arc<[String]>),
     Dur(Arc<AtomicI64>),
+    Time(Arc<AtomicI64>),
+    Vector(Arc<(RwLock<Vector3>,AtomicUsize)>),
     Arr(Arc<ArrayValueContainer>)
 }
 
 const SAVE_ORDERING: Ordering = Ordering::Release;
 const READ_ORDERING: Ordering = Ordering::Acquire;
 
+/// Default share of an array up to which only the changed indices are send to the web
+/// (see websocket_array_delta in the config)
+pub(crate) const ARRAY_DELTA: f64 = 0.5;
+/// Rough size of an array entry in an update besides the text of strings (index and value)
+const ARRAY_ENTRY_SIZE: usize = 12;
+
 impl ValueContainer {
     pub(crate) fn new(val: Property, plugin_handle: &PluginHandle) -> Self {
         let new = match val.sort {
@@ -123,6 +169,8 @@ impl ValueContainer {
             PropertyType::Boolean => ValueContainer::Bool(Arc::default()),
             PropertyType::Str => ValueContainer::Str(Arc::default()),
             PropertyType::Duration => ValueContainer::Dur(Arc::default()),
+            PropertyType::Timestamp => ValueContainer::Time(Arc::default()),
+            PropertyType::Vector => ValueContainer::Vector(Arc::default()),
 
             PropertyType::Array => {
                 let ptr = unsafe { 
@@ -130,6 +178,7 @@ impl ValueContainer {
                 };
 
                 if !ptr.is_null() {
+                    crate::leaks::release(ptr);
                     let handle = unsafe {
                         ptr.read()
                     };
@@ -196,12 +245,39 @@ impl ValueContainer {
 
                 write_string(ptr, &arc.0, &arc.1, plugin_handle)
             },
+            (PropertyType::Str, ValueContainer::Enum(arc, allowed)) => {
+                let ptr = unsafe {
+                    val.value.str
+                };
+
+                let allowed = get_string(ptr).is_some_and(|text| allowed.contains(&text));
+                if allowed {
+                    write_string(ptr, &arc.0, &arc.1, plugin_handle)
+                } else {
+                    unsafe {
+                        plugin_handle.free_string_ptr(ptr);
+                    }
+                    false
+                }
+            },
             (PropertyType::Duration, ValueContainer::Dur(at)) => {
                 let d = unsafe { val.value.dur };
                 at.store(d, SAVE_ORDERING);
 
                 true
             },
+            (PropertyType::Timestamp, ValueContainer::Time(at)) => {
+                let t = unsafe { val.value.timestamp };
+                at.store(t, SAVE_ORDERING);
+
+                true
+            },
+            (PropertyType::Vector, ValueContainer::Vector(arc)) => {
+                let v = unsafe { val.value.vector };
+                write_vector(&arc.0, &arc.1, v);
+
+                true
+            },
             (PropertyType::Str, _) => {
                 // Deallocating the string, even if this is a missmatch
                 unsafe {
@@ -215,6 +291,7 @@ impl ValueContainer {
                 // but still not allowed
                 unsafe {
                     if !val.value.arr.is_null() {
+                        crate::leaks::release(val.value.arr);
                         val.value.arr.drop_in_place();
                     }
                 }
@@ -258,6 +335,29 @@ impl ValueContainer {
     //     }
     // }
 
+    /// The type of the contained value, without reading (and allocating) it
+    pub(crate) fn get_type(&self) -> PropertyType {
+        match self {
+            ValueContainer::None => PropertyType::None,
+            ValueContainer::Int(_) => PropertyType::Int,
+            ValueContainer::Float(_) => PropertyType::Float,
+            ValueContainer::Bool(_) => PropertyType::Boolean,
+            ValueContainer::Str(_) | ValueContainer::Enum(_, _) => PropertyType::Str,
+            ValueContainer::Dur(_) => PropertyType::Duration,
+            ValueContainer::Time(_) => PropertyType::Timestamp,
+            ValueContainer::Vector(_) => PropertyType::Vector,
+            ValueContainer::Arr(_) => PropertyType::Array
+        }
+    }
+
+    /// The values an enum property accepts, None for any other property
+    pub(crate) fn allowed_values(&self) -> Option<&[String]> {
+        match self {
+            ValueContainer::Enum(_, allowed) => Some(allowed),
+            _ => None
+        }
+    }
+
     pub(crate) fn read(&self, allow_modify: bool) -> Property {
         match self {
             ValueContainer::None => Property::default(),
@@ -276,7 +376,7 @@ impl ValueContainer {
                 sort: PropertyType::Boolean,
                 value: PropertyValue { boolean: at.load(READ_ORDERING) }
             },
-            ValueContainer::Str(arc) => {
+            ValueContainer::Str(arc) | ValueContainer::Enum(arc, _) => {
                 let store = &arc.0;
                 let res = match store.read() {
                     Ok(res) => {
@@ -307,9 +407,21 @@ impl ValueContainer {
                     value: PropertyValue { dur: at.load(READ_ORDERING) }
                 }
             },
+            ValueContainer::Time(at) => {
+                Property {
+                    sort: PropertyType::Timestamp,
+                    value: PropertyValue { timestamp: at.load(READ_ORDERING) }
+                }
+            },
+            ValueContainer::Vector(arc) => {
+                Property {
+                    sort: PropertyType::Vector,
+                    value: PropertyValue { vector: read_vector(&arc.0) }
+                }
+            },
             ValueContainer::Arr(arr) => {
                 let arr = arr.clone();
-                let arr_handle = crate::ArrayValueHandle { arr, allow_modify };
+                let arr_handle = crate::ArrayValueHandle::new(arr, allow_modify);
                 
                 Property {
                     sort: PropertyType::Array,
@@ -319,13 +431,71 @@ impl ValueContainer {
         }
     }
 
+    /// Writes a value parsed from text (as received from the control socket) into the container.
+    /// The text is parsed according to the current type, Durations are in seconds,
+    /// Timestamps in seconds since the unix epoch, Vectors as `x,y,z` (or `x,y`).
+    /// This bypasses the plugin owning the property, so use only for external control
+    pub(crate) fn write_text(&self, text: &str) -> DataStoreReturnCode {
+        match self {
+            ValueContainer::Int(at) => match text.parse::<i64>() {
+                Ok(i) => at.store(i, SAVE_ORDERING),
+                Err(_) => return DataStoreReturnCode::ParameterCorrupted
+            },
+            ValueContainer::Float(at) => match text.parse::<f64>() {
+                Ok(f) => at.store(u64::from_be_bytes(f.to_be_bytes()), SAVE_ORDERING),
+                Err(_) => return DataStoreReturnCode::ParameterCorrupted
+            },
+            ValueContainer::Bool(at) => match text.parse::<bool>() {
+                Ok(b) => at.store(b, SAVE_ORDERING),
+                Err(_) => return DataStoreReturnCode::ParameterCorrupted
+            },
+            ValueContainer::Dur(at) | ValueContainer::Time(at) => match text.parse::<f64>() {
+                Ok(d) => at.store((d * US_PER_SEC) as i64, SAVE_ORDERING),
+                Err(_) => return DataStoreReturnCode::ParameterCorrupted
+            },
+            ValueContainer::Enum(_, allowed) if !allowed.iter().any(|val| val == text) => {
+                return DataStoreReturnCode::ParameterCorrupted
+            },
+            ValueContainer::Str(arc) | ValueContainer::Enum(arc, _) => {
+                let mut res = match arc.0.write() {
+                    Ok(res) => res,
+                    Err(e) => {
+                        arc.0.clear_poison();
+                        e.into_inner()
+                    }
+                };
+                *res = text.to_string();
+                arc.1.fetch_add(1, Ordering::AcqRel);
+            },
+            ValueContainer::Vector(arc) => {
+                let parts: Vec<Result<f64, _>> = text.split(',').map(|t| t.trim().parse::<f64>()).collect();
+                let v = match parts.as_slice() {
+                    [Ok(x), Ok(y)] => Vector3 { x: *x, y: *y, z: 0.0 },
+                    [Ok(x), Ok(y), Ok(z)] => Vector3 { x: *x, y: *y, z: *z },
+                    _ => return DataStoreReturnCode::ParameterCorrupted
+                };
+                write_vector(&arc.0, &arc.1, v);
+            },
+            ValueContainer::None => return DataStoreReturnCode::TypeMissmatch,
+            ValueContainer::Arr(_) => return DataStoreReturnCode::NotImplemented
+        }
+
+        DataStoreReturnCode::Ok
+    }
+
     pub(crate) fn read_web(&self, cache: &mut ValueCache) -> bool {
+        self.read_web_delta(cache, ARRAY_DELTA)
+    }
+
+    /// Same as read_web, for arrays the changed indices are only set in the cache as long as at
+    /// most max_delta (0 to 1) of the array changed
+    pub(crate) fn read_web_delta(&self, cache: &mut ValueCache, max_delta: f64) -> bool {
         let val = match self {
             ValueContainer::None => Value::None,
             ValueContainer::Int(at) => Value::Int(at.load(READ_ORDERING)),
             ValueContainer::Float(at) => Value::Float(f64::from_be_bytes(at.load(READ_ORDERING).to_be_bytes())),
             ValueContainer::Bool(at) => Value::Bool(at.load(READ_ORDERING)),
-            ValueContainer::Str(arc) => {
+            ValueContainer::Str(arc) | ValueContainer::Enum(arc, _) => {
                 let (store, index) = (&arc.0, arc.1.load(Ordering::Acquire));
                 
                 if let Some(arr) = cache.version.as_mut() {
@@ -360,8 +530,19 @@ impl ValueContainer {
                 return true;
             },
             ValueContainer::Dur(at) => Value::Dur(at.load(READ_ORDERING)),
+            ValueContainer::Time(at) => Value::Time(at.load(READ_ORDERING)),
+            ValueContainer::Vector(arc) => {
+                let last_change = (Arc::as_ptr(arc) as usize, arc.1.load(Ordering::Acquire));
+                if cache.last_change == Some(last_change) {
+                    return false;
+                }
+                cache.last_change = Some(last_change);
+
+                let v = read_vector(&arc.0);
+                Value::Vector(v.x, v.y, v.z)
+            },
             ValueContainer::Arr(arr) => {
-                return arr.read_web(cache);
+                return arr.read_web(cache, max_delta);
             }
         };
 
@@ -373,18 +554,101 @@ impl ValueContainer {
         }
     }
 
+    /// Number of writes to the array so far, None for other types (they are updated as a whole)
+    pub(crate) fn array_changes(&self) -> Option<usize> {
+        match self {
+            ValueContainer::Arr(arr) => Some(arr.changes.load(READ_ORDERING)),
+            _ => None
+        }
+    }
+
     /// This generates a shallow clone, which still receives all the same value changes
-    fn shallow_clone(&self) -> ValueContainer {
+    pub(crate) fn shallow_clone(&self) -> ValueContainer {
         match self {
             ValueContainer::None => ValueContainer::None,
             ValueContainer::Int(a) => ValueContainer::Int(a.clone()),
             ValueContainer::Float(a) => ValueContainer::Float(a.clone()),
             ValueContainer::Bool(a) => ValueContainer::Bool(a.clone()),
             ValueContainer::Str(a) => ValueContainer::Str(a.clone()),
+            ValueContainer::Enum(a, allowed) => ValueContainer::Enum(a.clone(), allowed.clone()),
             ValueContainer::Dur(a) => ValueContainer::Dur(a.clone()),
+            ValueContainer::Time(a) => ValueContainer::Time(a.clone()),
+            ValueContainer::Vector(a) => ValueContainer::Vector(a.clone()),
             ValueContainer::Arr(arr) => ValueContainer::Arr(arr.clone())
         }
     }
+
+    /// If both share the value (shallow clones of each other), not just hold an equal value
+    pub(crate) fn same_container(&self, other: &ValueContainer) -> bool {
+        match (self, other) {
+            (ValueContainer::None, ValueContainer::None) => true,
+            (ValueContainer::Int(a), ValueContainer::Int(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Float(a), ValueContainer::Float(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Bool(a), ValueContainer::Bool(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Str(a), ValueContainer::Str(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Enum(a, _), ValueContainer::Enum(b, _)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Dur(a), ValueContainer::Dur(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Time(a), ValueContainer::Time(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Vector(a), ValueContainer::Vector(b)) => Arc::ptr_eq(a, b),
+            (ValueContainer::Arr(a), ValueContainer::Arr(b)) => Arc::ptr_eq(a, b),
+            _ => false
+        }
+    }
+
+    /// Adds this container (and the value behind it) to the usage.
+    /// Shallow clones share the value, so only call this on one of them
+    pub(crate) fn add_memory_usage(&self, usage: &mut MemoryUsage) {
+        let inner = match self {
+            ValueContainer::None => 0,
+            ValueContainer::Int(_) | ValueContainer::Dur(_) | ValueContainer::Time(_) => arc_size::<AtomicI64>(),
+            ValueContainer::Float(_) => arc_size::<AtomicU64>(),
+            ValueContainer::Bool(_) => arc_size::<AtomicBool>(),
+            ValueContainer::Str(arc) => {
+                usage.add_string(string_capacity(&arc.0));
+                arc_size::<(RwLock<String>,AtomicUsize)>()
+            },
+            ValueContainer::Enum(arc, allowed) => {
+                usage.add_string(string_capacity(&arc.0));
+                for value in allowed.iter() {
+                    usage.add_string(value.capacity());
+                }
+                arc_size::<(RwLock<String>,AtomicUsize)>() + arc_size::<()>() + std::mem::size_of_val(allowed.as_ref())
+            },
+            ValueContainer::Vector(_) => arc_size::<(RwLock<Vector3>,AtomicUsize)>(),
+            ValueContainer::Arr(arr) => {
+                arr.add_memory_usage(usage);
+                arc_size::<ArrayValueContainer>()
+            }
+        };
+
+        usage.add_property(std::mem::size_of::<ValueContainer>() + inner);
+    }
+}
+
+fn string_capacity(lock: &RwLock<String>) -> usize {
+    match lock.read() {
+        Ok(text) => text.capacity(),
+        Err(e) => e.into_inner().capacity()
+    }
+}
+
+/// Poison can be ignored, as Vector is Copy it is always written in one piece
+fn read_vector(lock: &RwLock<Vector3>) -> Vector3 {
+    match lock.read() {
+        Ok(res) => *res,
+        Err(e) => *e.into_inner()
+    }
+}
+
+fn write_vector(lock: &RwLock<Vector3>, version: &AtomicUsize, value: Vector3) {
+    match lock.write() {
+        Ok(mut res) => *res = value,
+        Err(e) => {
+            lock.clear_poison();
+            *e.into_inner() = value;
+        }
+    }
+    version.fetch_add(1, Ordering::AcqRel);
 }
 
 fn write_string(ptr: *mut c_char, store: &RwLock<String>, version: &AtomicUsize, plugin_handle: &PluginHandle) -> bool {
@@ -418,12 +682,20 @@ fn write_string(ptr: *mut c_char, store: &RwLock<String>, version: &AtomicUsize,
 }
 
 #[derive(Debug)]
-pub(crate) enum ArrayValueContainer {
+pub(crate) struct ArrayValueContainer {
+    values: ArrayValues,
+    /// Increased on every write, so pollers can skip unchanged arrays without comparing every value
+    changes: AtomicUsize
+}
+
+#[derive(Debug)]
+enum ArrayValues {
     Int(Box<[AtomicI64]>),
     Float(Box<[AtomicU64]>),
     Bool(Box<[AtomicBool]>),
     Str(Box<[(RwLock<String>, AtomicUsize)]>),
     Dur(Box<[AtomicI64]>),
+    Time(Box<[AtomicI64]>),
 
     // Arr(Arc<[ArrayValueContainer]>)
     // Multilayer arrays present multiple issues:
@@ -437,6 +709,24 @@ pub(crate) enum ArrayValueContainer {
     // future use
 }
 
+/// Deallocates what the value owns (strings and array handles), for values that could not be written
+pub(crate) fn release_value(value: Property, plugin_handle: &PluginHandle) {
+    match value.sort {
+        PropertyType::Str => unsafe {
+            let ptr = value.value.str;
+            plugin_handle.free_string_ptr(ptr);
+        },
+        // Deallocating arrayhandle, as we were given ownership of it
+        PropertyType::Array => unsafe {
+            if !value.value.arr.is_null() {
+                crate::leaks::release(value.value.arr);
+                value.value.arr.drop_in_place();
+            }
+        },
+        _ => ()
+    }
+}
+
 macro_rules! array_read {
     ($arc:ident, $index:ident) => {
         if let Some(item) = $arc.get($index) {
@@ -495,27 +785,27 @@ macro_rules! web_read_value {
 
 impl ArrayValueContainer {
     pub(crate) fn new(size: usize, init: Property, plugin_handle: &PluginHandle) -> Option<Self> {
-        Some(match init.sort {
+        let values = match init.sort {
             PropertyType::Int => {
                 let val = unsafe {
                     init.value.integer
                 };
                 
-                ArrayValueContainer::Int(array_create!(val, size, AtomicI64))
+                ArrayValues::Int(array_create!(val, size, AtomicI64))
             },
             PropertyType::Float => {
                 let val = u64::from_be_bytes(unsafe {
                     init.value.decimal
                 }.to_be_bytes());
 
-                ArrayValueContainer::Float(array_create!(val, size, AtomicU64))
+                ArrayValues::Float(array_create!(val, size, AtomicU64))
             },
             PropertyType::Boolean => {
                 let val = unsafe {
                     init.value.boolean
                 };
 
-                ArrayValueContainer::Bool(array_create!(val, size, AtomicBool))
+                ArrayValues::Bool(array_create!(val, size, AtomicBool))
             },
             PropertyType::Str => {
                 let ptr = unsafe {
@@ -541,31 +831,57 @@ impl ArrayValueContainer {
                     return None;
                 } 
 
-                ArrayValueContainer::Str(v.into_boxed_slice())
+                ArrayValues::Str(v.into_boxed_slice())
             },
             PropertyType::Duration => {
                 let val = unsafe {
                     init.value.dur
                 };
 
-                ArrayValueContainer::Dur(array_create!(val, size, AtomicI64))
+                ArrayValues::Dur(array_create!(val, size, AtomicI64))
+            },
+            PropertyType::Timestamp => {
+                let val = unsafe {
+                    init.value.timestamp
+                };
+
+                ArrayValues::Time(array_create!(val, size, AtomicI64))
             },
             _ => None?
-        })
+        };
+
+        Some(ArrayValueContainer { values, changes: AtomicUsize::new(0) })
+    }
+
+    /// Creates a string array out of owned strings, used for lists handed to plugins
+    pub(crate) fn from_strings(values: Vec<String>) -> Self {
+        ArrayValueContainer {
+            values: ArrayValues::Str(values.into_iter().map(|t| (RwLock::new(t), AtomicUsize::new(1))).collect()),
+            changes: AtomicUsize::new(0)
+        }
+    }
+
+    /// Copies the values out of a string array, None for any other type
+    pub(crate) fn to_strings(&self) -> Option<Vec<String>> {
+        if let ArrayValues::Str(arc) = &self.values {
+            Some(arc.iter().map(|(store, _)| store.read().map(|t| t.clone()).unwrap_or_default()).collect())
+        } else {
+            None
+        }
     }
 
     pub(crate) fn read(&self, index: usize) -> Property {
-        match self {
-            Self::Int(arc) => {
+        match &self.values {
+            ArrayValues::Int(arc) => {
                 Property { sort: PropertyType::Int, value: PropertyValue { integer: array_read!(arc, index) } }
             },
-            Self::Float(arc) => {
+            ArrayValues::Float(arc) => {
                 Property { sort: PropertyType::Float, value: PropertyValue { decimal: f64::from_be_bytes(array_read!(arc, index).to_be_bytes())  } }
             },
-            Self::Bool(arc) => {
+            ArrayValues::Bool(arc) => {
                 Property { sort: PropertyType::Boolean, value: PropertyValue { boolean: array_read!(arc, index) } }
             },
-            Self::Str(arc) => {
+            ArrayValues::Str(arc) => {
                 if let Some(item) = arc.get(index) {
                     let store = &item.0;
                     let res = match store.read() {
@@ -593,9 +909,12 @@ impl ArrayValueContainer {
                     Property::default()
                 }
             },
-            Self::Dur(arc) => {
+            ArrayValues::Dur(arc) => {
                 Property { sort: PropertyType::Duration, value: PropertyValue { dur: array_read!(arc, index) } }
             },
+            ArrayValues::Time(arc) => {
+                Property { sort: PropertyType::Timestamp, value: PropertyValue { timestamp: array_read!(arc, index) } }
+            },
             // Self::Arr(arc) => {
             //     if let Some(item) = arc.get(index) {
             //         let arr = item.clone();
@@ -613,20 +932,27 @@ impl ArrayValueContainer {
     }
 
 
-    pub(crate) fn read_web(&self, cache: &mut ValueCache) -> bool {
+    pub(crate) fn read_web(&self, cache: &mut ValueCache, max_delta: f64) -> bool {
+        let last_change = (self as *const Self as usize, self.changes.load(Ordering::Acquire));
+        if cache.last_change == Some(last_change) {
+            return false;
+        }
+
         let cache_arr = if let Value::Arr(arr) = &mut cache.value {
             arr
         } else {
             // This handles a type change, and this recursion can only happen once
             cache.value = Value::Arr(Vec::<Value>::with_capacity(self.length()));
-            return self.read_web(cache);
+            return self.read_web(cache, max_delta);
         };
 
+        // The first read (or the array growing) always sends the whole array, as the client has nothing to apply the changes to
+        let grown = cache_arr.len() < self.length();
         let mut changes = Vec::<(usize, Value)>::with_capacity(self.length());
 
-        match self {
-            Self::Int(arr) => { web_read_value!(arr, changes, cache_arr, Int); },
-            Self::Float(arr) => {
+        match &self.values {
+            ArrayValues::Int(arr) => { web_read_value!(arr, changes, cache_arr, Int); },
+            ArrayValues::Float(arr) => {
                 let mut index = 0;
                 while let Some(at) = arr.get(index) {
                     let value = at.load(READ_ORDERING);
@@ -646,9 +972,10 @@ impl ArrayValueContainer {
                     index += 1;
                 }
             },
-            Self::Dur(arr) => { web_read_value!(arr, changes, cache_arr, Dur); },
-            Self::Bool(arr) => { web_read_value!(arr, changes, cache_arr, Bool); },
-            Self::Str(arr) => {
+            ArrayValues::Dur(arr) => { web_read_value!(arr, changes, cache_arr, Dur); },
+            ArrayValues::Time(arr) => { web_read_value!(arr, changes, cache_arr, Time); },
+            ArrayValues::Bool(arr) => { web_read_value!(arr, changes, cache_arr, Bool); },
+            ArrayValues::Str(arr) => {
                 let mut index = 0;
 
                 let version_arr = if let Some(ver) = &mut cache.version {
@@ -657,7 +984,7 @@ impl ArrayValueContainer {
                         ver.resize_with(self.length(), Default::default);
                         cache.version = Some(ver);
 
-                        return self.read_web(cache);
+                        return self.read_web(cache, max_delta);
                     }
 
                     ver
@@ -666,7 +993,7 @@ impl ArrayValueContainer {
                     ver.resize_with(self.length(), Default::default);
                     cache.version = Some(ver);
 
-                    return self.read_web(cache);
+                    return self.read_web(cache, max_delta);
                 };
 
                 while let (Some((text,version)),Some(old_version)) = (arr.get(index), version_arr.get_mut(index)) {
@@ -701,6 +1028,7 @@ impl ArrayValueContainer {
                 }
             }
         }
+        cache.last_change = Some(last_change);
 
         if cache_arr.len() > self.length() {
             cache_arr.truncate(self.length());
@@ -713,10 +1041,16 @@ impl ArrayValueContainer {
             cache.change = None;
             false
         } else {
-            let threshhold = self.length() / 2; // If half the values in the arrary changed we
-            // update all
-
-            if threshhold < changes.len() {
+            // Strings vary in length, so they are weighted by their text (otherwise a long name
+            // changing would count the same as a short one)
+            let size = |value: &Value| match value {
+                Value::Str(text) => text.len() + ARRAY_ENTRY_SIZE,
+                _ => ARRAY_ENTRY_SIZE
+            };
+            let changed: usize = changes.iter().map(|(_, value)| size(value)).sum();
+            let total: usize = cache_arr.iter().map(size).sum();
+
+            if grown || changed as f64 > total as f64 * max_delta {
                 cache.change = None;
             } else {
                 cache.change = Some(changes);
@@ -726,20 +1060,20 @@ impl ArrayValueContainer {
     }
 
     pub(crate) fn write(&self, index: usize, value: Property, plugin_handle: &PluginHandle) -> DataStoreReturnCode {
-        match (self,value.sort) {
-            (Self::Int(arc),PropertyType::Int) => {
+        let res = match (&self.values, &value.sort) {
+            (ArrayValues::Int(arc),PropertyType::Int) => {
                 let val = unsafe { value.value.integer };
                 array_write!(arc, index, val)
             },
-            (Self::Float(arc),PropertyType::Float) => {
+            (ArrayValues::Float(arc),PropertyType::Float) => {
                 let val = u64::from_be_bytes(unsafe { value.value.decimal }.to_be_bytes());
                 array_write!(arc, index, val)
             },
-            (Self::Bool(arc),PropertyType::Boolean) => {
+            (ArrayValues::Bool(arc),PropertyType::Boolean) => {
                 let val = unsafe { value.value.boolean };
                 array_write!(arc, index, val)
             },
-            (Self::Str(arc),PropertyType::Str) => {
+            (ArrayValues::Str(arc),PropertyType::Str) => {
                 if let Some((store, version)) = arc.get(index) {
                     let ptr = unsafe {
                         value.value.str
@@ -754,53 +1088,92 @@ impl ArrayValueContainer {
                     DataStoreReturnCode::DoesNotExist
                 }
             },
-            (Self::Dur(arc),PropertyType::Duration) => {
+            (ArrayValues::Dur(arc),PropertyType::Duration) => {
                 let val = unsafe { value.value.dur };
                 array_write!(arc, index, val)
             },
-            (_, PropertyType::Str) => {
-                // Deallocating the string, even on a type missmatch
-                unsafe {
-                    let ptr = value.value.str;
-                    plugin_handle.free_string_ptr(ptr);
-                }
-
-                DataStoreReturnCode::TypeMissmatch
+            (ArrayValues::Time(arc),PropertyType::Timestamp) => {
+                let val = unsafe { value.value.timestamp };
+                array_write!(arc, index, val)
             },
-            (_, PropertyType::Array) => {
-                // Deallocating arrayhandle, as we were given ownership of it
-                unsafe {
-                    if !value.value.arr.is_null() {
-                        value.value.arr.drop_in_place();
-                    }
-                }
-
+            (_, _) => {
+                release_value(value, plugin_handle);
                 DataStoreReturnCode::TypeMissmatch
-            },
-            (_, _) => DataStoreReturnCode::TypeMissmatch
+            }
+        };
+
+        if res == DataStoreReturnCode::Ok {
+            self.changes.fetch_add(1, Ordering::AcqRel);
+        }
+        res
+    }
+
+    /// Writes the values to the indices start..start + values.len(), either all or (if the range does not fit
+    /// or a value has a different type) none of them. Takes ownership of the values, same as write
+    pub(crate) fn write_range(&self, start: usize, values: Vec<Property>, plugin_handle: &PluginHandle) -> DataStoreReturnCode {
+        let fits = start.checked_add(values.len()).is_some_and(|end| end <= self.length());
+        let sort = self.get_type();
+        let same_type = values.iter().all(|value| value.sort == sort);
+
+        if !fits || !same_type {
+            for value in values {
+                release_value(value, plugin_handle);
+            }
+
+            return if fits { DataStoreReturnCode::TypeMissmatch } else { DataStoreReturnCode::DoesNotExist };
         }
+
+        // Only strings can still fail (not valid utf8), the rest is written regardless
+        let mut res = DataStoreReturnCode::Ok;
+        for (index, value) in (start..).zip(values) {
+            let code = self.write(index, value, plugin_handle);
+            if res == DataStoreReturnCode::Ok {
+                res = code;
+            }
+        }
+
+        res
     }
 
     pub(crate) fn length(&self) -> usize {
-        match self {
-            Self::Int(arr) => arr.len(),
-            Self::Float(arr) => arr.len(),
-            Self::Bool(arr) => arr.len(),
-            Self::Str(arr) => arr.len(),
-            Self::Dur(arr) => arr.len(),
+        match &self.values {
+            ArrayValues::Int(arr) => arr.len(),
+            ArrayValues::Float(arr) => arr.len(),
+            ArrayValues::Bool(arr) => arr.len(),
+            ArrayValues::Str(arr) => arr.len(),
+            ArrayValues::Dur(arr) => arr.len(),
+            ArrayValues::Time(arr) => arr.len(),
 
         }
     }
 
     pub(crate) fn get_type(&self) -> PropertyType {
-        match self {
-            Self::Int(_) => PropertyType::Int,
-            Self::Float(_) => PropertyType::Float,
-            Self::Bool(_) => PropertyType::Boolean,
-            Self::Str(_) => PropertyType::Str,
-            Self::Dur(_) => PropertyType::Duration,
+        match &self.values {
+            ArrayValues::Int(_) => PropertyType::Int,
+            ArrayValues::Float(_) => PropertyType::Float,
+            ArrayValues::Bool(_) => PropertyType::Boolean,
+            ArrayValues::Str(_) => PropertyType::Str,
+            ArrayValues::Dur(_) => PropertyType::Duration,
+            ArrayValues::Time(_) => PropertyType::Timestamp,
         }
     }
+
+    /// Adds the array (and the strings in it) to the usage
+    pub(crate) fn add_memory_usage(&self, usage: &mut MemoryUsage) {
+        let bytes = match &self.values {
+            ArrayValues::Int(arr) | ArrayValues::Dur(arr) | ArrayValues::Time(arr) => std::mem::size_of_val(arr.as_ref()),
+            ArrayValues::Float(arr) => std::mem::size_of_val(arr.as_ref()),
+            ArrayValues::Bool(arr) => std::mem::size_of_val(arr.as_ref()),
+            ArrayValues::Str(arr) => {
+                for (lock, _) in arr.iter() {
+                    usage.add_string(string_capacity(lock));
+                }
+                std::mem::size_of_val(arr.as_ref())
+            }
+        };
+
+        usage.add_array(bytes);
+    }
 }
 
 /// Serves to define the datatype for nested Array types
@@ -819,6 +1192,9 @@ impl ArrayValueContainer {
 pub(crate) struct ValueCache {
     pub value: Value,
     version: Option<Vec<usize>>,
+    /// Address and change counter of the container at the last read (vectors and arrays), if
+    /// neither changed the value is not read again
+    last_change: Option<(usize, usize)>,
 
     // If this is set to None, then there might have been change, but it is considered easier to
     // send the whole array
@@ -827,7 +1203,7 @@ pub(crate) struct ValueCache {
 
 impl Default for ValueCache {
     fn default() -> Self {
-        ValueCache { value: Value::None, version: None, change: None }
+        ValueCache { value: Value::None, version: None, last_change: None, change: None }
     }
 }
 
@@ -849,11 +1225,85 @@ pub(crate) enum Value {
     // precision, although if your number reads over 100years I think you have different
     // priorities, and while internally i64 hard caps Duration to 500k years, js can handle more.
     Dur(i64),
+    /// Micro seconds since the unix epoch
+    Time(i64),
+    /// x, y, z
+    Vector(f64, f64, f64),
 
     Arr(Vec<Value>),
     ArrUpdate(Vec<(usize, Value)>)
 }
 
+/// Microseconds per second: 1s = 1000ms, 1ms = 1000us
+const US_PER_SEC: f64 = 1000.0 * 1000.0;
+
+/// First micro second of the year 10000, RFC3339 (and humantime, which panics) only covers 4 digit years
+const RFC3339_END_US: u64 = 253_402_300_800 * 1_000_000;
+
+/// Formats micro seconds since the unix epoch as RFC3339 (UTC).
+/// Timestamps before the epoch (or after the year 9999) are printed as the raw micro seconds
+fn format_timestamp(micros: i64) -> String {
+    if let Some(micros) = u64::try_from(micros).ok().filter(|micros| *micros < RFC3339_END_US) {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_micros(micros);
+        humantime::format_rfc3339_micros(time).to_string()
+    } else {
+        format!("{}us", micros)
+    }
+}
+
+impl Value {
+    /// The value as a number for calculations (aggregates, rules), durations are in seconds,
+    /// booleans 0 or 1, None for anything else
+    pub(crate) fn to_number(&self) -> Option<f64> {
+        match self {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::Dur(d) => Some(*d as f64 / US_PER_SEC),
+            _ => None
+        }
+    }
+
+    /// Formats the value human readable, prefixed with the type
+    pub(crate) fn to_display_string(&self) -> String {
+        match self {
+            Value::None => "None".to_string(),
+            Value::Int(i) => format!("Int: {}", i),
+            Value::Float(f) => format!("Float: {}", f),
+            Value::Dur(d) => format!("Duration: {}s", (*d as f64) / US_PER_SEC ),
+            Value::Time(t) => format!("Timestamp: {}", format_timestamp(*t)),
+            Value::Vector(x, y, z) => format!("Vector: ({}, {}, {})", x, y, z),
+            Value::Bool(b) => format!("Boolean: {}", b),
+            Value::Str(s) => format!("Str: {}", s),
+            Value::Arr(arr) => {
+                let mut arr_str = "Arr: [".to_string();
+                for item in arr {
+                    arr_str = format!{"{}{}, ", arr_str,
+                        match item {
+                            Value::Int(i) => i.to_string(),
+                            Value::Str(s) => s.clone(),
+                            Value::Bool(b) => b.to_string(),
+                            Value::Dur(d) => format!("{}s", (*d as f64) / US_PER_SEC),
+                            Value::Time(t) => format_timestamp(*t),
+                            Value::Float(f) => f.to_string(),
+                            _ => String::new()
+                        }
+                    }
+                }
+
+                if let Some(stripped) = arr_str.strip_suffix(", ") {
+                    arr_str = format!("{}]",stripped);
+                } else {
+                    arr_str = format!("{}]", arr_str);
+                }
+
+                arr_str
+            },
+            Value::ArrUpdate(_) => String::new()
+        }
+    }
+}
+
 const HASH_KEY_NAME:Key = Key([1,2,3,4]);
 
 /// Serves to generate hashes for the name of a plugin
@@ -862,6 +1312,9 @@ pub(crate) fn generate_plugin_name_hash(str: &str) -> Option<u64> {
         return None;
     }
     let str = str.to_lowercase();
+    if str == crate::system::CORE_PLUGIN_NAME {
+        return Some(crate::system::CORE_PLUGIN_ID);
+    }
 
     let mut hasher = HighwayHasher::new(HASH_KEY_NAME);
 
@@ -887,6 +1340,22 @@ pub(crate) fn generate_property_name_hash(str: &str) -> Option<u64> {
     Some(hasher.finalize64())
 }
 
+const HASH_KEY_GROUP:Key = Key([64,128,96,32]);
+
+/// Serves to generate hashes for the name of a subscription group
+pub(crate) fn generate_group_name_hash(str: &str) -> Option<u64> {
+    if str.is_empty() {
+        return None;
+    }
+    let str = str.to_lowercase();
+
+    let mut hasher = HighwayHasher::new(HASH_KEY_GROUP);
+
+    hasher.append(str.as_bytes());
+
+    Some(hasher.finalize64())
+}
+
 const HASH_KEY_EVENT:Key = Key([256,432,1024,512]);
 
 /// Serves to generate hashes for the name of a plugin
@@ -0,0 +1,176 @@
+//! Keeps track of the clients of the dashboard socket for the clients page: which dashboard they
+//! render, what properties that subscribes to, and how many updates they actually receive.
+//! Meant for debugging a dashboard that is not updating.
+
+use std::{net::IpAddr, sync::Mutex, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+use socketioxide::socket::Sid;
+use tokio::time::Instant;
+
+use crate::PropertyHandle;
+
+use super::{downsample::{self, Aggregation, Tier}, socket::Encoding};
+
+/// Over how long the update rate is measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+struct Client {
+    address: IpAddr,
+    /// Milliseconds since the unix epoch
+    connected: i64,
+    dashboard: Option<ClientDashboard>
+}
+
+struct ClientDashboard {
+    name: String,
+    encoding: Encoding,
+    tier: Tier,
+    aggregation: Aggregation,
+    /// Opened through a share link
+    shared: bool,
+    room: String,
+    /// Updates send to the room before the client joined
+    base: u64
+}
+
+/// Updates send to a room, every client in it receives them
+struct RoomStats {
+    updates: u64,
+    window_start: Instant,
+    window_updates: u64,
+    rate: f64,
+    last: Instant
+}
+
+impl RoomStats {
+    fn rate(&self) -> f64 {
+        // No updates for a while, the stored rate would be stale
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= RATE_WINDOW * 2 {
+            self.window_updates as f64 / elapsed.as_secs_f64()
+        } else {
+            self.rate
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    clients: HashMap<Sid, Client>,
+    rooms: HashMap<String, RoomStats>,
+    /// Dashboard -> the properties the socket sends for it
+    subscriptions: HashMap<String, Vec<PropertyHandle>>
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+fn with_registry<R>(func: impl FnOnce(&mut Registry) -> R) -> R {
+    let mut guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    func(guard.get_or_insert_with(Registry::default))
+}
+
+pub(super) fn connect(id: Sid, address: IpAddr) {
+    let connected = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+    with_registry(|reg| reg.clients.insert(id, Client { address, connected, dashboard: None }));
+}
+
+pub(super) fn disconnect(id: &Sid) {
+    with_registry(|reg| reg.clients.remove(id));
+}
+
+/// The client authenticated as this dashboard
+pub(super) fn set_dashboard(id: Sid, name: &str, encoding: Encoding, tier: Tier, aggregation: Aggregation, shared: bool) {
+    let room = downsample::room_name(name, encoding, tier, aggregation);
+
+    with_registry(|reg| {
+        let base = reg.rooms.get(&room).map(|stats| stats.updates).unwrap_or_default();
+        if let Some(client) = reg.clients.get_mut(&id) {
+            client.dashboard = Some(ClientDashboard { name: name.to_string(), encoding, tier, aggregation, shared, room, base });
+        }
+    });
+}
+
+/// Called by the socket updater, once the dashboard is subscribed (or when its last client left with None)
+pub(super) fn set_subscriptions(name: &str, properties: Option<Vec<PropertyHandle>>) {
+    with_registry(|reg| match properties {
+        Some(properties) => { reg.subscriptions.insert(name.to_string(), properties); },
+        None => { reg.subscriptions.remove(name); }
+    });
+}
+
+/// An update was send to the room
+pub(super) fn record_update(room: &str) {
+    let now = Instant::now();
+
+    with_registry(|reg| {
+        let stats = reg.rooms.entry_ref(room).or_insert_with(|| RoomStats { updates: 0, window_start: now, window_updates: 0, rate: 0.0, last: now });
+
+        let elapsed = now.duration_since(stats.window_start);
+        if elapsed >= RATE_WINDOW {
+            stats.rate = stats.window_updates as f64 / elapsed.as_secs_f64();
+            stats.window_start = now;
+            stats.window_updates = 0;
+        }
+
+        stats.updates += 1;
+        stats.window_updates += 1;
+        stats.last = now;
+    });
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ClientInfo {
+    pub(super) id: String,
+    pub(super) address: String,
+    /// Milliseconds since the unix epoch
+    pub(super) connected: i64,
+    /// Not set till the client authenticated as a dashboard (like the notifications page, which never does)
+    pub(super) dashboard: Option<DashboardInfo>
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct DashboardInfo {
+    pub(super) name: String,
+    pub(super) encoding: Encoding,
+    pub(super) tier: Tier,
+    pub(super) aggregation: Aggregation,
+    pub(super) shared: bool,
+    /// Names of the subscribed properties, properties that do not exist (yet) are included with their id
+    pub(super) properties: Vec<String>,
+    /// Updates received since connecting
+    pub(super) updates: u64,
+    /// Updates per second
+    pub(super) rate: f64,
+    /// Milliseconds since the last update, None if there was none
+    pub(super) last_update: Option<u64>
+}
+
+/// All connected clients, oldest first. The name resolves the property handles
+pub(super) fn list(name: impl Fn(&PropertyHandle) -> String) -> Vec<ClientInfo> {
+    let mut list: Vec<ClientInfo> = with_registry(|reg| reg.clients.iter().map(|(id, client)| {
+        let dashboard = client.dashboard.as_ref().map(|dash| {
+            let stats = reg.rooms.get(&dash.room);
+            let mut properties: Vec<String> = reg.subscriptions.get(&dash.name).into_iter().flatten().map(&name).collect();
+            properties.sort();
+
+            DashboardInfo {
+                name: dash.name.clone(),
+                encoding: dash.encoding,
+                tier: dash.tier,
+                aggregation: dash.aggregation,
+                shared: dash.shared,
+                properties,
+                updates: stats.map(|stats| stats.updates.saturating_sub(dash.base)).unwrap_or_default(),
+                rate: stats.map(RoomStats::rate).unwrap_or_default(),
+                last_update: stats.filter(|stats| stats.updates > dash.base).map(|stats| stats.last.elapsed().as_millis() as u64)
+            }
+        });
+
+        ClientInfo { id: id.to_string(), address: client.address.to_string(), connected: client.connected, dashboard }
+    }).collect());
+
+    list.sort_by_key(|client| client.connected);
+    list
+}
@@ -3,7 +3,7 @@ use log::error;
 use maud::{html, Markup, PreEscaped, Render, DOCTYPE};
 use serde::{Deserialize, Serialize};
 
-use crate::PropertyHandle;
+use crate::{EventHandle, PropertyHandle};
 
 fn header(name: &String) -> Markup {
     html! {
@@ -13,6 +13,63 @@ fn header(name: &String) -> Markup {
     }
 } 
 
+/// Script added to a dashboard rendered in kiosk mode (for a screen mounted in the rig):
+/// hides the cursor, goes fullscreen on the first tap, keeps the screen awake,
+/// reconnects after the server closed the connection, and rotates through the given dashboards
+pub(crate) fn kiosk_script(name: &str, rotate: &[String], interval: u64) -> Markup {
+    // Switching to the next dashboard in the list (or the first if the current one isn't in the list)
+    let next = if rotate.len() > 1 {
+        let index = rotate.iter().position(|d| d == name).map(|i| (i + 1) % rotate.len()).unwrap_or(0);
+        Some(rotate[index].as_str())
+    } else {
+        None
+    };
+
+    // Names end up in js strings, so we escape them through json (and < to not close the script tag)
+    let to_js = |val: &str| serde_json::to_string(val).unwrap_or_default().replace('<', "\\u003c");
+    let rotate_js = to_js(rotate.join(",").as_str());
+
+    html! {
+        script {
+            "document.body.style.cursor = 'none';"
+
+            "async function request_wake_lock() {"
+                (PreEscaped("if ('wakeLock' in navigator && document.visibilityState === 'visible') {"))
+                    "try {"
+                        "await navigator.wakeLock.request('screen');"
+                    "} catch (e) {"
+                        "console.log('Unable to aquire wake lock: ' + e);"
+                    "}"
+                "}"
+            "}"
+            // The wake lock is released when the page is hidden, so we have to aquire it again
+            "document.addEventListener('visibilitychange', request_wake_lock);"
+            "request_wake_lock();"
+
+            // Browsers only allow fullscreen after a user interaction
+            "document.addEventListener('click', function() {"
+                (PreEscaped("if (!document.fullscreenElement && document.documentElement.requestFullscreen) {"))
+                    "document.documentElement.requestFullscreen().catch(function(e) { console.log('Unable to go fullscreen: ' + e); });"
+                "}"
+                "request_wake_lock();"
+            "});"
+
+            // socket.io only reconnects by itself if the connection was lost, not when the server closed it
+            "socket.on('disconnect', function(reason) {"
+                "if (reason === 'io server disconnect') {"
+                    "setTimeout(function() { location.reload(); }, 10000);"
+                "}"
+            "});"
+
+            @if let Some(next) = next {
+                // Absolute, as dashboards can be in subfolders
+                (PreEscaped(format!("setTimeout(function() {{ location.href = '/dashboard/render/' + {}.split('/').map(encodeURIComponent).join('/') + '?mode=kiosk&rotate=' + encodeURIComponent({}) + '&interval={}'; }}, {});",
+                    to_js(next), rotate_js, interval, interval.saturating_mul(1000))))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Dashboard {
     pub(crate) name: String,
@@ -31,11 +88,46 @@ impl Dashboard {
 
         res
     }
+
+    /// Events the elements react to
+    pub(crate) fn list_events(&self) -> HashSet<EventHandle> {
+        let mut res = HashSet::<EventHandle>::new();
+
+        for e in &self.elements {
+            e.list_events(&mut res);
+        }
+
+        res
+    }
 }
 
 impl Render for Dashboard {
     fn render(&self) -> Markup {
-    
+        self.render_page(None)
+    }
+}
+
+impl Dashboard {
+    /// Renders the dashboard opened through a share link, the query (with the share) is send
+    /// with every request of the socket, so it is let through the whitelist
+    pub(crate) fn render_shared(&self, socket_query: serde_json::Value) -> Markup {
+        self.render_page(Some(socket_query))
+    }
+
+    /// Renders a simplified svg of the layout for the dashboards page: squares in their color,
+    /// outlines for the other elements (with the text of fixed texts), computed values are 0/empty
+    pub(crate) fn render_preview(&self) -> Markup {
+        html! {
+            svg xmlns="http://www.w3.org/2000/svg" viewBox=(format!("0 0 {} {}", self.size_x.max(1), self.size_y.max(1))) {
+                rect width="100%" height="100%" fill="#F2F2F2" {}
+                @for item in &self.elements {
+                    (item.render_preview(0, 0))
+                }
+            }
+        }
+    }
+
+    fn render_page(&self, socket_query: Option<serde_json::Value>) -> Markup {
         let mut names = vec![];
         for e in &self.elements {
             if !e.gather_names(&mut names) {
@@ -49,13 +141,14 @@ impl Render for Dashboard {
         html! {
             (header(&self.name))
             body {
-                div id="BODY" style=(format!("position: absolute; left: 0px; top: 0px; width: {}px; height: {}px;", self.size_x, self.size_y)) {
+                // z-index, so the layers of the elements stay below the disconnect overlay
+                div id="BODY" style=(format!("position: absolute; left: 0px; top: 0px; width: {}px; height: {}px; z-index: 0;", self.size_x, self.size_y)) {
                     @for item in &self.elements {
                         (item)
                     }
                 }
                 div id="DISCO" style="position: absolute; left: 0px; top: 0px; width: 100%; height: 100%; display: none; background-color: #F2F2F288;" {
-                    div style="text-align: center; margin-top: 48vh; font-size: 2rem;" {
+                    div id="DISCO_MSG" style="text-align: center; margin-top: 48vh; font-size: 2rem;" {
                         "Disconnected"
                     } 
                 }
@@ -67,26 +160,60 @@ impl Render for Dashboard {
 
             script {
                 "const DISCO = document.getElementById('DISCO');"
+                "const DISCO_MSG = document.getElementById('DISCO_MSG');"
+                (format!("const PROTOCOL = {};", super::socket::PROTOCOL_VERSION))
+                "let AUTH_FAILED = false;"
+                // Send back on auth after a reconnect, so the server resumes instead of subscribing anew
+                "let SESSION = null;"
                 "const BODY = document.getElementById('BODY');"
                 @for n in names {
                     (format!("const {0} = document.getElementById('{0}');", n))
                 }
 
                 "let DATA = new Map();"
+                "let STALE = new Set();"
                 "let SCALE = 0;"
                 "console.log('Hello Everynya!');"
 
-                "var socket = io();"
+                @if let Some(query) = &socket_query {
+                    // < is escaped so the query can not close the script tag
+                    (PreEscaped(format!("var socket = io({{ query: {} }});", query.to_string().replace('<', "\\u003c"))))
+                } @else {
+                    "var socket = io();"
+                }
                 "socket.on('test', function(msg) {"
                     "console.log(msg);"
                 "});"
 
-                "socket.on('require-auth', function() {"
+                "socket.on('require-auth', function(INFO) {"
                     "console.log('Server requested auth');"
-                    (format!("socket.emit('auth-dashboard', '{}');", &self.name))
+                    (PreEscaped("if (INFO == null || INFO.protocol !== PROTOCOL) {"))
+                        "AUTH_FAILED = true;"
+                        "DISCO_MSG.innerText = 'DataRace uses a different protocol version. Please reload the dashboard';"
+                        "DISCO.style.display = 'block';"
+                        "socket.disconnect();"
+                        "return;"
+                    "}"
+                    // Binary updates need BigInt to decode the 64bit ids, older browsers fall back to text (or json on older servers)
+                    (PreEscaped("const ENCODING = (typeof BigInt !== 'undefined' && INFO.features.includes('binary')) ? 'binary' : (INFO.features.includes('text') ? 'text' : 'json');"))
+                    // Slower rate tiers can be chosen with ?tier=fast|slow&aggregation=last|min|max (for example on cellular)
+                    "const TIER = query_choice('tier', ['realtime', 'fast', 'slow']);"
+                    "const AGGREGATION = query_choice('aggregation', ['last', 'min', 'max']);"
+                    (format!("socket.emit('auth-dashboard', {{ name: '{}', encoding: ENCODING, protocol: PROTOCOL, session: SESSION, tier: TIER, aggregation: AGGREGATION }});", &self.name))
                     "DISCO.style.display = 'none';"
                 "});"
 
+                "socket.on('session', function(TOKEN) {"
+                    "SESSION = TOKEN;"
+                "});"
+
+                "socket.on('auth-error', function(MSG) {"
+                    "console.log('Auth failed: ' + MSG);"
+                    "AUTH_FAILED = true;"
+                    "DISCO_MSG.innerText = MSG;"
+                    "DISCO.style.display = 'block';"
+                "});"
+
 
                 "function resize_event() {"        
                     "{"
@@ -129,14 +256,16 @@ impl Render for Dashboard {
                 "window.onresize = resize_event;"
                 "resize_event();"
 
-                "socket.on('update', function(UP_ARR) {"
+                "function apply_update(UP_ARR) {"
                     "const UPDATE = new Map(UP_ARR);"
                     // "console.log(UPDATE);"
 
                     (PreEscaped("UPDATE.forEach((value, key) => { if (value.ArrUpdate != null) {
                             let Arr = DATA.get(key);
-                            const ArrUp = new Map(value.ArrUpdate);
-                            ArrUp.forEach((value, key) => Arr.Arr[key] = value);
+                            if (Arr == null || Arr.Arr == null) {
+                                return;
+                            }
+                            value.ArrUpdate.forEach(([index, item]) => Arr.Arr[index] = item);
                         } else {
                             DATA.set(key, value);
                         }});"))
@@ -145,11 +274,34 @@ impl Render for Dashboard {
                     @for item in &self.elements {
                         (item.generate_update_js())
                     }
+                "}"
+
+                // Stale properties (see set_property_max_age), the server sends the full list whenever it changes
+                "function apply_stale(LIST) {"
+                    "STALE = new Set(LIST);"
+                    @for item in &self.elements {
+                        (item.generate_stale_js())
+                    }
+                "}"
+
+                "socket.on('event', function(EV) {"
+                    @for item in &self.elements {
+                        (item.generate_event_js())
+                    }
                 "});"
 
+                "socket.on('update', apply_update);"
+                "socket.on('update-bin', function(BUF) { apply_update(decode_update_bin(BUF)); });"
+                "socket.on('update-text', function(TEXT) { apply_update(JSON.parse(TEXT)); });"
+                "socket.on('notification', show_notification);"
+                "socket.on('stale', apply_stale);"
+
                 // Disconnect handler
                 "socket.on('disconnect', function() {"
                     "console.log('Lost connection');"
+                    "if (!AUTH_FAILED) {"
+                        "DISCO_MSG.innerText = 'Disconnected';"
+                    "}"
                     "DISCO.style.display = 'block';"
                 "});"
             }
@@ -167,6 +319,21 @@ pub(crate) struct DashElement {
     pub(crate) size_y: Property<i64>,
     pub(crate) visible: Property<bool>,
     pub(crate) element: DashElementType,
+    /// Layer of the element among its siblings (higher is in front), without it the element
+    /// is layered by its position in the list. Inside a folder it only orders the folder content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) z: Option<i64>,
+    /// From 0 (transparent) to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) opacity: Option<Property<f64>>,
+    /// Animates changes of visible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) transition: Option<Transition>,
+    /// Blinks per second, 0 does not blink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) blink: Option<Property<f64>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) on_event: Vec<EventTrigger>
 }
 
 impl Render for DashElement {
@@ -177,9 +344,15 @@ impl Render for DashElement {
             return html!();
         };
 
+        // Computed opacity is set by the update
+        let mut extra = self.z.map(|z| format!(" z-index:{};", z)).unwrap_or_default();
+        if let Some(Property::Fixed(opacity)) = &self.opacity {
+            extra.push_str(format!(" opacity:{};", opacity).as_str());
+        }
+
         html! {
-            div id=(name) style=(format!("position: absolute; left:{}px; top:{}px; width:{}px; height:{}px;",
-                self.x.get_static_value(), self.y.get_static_value(), self.size_x.get_static_value(), self.size_y.get_static_value())) {
+            div id=(name) style=(format!("position: absolute; left:{}px; top:{}px; width:{}px; height:{}px;{}",
+                self.x.get_static_value(), self.y.get_static_value(), self.size_x.get_static_value(), self.size_y.get_static_value(), extra)) {
                 @match &self.element {
                     DashElementType::Square(color) => {
                         div style=(format!("width:100%;height:100%;background:{}", color)) {}
@@ -190,7 +363,14 @@ impl Render for DashElement {
                         }
                     },
                     DashElementType::Text(text) => {
-                        div { (text.get_static_value()) }
+                        // pre-line, so the lines of a DerefRange are shown as such
+                        div style="white-space:pre-line;" { (text.get_static_value()) }
+                    },
+                    DashElementType::Trace(_) => {
+                        canvas style="width:100%;height:100%;" {}
+                    },
+                    DashElementType::Bar(_) => {
+                        div style="width:100%;height:100%;" {}
                     }
                 }
             }
@@ -199,6 +379,39 @@ impl Render for DashElement {
 }
 
 impl DashElement {
+    /// Part of Dashboard::render_preview, the offset is the position of the containing folder
+    fn render_preview(&self, offset_x: i64, offset_y: i64) -> Markup {
+        if let Property::Fixed(false) = self.visible {
+            return html!();
+        }
+
+        let (x, y) = (offset_x + self.x.get_static_value(), offset_y + self.y.get_static_value());
+        let (width, height) = (self.size_x.get_static_value().max(0), self.size_y.get_static_value().max(0));
+
+        html! {
+            @match &self.element {
+                DashElementType::Square(color) => {
+                    rect x=(x) y=(y) width=(width) height=(height) fill=(color) {}
+                },
+                DashElementType::Folder(elements) => {
+                    @for item in elements {
+                        (item.render_preview(x, y))
+                    }
+                },
+                DashElementType::Text(text) => {
+                    rect x=(x) y=(y) width=(width) height=(height) fill="none" stroke="#888888" {}
+                    text x=(x) y=(y + height / 2) dominant-baseline="middle" font-size=((height / 2).max(1)) { (text.get_static_value()) }
+                },
+                DashElementType::Trace(trace) => {
+                    rect x=(x) y=(y) width=(width) height=(height) fill="none" stroke=(trace.color) stroke-dasharray="8" {}
+                },
+                DashElementType::Bar(bar) => {
+                    rect x=(x) y=(y) width=(width) height=(height) fill=(bar.stops.first().map(|stop| stop.color.as_str()).unwrap_or("none")) stroke="#888888" {}
+                }
+            }
+        }
+    }
+
     /// Names are reformated to lower case, but are also checked to insure requirements:
     /// ascii alphanumeric with additionally _
     fn normalize_name(&self) -> Option<String> {
@@ -255,6 +468,16 @@ impl DashElement {
             },
             DashElementType::Text(text) => {
                 text.add_property_handle_to_collection(&mut res);
+            },
+            DashElementType::Trace(trace) => {
+                if let Some(handle) = PropertyHandle::new(trace.source.as_str()) {
+                    res.insert(handle);
+                }
+            },
+            DashElementType::Bar(bar) => {
+                if let Some(handle) = PropertyHandle::new(bar.source.as_str()) {
+                    res.insert(handle);
+                }
             }
         }
 
@@ -263,11 +486,55 @@ impl DashElement {
         self.size_x.add_property_handle_to_collection(&mut res);
         self.size_y.add_property_handle_to_collection(&mut res);
         self.visible.add_property_handle_to_collection(&mut res);
-
+        if let Some(opacity) = &self.opacity {
+            opacity.add_property_handle_to_collection(&mut res);
+        }
+        if let Some(blink) = &self.blink {
+            blink.add_property_handle_to_collection(&mut res);
+        }
 
         res
     }
 
+    /// Adds the events of the triggers of this element and all elements contained in it
+    fn list_events(&self, res: &mut HashSet<EventHandle>) {
+        res.extend(self.on_event.iter().filter_map(|trigger| EventHandle::new(trigger.event.as_str())));
+
+        if let DashElementType::Folder(elements) = &self.element {
+            for e in elements {
+                e.list_events(res);
+            }
+        }
+    }
+
+    /// Runs the triggers of this element (and the contained elements) matching the event EV
+    fn generate_event_js(&self) -> Markup {
+        let name = if let Some(n) = self.normalize_name() {
+            n
+        } else {
+            return html!();
+        };
+
+        html!{
+            @for (index, trigger) in self.on_event.iter().enumerate() {
+                @if let Some(ev) = EventHandle::new(trigger.event.as_str()) {
+                    @if trigger.debounce != 0 || trigger.every > 1 {
+                        (PreEscaped(format!("if (EV === '{}|{}' && event_filter({}, {}, {}, {})) {{ event_action({}, '{}', {}); }}",
+                            ev.plugin, ev.event, name, index, trigger.debounce, trigger.every, name, trigger.action.name(), trigger.duration)))
+                    } @else {
+                        (PreEscaped(format!("if (EV === '{}|{}') {{ event_action({}, '{}', {}); }}", ev.plugin, ev.event, name, trigger.action.name(), trigger.duration)))
+                    }
+                }
+            }
+
+            @if let DashElementType::Folder(elements) = &self.element {
+                @for e in elements {
+                    (e.generate_event_js())
+                }
+            }
+        }
+    }
+
     fn generate_update_js(&self) -> Markup {
         let name = if let Some(n) = self.normalize_name() {
             n
@@ -275,10 +542,19 @@ impl DashElement {
             return html!();
         };
 
+        // Show and Hide triggers override the visibility while they last
+        let overridable = self.on_event.iter().any(|trigger| trigger.action != EventAction::Flash);
+
         html!{
             "{"
+                @if overridable {
+                    (format!("if ({0}.event_display != null) {{ {0}.style.display = {0}.event_display; }} else ", name.as_str()))
+                }
                 // Handling visibility
-                @if self.visible.is_computed() {
+                @if let Some(transition) = &self.transition {
+                    (PreEscaped(format!("set_visible({}, {}, '{}', {});", name.as_str(), self.visible.generate_read_js(),
+                        transition.effect.name(), transition.duration)))
+                } @else if self.visible.is_computed() {
                     (PreEscaped(format!("if({})", self.visible.generate_read_js())))
                     "{"
                          (format!("{}.style.display = 'block';", name.as_str()))
@@ -293,7 +569,13 @@ impl DashElement {
                         } ))
                 }
 
-                
+                @if let Some(opacity) = self.opacity.as_ref().filter(|o| o.is_computed()) {
+                    (PreEscaped(format!("{}.style.opacity = {};", name.as_str(), opacity.generate_read_js())))
+                }
+                @if let Some(blink) = &self.blink {
+                    (PreEscaped(format!("set_blink({}, {});", name.as_str(), blink.generate_read_js())))
+                }
+
                 // Updating internal value
                 @match &self.element {
                     DashElementType::Square(color) => (format!("{}.firstElementChild.style.background = '{}';", name.as_str(), color)),
@@ -308,6 +590,12 @@ impl DashElement {
                         @if text.is_computed() {
                             (PreEscaped(format!("{}.firstElementChild.textContent = {};", name.as_str(), text.generate_read_js())))
                         }
+                    },
+                    DashElementType::Trace(trace) => {
+                        (PreEscaped(trace.generate_update_js(name.as_str())))
+                    },
+                    DashElementType::Bar(bar) => {
+                        (PreEscaped(bar.generate_update_js(name.as_str())))
                     }
                 } 
             "}"
@@ -347,15 +635,221 @@ impl DashElement {
             }
         }
     }
+
+    /// Greys out the element while any of its properties is stale (in STALE).
+    /// Folders are left as is, greying them would grey out every element in them
+    fn generate_stale_js(&self) -> Markup {
+        if let DashElementType::Folder(elements) = &self.element {
+            return html!{
+                @for e in elements {
+                    (e.generate_stale_js())
+                }
+            };
+        }
+
+        let name = if let Some(n) = self.normalize_name() {
+            n
+        } else {
+            return html!();
+        };
+        let list: Vec<PropertyHandle> = self.list_properties().into_iter().collect();
+        if list.is_empty() {
+            return html!();
+        }
+
+        html!{
+            (PreEscaped(format!("set_stale({}, {});", name, serde_json::to_string(&list).unwrap_or("[]".to_string()))))
+        }
+    }
+}
+
+/// Reacts to an event, like flashing the element when sample_plugin.lap_completed fires
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct EventTrigger {
+    /// Full name of the event
+    event: String,
+    action: EventAction,
+    /// How long the action lasts in ms
+    #[serde(default = "EventTrigger::default_duration")]
+    duration: u64,
+    /// Only the first trigger after this many ms without one runs the action, 0 runs all
+    #[serde(default)]
+    debounce: u64,
+    /// Only every nth trigger (after the debounce) runs the action, 0 and 1 run all
+    #[serde(default)]
+    every: u64
+}
+
+impl EventTrigger {
+    fn default_duration() -> u64 {
+        1000
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum EventAction {
+    /// Blinks the element
+    Flash,
+    /// Shows the element, even if visible is false
+    Show,
+    /// Hides the element, even if visible is true
+    Hide
+}
+
+impl EventAction {
+    /// Name of the action in event_action of datarace.dash.js
+    fn name(&self) -> &'static str {
+        match self {
+            EventAction::Flash => "flash",
+            EventAction::Show => "show",
+            EventAction::Hide => "hide"
+        }
+    }
+}
+
+/// Animation when the element is shown or hidden
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Transition {
+    effect: TransitionEffect,
+    /// In ms
+    #[serde(default = "Transition::default_duration")]
+    duration: u64
+}
+
+impl Transition {
+    fn default_duration() -> u64 {
+        250
+    }
+}
+
+/// Slides are named by the direction the element moves in when shown
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) enum TransitionEffect {
+    Fade,
+    SlideUp,
+    SlideDown,
+    SlideLeft,
+    SlideRight
+}
+
+impl TransitionEffect {
+    /// Name of the effect in set_visible of datarace.dash.js
+    fn name(&self) -> &'static str {
+        match self {
+            TransitionEffect::Fade => "fade",
+            TransitionEffect::SlideUp => "slide-up",
+            TransitionEffect::SlideDown => "slide-down",
+            TransitionEffect::SlideLeft => "slide-left",
+            TransitionEffect::SlideRight => "slide-right"
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) enum DashElementType {
     Square(String),
     Text(Property<String>),
-    Folder(Vec<DashElement>)
+    Folder(Vec<DashElement>),
+    Trace(Trace),
+    Bar(Bar)
+}
+
+/// Draws the position from a Vector property (like the car on a track map),
+/// with the previous positions drawn as a line
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Trace {
+    /// PropertyHandle of the Vector
+    source: String,
+    /// The area shown, as min and max of the plane: [min_x, min_y, max_x, max_y]
+    bounds: [f64; 4],
+    #[serde(default)]
+    plane: TracePlane,
+    color: String,
+    /// Number of previous positions in the line, 0 only draws the current position
+    #[serde(default)]
+    length: usize
 }
 
+/// Which components of the Vector are drawn (XZ is top down in most games, as Y is up)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) enum TracePlane {
+    #[default]
+    XY,
+    XZ
+}
+
+impl Trace {
+    fn generate_update_js(&self, name: &str) -> String {
+        let handle = if let Some(handle) = PropertyHandle::new(self.source.as_str()).and_then(|h| serde_json::to_string(&h).ok()) {
+            handle
+        } else {
+            return String::new();
+        };
+
+        let options = serde_json::json!({
+            "bounds": self.bounds,
+            "plane": self.plane,
+            "color": self.color,
+            "length": self.length
+        });
+
+        // < is escaped so the color can not close the script tag
+        format!("draw_trace({}.firstElementChild, read_vector(DATA.get({})), {});",
+            name, handle, options.to_string().replace('<', "\\u003c"))
+    }
+}
+
+/// Fills from the start to the value of a number property (like rpm or fuel),
+/// colored by the stops the value reached
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Bar {
+    /// PropertyHandle of the value
+    source: String,
+    /// Value at which the bar is empty
+    min: f64,
+    /// Value at which the bar is full
+    max: f64,
+    /// Fills from the bottom up instead of left to right
+    #[serde(default)]
+    vertical: bool,
+    /// Sorted by value, the fill takes the color of the last stop at or below the value
+    stops: Vec<ColorStop>,
+    /// Blends between the stops across the bar instead
+    #[serde(default)]
+    gradient: bool,
+    /// The fill blinks while the value is above this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blink_above: Option<f64>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ColorStop {
+    value: f64,
+    color: String
+}
+
+impl Bar {
+    fn generate_update_js(&self, name: &str) -> String {
+        let handle = if let Some(handle) = PropertyHandle::new(self.source.as_str()).and_then(|h| serde_json::to_string(&h).ok()) {
+            handle
+        } else {
+            return String::new();
+        };
+
+        let options = serde_json::json!({
+            "min": self.min,
+            "max": self.max,
+            "vertical": self.vertical,
+            "stops": self.stops,
+            "gradient": self.gradient,
+            "blink_above": self.blink_above
+        });
+
+        // < is escaped so the colors can not close the script tag
+        format!("draw_bar({}.firstElementChild, read_float(DATA.get({})), {});",
+            name, handle, options.to_string().replace('<', "\\u003c"))
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) enum Property<T> {
@@ -367,7 +861,57 @@ pub(crate) enum Property<T> {
     // - Code can (likely) access variables, like Dashboard elements, and break the dashboard
     Formated{ source: String, formater: String },
 
-    Deref{ source: String, index: Box<Property<i64>> }
+    Deref{ source: String, index: Box<Property<i64>> },
+
+    // Window of `length` elements starting at `start`, moved back to stay within the array.
+    // Text joins the elements with newlines, the other types read the first element
+    DerefRange{ source: String, start: Box<Property<i64>>, length: Box<Property<i64>> },
+
+    // Formater from the library in datarace.dash.js, decimals are used for the numeric conversions
+    Named{ source: String, formatter: NamedFormatter, #[serde(default)] decimals: u8 }
+}
+
+/// The formatters of format_named in datarace.dash.js.
+/// Unknown names fail deserializing, so a typo does not result in a broken dashboard at runtime
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) enum NamedFormatter {
+    /// Meters per second to kilometers per hour
+    Kmh,
+    /// Meters per second to miles per hour
+    Mph,
+    /// Liters
+    Liters,
+    /// Liters to US gallons
+    Gallons,
+    /// Degrees celsius
+    Celsius,
+    /// Degrees celsius to fahrenheit
+    Fahrenheit,
+    /// Fraction (0 to 1) to a percentage with % sign
+    Percent,
+    /// -1 is R, 0 is N, everything else the number
+    Gear
+}
+
+impl NamedFormatter {
+    /// Name as used by format_named
+    fn js_name(self) -> &'static str {
+        match self {
+            NamedFormatter::Kmh => "kmh",
+            NamedFormatter::Mph => "mph",
+            NamedFormatter::Liters => "liters",
+            NamedFormatter::Gallons => "gallons",
+            NamedFormatter::Celsius => "celsius",
+            NamedFormatter::Fahrenheit => "fahrenheit",
+            NamedFormatter::Percent => "percent",
+            NamedFormatter::Gear => "gear"
+        }
+    }
+
+    fn generate_js(self, res: String, decimals: u8) -> String {
+        // toFixed only takes up to 100
+        format!("format_named({}, '{}', {})", res, self.js_name(), decimals.min(20))
+    }
 }
 
 impl Property<bool> {
@@ -397,6 +941,20 @@ impl Property<bool> {
                     self.get_static_value().to_string()
                 }
             }
+            Property::DerefRange { source: _, start, length } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("read_bool(read_arr_range({},{},{}))", res, start.generate_read_js(), length.generate_read_js())
+                } else {
+                    self.get_static_value().to_string()
+                }
+            },
+            Property::Named { source: _, formatter, decimals } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("parse_to_bool({})", formatter.generate_js(res, *decimals))
+                } else {
+                    self.get_static_value().to_string()
+                }
+            }
         }
     }
 }
@@ -428,6 +986,20 @@ impl Property<i64> {
                     self.get_static_value().to_string()
                 }
             }
+            Property::DerefRange { source: _, start, length } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("read_int(read_arr_range({},{},{}))", res, start.generate_read_js(), length.generate_read_js())
+                } else {
+                    self.get_static_value().to_string()
+                }
+            },
+            Property::Named { source: _, formatter, decimals } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("parse_to_int({})", formatter.generate_js(res, *decimals))
+                } else {
+                    self.get_static_value().to_string()
+                }
+            }
         }
     }
 }
@@ -460,6 +1032,20 @@ impl Property<f64> {
                     self.get_static_value().to_string()
                 }
             }
+            Property::DerefRange { source: _, start, length } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("read_float(read_arr_range({},{},{}))", res, start.generate_read_js(), length.generate_read_js())
+                } else {
+                    self.get_static_value().to_string()
+                }
+            },
+            Property::Named { source: _, formatter, decimals } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("parse_to_float({})", formatter.generate_js(res, *decimals))
+                } else {
+                    self.get_static_value().to_string()
+                }
+            }
         }
     }
 }
@@ -491,6 +1077,20 @@ impl Property<String> {
                     self.get_static_value().to_string()
                 }
             }
+            Property::DerefRange { source: _, start, length } => {
+                if let Some(res) = self.gen_handle_js() {
+                    format!("join_arr(read_arr_range({},{},{}), '\\n')", res, start.generate_read_js(), length.generate_read_js())
+                } else {
+                    self.get_static_value().to_string()
+                }
+            },
+            Property::Named { source: _, formatter, decimals } => {
+                if let Some(res) = self.gen_handle_js() {
+                    formatter.generate_js(res, *decimals)
+                } else {
+                    self.get_static_value().to_string()
+                }
+            }
         }
     }
 }
@@ -519,6 +1119,12 @@ impl<T> Property<T> {
             },
             Property::Deref { source, index: _ } => {
                 PropertyHandle::new(source.as_str())
+            },
+            Property::DerefRange { source, start: _, length: _ } => {
+                PropertyHandle::new(source.as_str())
+            },
+            Property::Named { source, formatter: _, decimals: _ } => {
+                PropertyHandle::new(source.as_str())
             }
         }
 
@@ -538,6 +1144,10 @@ impl<T> Property<T> {
             if let Property::Deref { source: _, index } = self {
                 index.add_property_handle_to_collection(set);
             }
+            if let Property::DerefRange { source: _, start, length } = self {
+                start.add_property_handle_to_collection(set);
+                length.add_property_handle_to_collection(set);
+            }
         }
     }
 }
@@ -554,6 +1164,12 @@ impl<T> Property<T> where T: Default + Clone {
             },
             Property::Deref { source: _, index: _ } => {
                 T::default()
+            },
+            Property::DerefRange { source: _, start: _, length: _ } => {
+                T::default()
+            },
+            Property::Named { source: _, formatter: _, decimals: _ } => {
+                T::default()
             }
         }
     }
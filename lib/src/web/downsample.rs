@@ -0,0 +1,145 @@
+//! Rate tiers of dashboard clients: realtime clients get every update of the socket updater,
+//! fast and slow ones (like a phone on cellular) get the updates collected and send at a lower rate,
+//! with the values in between combined by the aggregation the client asked for.
+
+use std::time::Duration;
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{utils::Value, PropertyHandle};
+
+use super::socket::{Encoding, UpdatePackage};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Tier {
+    /// Every cycle (the websocket_update_rate)
+    #[default]
+    Realtime,
+    /// 10 times a second
+    Fast,
+    /// Once a second
+    Slow
+}
+
+impl Tier {
+    fn name(self) -> &'static str {
+        match self {
+            Tier::Realtime => "realtime",
+            Tier::Fast => "fast",
+            Tier::Slow => "slow"
+        }
+    }
+
+    /// None for realtime, which is send every cycle
+    fn interval(self) -> Option<Duration> {
+        match self {
+            Tier::Realtime => None,
+            Tier::Fast => Some(Duration::from_millis(100)),
+            Tier::Slow => Some(Duration::from_secs(1))
+        }
+    }
+}
+
+/// How the updates between two sends are combined, only numbers can be combined by min/max,
+/// everything else always takes the last value
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Aggregation {
+    #[default]
+    Last,
+    Min,
+    Max
+}
+
+impl Aggregation {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregation::Last => "last",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max"
+        }
+    }
+}
+
+/// The room a client with this encoding, tier and aggregation joins.
+/// Realtime clients share the rooms of the updates, no matter the aggregation
+pub(super) fn room_name(name: &str, encoding: Encoding, tier: Tier, aggregation: Aggregation) -> String {
+    let prefix = match encoding {
+        Encoding::Json => "dash",
+        Encoding::Binary => "dash-bin",
+        Encoding::Text => "dash-text"
+    };
+
+    match tier {
+        Tier::Realtime => format!("{}.{}", prefix, name),
+        _ => format!("{}.{}.{}.{}", prefix, tier.name(), aggregation.name(), name)
+    }
+}
+
+/// The updates collected for a tier and aggregation of a dashboard
+#[derive(Debug)]
+pub(super) struct Downsample {
+    pub(super) tier: Tier,
+    pub(super) aggregation: Aggregation,
+    pending: HashMap<PropertyHandle, Value>,
+    last_sent: Instant
+}
+
+impl Downsample {
+    pub(super) fn new(tier: Tier, aggregation: Aggregation) -> Self {
+        Downsample { tier, aggregation, pending: HashMap::new(), last_sent: Instant::now() }
+    }
+
+    pub(super) fn add(&mut self, list: &UpdatePackage) {
+        for (handle, value) in list {
+            let value = match self.pending.remove(handle) {
+                Some(old) => self.merge(old, value.clone()),
+                None => value.clone()
+            };
+            self.pending.insert(*handle, value);
+        }
+    }
+
+    fn merge(&self, old: Value, new: Value) -> Value {
+        match (old, new) {
+            // Array changes are applied onto what is pending, as the client needs all of them
+            (Value::Arr(mut arr), Value::ArrUpdate(changes)) => {
+                for (index, item) in changes {
+                    if let Some(slot) = arr.get_mut(index) {
+                        *slot = item;
+                    }
+                }
+                Value::Arr(arr)
+            },
+            (Value::ArrUpdate(mut pending), Value::ArrUpdate(changes)) => {
+                for (index, item) in changes {
+                    if let Some(slot) = pending.iter_mut().find(|(i, _)| *i == index) {
+                        slot.1 = item;
+                    } else {
+                        pending.push((index, item));
+                    }
+                }
+                Value::ArrUpdate(pending)
+            },
+            (old, new) => match (self.aggregation, old.to_number(), new.to_number()) {
+                (Aggregation::Min, Some(o), Some(n)) if o < n => old,
+                (Aggregation::Max, Some(o), Some(n)) if o > n => old,
+                _ => new
+            }
+        }
+    }
+
+    /// Takes the collected updates once the interval of the tier passed (and there are any)
+    pub(super) fn take_due(&mut self, now: Instant) -> Option<UpdatePackage> {
+        let interval = self.tier.interval()?;
+        if self.pending.is_empty() || now.duration_since(self.last_sent) < interval {
+            return None;
+        }
+
+        self.last_sent = now;
+        Some(self.pending.drain().collect())
+    }
+}
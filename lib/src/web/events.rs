@@ -0,0 +1,89 @@
+use hashbrown::HashMap;
+use kanal::{AsyncReceiver, AsyncSender};
+use log::error;
+use socketioxide::SocketIo;
+
+use crate::{events::EventMessage, pluginloader::LoaderMessage, system, EventFilter, EventHandle};
+
+/// The room all clients of the dashboard join, events are send to every one of them (no matter encoding or tier)
+pub(super) fn event_room(name: &str) -> String {
+    format!("dash-ev.{}", name)
+}
+
+/// Key of the event send to the client, same format as property handles
+fn event_key(ev: &EventHandle) -> String {
+    format!("{}|{}", ev.plugin, ev.event)
+}
+
+/// Events used by the connected dashboards, the socket subscribes to them in the event loop
+/// (as listener system::WEB_LISTENER_ID) and forwards them to the dashboards using them
+pub(super) struct DashboardEvents {
+    dashes: HashMap<EventHandle, Vec<String>>,
+    channel: kanal::Sender<EventMessage>,
+    sender: AsyncSender<LoaderMessage>,
+    receiver: AsyncReceiver<LoaderMessage>
+}
+
+impl DashboardEvents {
+    pub(super) fn new(channel: kanal::Sender<EventMessage>) -> Self {
+        let (sender, receiver) = kanal::unbounded_async();
+        DashboardEvents { dashes: HashMap::new(), channel, sender, receiver }
+    }
+
+    /// Subscribes to the events of the dashboard, this also works if the event was not created yet
+    pub(super) async fn add_dashboard(&mut self, name: &str, events: impl IntoIterator<Item = EventHandle>) {
+        for ev in events {
+            if let Some(dashes) = self.dashes.get_mut(&ev) {
+                if !dashes.iter().any(|d| d == name) {
+                    dashes.push(name.to_string());
+                }
+                continue;
+            }
+
+            self.dashes.insert(ev, vec![name.to_string()]);
+            self.subscribe(ev).await;
+        }
+    }
+
+    /// Removes the dashboard, unsubscribing events no other dashboard uses
+    pub(super) async fn remove_dashboard(&mut self, name: &str) {
+        let mut removal = Vec::new();
+        for (ev, dashes) in self.dashes.iter_mut() {
+            dashes.retain(|d| d != name);
+
+            if dashes.is_empty() {
+                removal.push(*ev);
+            }
+        }
+
+        for ev in removal {
+            self.dashes.remove(&ev);
+            let _ = self.channel.as_async().send(EventMessage::Unsubscribe(ev, system::WEB_LISTENER_ID)).await;
+        }
+    }
+
+    /// Forwards the events triggered since the last call
+    pub(super) async fn forward(&mut self, io: &SocketIo) {
+        while let Ok(Some(msg)) = self.receiver.try_recv() {
+            match msg {
+                LoaderMessage::EventTriggered(ev) => {
+                    for name in self.dashes.get(&ev).into_iter().flatten() {
+                        if let Err(e) = io.within(event_room(name)).emit("event", event_key(&ev)) {
+                            error!("Failed to send event to dashboard {}: {}", name, e);
+                        }
+                    }
+                },
+                // The event was deleted (like the plugin shutting down), we subscribe again for when it returns.
+                // Events we unsubscribed ourselves are no longer in the map
+                LoaderMessage::EventUnsubscribed(ev) if self.dashes.contains_key(&ev) => self.subscribe(ev).await,
+                _ => ()
+            }
+        }
+    }
+
+    async fn subscribe(&self, ev: EventHandle) {
+        if self.channel.as_async().send(EventMessage::Subscribe(ev, system::WEB_LISTENER_ID, self.sender.clone(), EventFilter::default())).await.is_err() {
+            error!("Unable to subscribe the dashboards to event {}|{}", ev.plugin, ev.event);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use axum::{async_trait, extract::FromRequestParts, http::{header::ACCEPT_LANGUAGE, request::Parts}};
+
+use super::utils::DataStoreLocked;
+
+/// Languages the web ui is translated into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Lang {
+    En,
+    De
+}
+
+impl Lang {
+    /// Parses a language code (like `de` or `de-AT`), None if we have no translation for it
+    pub(crate) fn from_code(code: &str) -> Option<Lang> {
+        let primary = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "de" => Some(Lang::De),
+            _ => None
+        }
+    }
+
+    pub(super) fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de"
+        }
+    }
+
+    /// Picks the language with the highest weight in an Accept-Language header that we support
+    fn negotiate(header: &str) -> Option<Lang> {
+        let mut options: Vec<(Lang, f32)> = header.split(',').filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let lang = Lang::from_code(parts.next()?)?;
+            let weight = parts.find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok())).unwrap_or(1.0);
+
+            Some((lang, weight))
+        }).filter(|(_, weight)| *weight > 0.0).collect();
+
+        // stable sort, so with equal weight the first listed wins
+        options.sort_by(|a, b| b.1.total_cmp(&a.1));
+        options.first().map(|(lang, _)| *lang)
+    }
+
+    /// Returns the translation of the text
+    pub(super) fn get(self, text: Text) -> &'static str {
+        let (en, de) = match text {
+            Text::Home => ("Home", "Start"),
+            Text::Dashboards => ("Dashboards", "Dashboards"),
+            Text::Properties => ("Properties", "Eigenschaften"),
+            Text::Notifications => ("Notifications", "Benachrichtigungen"),
+            Text::NoNotifications => ("No notifications yet", "Noch keine Benachrichtigungen"),
+            Text::Inputs => ("Inputs", "Eingaben"),
+            Text::InputsDisabled => ("Reading inputs is disabled, set enabled in inputs of the config to use them", "Das Lesen von Eingaben ist deaktiviert, setze enabled unter inputs in der Konfiguration um sie zu nutzen"),
+            Text::InputMappings => ("Mappings", "Zuordnungen"),
+            Text::NoInputMappings => ("No inputs mapped", "Keine Eingaben zugeordnet"),
+            Text::RecentInputs => ("Recent inputs", "Letzte Eingaben"),
+            Text::PressAnInput => ("Mapped inputs are listed when used. To find the name of another input for the config, start learning and press it within 30 seconds (only possible on the host running DataRace)", "Zugeordnete Eingaben werden bei Benutzung angezeigt. Um den Namen einer anderen Eingabe für die Konfiguration zu finden, starte das Anlernen und drücke sie innerhalb von 30 Sekunden (nur auf dem Rechner möglich, auf dem DataRace läuft)"),
+            Text::LearnInputs => ("Learn inputs", "Eingaben anlernen"),
+            Text::LearningInputs => ("Learning: all inputs are listed for the next 30 seconds", "Anlernen: alle Eingaben werden für die nächsten 30 Sekunden angezeigt"),
+            Text::LearnOnlyLocal => ("Learning inputs is only possible on the host running DataRace", "Das Anlernen von Eingaben ist nur auf dem Rechner möglich, auf dem DataRace läuft"),
+            Text::PropertyAliases => ("Aliases", "Aliase"),
+            Text::NamespaceVersion => ("Namespace version", "Namensraum-Version"),
+            Text::Collisions => ("Id Collisions", "Id-Kollisionen"),
+            Text::NoCollisions => ("No id collisions", "Keine Id-Kollisionen"),
+            Text::CollidesWith => ("rejected, same id as", "abgelehnt, gleiche Id wie"),
+            Text::Attempts => ("Attempts", "Versuche"),
+            Text::Clients => ("Clients", "Clients"),
+            Text::NoClients => ("No clients connected", "Keine Clients verbunden"),
+            Text::NoDashboard => ("no dashboard", "kein Dashboard"),
+            Text::ConnectedSince => ("Connected since", "Verbunden seit"),
+            Text::SharedLink => ("share link", "Freigabelink"),
+            Text::UpdatesReceived => ("Updates received", "Empfangene Updates"),
+            Text::PerSecond => ("per second", "pro Sekunde"),
+            Text::SecondsSinceUpdate => ("Seconds since the last update", "Sekunden seit dem letzten Update"),
+            Text::NoUpdates => ("No updates received yet", "Noch keine Updates empfangen"),
+            Text::SubscribedProperties => ("Subscribed properties", "Abonnierte Eigenschaften"),
+            Text::Settings => ("Settings", "Einstellungen"),
+            Text::Version => ("Version", "Version"),
+            Text::ApiVersion => ("Apiversion", "API-Version"),
+            Text::PluginsLoaded => ("Plugins Loaded", "Geladene Plugins"),
+            Text::DuplicatePlugins => ("Duplicate Plugins", "Doppelte Plugins"),
+            Text::Launched => ("Launched", "Gestartet"),
+            Text::Skipped => ("Skipped", "Übersprungen"),
+            Text::NoneLaunched => ("none, see duplicate_plugins", "keines, siehe duplicate_plugins"),
+            Text::Memory => ("Memory", "Speicher"),
+            Text::Arrays => ("Arrays", "Arrays"),
+            Text::Strings => ("Strings", "Strings"),
+            Text::HistorySamples => ("History samples", "Verlaufswerte"),
+            Text::UpdateTimes => ("Update times", "Update-Zeiten"),
+            Text::Message => ("Message", "Nachricht"),
+            Text::Calls => ("Calls", "Aufrufe"),
+            Text::Mean => ("Mean", "Mittelwert"),
+            Text::Open => ("Open", "Öffnen"),
+            Text::Edit => ("Edit", "Bearbeiten"),
+            Text::Kiosk => ("Kiosk", "Kiosk"),
+            Text::Share => ("Share", "Teilen"),
+            Text::ShareLinkValidUntil => ("This link opens only this dashboard (also outside the whitelist), and is valid until", "Dieser Link öffnet nur dieses Dashboard (auch außerhalb der Whitelist) und ist gültig bis"),
+            Text::ValidForHours => ("Valid for (hours)", "Gültig für (Stunden)"),
+            Text::CreateLink => ("Create link", "Link erstellen"),
+            Text::ReloadConfig => ("Reload Config", "Konfiguration neu laden"),
+            Text::ConfigReloaded => ("Config reloaded", "Konfiguration neu geladen"),
+            Text::RestartRequired => ("Config reloaded, restart required to apply", "Konfiguration neu geladen, Neustart erforderlich für"),
+            Text::ReloadFailed => ("Failed to reload config", "Konfiguration konnte nicht geladen werden"),
+            Text::UnableToDisplayConfig => ("Unable to display config", "Konfiguration kann nicht angezeigt werden"),
+            Text::Backup => ("Backup", "Sicherung"),
+            Text::BackupDescription => ("Config and all dashboards as a single file, restoring it overwrites them (other dashboards are kept)", "Konfiguration und alle Dashboards als eine Datei, Wiederherstellen überschreibt diese (andere Dashboards bleiben erhalten)"),
+            Text::DownloadBackup => ("Download backup", "Sicherung herunterladen"),
+            Text::RestoreBackup => ("Restore backup", "Sicherung wiederherstellen"),
+            Text::PreviousStateSaved => ("previous state saved to", "vorheriger Stand gespeichert in"),
+            Text::RestoreFailed => ("Failed to restore backup", "Sicherung konnte nicht wiederhergestellt werden"),
+            Text::RestoreOnlyLocal => ("Restoring a backup is only possible on the host running DataRace", "Das Wiederherstellen einer Sicherung ist nur auf dem Rechner möglich, auf dem DataRace läuft"),
+            Text::ReadOnly => ("The web interface is read only, changes have to be made in the config on the host", "Die Weboberfläche ist schreibgeschützt, Änderungen müssen in der Konfiguration auf dem Host vorgenommen werden")
+        };
+
+        match self {
+            Lang::En => en,
+            Lang::De => de
+        }
+    }
+}
+
+/// All texts of the web ui, see Lang::get for the translations
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Text {
+    Home,
+    Dashboards,
+    Properties,
+    Notifications,
+    NoNotifications,
+    Inputs,
+    InputsDisabled,
+    InputMappings,
+    NoInputMappings,
+    RecentInputs,
+    PressAnInput,
+    LearnInputs,
+    LearningInputs,
+    LearnOnlyLocal,
+    PropertyAliases,
+    NamespaceVersion,
+    Collisions,
+    NoCollisions,
+    CollidesWith,
+    Attempts,
+    Clients,
+    NoClients,
+    NoDashboard,
+    ConnectedSince,
+    SharedLink,
+    UpdatesReceived,
+    PerSecond,
+    SecondsSinceUpdate,
+    NoUpdates,
+    SubscribedProperties,
+    Settings,
+    Version,
+    ApiVersion,
+    PluginsLoaded,
+    DuplicatePlugins,
+    Launched,
+    Skipped,
+    NoneLaunched,
+    Memory,
+    Arrays,
+    Strings,
+    HistorySamples,
+    UpdateTimes,
+    Message,
+    Calls,
+    Mean,
+    Open,
+    Edit,
+    Kiosk,
+    Share,
+    ShareLinkValidUntil,
+    ValidForHours,
+    CreateLink,
+    ReloadConfig,
+    ConfigReloaded,
+    RestartRequired,
+    ReloadFailed,
+    UnableToDisplayConfig,
+    Backup,
+    BackupDescription,
+    DownloadBackup,
+    RestoreBackup,
+    PreviousStateSaved,
+    RestoreFailed,
+    RestoreOnlyLocal,
+    ReadOnly
+}
+
+/// Uses the language set in the config, or if that is `auto` the one requested by the browser
+#[async_trait]
+impl FromRequestParts<DataStoreLocked> for Lang {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, datastore: &DataStoreLocked) -> Result<Self, Self::Rejection> {
+        if let Some(lang) = datastore.read().await.get_config().get_language() {
+            return Ok(lang);
+        }
+
+        Ok(parts.headers.get(ACCEPT_LANGUAGE)
+            .and_then(|val| val.to_str().ok())
+            .and_then(Lang::negotiate)
+            .unwrap_or(Lang::En))
+    }
+}
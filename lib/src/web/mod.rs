@@ -1,42 +1,202 @@
-use std::{path::PathBuf, sync::{atomic::AtomicBool, Arc}};
+use std::{net::{IpAddr, SocketAddr}, path::PathBuf};
 
-use axum::{http::StatusCode, response::{IntoResponse, Response}, routing::get};
+use axum::{extract::{ConnectInfo, DefaultBodyLimit, Request, State}, http::{header::{HOST, ORIGIN}, Method, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing::{get, post}};
 use log::{debug, error, info};
 use tokio::{fs, net::TcpListener};
 
 use utils::DataStoreLocked;
 
+use crate::{audit::{AuditAction, AuditEntry}, pluginloader, utils::ValueCache, DataStoreReturnCode, PropertyHandle};
+
 mod utils;
 mod socket;
+mod clients;
+mod downsample;
+mod events;
 mod pages;
 mod dashboard;
+mod share;
+mod stream;
+pub(crate) mod i18n;
+
+/// Largest backup that can be restored from the web, the default limit of 2MB is too small with
+/// many dashboards
+const MAX_BACKUP_SIZE: usize = 64 * 1024 * 1024;
 
-pub(crate) async fn run_webserver(datastore: DataStoreLocked, shutdown: Arc<AtomicBool>) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) async fn run_webserver(datastore: DataStoreLocked) -> Result<(), Box<dyn std::error::Error>> {
     debug!("Setting up webserver...");
     let layer = socket::create_socketio_layer(datastore).await;
 
     let app = axum::Router::new()
         .route("/", get(pages::index))
         .route("/dashboard", get(pages::dashboard_list))
-        .route("/dashboard/render/:id", get(pages::load_dashboard))
-        .route("/dashboard/edit/:id", get(pages::edit_dashboard))
+        .route("/dashboard/render/*id", get(pages::load_dashboard))
+        .route("/dashboard/edit/*id", get(pages::edit_dashboard))
+        .route("/dashboard/share/*id", get(pages::share_dashboard))
+        .route("/dashboard/preview/*id", get(pages::dashboard_preview))
+        .route("/shared/*id", get(pages::load_shared_dashboard))
         .route("/properties", get(pages::properties))
-        .route("/setting", get(pages::settings))
+        .route("/api/property-names", get(pages::property_names))
+        .route("/api/property-aliases", get(pages::property_aliases))
+        .route("/api/property/:name", post(pages::set_property))
+        .route("/api/memory", get(pages::memory_usage))
+        .route("/api/update-times", get(pages::update_times))
+        .route("/collisions", get(pages::collisions))
+        .route("/api/collisions", get(pages::collision_list))
+        .route("/api/audit", get(pages::audit_log))
+        .route("/notifications", get(pages::notifications))
+        .route("/inputs", get(pages::inputs).post(pages::learn_inputs))
+        .route("/api/inputs", get(pages::input_list))
+        .route("/clients", get(pages::clients))
+        .route("/api/clients", get(pages::client_list))
+        .route("/stream", get(stream::stream))
+        .route("/setting", get(pages::settings).post(pages::reload_config))
+        .route("/setting/backup", get(pages::download_backup))
+        .route("/setting/restore", post(pages::restore_backup).layer(DefaultBodyLimit::max(MAX_BACKUP_SIZE)))
         .route("/style.css", get(css_main_style))
         .route("/lib/socket.io.js", get(js_lib_socket_io))
         .route("/lib/datarace.dash.js", get(js_lib_datarace_dashboard))
         .with_state(datastore)
-        .layer(layer);
-    let listener = TcpListener::bind("0.0.0.0:3000").await?;
+        .layer(layer)
+        .layer(middleware::from_fn(check_origin))
+        .layer(middleware::from_fn_with_state(datastore, check_whitelist));
+    let mut address = datastore.read().await.get_config().get_web_address();
+    let mut listener = TcpListener::bind(address).await?;
+
+    // Restarts (like for a changed web_address) keep the app, and with it the socket.io layer and the dashboards connected to it
+    loop {
+        info!("Webserver Launched on {}", address);
+        crate::service::notify("READY=1");
+        axum::serve(listener, app.clone().into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move { while !stop_requested(datastore).await { tokio::time::sleep(std::time::Duration::from_secs(1)).await }  })
+            .await?;
+
+        let mut ds_w = datastore.write().await;
+        if ds_w.get_shutdown_status() {
+            break;
+        }
+        ds_w.finish_web_restart();
+        let new_address = ds_w.get_config().get_web_address();
+        drop(ds_w);
+
+        info!("Restarting webserver...");
+        listener = match TcpListener::bind(new_address).await {
+            Ok(listener) => {
+                address = new_address;
+                listener
+            },
+            Err(e) => {
+                error!("Unable to launch the webserver on {}, staying on {}: {}", new_address, address, e);
+                TcpListener::bind(address).await?
+            }
+        };
+    }
 
-    info!("Webserver Launched");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move { while !shutdown.load(std::sync::atomic::Ordering::Acquire) { std::thread::sleep(std::time::Duration::from_secs(1)) }  })
-        .await?;
     info!("Webserver stopped!");
     Ok(())
 }
 
+/// If the webserver should stop, for shutdown or a restart
+async fn stop_requested(datastore: DataStoreLocked) -> bool {
+    let ds_r = datastore.read().await;
+    ds_r.get_shutdown_status() || ds_r.is_web_restart_requested()
+}
+
+/// Number of clients currently connected to the dashboard socket
+pub(crate) fn count_clients() -> usize {
+    socket::count_clients()
+}
+
+/// Rejects requests changing something (anything but GET) that were send by a page of another origin.
+/// Browsers send simple requests (like a form posting text/plain) to any site without asking first,
+/// so without this any website opened on a whitelisted device could write properties or the config.
+/// Clients outside of a browser (like curl) send no Origin and are not affected
+async fn check_origin(req: Request, next: Next) -> Response {
+    if req.method() == Method::GET || req.method() == Method::HEAD {
+        return next.run(req).await;
+    }
+
+    if let Some(origin) = req.headers().get(ORIGIN) {
+        let host = req.headers().get(HOST).and_then(|host| host.to_str().ok());
+        // The origin is scheme://host[:port], "null" for sandboxed pages and local files
+        let origin_host = origin.to_str().ok().and_then(|origin| origin.split_once("://")).map(|(_, host)| host);
+        if origin_host.is_none() || origin_host != host {
+            debug!("Rejected {} {} from origin {:?}", req.method(), req.uri().path(), origin);
+            return (StatusCode::FORBIDDEN, "Forbidden: request from another website").into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+/// Rejects clients not in the web_whitelist of the config (this includes the websocket),
+/// except for dashboards opened through a valid share link
+async fn check_whitelist(State(datastore): State<DataStoreLocked>, ConnectInfo(addr): ConnectInfo<SocketAddr>, req: Request, next: Next) -> Response {
+    let allowed = {
+        let ds_r = datastore.read().await;
+        let config = ds_r.get_config();
+        config.is_web_client_allowed(addr.ip()) || share::is_share_request(config, req.uri())
+    };
+
+    if allowed {
+        next.run(req).await
+    } else {
+        debug!("Rejected {}, not in the web whitelist", addr);
+        (StatusCode::FORBIDDEN, "Forbidden: this device is not in the whitelist of DataRace").into_response()
+    }
+}
+
+/// Writes a property for a web client (the api or a dashboard), the value is parsed the same as the control socket.
+/// The name is checked against the web_writable of the config before anything is parsed or looked up.
+/// Every attempt is recorded in the audit log, source being `api` or `dashboard <name>`
+async fn write_property(datastore: DataStoreLocked, client: IpAddr, source: String, name: &str, value: &str) -> DataStoreReturnCode {
+    let mut entry = AuditEntry::new(AuditAction::Write, client.to_string(), source, name.to_string());
+    entry.new_value = Some(value.to_string());
+
+    let code = 'write: {
+        let ds_r = datastore.read().await;
+        if ds_r.get_config().is_web_read_only() {
+            debug!("Web client {} tried to write {}, but the web is read only", client, name);
+            break 'write DataStoreReturnCode::NotAuthenticated;
+        }
+        if !ds_r.get_config().is_web_writable(name) {
+            debug!("Web client {} tried to write {}, which is not in web_writable", client, name);
+            break 'write DataStoreReturnCode::NotAuthenticated;
+        }
+
+        let Some(handle) = PropertyHandle::new(name) else {
+            break 'write DataStoreReturnCode::ParameterCorrupted;
+        };
+        if handle.plugin == crate::system::CORE_PLUGIN_ID {
+            break 'write DataStoreReturnCode::NotAuthenticated;
+        }
+
+        let mut cache = ValueCache::default();
+        match ds_r.get_property_container(&handle) {
+            Some(cont) => cont.read_web(&mut cache),
+            None => break 'write DataStoreReturnCode::DoesNotExist
+        };
+        entry.old_value = Some(cache.value.to_display_string());
+        drop(ds_r);
+
+        // The owner writes it, so it is in order with its own changes
+        let code = pluginloader::write_property_text(datastore, handle, value.to_string()).await;
+        if code == DataStoreReturnCode::Ok {
+            // As parsed, instead of the text send
+            if let Some(cont) = datastore.read().await.get_property_container(&handle) {
+                cont.read_web(&mut cache);
+                entry.new_value = Some(cache.value.to_display_string());
+            }
+        }
+        code
+    };
+
+    let ds_r = datastore.read().await;
+    entry.result = format!("{:?}", code);
+    ds_r.get_audit_log().record(ds_r.get_config().get_audit_log().as_deref(), entry);
+    code
+}
+
 #[allow(dead_code)]
 async fn serve_page(asset: &str) -> maud::Markup {
     maud::html! {
@@ -70,14 +230,66 @@ async fn get_dashboard_folder(datastore: DataStoreLocked) -> Result<PathBuf, FsR
     Ok(folder)
 }
 
+/// Resolves the name of a dashboard (the path in the dashboards folder without .json, subfolders
+/// separated by /) to the file, None if the name could leave the folder
+pub(crate) fn dashboard_file(mut folder: PathBuf, name: &str) -> Option<PathBuf> {
+    for part in name.split('/') {
+        // The name ends up in a js string of the dashboard, so quotes are not allowed either
+        if part.is_empty() || part == "." || part == ".." || part.contains(['\\', ':', '\'', '"']) {
+            return None;
+        }
+        folder.push(part);
+    }
+    folder.set_extension("json");
+
+    Some(folder)
+}
+
 // Returns a certain dashboard by name
 async fn get_dashboard(datastore: DataStoreLocked, path: String) -> Result<dashboard::Dashboard, FsResourceError> {
-    let mut folder = get_dashboard_folder(datastore).await?;
+    let folder = get_dashboard_folder(datastore).await?;
+    let file = dashboard_file(folder, path.as_str()).ok_or(FsResourceError::DoesNotExist)?;
 
-    folder.push(path.as_str());
-    folder.set_extension("json");
+    // The socket loads the dashboard again by its name, so it has to be the path (also in subfolders)
+    let mut dash = read_dashboard_from_path(file).await?;
+    dash.name = path;
+    Ok(dash)
+}
+
+/// Folder in the dashboards folder the previews are cached in (hidden, so it is not listed)
+const PREVIEW_FOLDER: &str = ".previews";
+
+/// Returns the preview svg of the dashboard, cached on disk till the dashboard changes
+async fn get_dashboard_preview(datastore: DataStoreLocked, path: String) -> Result<String, FsResourceError> {
+    let folder = get_dashboard_folder(datastore).await?;
+    let file = dashboard_file(folder.clone(), path.as_str()).ok_or(FsResourceError::DoesNotExist)?;
+    let mut cache = dashboard_file(folder.join(PREVIEW_FOLDER), path.as_str()).ok_or(FsResourceError::DoesNotExist)?;
+    cache.set_extension("svg");
+
+    let dash_modified = fs::metadata(file.as_path()).await.and_then(|meta| meta.modified()).ok();
+    let cache_modified = fs::metadata(cache.as_path()).await.and_then(|meta| meta.modified()).ok();
+    if let (Some(dash_modified), Some(cache_modified)) = (dash_modified, cache_modified) {
+        if cache_modified >= dash_modified {
+            if let Ok(svg) = fs::read_to_string(cache.as_path()).await {
+                return Ok(svg);
+            }
+        }
+    }
+
+    let svg = read_dashboard_from_path(file).await?.render_preview().into_string();
+
+    // Failing to cache only means rendering it again next time
+    let res = async {
+        if let Some(parent) = cache.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(cache.as_path(), svg.as_bytes()).await
+    }.await;
+    if let Err(e) = res {
+        debug!("Unable to cache preview of Dashboard {}: {}", path, e);
+    }
 
-    read_dashboard_from_path(folder).await
+    Ok(svg)
 }
 
 async fn read_dashboard_from_path(folder: PathBuf) -> Result<dashboard::Dashboard, FsResourceError> {
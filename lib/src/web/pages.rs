@@ -1,13 +1,14 @@
-use std::str::FromStr;
+use std::{collections::BTreeMap, net::SocketAddr, str::FromStr, time::{Duration, UNIX_EPOCH}};
 
-use axum::{extract::{Path, State}, response::{IntoResponse, Response}};
+use axum::{extract::{ConnectInfo, Path, Query, State}, http::{header::{CONTENT_DISPOSITION, CONTENT_TYPE, HOST}, HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json};
 use log::error;
 use maud::{html, Markup, PreEscaped, DOCTYPE};
-use tokio::fs::{self, DirEntry};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
 
-use crate::utils::{Value, ValueCache};
+use crate::{aliases::AliasInfo, backup, audit::{self, AuditAction, AuditEntry, AuditQuery}, collisions::{self, Collision}, inputs, memory, notifications::Notification, profile::{self, PluginUpdateTimes}, utils::{Value, ValueCache}, DataStoreReturnCode};
 
-use super::{utils::DataStoreLocked, FsResourceError};
+use super::{clients::{self, ClientInfo}, i18n::{Lang, Text}, share::{self, ShareQuery}, utils::DataStoreLocked, FsResourceError};
 
 use super::dashboard::*;
 
@@ -20,20 +21,22 @@ pub(super) async fn serve_asset(file: &str) -> PreEscaped<String> {
     PreEscaped(val)
 }
 
-fn header(name: &str) -> Markup {
+fn header(name: &str, lang: Lang) -> Markup {
     html! {
         (DOCTYPE)
+        html lang=(lang.code());
         meta charset="utf-8";
         title { "DataRace - " (name) }
-        link rel="stylesheet" href="style.css";
+        link rel="stylesheet" href="/style.css";
     }
 } 
 
-async fn generate_page(content: Markup, item: usize) -> Markup {
-    let pages = [("./", "Home"),("./dashboard","Dashboards"),("./properties", "Properties"),("./setting","Settings")];
+async fn generate_page(content: Markup, item: usize, lang: Lang) -> Markup {
+    // Absolute, as some pages are nested (like /dashboard/share/{name})
+    let pages = [("/", lang.get(Text::Home)),("/dashboard",lang.get(Text::Dashboards)),("/properties", lang.get(Text::Properties)),("/notifications", lang.get(Text::Notifications)),("/inputs", lang.get(Text::Inputs)),("/clients", lang.get(Text::Clients)),("/setting",lang.get(Text::Settings))];
 
     html! {
-        (header(pages[item].1))
+        (header(pages[item].1, lang))
         nav {
             input type="checkbox" id="check";
             label for="check" class="mobile-nav-check-btn" {
@@ -72,70 +75,176 @@ async fn generate_page(content: Markup, item: usize) -> Markup {
     }
 }
 
-pub(super) async fn index(State(datastore): State<DataStoreLocked>) -> Markup {
-    let (plugin_count,properties_count) = {
+pub(super) async fn index(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
+    let (plugin_count,properties_count,memory,duplicates) = {
         let ds_r = datastore.read().await;
-        (ds_r.count_plugins(),ds_r.count_properties())
+        (ds_r.count_plugins(),ds_r.count_properties(),memory::collect(&ds_r),ds_r.get_duplicate_plugins().to_vec())
     };
 
     use crate::built_info::*;
     let cont = html!{
         h1 { "DataRace" }
         p {
-            "Version: " (PKG_VERSION_MAJOR) "." (PKG_VERSION_MINOR) "." (PKG_VERSION_PATCH)
+            (lang.get(Text::Version)) ": " (PKG_VERSION_MAJOR) "." (PKG_VERSION_MINOR) "." (PKG_VERSION_PATCH)
             br;
-            "Apiversion: " (crate::API_VERSION) " - " (CFG_OS)
+            (lang.get(Text::ApiVersion)) ": " (crate::API_VERSION) " - " (CFG_OS)
             br;
-            "Plugins Loaded: " (plugin_count)
+            (lang.get(Text::PluginsLoaded)) ": " (plugin_count)
             br;
             br;
-            "Properties: " (properties_count)
+            (lang.get(Text::Properties)) ": " (properties_count)
             br;
             br;
             a href=(PKG_REPOSITORY) { "GitHub" }
             br;
             (PKG_LICENSE)
         }
+
+        @if !duplicates.is_empty() {
+            h2 { (lang.get(Text::DuplicatePlugins)) }
+            ul class="property-list" {
+                @for dup in duplicates {
+                    li {
+                        div class="property-entry" {
+                            div { (dup.name) " (id " (dup.id) ")" }
+                            div {
+                                (lang.get(Text::Launched)) ": "
+                                @if let Some(launched) = &dup.launched {
+                                    (launched)
+                                } @else {
+                                    (lang.get(Text::NoneLaunched))
+                                }
+                                @for skipped in &dup.skipped {
+                                    br;
+                                    (lang.get(Text::Skipped)) ": " (skipped)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { (lang.get(Text::Memory)) ": " (memory::format_bytes(memory.iter().map(|usage| usage.total_bytes).sum())) }
+        ul class="property-list" {
+            @for usage in memory {
+                li {
+                    div class="property-entry" {
+                        div { (usage.plugin) " - " (memory::format_bytes(usage.total_bytes)) }
+                        div {
+                            (lang.get(Text::Properties)) ": " (usage.properties) " (" (memory::format_bytes(usage.property_bytes)) ")"
+                            br;
+                            (lang.get(Text::Arrays)) ": " (usage.arrays) " (" (memory::format_bytes(usage.array_bytes)) ")"
+                            br;
+                            (lang.get(Text::Strings)) ": " (usage.strings) " (" (memory::format_bytes(usage.string_bytes)) ")"
+                            @if usage.history_samples > 0 {
+                                br;
+                                (lang.get(Text::HistorySamples)) ": " (usage.history_samples) " (" (memory::format_bytes(usage.history_bytes)) ")"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { (lang.get(Text::UpdateTimes)) }
+        ul class="property-list" {
+            @for plugin in profile::collect() {
+                li {
+                    div class="property-entry" {
+                        div { (plugin.plugin) " - p99 " (profile::format_micros(plugin.total.p99_us)) ", max " (profile::format_micros(plugin.total.max_us)) }
+                        table class="profile-table" {
+                            tr {
+                                th { (lang.get(Text::Message)) }
+                                th { (lang.get(Text::Calls)) }
+                                th { (lang.get(Text::Mean)) }
+                                th { "p50" }
+                                th { "p90" }
+                                th { "p99" }
+                                th { "max" }
+                            }
+                            @for times in plugin.messages {
+                                tr {
+                                    td { (times.message) }
+                                    td { (times.calls) }
+                                    td { (profile::format_micros(times.mean_us)) }
+                                    td { (profile::format_micros(times.p50_us)) }
+                                    td { (profile::format_micros(times.p90_us)) }
+                                    td { (profile::format_micros(times.p99_us)) }
+                                    td { (profile::format_micros(times.max_us)) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
     };
-    generate_page(cont, 0).await
+    generate_page(cont, 0, lang).await
 }
 
 
-pub(super) async fn dashboard_list(State(datastore): State<DataStoreLocked>) -> Result<Markup, Response> {
-    async fn parse_dir_entry(item: DirEntry) -> Option<(String, Dashboard)> {
-        let path = item.path();
+pub(super) async fn dashboard_list(State(datastore): State<DataStoreLocked>, lang: Lang) -> Result<Markup, Response> {
+    let read_only = datastore.read().await.get_config().is_web_read_only();
+    let folder = super::get_dashboard_folder(datastore).await.map_err(|e| e.into_response("list of all Dashboards".to_string()))?;
 
-        let name = path.file_stem()?.to_str()?.to_string();
+    // Subfolder -> (path, dashboard), root first
+    let mut tree: BTreeMap<String, Vec<(String, Dashboard)>> = BTreeMap::new();
+    let mut dirs = vec![(folder, String::new())];
 
-        if let Ok(dash) = super::read_dashboard_from_path(path).await {
-            Some((name, dash))
-        } else {
-            None
-        }
-    }
+    while let Some((dir, prefix)) = dirs.pop() {
+        let mut iter = match fs::read_dir(dir.as_path()).await {
+            Ok(iter) => iter,
+            Err(e) => {
+                error!("Unable to read content of the Dashboards folder {}: {}", dir.display(), e);
+                return Err(super::FsResourceError::from(e).into_response("list of all Dashboards".to_string()));
+            }
+        };
 
-    let folder = super::get_dashboard_folder(datastore).await.map_err(|e| e.into_response("list of all Dashboards".to_string()))?;
+        while let Ok(Some(item)) = iter.next_entry().await {
+            let path = item.path();
 
-    let mut iter = match fs::read_dir(folder.as_path()).await {
-        Ok(iter) => iter,
-        Err(e) => {
-            error!("Unable to read content of the Dashboards folder: {}", e);
-            return Err(super::FsResourceError::from(e).into_response("list of all Dashboards".to_string()));
+            // Symlinks are not followed, so a link to a parent folder can not loop
+            if item.file_type().await.is_ok_and(|sort| sort.is_dir()) {
+                // Hidden folders (like the cached previews) are skipped
+                if let Some(name) = path.file_name().and_then(|name| name.to_str()).filter(|name| !name.starts_with('.')) {
+                    dirs.push((path.clone(), format!("{}{}/", prefix, name)));
+                }
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                let Some(name) = path.file_stem().and_then(|name| name.to_str()).map(|name| format!("{}{}", prefix, name)) else {
+                    continue;
+                };
+
+                if let Ok(dash) = super::read_dashboard_from_path(path).await {
+                    tree.entry(prefix.trim_end_matches('/').to_string()).or_default().push((name, dash));
+                }
+            }
         }
-    };
+    }
+    for list in tree.values_mut() {
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+    }
 
     let cont = html! {
-        h1 { "Dashboards" }
+        h1 { (lang.get(Text::Dashboards)) }
 
-        ul class="dashboard-list" {
-            @while let Ok(Some(item)) = iter.next_entry().await {
-                @if let Some((path, dash)) = parse_dir_entry(item).await {
+        @for (folder, list) in tree {
+            @if !folder.is_empty() {
+                h2 { (folder) }
+            }
+            ul class="dashboard-list" {
+                @for (path, dash) in list {
                     li {
                         div class="dashboard-entry" {
+                            img class="dashboard-preview" loading="lazy" alt="" src=(format!("./dashboard/preview/{}", path));
                             h3 { (dash.name) }
                             div {
-                                a class="button" target="_blank" href=(format!("./dashboard/render/{}", path)) { "Open" }
-                                a class="button" target="_blank" href=(format!("./dashboard/edit/{}", path)) { "Edit" }
+                                a class="button" target="_blank" href=(format!("./dashboard/render/{}", path)) { (lang.get(Text::Open)) }
+                                a class="button" target="_blank" href=(format!("./dashboard/render/{}?mode=kiosk", path)) { (lang.get(Text::Kiosk)) }
+                                @if !read_only {
+                                    a class="button" target="_blank" href=(format!("./dashboard/edit/{}", path)) { (lang.get(Text::Edit)) }
+                                }
+                                a class="button" href=(format!("./dashboard/share/{}", path)) { (lang.get(Text::Share)) }
                             }
                         }
                     }
@@ -143,13 +252,10 @@ pub(super) async fn dashboard_list(State(datastore): State<DataStoreLocked>) ->
             }
         }
     };
-    Ok(generate_page(cont, 1).await)
+    Ok(generate_page(cont, 1, lang).await)
 }
 
-/// Microseconds per second: 1s = 1000ms, 1ms = 1000us
-const US_PER_SEC: f64 = 1000.0 * 1000.0;
-
-pub(super) async fn properties(State(datastore): State<DataStoreLocked>) -> Markup {
+pub(super) async fn properties(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
     let property_list = {
         let ds_r = datastore.read().await;
         let mut list = vec![];
@@ -158,47 +264,26 @@ pub(super) async fn properties(State(datastore): State<DataStoreLocked>) -> Mark
             if let (Some(name),Some(cont)) = (ds_r.read_property_name(key),ds_r.get_property_container(key)) {
                 let mut cache = ValueCache::default();
                 cont.read_web(&mut cache);
-                let ouput = match cache.value {
-                    Value::None => "None".to_string(),
-                    Value::Int(i) => format!("Int: {}", i),
-                    Value::Float(f) => format!("Float: {}", f),
-                    Value::Dur(d) => format!("Duration: {}s", (d as f64) / US_PER_SEC ),
-                    Value::Bool(b) => format!("Boolean: {}", b),
-                    Value::Str(s) => format!("Str: {}", s),
-                    Value::Arr(arr) => {
-                        let mut arr_str = format!("Arr: [");
-                        for item in arr {
-                            arr_str = format!{"{}{}, ", arr_str,
-                                match item {
-                                    Value::Int(i) => i.to_string(),
-                                    Value::Str(s) => s,
-                                    Value::Bool(b) => b.to_string(),
-                                    Value::Dur(d) => format!("{}s", (d as f64) / US_PER_SEC),
-                                    Value::Float(f) => f.to_string(),
-                                    _ => String::new()
-                                }
-                            }
-                        }
-
-                        if let Some(stripped) = arr_str.strip_suffix(", ") {
-                            arr_str = format!("{}]",stripped);
-                        } else {
-                            arr_str = format!("{}]", arr_str);
-                        }
-
-                        arr_str
-                    },
-                    Value::ArrUpdate(_) => String::new()
-                };
-                list.push((name,ouput));
+                let mut output = cache.value.to_display_string();
+                if let Some(allowed) = cont.allowed_values() {
+                    output = format!("{} [{}]", output, allowed.join(" | "));
+                }
+                list.push((name,output));
             }
         }
 
         list
     };
 
+    let (aliases, namespaces) = list_aliases(datastore).await;
+    let collision_count = collisions::list().len();
+
     let cont = html! {
-        h1 { "Properties" }
+        h1 { (lang.get(Text::Properties)) }
+
+        @if collision_count > 0 {
+            p { a href="./collisions" { (lang.get(Text::Collisions)) ": " (collision_count) } }
+        }
 
         ul class="property-list" {
             @for (name, output) in property_list {
@@ -210,26 +295,602 @@ pub(super) async fn properties(State(datastore): State<DataStoreLocked>) -> Mark
                 }
             }
         }
+
+        @if !aliases.is_empty() || !namespaces.is_empty() {
+            h2 { (lang.get(Text::PropertyAliases)) }
+            ul class="property-list" {
+                @for namespace in &namespaces {
+                    li {
+                        div class="property-entry" {
+                            div { (namespace.plugin) }
+                            div { (lang.get(Text::NamespaceVersion)) ": " (namespace.version) }
+                        }
+                    }
+                }
+                @for alias in &aliases {
+                    li {
+                        div class="property-entry" {
+                            div { (alias.alias) " (v" (alias.version) ")" }
+                            div { "→ " (alias.target) }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    generate_page(cont, 2, lang).await
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct NamespaceVersion {
+    plugin: String,
+    version: u32
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PropertyAliases {
+    namespaces: Vec<NamespaceVersion>,
+    aliases: Vec<AliasInfo>
+}
+
+/// Declared namespace versions of the plugins and the aliases of renamed properties
+pub(super) async fn property_aliases(State(datastore): State<DataStoreLocked>) -> Json<PropertyAliases> {
+    let (aliases, namespaces) = list_aliases(datastore).await;
+    Json(PropertyAliases { namespaces, aliases })
+}
+
+async fn list_aliases(datastore: DataStoreLocked) -> (Vec<AliasInfo>, Vec<NamespaceVersion>) {
+    let ds_r = datastore.read().await;
+    let plugins = ds_r.list_plugins();
+    let aliases = ds_r.get_aliases().list(|handle| ds_r.property_label(handle));
+
+    let mut namespaces = ds_r.get_aliases().list_namespaces().into_iter().map(|(id, version)| NamespaceVersion {
+        plugin: plugins.iter().find(|(other, ..)| *other == id).map(|(_, name, ..)| name.clone()).unwrap_or_else(|| id.to_string()),
+        version
+    }).collect::<Vec<_>>();
+    namespaces.sort_by(|a, b| a.plugin.cmp(&b.plugin));
+
+    (aliases, namespaces)
+}
+
+/// Plugins and properties rejected because their name hashes to an id already taken by another name
+pub(super) async fn collisions(lang: Lang) -> Markup {
+    let list = collisions::list();
+
+    let cont = html! {
+        h1 { (lang.get(Text::Collisions)) }
+
+        ul class="property-list" {
+            @if list.is_empty() {
+                li { (lang.get(Text::NoCollisions)) }
+            }
+            @for entry in list {
+                li {
+                    div class="property-entry" {
+                        div { (format!("{:?}", entry.kind)) " " (entry.rejected) " " (lang.get(Text::CollidesWith)) " " (entry.existing) " (id " (entry.id) ")" }
+                        div { (humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_millis(entry.time))) ", " (lang.get(Text::Attempts)) ": " (entry.count) }
+                    }
+                }
+            }
+        }
+    };
+    generate_page(cont, 2, lang).await
+}
+
+pub(super) async fn collision_list() -> Json<Vec<Collision>> {
+    Json(collisions::list())
+}
+
+/// Query parameters of the property name lookup
+#[derive(Debug, Deserialize)]
+pub(super) struct PropertyNameQuery {
+    /// Only names containing this text (case insensitive) are returned
+    filter: Option<String>
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PropertyNameEntry {
+    name: String,
+    #[serde(rename = "type")]
+    sort: String,
+    value: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed: Option<Vec<String>>
+}
+
+/// Lists the names of all properties with their type and current value,
+/// used for autocomplete in the dashboard editor and by external tools
+pub(super) async fn property_names(Query(query): Query<PropertyNameQuery>, State(datastore): State<DataStoreLocked>) -> Json<Vec<PropertyNameEntry>> {
+    let filter = query.filter.unwrap_or_default().to_lowercase();
+
+    let mut list = {
+        let ds_r = datastore.read().await;
+        let mut list = vec![];
+
+        for key in ds_r.iter_properties() {
+            if let (Some(name),Some(cont)) = (ds_r.read_property_name(key),ds_r.get_property_container(key)) {
+                if !name.to_lowercase().contains(filter.as_str()) {
+                    continue;
+                }
+
+                let mut cache = ValueCache::default();
+                cont.read_web(&mut cache);
+                list.push(PropertyNameEntry {
+                    name,
+                    sort: format!("{:?}", cont.get_type()),
+                    value: cache.value,
+                    allowed: cont.allowed_values().map(<[String]>::to_vec)
+                });
+            }
+        }
+
+        list
+    };
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(list)
+}
+
+/// Memory held by the properties (and history buffers) of each plugin
+pub(super) async fn memory_usage(State(datastore): State<DataStoreLocked>) -> Json<Vec<memory::MemoryUsage>> {
+    Json(memory::collect(&*datastore.read().await))
+}
+
+/// Time spend by the plugins in their update function, slowest first
+pub(super) async fn update_times() -> Json<Vec<PluginUpdateTimes>> {
+    Json(profile::collect())
+}
+
+/// Entries of the audit log matching the query, newest first
+pub(super) async fn audit_log(Query(query): Query<AuditQuery>, State(datastore): State<DataStoreLocked>) -> Json<Vec<AuditEntry>> {
+    Json(datastore.read().await.get_audit_log().query(&query))
+}
+
+/// Sets the property to the text of the body, if allowed by web_writable
+pub(super) async fn set_property(Path(name): Path<String>, State(datastore): State<DataStoreLocked>, ConnectInfo(addr): ConnectInfo<SocketAddr>, body: String) -> Response {
+    match super::write_property(datastore, addr.ip(), "api".to_string(), name.as_str(), body.trim()).await {
+        DataStoreReturnCode::Ok => StatusCode::NO_CONTENT.into_response(),
+        DataStoreReturnCode::NotAuthenticated => (StatusCode::FORBIDDEN, format!("{} is not writable from the web", name)).into_response(),
+        DataStoreReturnCode::DoesNotExist => (StatusCode::NOT_FOUND, format!("{} does not exist", name)).into_response(),
+        DataStoreReturnCode::ParameterCorrupted => (StatusCode::BAD_REQUEST, format!("{} is not a valid value for {}", body.trim(), name)).into_response(),
+        code => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write {}: {:?}", name, code)).into_response()
+    }
+}
+
+pub(super) async fn notifications(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
+    // Newest first
+    let list: Vec<Notification> = datastore.read().await.get_notifications().since(0).rev().cloned().collect();
+
+    let cont = html! {
+        h1 { (lang.get(Text::Notifications)) }
+
+        ul class="notification-list" id="NOTIFICATIONS" {
+            @if list.is_empty() {
+                li id="NO_NOTIFICATIONS" { (lang.get(Text::NoNotifications)) }
+            }
+            @for n in list {
+                li {
+                    div class=(format!("notification-entry notification-{}", n.severity.name())) {
+                        div { (humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_millis(n.time as u64))) " - " (n.source) }
+                        div { (n.text) }
+                    }
+                }
+            }
+        }
+
+        script src="/lib/socket.io.js" {}
+        script {
+            // New notifications are added on top while the page is open
+            "const LIST = document.getElementById('NOTIFICATIONS');"
+            "var socket = io();"
+            "socket.on('notification', function(N) {"
+                "const EMPTY = document.getElementById('NO_NOTIFICATIONS');"
+                "if (EMPTY != null) { EMPTY.remove(); }"
+                "const ENTRY = document.createElement('div');"
+                "ENTRY.className = 'notification-entry notification-' + N.severity;"
+                "const HEAD = document.createElement('div');"
+                "HEAD.textContent = new Date(N.time).toISOString().split('.')[0] + 'Z - ' + N.source;"
+                "const TEXT = document.createElement('div');"
+                "TEXT.textContent = N.text;"
+                "ENTRY.append(HEAD, TEXT);"
+                "const ITEM = document.createElement('li');"
+                "ITEM.append(ENTRY);"
+                "LIST.prepend(ITEM);"
+            "});"
+        }
+    };
+    generate_page(cont, 3, lang).await
+}
+
+pub(super) async fn inputs(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
+    inputs_page(datastore, None, lang).await
+}
+
+/// Triggered by the learn button on the inputs page, lists all inputs for a while (not only the mapped ones).
+/// This shows every key typed, so it is only allowed on the host itself
+pub(super) async fn learn_inputs(State(datastore): State<DataStoreLocked>, ConnectInfo(addr): ConnectInfo<SocketAddr>, lang: Lang) -> Markup {
+    let msg = if addr.ip().is_loopback() {
+        inputs::start_learning();
+        lang.get(Text::LearningInputs)
+    } else {
+        lang.get(Text::LearnOnlyLocal)
     };
-    generate_page(cont, 2).await
+
+    inputs_page(datastore, Some(msg), lang).await
+}
+
+async fn inputs_page(datastore: DataStoreLocked, msg: Option<&str>, lang: Lang) -> Markup {
+    let config = datastore.read().await.get_config().get_inputs().clone();
+
+    let cont = html! {
+        h1 { (lang.get(Text::Inputs)) }
+
+        @if !config.is_enabled() {
+            p { (lang.get(Text::InputsDisabled)) }
+        }
+
+        h3 { (lang.get(Text::InputMappings)) }
+        ul class="property-list" {
+            @if config.get_map().is_empty() {
+                li { (lang.get(Text::NoInputMappings)) }
+            }
+            @for mapping in config.get_map() {
+                li {
+                    div class="property-entry" {
+                        div { (mapping.get_input()) }
+                        div { (mapping.describe()) }
+                    }
+                }
+            }
+        }
+
+        h3 { (lang.get(Text::RecentInputs)) }
+        p { (lang.get(Text::PressAnInput)) }
+        form method="post" {
+            button class="button" type="submit" { (lang.get(Text::LearnInputs)) }
+        }
+        @if let Some(msg) = msg {
+            p { (msg) }
+        }
+        ul class="property-list" id="INPUTS" {}
+
+        // Polled, as inputs are not send over the socket
+        script { (PreEscaped(concat!(
+            "const LIST = document.getElementById('INPUTS');",
+            "function update() {",
+                "fetch('/api/inputs').then(res => res.json()).then(INPUTS => {",
+                    "LIST.replaceChildren(...INPUTS.slice(0, 20).map(I => {",
+                        "const ENTRY = document.createElement('div');",
+                        "ENTRY.className = 'property-entry';",
+                        "const NAME = document.createElement('div');",
+                        "NAME.textContent = 'input = \"' + I.input + '\"';",
+                        "const VALUE = document.createElement('div');",
+                        "VALUE.textContent = I.value.toFixed(2) + ' - ' + new Date(I.changed).toISOString().split('.')[0] + 'Z';",
+                        "ENTRY.append(NAME, VALUE);",
+                        "const ITEM = document.createElement('li');",
+                        "ITEM.append(ENTRY);",
+                        "return ITEM;",
+                    "}));",
+                "}).catch(() => {});",
+            "}",
+            "update();",
+            "setInterval(update, 500);"
+        ))) }
+    };
+    generate_page(cont, 4, lang).await
+}
+
+/// Inputs seen since launch, last changed first (inputs without a mapping only while learning, and only for the host itself)
+pub(super) async fn input_list(ConnectInfo(addr): ConnectInfo<SocketAddr>) -> Json<Vec<inputs::SeenInput>> {
+    Json(inputs::seen_inputs(addr.ip().is_loopback()))
+}
+
+pub(super) async fn clients(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
+    let list = list_clients(datastore).await;
+
+    let cont = html! {
+        h1 { (lang.get(Text::Clients)) }
+
+        ul class="property-list" {
+            @if list.is_empty() {
+                li { (lang.get(Text::NoClients)) }
+            }
+            @for client in list {
+                li {
+                    div class="client-entry" {
+                        div {
+                            (client.address) " - "
+                            @if let Some(dash) = &client.dashboard {
+                                (dash.name) " (" (format!("{:?}, {:?}, {:?}", dash.encoding, dash.tier, dash.aggregation).to_lowercase())
+                                @if dash.shared {
+                                    ", " (lang.get(Text::SharedLink))
+                                }
+                                ")"
+                            } @else {
+                                (lang.get(Text::NoDashboard))
+                            }
+                        }
+                        div { (lang.get(Text::ConnectedSince)) ": " (humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_millis(client.connected as u64))) }
+                        @if let Some(dash) = &client.dashboard {
+                            div {
+                                (lang.get(Text::UpdatesReceived)) ": " (dash.updates) " (" (format!("{:.1}", dash.rate)) " " (lang.get(Text::PerSecond)) ")"
+                                br;
+                                @if let Some(last) = dash.last_update {
+                                    (lang.get(Text::SecondsSinceUpdate)) ": " (format!("{:.1}", last as f64 / 1000.0))
+                                } @else {
+                                    (lang.get(Text::NoUpdates))
+                                }
+                            }
+                            details {
+                                summary { (lang.get(Text::SubscribedProperties)) " (" (dash.properties.len()) ")" }
+                                @for name in &dash.properties {
+                                    div { (name) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    generate_page(cont, 5, lang).await
+}
+
+/// Connected dashboard clients with their subscriptions and update rate
+pub(super) async fn client_list(State(datastore): State<DataStoreLocked>) -> Json<Vec<ClientInfo>> {
+    Json(list_clients(datastore).await)
+}
+
+async fn list_clients(datastore: DataStoreLocked) -> Vec<ClientInfo> {
+    let ds_r = datastore.read().await;
+    clients::list(|handle| ds_r.property_label(handle))
+}
+
+pub(super) async fn settings(State(datastore): State<DataStoreLocked>, lang: Lang) -> Markup {
+    settings_page(datastore, None, lang).await
+}
+
+/// Triggered by the reload button on the settings page
+pub(super) async fn reload_config(State(datastore): State<DataStoreLocked>, ConnectInfo(addr): ConnectInfo<SocketAddr>, lang: Lang) -> Markup {
+    let msg = if datastore.read().await.get_config().is_web_read_only() {
+        lang.get(Text::ReadOnly).to_string()
+    } else {
+        reload_and_audit(datastore, addr, "settings", lang).await
+    };
+
+    settings_page(datastore, Some(msg), lang).await
+}
+
+/// Reloads the config, records the changed settings in the audit log and returns the message for the user
+async fn reload_and_audit(datastore: DataStoreLocked, addr: SocketAddr, source: &str, lang: Lang) -> String {
+    let before = datastore.read().await.get_config().clone();
+    let res = crate::config::reload_config(datastore).await;
+
+    {
+        let ds_r = datastore.read().await;
+        let file = ds_r.get_config().get_audit_log();
+        for (setting, old, new) in audit::diff_settings(&before, ds_r.get_config()) {
+            let mut entry = AuditEntry::new(AuditAction::Setting, addr.ip().to_string(), source.to_string(), setting);
+            entry.old_value = old;
+            entry.new_value = new;
+            ds_r.get_audit_log().record(file.as_deref(), entry);
+        }
+    }
+
+    match res {
+        Ok(restart) if restart.is_empty() => lang.get(Text::ConfigReloaded).to_string(),
+        Ok(restart) => format!("{}: {}", lang.get(Text::RestartRequired), restart.join(", ")),
+        Err(e) => {
+            error!("Failed to reload config, keeping previous config: {}", e);
+            format!("{}: {}", lang.get(Text::ReloadFailed), e)
+        }
+    }
+}
+
+/// Downloads a backup of the config and dashboards
+pub(super) async fn download_backup(State(datastore): State<DataStoreLocked>) -> Response {
+    let config = datastore.read().await.get_config().clone();
+    let res = tokio::task::spawn_blocking(move || {
+        let backup = backup::Backup::create(&config)?;
+        Ok::<_, String>((backup.file_name(), backup.to_json()?))
+    }).await.unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok((name, json)) => ([(CONTENT_TYPE, "application/json".to_string()), (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name))], json).into_response(),
+        Err(e) => {
+            error!("Failed to create backup: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
 }
 
-pub(super) async fn settings() -> Markup {
+/// Restores the backup send as body and reloads the config, the previous state is saved as a
+/// backup in the working directory
+pub(super) async fn restore_backup(State(datastore): State<DataStoreLocked>, ConnectInfo(addr): ConnectInfo<SocketAddr>, headers: HeaderMap, lang: Lang, body: String) -> Response {
+    let config = datastore.read().await.get_config().clone();
+    if config.is_web_read_only() {
+        return (StatusCode::FORBIDDEN, lang.get(Text::ReadOnly)).into_response();
+    }
+    // This replaces the whole config (including the whitelist), so only the host itself may do it
+    if !addr.ip().is_loopback() {
+        return (StatusCode::FORBIDDEN, lang.get(Text::RestoreOnlyLocal)).into_response();
+    }
+    // Browsers can only send json to another site after asking it (which we never allow), unlike text
+    if !headers.get(CONTENT_TYPE).and_then(|sort| sort.to_str().ok()).is_some_and(|sort| sort.starts_with("application/json")) {
+        return (StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("{}: Content-Type has to be application/json", lang.get(Text::RestoreFailed))).into_response();
+    }
+    let res = tokio::task::spawn_blocking(move || {
+        backup::Backup::parse(body.as_str())?.restore(Some(&config), std::path::Path::new("."))
+    }).await.unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok(previous) => {
+            let msg = reload_and_audit(datastore, addr, "restore", lang).await;
+            format!("{} ({}: {})", msg, lang.get(Text::PreviousStateSaved), previous.display()).into_response()
+        },
+        Err(e) => {
+            error!("Failed to restore backup: {}", e);
+            (StatusCode::BAD_REQUEST, format!("{}: {}", lang.get(Text::RestoreFailed), e)).into_response()
+        }
+    }
+}
+
+async fn settings_page(datastore: DataStoreLocked, msg: Option<String>, lang: Lang) -> Markup {
+    let (config, read_only) = {
+        let ds_r = datastore.read().await;
+        (toml::to_string(ds_r.get_config()).unwrap_or_else(|e| format!("{}: {}", lang.get(Text::UnableToDisplayConfig), e)), ds_r.get_config().is_web_read_only())
+    };
+
     let cont = html! {
-        h1 style="font-style: italic;" { "Todo..." }
+        h1 { (lang.get(Text::Settings)) }
+
+        h3 { (crate::config::get_config_path().to_str().unwrap_or_default()) }
+        pre class="config-view" { (config) }
+
+        @if read_only {
+            p { (lang.get(Text::ReadOnly)) }
+        } @else {
+            form method="post" {
+                button class="button" type="submit" { (lang.get(Text::ReloadConfig)) }
+            }
+        }
+        @if let Some(msg) = msg {
+            p { (msg) }
+        }
+
+        h2 { (lang.get(Text::Backup)) }
+        p { (lang.get(Text::BackupDescription)) }
+        a class="button" href="./setting/backup" { (lang.get(Text::DownloadBackup)) }
+        @if !read_only {
+            div {
+                input type="file" id="RESTORE_FILE" accept=".json,application/json";
+                button class="button" id="RESTORE" { (lang.get(Text::RestoreBackup)) }
+            }
+            p id="RESTORE_RESULT" {}
+            script {
+                (PreEscaped(concat!(
+                    "document.getElementById('RESTORE').addEventListener('click', async () => {",
+                    "const FILE = document.getElementById('RESTORE_FILE').files[0];",
+                    "if (!FILE) { return; }",
+                    "const RES = await fetch('./setting/restore', { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: await FILE.text() });",
+                    "document.getElementById('RESTORE_RESULT').textContent = await RES.text();",
+                    "});"
+                )))
+            }
+        }
     };
-    generate_page(cont, 3).await
+    generate_page(cont, 6, lang).await
 }
 
-pub(super) async fn load_dashboard(Path(path): Path<String>, State(datastore): State<DataStoreLocked>) -> Response {
+/// How long each dashboard is shown when rotating in kiosk mode, if no interval is set
+const DEFAULT_ROTATE_INTERVAL: u64 = 30;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum RenderMode {
+    #[default]
+    Normal,
+    Kiosk
+}
+
+/// Query parameters of the dashboard render
+#[derive(Debug, Deserialize)]
+pub(super) struct RenderOptions {
+    #[serde(default)]
+    mode: RenderMode,
+    /// Comma separated list of dashboards to rotate through (kiosk mode only)
+    rotate: Option<String>,
+    /// Seconds each dashboard is shown while rotating
+    interval: Option<u64>
+}
+
+pub(super) async fn load_dashboard(Path(path): Path<String>, Query(options): Query<RenderOptions>, State(datastore): State<DataStoreLocked>) -> Response {
     match super::get_dashboard(datastore, path.clone()).await {
+        Ok(dash) if options.mode == RenderMode::Kiosk => {
+            let rotate: Vec<String> = options.rotate.unwrap_or_default().split(',')
+                .map(|d| d.trim().to_string())
+                .filter(|d| !d.is_empty())
+                .collect();
+            let interval = options.interval.unwrap_or(DEFAULT_ROTATE_INTERVAL).max(1);
+
+            html!{
+                (dash)
+                (kiosk_script(path.as_str(), rotate.as_slice(), interval))
+            }.into_response()
+        },
         Ok(dash) => html!{ (dash) }.into_response(),
         Err(e) => e.into_response(path)
     }
 }
 
-pub(super) async fn edit_dashboard(Path(path): Path<String>, State(datastore): State<DataStoreLocked>) -> Result<Markup, Response> {
-    let mut folder = super::get_dashboard_folder(datastore).await.map_err(|e| e.into_response(path.clone()))?;
+/// Query parameters of the share link creation
+#[derive(Debug, Deserialize)]
+pub(super) struct ShareOptions {
+    /// How long the link is valid
+    hours: Option<u64>
+}
+
+/// Preview image of the dashboard, shown on the dashboards page
+pub(super) async fn dashboard_preview(Path(path): Path<String>, State(datastore): State<DataStoreLocked>) -> Response {
+    match super::get_dashboard_preview(datastore, path.clone()).await {
+        Ok(svg) => ([(CONTENT_TYPE, "image/svg+xml")], svg).into_response(),
+        Err(e) => e.into_response(path)
+    }
+}
+
+/// Creates a share link for the dashboard, which also works for clients outside the whitelist
+pub(super) async fn share_dashboard(Path(path): Path<String>, Query(options): Query<ShareOptions>, headers: HeaderMap, State(datastore): State<DataStoreLocked>, lang: Lang) -> Result<Markup, Response> {
+    // Only existing dashboards can be shared
+    let dash = super::get_dashboard(datastore, path.clone()).await.map_err(|e| e.into_response(path.clone()))?;
+
+    let hours = options.hours.unwrap_or(share::DEFAULT_SHARE_HOURS).clamp(1, share::MAX_SHARE_HOURS);
+    let Some(share) = share::create_share(datastore.read().await.get_config(), path.as_str(), Duration::from_secs(hours * 3600)) else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "Sharing is disabled: no secret to sign the links with, set share_secret in the config").into_response());
+    };
+    let valid_until = UNIX_EPOCH.checked_add(Duration::from_secs(share.expires)).map(humantime::format_rfc3339_seconds);
+
+    let link = format!("/shared/{}?expires={}&token={}", path, share.expires, share.token);
+    let link = match headers.get(HOST).and_then(|host| host.to_str().ok()) {
+        Some(host) => format!("http://{}{}", host, link),
+        None => link
+    };
+
+    let cont = html! {
+        h1 { (lang.get(Text::Share)) ": " (dash.name) }
+
+        p {
+            (lang.get(Text::ShareLinkValidUntil)) " "
+            @if let Some(valid_until) = valid_until { (valid_until) } @else { (share.expires) }
+        }
+        pre class="config-view" { a href=(link) { (link) } }
+
+        form method="get" {
+            label for="hours" { (lang.get(Text::ValidForHours)) " " }
+            input type="number" id="hours" name="hours" min="1" max=(share::MAX_SHARE_HOURS) value=(hours);
+            button class="button" type="submit" { (lang.get(Text::CreateLink)) }
+        }
+    };
+    Ok(generate_page(cont, 1, lang).await)
+}
+
+/// Dashboard opened through a share link
+pub(super) async fn load_shared_dashboard(Path(path): Path<String>, Query(share): Query<ShareQuery>, State(datastore): State<DataStoreLocked>) -> Response {
+    if !share::verify_share(datastore.read().await.get_config(), path.as_str(), &share) {
+        return (StatusCode::FORBIDDEN, "Forbidden: this share link is invalid or has expired").into_response();
+    }
+
+    match super::get_dashboard(datastore, path.clone()).await {
+        Ok(dash) => dash.render_shared(serde_json::json!({ "share_dashboard": path, "expires": share.expires, "token": share.token })).into_response(),
+        Err(e) => e.into_response(path)
+    }
+}
+
+pub(super) async fn edit_dashboard(Path(path): Path<String>, State(datastore): State<DataStoreLocked>, lang: Lang) -> Result<Markup, Response> {
+    if datastore.read().await.get_config().is_web_read_only() {
+        return Err((StatusCode::FORBIDDEN, lang.get(Text::ReadOnly)).into_response());
+    }
+
+    let folder = super::get_dashboard_folder(datastore).await.map_err(|e| e.into_response(path.clone()))?;
 
     let test_dash = Dashboard {
         size_x: 1000,
@@ -243,13 +904,22 @@ pub(super) async fn edit_dashboard(Path(path): Path<String>, State(datastore): S
                 size_x: Property::Fixed(500),
                 size_y: Property::Fixed(400),
                 visible: Property::Fixed(true),
-                element: super::dashboard::DashElementType::Square("red".to_string()) 
+                element: super::dashboard::DashElementType::Square("red".to_string()),
+                z: None,
+                opacity: None,
+                transition: None,
+                blink: None,
+                on_event: Vec::new()
             }]
     };
 
-    folder.push(path.as_str());
-    folder.set_extension("json");
-
+    let file = super::dashboard_file(folder, path.as_str()).ok_or(FsResourceError::DoesNotExist).map_err(|e| e.into_response(path.clone()))?;
+    if let Some(parent) = file.parent() {
+        if let Err(e) = fs::create_dir_all(parent).await {
+            error!("Unable to create folder for Dashboard {}: {}", path, e);
+            return Err(FsResourceError::from(e).into_response(path));
+        }
+    }
 
     let json = match serde_json::to_string_pretty(&test_dash) {
         Ok(val) => val,
@@ -259,7 +929,7 @@ pub(super) async fn edit_dashboard(Path(path): Path<String>, State(datastore): S
         }
     };
 
-    if let Err(e) = fs::write(folder.as_path(), json.as_bytes()).await {
+    if let Err(e) = fs::write(file.as_path(), json.as_bytes()).await {
         error!("Unable to save Dashboard {}: {}", path, e);
         return Err(FsResourceError::from(e).into_response(path));
     }
@@ -0,0 +1,131 @@
+//! Share links: a dashboard can be opened through a signed link that expires,
+//! so a single read-only dashboard can be given to clients outside the web whitelist.
+//!
+//! The token is a HMAC-SHA256 over the dashboard and the expiry, keyed with the share_secret
+//! of the config (or a random secret generated on launch, so links only last till a restart).
+//! If neither is available sharing is disabled.
+
+use std::{sync::OnceLock, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use axum::{extract::Query, http::Uri};
+use log::error;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// How long a link is valid if no duration is given
+pub(super) const DEFAULT_SHARE_HOURS: u64 = 24;
+/// Links can be valid for at most a year
+pub(super) const MAX_SHARE_HOURS: u64 = 24 * 366;
+
+/// Query parameters of a share link, also send by the client when connecting to the socket
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct ShareQuery {
+    /// Seconds since the unix epoch
+    pub(super) expires: u64,
+    pub(super) token: String
+}
+
+/// Used when the config has no share_secret, None if it could not be generated
+static RANDOM_SECRET: OnceLock<Option<[u8; 32]>> = OnceLock::new();
+
+/// None disables sharing, as any fallback (like the time or a fixed key) would make the links guessable
+fn secret(config: &Config) -> Option<Vec<u8>> {
+    if let Some(secret) = config.get_share_secret() {
+        return Some(secret.as_bytes().to_vec());
+    }
+
+    RANDOM_SECRET.get_or_init(|| {
+        let mut buf = [0u8; 32];
+        match getrandom::getrandom(&mut buf) {
+            Ok(()) => Some(buf),
+            Err(e) => {
+                error!("Unable to generate a secret for share links, sharing is disabled till share_secret is set in the config: {}", e);
+                None
+            }
+        }
+    }).map(|secret| secret.to_vec())
+}
+
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(msg)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn sign(config: &Config, dashboard: &str, expires: u64) -> Option<String> {
+    let msg = format!("{}|{}", dashboard, expires);
+    Some(hmac_sha256(secret(config)?.as_slice(), msg.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Creates the query of a share link for the dashboard, valid for the given duration (at most MAX_SHARE_HOURS).
+/// None if sharing is disabled
+pub(super) fn create_share(config: &Config, dashboard: &str, valid: Duration) -> Option<ShareQuery> {
+    let expires = now().checked_add(valid.min(Duration::from_secs(MAX_SHARE_HOURS * 3600)).as_secs())?;
+    Some(ShareQuery { expires, token: sign(config, dashboard, expires)? })
+}
+
+/// Checks the token belongs to the dashboard and has not expired yet
+pub(super) fn verify_share(config: &Config, dashboard: &str, share: &ShareQuery) -> bool {
+    if share.expires < now() {
+        return false;
+    }
+
+    let Some(expected) = sign(config, dashboard, share.expires) else {
+        return false;
+    };
+    // Comparing every byte, so the time taken does not reveal how much of the token was correct
+    expected.len() == share.token.len() &&
+        expected.bytes().zip(share.token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Reads the share from the query of a socket connection, which also contains the dashboard
+pub(super) fn parse_socket_share(uri: &Uri) -> Option<(String, ShareQuery)> {
+    #[derive(Deserialize)]
+    struct SocketShare {
+        share_dashboard: String,
+        expires: u64,
+        token: String
+    }
+
+    let Query(share) = Query::<SocketShare>::try_from_uri(uri).ok()?;
+    Some((share.share_dashboard, ShareQuery { expires: share.expires, token: share.token }))
+}
+
+/// If a client outside the whitelist may access this: the shared dashboard, the scripts it loads
+/// and the socket connection of it
+pub(super) fn is_share_request(config: &Config, uri: &Uri) -> bool {
+    let path = uri.path();
+
+    if path == "/lib/socket.io.js" || path == "/lib/datarace.dash.js" {
+        return true;
+    }
+    if let Some(dashboard) = path.strip_prefix("/shared/") {
+        return Query::<ShareQuery>::try_from_uri(uri).is_ok_and(|Query(share)| verify_share(config, dashboard, &share));
+    }
+    if path.starts_with("/socket.io") {
+        return parse_socket_share(uri).is_some_and(|(dashboard, share)| verify_share(config, dashboard.as_str(), &share));
+    }
+
+    false
+}
@@ -1,13 +1,25 @@
-use hashbrown::HashMap;
-use tokio::time::{self, Duration, Instant};
+use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::atomic::{AtomicUsize, Ordering}};
+
+use axum::extract::ConnectInfo;
+
+use hashbrown::{HashMap, HashSet};
+use tokio::time::{self, Instant};
 use kanal::AsyncReceiver;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
-use socketioxide::{extract::{Data, SocketRef, State}, SocketIo};
+use tracing::Instrument;
+use socketioxide::{extract::{AckSender, Data, SocketRef, State}, SocketIo};
+
+use crate::{datastore::DataStore, frames::FrameReader, notifications::Notification, utils::{Value, ValueCache}, DataStoreReturnCode, PropertyHandle};
+
+use super::{clients, downsample::{self, Aggregation, Downsample, Tier}, events::{self, DashboardEvents}, utils::{Auth, DataStoreLocked, SocketChMsg, SocketDataRef}};
 
-use crate::{utils::{Value, ValueCache}, PropertyHandle};
+/// Version of the dashboard socket protocol, has to be increased on incompatible changes.
+/// Send to the client with require-auth, and the client has to send it back in auth-dashboard
+pub(super) const PROTOCOL_VERSION: u32 = 3;
 
-use super::utils::{DataStoreLocked, SocketChMsg, SocketDataRef};
+/// Optional features the client can make use of, announced in require-auth
+const FEATURES: [&str; 2] = ["binary", "text"];
 
 pub(super) async fn create_socketio_layer(datastore: DataStoreLocked) -> socketioxide::layer::SocketIoLayer {
     let (store,rx) = super::utils::SocketData::new(datastore);
@@ -23,13 +35,47 @@ pub(super) async fn create_socketio_layer(datastore: DataStoreLocked) -> socketi
     layer
 }
 
+/// Number of currently connected sockets
+static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+pub(super) fn count_clients() -> usize {
+    CLIENTS.load(Ordering::Acquire)
+}
+
 async fn on_connect(socket: SocketRef) {
     debug!("Someone is trying to connect, {}", socket.id);
+    CLIENTS.fetch_add(1, Ordering::AcqRel);
+    clients::connect(socket.id, client_address(&socket));
 
     // For some reason I can't serialize the Plugin version through Serializer,
     // the function just isn't called
-    socket.on("auth-dashboard", |socket: SocketRef, Data(name): Data<String>, State(store): State<SocketDataRef>| async move {
-        debug!("{} socket trying to auth as dashboard {}", socket.id, &name);
+    socket.on("auth-dashboard", |socket: SocketRef, Data(auth): Data<DashboardAuth>, State(store): State<SocketDataRef>| async move {
+        let (name, encoding, session, tier, aggregation) = match auth {
+            // Clients from before the handshake, they only know json
+            DashboardAuth::Name(name) => (name, Encoding::Json, None, Tier::Realtime, Aggregation::Last),
+            DashboardAuth::Options { name, encoding, protocol: Some(PROTOCOL_VERSION), session, tier, aggregation } => (name, encoding, session, tier, aggregation),
+            DashboardAuth::Options { name, protocol, .. } => {
+                let protocol = protocol.map(|p| p.to_string()).unwrap_or("unknown".to_string());
+                error!("Dashboard {} uses protocol version {}, but this server uses {}, rejecting", name, protocol, PROTOCOL_VERSION);
+
+                let _ = socket.emit("auth-error", format!("Dashboard uses protocol version {}, but DataRace uses {}. Please reload the dashboard", protocol, PROTOCOL_VERSION));
+                let _ = socket.disconnect();
+                return;
+            }
+        };
+        debug!("{} socket trying to auth as dashboard {} ({:?})", socket.id, &name, encoding);
+
+        // Clients of a share link only get the shared dashboard
+        let share = super::share::parse_socket_share(&socket.req_parts().uri);
+        if let Some((shared, _)) = &share {
+            if shared != &name {
+                error!("Socket {} connected through the share link of {}, but tried to auth as dashboard {}, rejecting", socket.id, shared, name);
+
+                let _ = socket.emit("auth-error", "This link only grants access to a different dashboard");
+                let _ = socket.disconnect();
+                return;
+            }
+        }
 
         if store.get_auth(&socket.id).await.is_some() {
             // This is an error, you should not be able to auth twice
@@ -37,8 +83,45 @@ async fn on_connect(socket: SocketRef) {
             return;
         }
 
-        store.insert_dashboard(socket.id, name.clone()).await;
-        let _ = socket.join(format!("dash.{}", name));
+        let room = downsample::room_name(name.as_str(), encoding, tier, aggregation);
+        let _ = socket.join(events::event_room(name.as_str()));
+        clients::set_dashboard(socket.id, name.as_str(), encoding, tier, aggregation, share.is_some());
+        if tier != Tier::Realtime {
+            let _ = store.sender.send(SocketChMsg::Downsample(name.clone(), tier, aggregation)).await;
+        }
+
+        // A client that reconnected takes over its old subscriptions, and only it needs the current values
+        if let Some(token) = session {
+            if store.resume_session(socket.id, name.as_str(), token.as_str()).await {
+                debug!("{} socket resumed session of dashboard {}", socket.id, &name);
+                let _ = socket.join(room);
+                let _ = store.sender.send(SocketChMsg::Snapshot(socket.id, name, encoding)).await;
+                return;
+            }
+        }
+
+        if let Some(token) = store.create_session(socket.id, name.clone()).await {
+            let _ = socket.emit("session", token);
+        }
+        let _ = socket.join(room);
+    });
+
+    // Answered through the ack with the DataStoreReturnCode (like "Ok" or "NotAuthenticated")
+    socket.on("set-property", |socket: SocketRef, Data(req): Data<SetProperty>, ack: AckSender, State(store): State<SocketDataRef>| async move {
+        // Shared dashboards are read-only
+        let code = match store.get_auth(&socket.id).await {
+            Some(Auth::Dashboard(name)) if super::share::parse_socket_share(&socket.req_parts().uri).is_none() => {
+                // Numbers and booleans are taken as their text, same as strings
+                let value = match req.value {
+                    serde_json::Value::String(text) => text,
+                    other => other.to_string()
+                };
+                super::write_property(store.datastore, client_address(&socket), format!("dashboard {}", name), req.name.as_str(), value.as_str()).await
+            },
+            _ => DataStoreReturnCode::NotAuthenticated
+        };
+
+        let _ = ack.send(format!("{:?}", code));
     });
 
     // socket.on("message", |socket: SocketRef, Data(data): Data<serde_json::Value>, State(store): State<SocketDataRef>| async move {
@@ -66,89 +149,344 @@ async fn on_connect(socket: SocketRef) {
 
     socket.on_disconnect(|socket: SocketRef, State(store): State<SocketDataRef>| async move {
         store.remove_auth(&socket.id).await;
+        CLIENTS.fetch_sub(1, Ordering::AcqRel);
+        clients::disconnect(&socket.id);
 
         debug!("Left *big sad*");
     });
     
-    let _ = socket.emit("require-auth", ());
+    let _ = socket.emit("require-auth", serde_json::json!({ "protocol": PROTOCOL_VERSION, "features": FEATURES }));
 }
 
-const UPDATE_RATE: Duration = Duration::from_millis(10);
+fn client_address(socket: &SocketRef) -> IpAddr {
+    socket.req_parts().extensions.get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip()).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
 
-type UpdatePackage = Vec<(PropertyHandle, Value)>;
+/// Send by the client on auth-dashboard, either only the name (clients from before the handshake, receiving json)
+/// or an object with the name, the encoding and the protocol version
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DashboardAuth {
+    Name(String),
+    Options {
+        name: String,
+        #[serde(default)]
+        encoding: Encoding,
+        #[serde(default)]
+        protocol: Option<u32>,
+        /// Token of the session from before a reconnect
+        #[serde(default)]
+        session: Option<String>,
+        #[serde(default)]
+        tier: Tier,
+        #[serde(default)]
+        aggregation: Aggregation
+    }
+}
+
+/// Send by a dashboard on set-property
+#[derive(Debug, Deserialize)]
+struct SetProperty {
+    name: String,
+    value: serde_json::Value
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Encoding {
+    /// updates are send as update event with json
+    #[default]
+    Json,
+    /// updates are send as update-bin event with a single binary attachment, see encode_binary
+    Binary,
+    /// updates are send as update-text event with the json as a string, which is serialized once for the
+    /// whole room (socket.io serializes json for every client again, which adds up with many clients)
+    Text
+}
+
+pub(super) type UpdatePackage = Vec<(PropertyHandle, Value)>;
+
+/// Encodes the update into the compact binary frame (all numbers little endian).
+/// For every entry:
+/// - u64 plugin id, u64 property id
+/// - the value: u8 tag followed by the payload
+///
+/// Value tags:
+/// - 0 None
+/// - 1 Int (i64)
+/// - 2 Float (f64)
+/// - 3 Bool (u8)
+/// - 4 Str (u32 byte length, utf8)
+/// - 5 Dur (i64)
+/// - 6 Arr (u32 count, values)
+/// - 7 ArrUpdate (u32 count, for each u32 index and value)
+/// - 8 Time (i64, micro seconds since the unix epoch)
+/// - 9 Vector (3 f64: x, y, z)
+///
+/// The decoder is in datarace.dash.js
+fn encode_binary(list: &UpdatePackage) -> Vec<u8> {
+    fn encode_value(buf: &mut Vec<u8>, value: &Value) {
+        match value {
+            Value::None => buf.push(0),
+            Value::Int(i) => {
+                buf.push(1);
+                buf.extend_from_slice(&i.to_le_bytes());
+            },
+            Value::Float(f) => {
+                buf.push(2);
+                buf.extend_from_slice(&f.to_le_bytes());
+            },
+            Value::Bool(b) => {
+                buf.push(3);
+                buf.push(*b as u8);
+            },
+            Value::Str(s) => {
+                buf.push(4);
+                buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+            },
+            Value::Dur(d) => {
+                buf.push(5);
+                buf.extend_from_slice(&d.to_le_bytes());
+            },
+            Value::Time(t) => {
+                buf.push(8);
+                buf.extend_from_slice(&t.to_le_bytes());
+            },
+            Value::Vector(x, y, z) => {
+                buf.push(9);
+                buf.extend_from_slice(&x.to_le_bytes());
+                buf.extend_from_slice(&y.to_le_bytes());
+                buf.extend_from_slice(&z.to_le_bytes());
+            },
+            Value::Arr(arr) => {
+                buf.push(6);
+                buf.extend_from_slice(&(arr.len() as u32).to_le_bytes());
+                for item in arr {
+                    encode_value(buf, item);
+                }
+            },
+            Value::ArrUpdate(changes) => {
+                buf.push(7);
+                buf.extend_from_slice(&(changes.len() as u32).to_le_bytes());
+                for (index, item) in changes {
+                    buf.extend_from_slice(&(*index as u32).to_le_bytes());
+                    encode_value(buf, item);
+                }
+            }
+        }
+    }
+
+    let mut buf = Vec::new();
+    for (handle, value) in list {
+        buf.extend_from_slice(&handle.plugin.to_le_bytes());
+        buf.extend_from_slice(&handle.property.to_le_bytes());
+        encode_value(&mut buf, value);
+    }
+
+    buf
+}
 
 async fn update(io: SocketIo, datastore: SocketDataRef, rx: AsyncReceiver<SocketChMsg>) {
     let mut props = HashMap::<PropertyHandle, (ValueCache, Vec<String>)>::new();
     let mut cache = HashMap::<String, (UpdatePackage, usize)>::new();
+    let mut downsamples = HashMap::<String, Vec<Downsample>>::new();
+    let mut events = DashboardEvents::new(datastore.datastore.read().await.get_event_channel());
+    // Only notifications raised after the start are send out, older ones are on the notifications page
+    let mut last_notification = datastore.datastore.read().await.get_notifications().last_id();
+    // Stale properties last send to each dashboard, dashboards missing here get the list on the next cycle
+    let mut stale_sent = HashMap::<String, HashSet<PropertyHandle>>::new();
 
     loop {
         // Timing start
-        let update_cycle_end_time = Instant::now() + UPDATE_RATE;
-
-        // Code start, aquiring messages
-        if let Ok(Some(msg)) = rx.try_recv() {
-            process_msg(msg, datastore, &mut props, &mut cache).await;
-        }
+        let cycle_start = Instant::now();
 
-        // Updating
-        let ds_r = datastore.datastore.read().await;
-        for (handle, (value_cache, dashes)) in props.iter_mut() {
-            let new = if let Some(cont) = ds_r.get_property_container(handle) {
-                cont.read_web(value_cache)
-            } else {
-                if value_cache.value != Value::None {
-                    value_cache.value = Value::None;
-                    true
-                } else {
-                    false
+        let span = tracing::trace_span!("socket_update_cycle", properties = props.len(), dashboards = cache.len());
+        let update_cycle_end_time = async {
+            // Code start, aquiring messages
+            if let Ok(Some(msg)) = rx.try_recv() {
+                // New and resumed clients do not know which properties are stale
+                if let SocketChMsg::AddDashboard(name) | SocketChMsg::Snapshot(_, name, _) = &msg {
+                    stale_sent.remove(name);
                 }
-            };
-            
-            if new {
-                let val = if let Some(arr) = &value_cache.change {
-                    Value::ArrUpdate(arr.clone())
+                process_msg(msg, &io, datastore, &mut props, &mut cache, &mut downsamples, &mut events).await;
+            }
+            events.forward(&io).await;
+
+            // Updating
+            let ds_r = datastore.datastore.read().await;
+            // Read every cycle, so a config reload applies immediatly
+            let update_cycle_end_time = cycle_start + ds_r.get_config().get_websocket_update_rate();
+            let array_delta = ds_r.get_config().get_websocket_array_delta();
+            let mut frames = FrameReader::default();
+            for (handle, (value_cache, dashes)) in props.iter_mut() {
+                let new = if let Some(cont) = ds_r.get_property_container(handle) {
+                    // Groups in the middle of a frame are sent next cycle
+                    frames.can_read(handle) && cont.read_web_delta(value_cache, array_delta)
                 } else {
-                    value_cache.value.clone()
+                    if value_cache.value != Value::None {
+                        value_cache.value = Value::None;
+                        true
+                    } else {
+                        false
+                    }
                 };
+            
+                if new {
+                    let val = if let Some(arr) = &value_cache.change {
+                        Value::ArrUpdate(arr.clone())
+                    } else {
+                        value_cache.value.clone()
+                    };
 
-                for d in dashes {
-                    if let Some((list, _)) = cache.get_mut(d) {
-                        list.push((handle.clone(), val.clone()));
+                    for d in dashes {
+                        if let Some((list, _)) = cache.get_mut(d) {
+                            list.push((handle.clone(), val.clone()));
+                        }
                     }
                 }
             }
-        }
-        drop(ds_r);
+            drop(frames);
+            let mut stale = stale_properties(&ds_r, &props);
+            let notifications: Vec<Notification> = ds_r.get_notifications().since(last_notification).cloned().collect();
+            drop(ds_r);
 
-        // Sending
-        for (name, (list, _)) in cache.iter_mut() {
-            if !list.is_empty() {
-                if let Err(e) = io.within(format!("dash.{}", name)).emit("update", [&list]) {
-                    error!("Failed to send update to dashboard {}: {}", name, e);
-                } else {
-                    list.clear();
+            // Notifications go to every client (dashboards and the notifications page)
+            for n in notifications {
+                last_notification = n.id;
+                if let Err(e) = io.emit("notification", &n) {
+                    error!("Failed to send notification: {}", e);
                 }
             }
 
-            // let _ = io.within(format!("dash.{}", name)).emit("test", format!("FreeBird!")).ok();
-        }
+            // The full list of stale properties is send whenever it changes
+            stale_sent.retain(|name, _| cache.contains_key(name));
+            for name in cache.keys() {
+                let current = stale.remove(name).unwrap_or_default();
+                if stale_sent.get(name) == Some(&current) {
+                    continue;
+                }
+
+                let list: Vec<&PropertyHandle> = current.iter().collect();
+                if let Err(e) = io.within(events::event_room(name)).emit("stale", [&list]) {
+                    error!("Failed to send stale properties to dashboard {}: {}", name, e);
+                }
+                stale_sent.insert(name.clone(), current);
+            }
+
+            // Sending
+            for (name, (list, _)) in cache.iter_mut() {
+                let mut tiers = downsamples.get_mut(name);
+
+                if !list.is_empty() {
+                    if let Some(tiers) = tiers.as_mut() {
+                        for ds in tiers.iter_mut() {
+                            ds.add(list);
+                        }
+                    }
+
+                    if send_update(&io, name.as_str(), Tier::Realtime, Aggregation::Last, list) {
+                        list.clear();
+                    }
+                }
+
+                for ds in tiers.into_iter().flatten() {
+                    if let Some(package) = ds.take_due(cycle_start) {
+                        send_update(&io, name.as_str(), ds.tier, ds.aggregation, &package);
+                    }
+                }
+
+                // let _ = io.within(format!("dash.{}", name)).emit("test", format!("FreeBird!")).ok();
+            }
+
+            update_cycle_end_time
+        }.instrument(span).await;
 
         // Sleeping to keep the update rate
         time::sleep_until(update_cycle_end_time).await;
     }
 }
 
+/// The stale properties used by each dashboard (dashboards without any are missing).
+/// Aliases are stale when their target is
+fn stale_properties(ds: &DataStore, props: &HashMap<PropertyHandle, (ValueCache, Vec<String>)>) -> HashMap<String, HashSet<PropertyHandle>> {
+    let mut res = HashMap::<String, HashSet<PropertyHandle>>::new();
+    let list: HashSet<PropertyHandle> = crate::stale::list().into_iter().collect();
+    if list.is_empty() {
+        return res;
+    }
+
+    for (handle, (_, dashes)) in props.iter() {
+        let target = ds.get_aliases().resolve(handle).unwrap_or(*handle);
+        if list.contains(&target) {
+            for d in dashes {
+                res.entry(d.clone()).or_default().insert(*handle);
+            }
+        }
+    }
+
+    res
+}
+
+/// Sends the update to the rooms of the dashboard tier, each encoding only if anyone is in its room.
+/// Returns false if sending the json failed
+fn send_update(io: &SocketIo, name: &str, tier: Tier, aggregation: Aggregation, list: &UpdatePackage) -> bool {
+    let room = downsample::room_name(name, Encoding::Json, tier, aggregation);
+    let sent = if !has_clients(io, room.as_str()) {
+        true
+    } else if let Err(e) = io.within(room.clone()).emit("update", [list]) {
+        error!("Failed to send update to dashboard {}: {}", name, e);
+        false
+    } else {
+        clients::record_update(room.as_str());
+        true
+    };
+
+    // Only encode if there is someone using binary
+    let bin_room = downsample::room_name(name, Encoding::Binary, tier, aggregation);
+    if has_clients(io, bin_room.as_str()) {
+        if let Err(e) = io.within(bin_room.clone()).bin(vec![encode_binary(list)]).emit("update-bin", Vec::<u8>::new()) {
+            error!("Failed to send binary update to dashboard {}: {}", name, e);
+        } else {
+            clients::record_update(bin_room.as_str());
+        }
+    }
+
+    let text_room = downsample::room_name(name, Encoding::Text, tier, aggregation);
+    if has_clients(io, text_room.as_str()) {
+        let res = serde_json::to_string(list).map_err(|e| e.to_string())
+            .and_then(|text| io.within(text_room.clone()).emit("update-text", text).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            error!("Failed to send text update to dashboard {}: {}", name, e);
+        } else {
+            clients::record_update(text_room.as_str());
+        }
+    }
+
+    sent
+}
+
+fn has_clients(io: &SocketIo, room: &str) -> bool {
+    io.within(room.to_string()).sockets().is_ok_and(|s| !s.is_empty())
+}
+
 async fn process_msg(
     msg: SocketChMsg,
+    io: &SocketIo,
     datastore: SocketDataRef,
     props: &mut HashMap<PropertyHandle, (ValueCache, Vec<String>)>,
-    cache: &mut HashMap<String, (UpdatePackage, usize)>
+    cache: &mut HashMap<String, (UpdatePackage, usize)>,
+    downsamples: &mut HashMap<String, Vec<Downsample>>,
+    events: &mut DashboardEvents
 ) {
     // debug!("Socket updater received message");
     match msg {
         SocketChMsg::AddDashboard(name) => {
             if let Ok(dash) = super::get_dashboard(datastore.datastore, name.clone()).await {
                 let list = dash.list_properties();
+                events.add_dashboard(name.as_str(), dash.list_events()).await;
+                clients::set_subscriptions(name.as_str(), Some(list.iter().copied().collect()));
 
                 for p in list {
                     if let Some((value_cache, dashes)) = props.get_mut(&p) {
@@ -195,10 +533,40 @@ async fn process_msg(
                     for item in removal {
                         props.remove(&item);
                     }
+                    downsamples.remove(&name);
+                    events.remove_dashboard(name.as_str()).await;
+                    clients::set_subscriptions(name.as_str(), None);
                     
                 }
             }
             
+        },
+        SocketChMsg::Downsample(name, tier, aggregation) => {
+            let tiers = downsamples.entry(name).or_default();
+            if !tiers.iter().any(|ds| ds.tier == tier && ds.aggregation == aggregation) {
+                tiers.push(Downsample::new(tier, aggregation));
+            }
+        },
+        SocketChMsg::Snapshot(id, name, encoding) => {
+            let Some(socket) = io.get_socket(id) else {
+                // Disconnected again already
+                return;
+            };
+
+            let list: UpdatePackage = props.iter()
+                .filter(|(_, (_, dashes))| dashes.contains(&name))
+                .map(|(handle, (value_cache, _))| (*handle, value_cache.value.clone()))
+                .collect();
+
+            let res = match encoding {
+                Encoding::Json => socket.emit("update", [&list]).map_err(|e| e.to_string()),
+                Encoding::Binary => socket.bin(vec![encode_binary(&list)]).emit("update-bin", Vec::<u8>::new()).map_err(|e| e.to_string()),
+                Encoding::Text => serde_json::to_string(&list).map_err(|e| e.to_string())
+                    .and_then(|text| socket.emit("update-text", text).map_err(|e| e.to_string()))
+            };
+            if let Err(e) = res {
+                error!("Failed to send snapshot to {} of dashboard {}: {}", id, name, e);
+            }
         }
     }
 }
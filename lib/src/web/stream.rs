@@ -0,0 +1,131 @@
+//! Binary stream of a fixed set of numeric properties at a high rate, for overlays like steering or pedal traces,
+//! which the dashboard socket (sending changes at the websocket_update_rate) is too slow for.
+//!
+//! A plain WebSocket under /stream, separate from socket.io: the properties and rate are set through the query,
+//! and can be changed by sending a text message. Every tick a binary frame is send, see encode_frame
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{extract::{ws::{Message, WebSocket}, Query, State, WebSocketUpgrade}, response::Response};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use crate::{frames::FrameReader, utils::ValueCache, PropertyHandle};
+
+use super::utils::DataStoreLocked;
+
+/// Frames per second if the client does not set a rate
+const DEFAULT_RATE: u32 = 100;
+const MAX_RATE: u32 = 1000;
+
+/// The query of /stream: props is a comma separated list of property names
+#[derive(Debug, Deserialize)]
+pub(super) struct StreamQuery {
+    #[serde(default)]
+    props: Option<String>,
+    #[serde(default)]
+    rate: Option<u32>
+}
+
+/// Send by the client as text message to change the subscription,
+/// and send back by the server (with the rate clamped) whenever it is applied
+#[derive(Debug, Serialize, Deserialize)]
+struct Subscription {
+    props: Vec<String>,
+    #[serde(default = "default_rate")]
+    rate: u32
+}
+
+fn default_rate() -> u32 {
+    DEFAULT_RATE
+}
+
+pub(super) async fn stream(ws: WebSocketUpgrade, State(datastore): State<DataStoreLocked>, Query(query): Query<StreamQuery>) -> Response {
+    let sub = Subscription {
+        props: query.props.map(|p| p.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect()).unwrap_or_default(),
+        rate: query.rate.unwrap_or(DEFAULT_RATE)
+    };
+
+    ws.on_upgrade(move |socket| run_stream(socket, datastore, sub))
+}
+
+/// Encodes a frame (all numbers little endian):
+/// - u64 microseconds since the unix epoch
+/// - f64 for every property, in the order of the subscription (NaN if it does not exist or is not a number)
+fn encode_frame(datastore: &crate::datastore::DataStore, props: &mut [(Option<PropertyHandle>, ValueCache)]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(8 + props.len() * 8);
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or_default();
+    buf.extend_from_slice(&time.to_le_bytes());
+
+    // Groups in the middle of a frame repeat their previous value
+    let mut frames = FrameReader::default();
+    for (handle, cache) in props.iter_mut() {
+        let value = handle.as_ref().and_then(|h| Some((h, datastore.get_property_container(h)?))).and_then(|(h, cont)| {
+            if frames.can_read(h) {
+                cont.read_web(cache);
+            }
+            cache.value.to_number()
+        }).unwrap_or(f64::NAN);
+
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    buf
+}
+
+async fn run_stream(mut socket: WebSocket, datastore: DataStoreLocked, mut sub: Subscription) {
+    debug!("Property stream connected");
+
+    'subscribe: loop {
+        sub.rate = sub.rate.clamp(1, MAX_RATE);
+        let mut props: Vec<(Option<PropertyHandle>, ValueCache)> = sub.props.iter()
+            .map(|name| (PropertyHandle::new(name.as_str()), ValueCache::default()))
+            .collect();
+
+        // Confirms the order of the values in the frames
+        match serde_json::to_string(&sub) {
+            Ok(text) => if socket.send(Message::Text(text)).await.is_err() {
+                break;
+            },
+            Err(e) => error!("Failed to serialize stream subscription: {}", e)
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / sub.rate as f64));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let frame = {
+                        let ds_r = datastore.read().await;
+                        if ds_r.get_shutdown_status() {
+                            break 'subscribe;
+                        }
+                        encode_frame(&ds_r, props.as_mut_slice())
+                    };
+
+                    if socket.send(Message::Binary(frame)).await.is_err() {
+                        break 'subscribe;
+                    }
+                },
+                msg = socket.recv() => match msg {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<Subscription>(text.as_str()) {
+                        Ok(new) => {
+                            sub = new;
+                            continue 'subscribe;
+                        },
+                        Err(e) => {
+                            let _ = socket.send(Message::Text(serde_json::json!({ "error": e.to_string() }).to_string())).await;
+                        }
+                    },
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break 'subscribe,
+                    // Ping is answered by axum
+                    Some(Ok(_)) => ()
+                }
+            }
+        }
+    }
+
+    debug!("Property stream disconnected");
+}
@@ -1,10 +1,17 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use kanal::{AsyncReceiver, AsyncSender};
+use log::{debug, error};
 use socketioxide::socket::Sid;
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, time::Instant};
 use crate::datastore::DataStore;
 
+use super::{downsample::{Aggregation, Tier}, socket::Encoding};
+
+/// How long a disconnected dashboard keeps its subscriptions, so a client that reconnects
+/// (like a tablet briefly dropping Wi-Fi) can resume its session
+const RESUME_GRACE: Duration = Duration::from_secs(30);
+
 pub(super) type DataStoreLocked = &'static RwLock<DataStore>;
 pub(super) type SocketDataRef = &'static SocketData;
 
@@ -15,9 +22,19 @@ pub(super) enum Auth {
     Plugin(u64, Arc<String>)
 }
 
+/// A dashboard client, identified by the token it was send on auth, outliving the socket for RESUME_GRACE
+#[derive(Debug)]
+struct Session {
+    dashboard: String,
+    socket: Sid,
+    /// Set while the socket is disconnected
+    detached: Option<Instant>
+}
+
 pub(super) struct SocketData {
     pub datastore: DataStoreLocked,
     access_table: RwLock<HashMap<Sid, Auth>>,
+    sessions: RwLock<HashMap<String, Session>>,
     pub sender: AsyncSender<SocketChMsg>
 }
 
@@ -29,6 +46,7 @@ impl SocketData {
                 SocketData {
                     datastore,
                     access_table: RwLock::new(HashMap::new()),
+                    sessions: RwLock::new(HashMap::new()),
                     sender: sx
                 }
             )
@@ -47,6 +65,39 @@ impl SocketData {
         let _ = self.sender.send(SocketChMsg::AddDashboard(name)).await;
     }
 
+    /// Subscribes the dashboard (same as insert_dashboard) and starts a session for it,
+    /// returns the token the client uses to resume it (None if no token could be generated)
+    pub(super) async fn create_session(&self, id: Sid, name: String) -> Option<String> {
+        self.insert_dashboard(id, name.clone()).await;
+
+        let mut buf = [0u8; 16];
+        if let Err(e) = getrandom::getrandom(&mut buf) {
+            error!("Unable to generate a session token, socket {} won't be able to resume: {}", id, e);
+            return None;
+        }
+        let token: String = buf.iter().map(|b| format!("{:02x}", b)).collect();
+
+        self.sessions.write().await.insert(token.clone(), Session { dashboard: name, socket: id, detached: None });
+        Some(token)
+    }
+
+    /// Moves a disconnected session to the new socket, keeping the subscriptions of the old one.
+    /// Returns false if there is no such session (expired, or the server restarted)
+    pub(super) async fn resume_session(&self, id: Sid, name: &str, token: &str) -> bool {
+        let mut w_sessions = self.sessions.write().await;
+        match w_sessions.get_mut(token) {
+            Some(session) if session.dashboard == name && session.detached.is_some() => {
+                session.socket = id;
+                session.detached = None;
+            },
+            _ => return false
+        }
+        drop(w_sessions);
+
+        self.insert_auth(id, Auth::Dashboard(name.to_string())).await;
+        true
+    }
+
     pub(super) async fn get_auth(&self, id: &Sid) -> Option<Auth> {
         let r_table = self.access_table.read().await;
         if let Some(value) = r_table.get(id) {
@@ -60,22 +111,57 @@ impl SocketData {
         }
     }
 
-    pub(super) async fn remove_auth(&self, id: &Sid) {
+    pub(super) async fn remove_auth(&'static self, id: &Sid) {
         let mut w_table = self.access_table.write().await;
         if let Some(res) = w_table.remove(id) {
             drop(w_table);
             match res {
                 Auth::Dashboard(name) => { 
-                    let _ = self.sender.send(SocketChMsg::RmDashboard(name)).await;
+                    if !self.detach_session(id).await {
+                        let _ = self.sender.send(SocketChMsg::RmDashboard(name)).await;
+                    }
                 },
                 Auth::Plugin(_, _) => todo!("Plugin removal not yet implemented")
             }
         }
     }
+
+    /// Marks the session of the socket as disconnected, it is ended (and the dashboard removed)
+    /// if it is not resumed within RESUME_GRACE.
+    /// Returns false if the socket had no session
+    async fn detach_session(&'static self, id: &Sid) -> bool {
+        let mut w_sessions = self.sessions.write().await;
+        let Some((token, session)) = w_sessions.iter_mut().find(|(_, s)| &s.socket == id && s.detached.is_none()) else {
+            return false;
+        };
+        session.detached = Some(Instant::now());
+        let token = token.clone();
+        drop(w_sessions);
+
+        tokio::task::spawn(async move {
+            tokio::time::sleep(RESUME_GRACE).await;
+
+            let mut w_sessions = self.sessions.write().await;
+            // The session may have been resumed and detached again since, then that disconnect ends it
+            if w_sessions.get(&token).is_some_and(|s| s.detached.is_some_and(|t| t.elapsed() >= RESUME_GRACE)) {
+                if let Some(session) = w_sessions.remove(&token) {
+                    drop(w_sessions);
+                    debug!("Session of dashboard {} was not resumed, removing", session.dashboard);
+                    let _ = self.sender.send(SocketChMsg::RmDashboard(session.dashboard)).await;
+                }
+            }
+        });
+
+        true
+    }
 }
 
 /// Serves as the Messaging Protocol of the Socket.io Server Channel
 pub(super) enum SocketChMsg {
     AddDashboard(String),
-    RmDashboard(String)
+    RmDashboard(String),
+    /// A client of the dashboard uses this tier, so updates have to be collected for it
+    Downsample(String, Tier, Aggregation),
+    /// Sends the current value of every property of the dashboard to the socket (after resuming)
+    Snapshot(Sid, String, Encoding)
 }
@@ -0,0 +1,239 @@
+use std::{net::TcpStream, sync::{atomic::{AtomicBool, Ordering}, mpsc::{Receiver, Sender}, Arc, Mutex}, thread::JoinHandle, time::Duration};
+
+use datarace_plugin_api::wrappers::{EventHandle, Message, PluginError, PluginHandle, Property};
+
+mod protocol;
+
+use protocol::Inbound;
+
+pub(crate) type PluginState = State;
+
+datarace_plugin_api::macros::free_string_fn!();
+
+datarace_plugin_api::macros::plugin_descriptor_fn!("obs", 0, 1, 0);
+
+// The properties are documented in docs/OBS.md
+datarace_plugin_api::macros::propertys_initor!{ create_properties, "obs",
+    (CONNECTED, "connected", false),
+    (SCENE, "scene", ""),
+    (STREAMING, "streaming", false),
+    (RECORDING, "recording", false),
+    (TARGET_SCENE, "target_scene", "")
+}
+
+const START_RECORDING: EventHandle = datarace_plugin_api::macros::generate_event_handle!("obs.start_recording");
+const STOP_RECORDING: EventHandle = datarace_plugin_api::macros::generate_event_handle!("obs.stop_recording");
+const START_STREAMING: EventHandle = datarace_plugin_api::macros::generate_event_handle!("obs.start_streaming");
+const STOP_STREAMING: EventHandle = datarace_plugin_api::macros::generate_event_handle!("obs.stop_streaming");
+
+/// Events we listen to, with the request send to OBS
+const ACTIONS: &[(EventHandle, &str)] = &[
+    (START_RECORDING, "StartRecord"),
+    (STOP_RECORDING, "StopRecord"),
+    (START_STREAMING, "StartStream"),
+    (STOP_STREAMING, "StopStream")
+];
+
+/// How long we wait for a message before checking for actions (and if we should stop)
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often we try to connect while OBS is not running
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings read from the environment
+struct Settings {
+    address: String,
+    password: String
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|val| !val.is_empty());
+
+        Settings {
+            address: env("DATARACE_OBS_ADDRESS").unwrap_or("127.0.0.1:4455".to_string()),
+            password: env("DATARACE_OBS_PASSWORD").unwrap_or_default()
+        }
+    }
+}
+
+pub(crate) struct State {
+    running: Arc<AtomicBool>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    /// Requests for the worker, from the events
+    actions: Mutex<Option<Sender<&'static str>>>
+}
+
+#[datarace_plugin_api::macros::plugin_init]
+fn handle_init(handle: PluginHandle) -> Result<PluginState,String> {
+    create_properties(&handle)?;
+
+    for (ev, _) in ACTIONS {
+        handle.create_event(*ev).to_result().map_err(|e| e.to_string())?;
+        handle.subscribe_event(*ev).to_result().map_err(|e| e.to_string())?;
+    }
+
+    Ok(State { running: Arc::new(AtomicBool::new(true)), worker: Mutex::new(None), actions: Mutex::new(None) })
+}
+
+#[datarace_plugin_api::macros::plugin_update]
+fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), PluginError> {
+    match msg {
+        Message::StartupFinished => {
+            let state = datarace_plugin_api::macros::get_state!(handle).ok_or("No state".to_string())?;
+
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let worker_handle = handle.clone();
+            let running = state.running.clone();
+            let worker = std::thread::spawn(move || run(worker_handle, running, Settings::from_env(), receiver));
+
+            if let Ok(mut lock) = state.worker.lock() {
+                *lock = Some(worker);
+            }
+            if let Ok(mut lock) = state.actions.lock() {
+                *lock = Some(sender);
+            }
+        },
+        Message::EventTriggered(ev) => {
+            let state = datarace_plugin_api::macros::get_state!(handle).ok_or("No state".to_string())?;
+
+            if let Some((_, request)) = ACTIONS.iter().find(|(action, _)| *action == ev) {
+                if let Some(sender) = state.actions.lock().ok().as_ref().and_then(|lock| lock.as_ref()) {
+                    // Only fails once the worker stopped
+                    let _ = sender.send(request);
+                }
+            }
+        },
+        Message::Lock | Message::Unlock => {
+            // The worker takes the lock itself before updating properties
+        },
+        Message::Shutdown => {
+            if let Some(state) = datarace_plugin_api::macros::get_state!(handle) {
+                state.running.store(false, Ordering::Release);
+
+                let worker = state.worker.lock().ok().and_then(|mut lock| lock.take());
+                if let Some(worker) = worker {
+                    if worker.join().is_err() {
+                        handle.log_error("OBS worker panicked");
+                    }
+                }
+            }
+
+            unsafe { datarace_plugin_api::macros::drop_state_now!(handle) }
+        },
+        _ => ()
+    }
+
+    Ok(())
+}
+
+/// Worker thread connecting to OBS until shutdown
+fn run(handle: PluginHandle, running: Arc<AtomicBool>, settings: Settings, actions: Receiver<&'static str>) {
+    let mut logged_error = false;
+
+    while running.load(Ordering::Acquire) {
+        match connect(&settings) {
+            Ok(socket) => {
+                logged_error = false;
+                session(&handle, &running, &settings, &actions, socket);
+
+                let _lock = handle.lock_plugin();
+                handle.update_property(CONNECTED, Property::from(false));
+            },
+            Err(e) => {
+                // OBS is likely just not running, so we only log the first failure
+                if !logged_error {
+                    handle.log_info(format!("Unable to connect to OBS at {}: {}", settings.address, e));
+                    logged_error = true;
+                }
+            }
+        }
+
+        // Actions while disconnected are dropped
+        while actions.try_recv().is_ok() {}
+
+        let mut waited = Duration::ZERO;
+        while waited < RECONNECT_INTERVAL && running.load(Ordering::Acquire) {
+            std::thread::sleep(POLL_INTERVAL);
+            waited += POLL_INTERVAL;
+        }
+    }
+}
+
+type Socket = tungstenite::WebSocket<TcpStream>;
+
+fn connect(settings: &Settings) -> Result<Socket, String> {
+    let stream = TcpStream::connect(settings.address.as_str()).map_err(|e| e.to_string())?;
+    let (socket, _) = tungstenite::client(format!("ws://{}", settings.address), stream).map_err(|e| e.to_string())?;
+
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL)).map_err(|e| e.to_string())?;
+    Ok(socket)
+}
+
+fn send(socket: &mut Socket, text: String) -> bool {
+    socket.send(tungstenite::Message::Text(text)).is_ok()
+}
+
+/// Handles the connection till it is closed (or we shut down)
+fn session(handle: &PluginHandle, running: &AtomicBool, settings: &Settings, actions: &Receiver<&'static str>, mut socket: Socket) {
+    let mut identified = false;
+
+    while running.load(Ordering::Acquire) {
+        let msg = match socket.read() {
+            Ok(tungstenite::Message::Text(text)) => protocol::parse(text.as_str()),
+            Ok(_) => None,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => None,
+            Err(_) => break
+        };
+
+        let _lock = handle.lock_plugin();
+
+        match msg {
+            Some(Inbound::Hello(auth)) => {
+                if !send(&mut socket, protocol::identify(settings.password.as_str(), auth)) {
+                    break;
+                }
+            },
+            Some(Inbound::Identified) => {
+                identified = true;
+                handle.update_property(CONNECTED, Property::from(true));
+                handle.log_info("Connected to OBS");
+
+                for request in ["GetCurrentProgramScene", "GetStreamStatus", "GetRecordStatus"] {
+                    send(&mut socket, protocol::request(request, None));
+                }
+            },
+            Some(Inbound::Scene(scene)) => {
+                handle.update_property(SCENE, Property::from(scene));
+            },
+            Some(Inbound::Streaming(active)) => {
+                handle.update_property(STREAMING, Property::from(active));
+            },
+            Some(Inbound::Recording(active)) => {
+                handle.update_property(RECORDING, Property::from(active));
+            },
+            Some(Inbound::Failed(request, comment)) => {
+                handle.log_error(format!("OBS request {} failed: {}", request, comment));
+            },
+            Some(Inbound::Other) | None => ()
+        }
+
+        if !identified {
+            continue;
+        }
+
+        while let Ok(request) = actions.try_recv() {
+            send(&mut socket, protocol::request(request, None));
+        }
+
+        // Written by rules or dashboards, we reset it after switching so the same scene can be
+        // requested again
+        if let Ok(Property::Str(target)) = handle.get_property_value(TARGET_SCENE) {
+            if !target.is_empty() {
+                send(&mut socket, protocol::set_scene(target.as_str()));
+                handle.update_property(TARGET_SCENE, Property::from(""));
+            }
+        }
+    }
+
+    let _ = socket.close(None);
+}
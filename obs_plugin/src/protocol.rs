@@ -0,0 +1,117 @@
+// Messages of obs-websocket (version 5, included in OBS since 28).
+//
+// Every message is json with the opcode in op and the data in d. After connecting OBS sends Hello
+// (with an authentication challenge if a password is set), we answer with Identify and then
+// receive Identified, after which we can send requests and receive the events we subscribed to.
+
+use base64::Engine;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const RPC_VERSION: u64 = 1;
+
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_EVENT: u64 = 5;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+/// Scenes (1 << 2) and Outputs (1 << 6)
+const EVENT_SUBSCRIPTIONS: u64 = (1 << 2) | (1 << 6);
+
+#[derive(Debug)]
+pub(crate) enum Inbound {
+    /// Challenge and salt, if a password is required
+    Hello(Option<(String, String)>),
+    Identified,
+    Scene(String),
+    Streaming(bool),
+    Recording(bool),
+    /// A request failed, with the request type and the comment of OBS
+    Failed(String, String),
+    Other
+}
+
+fn hash(text: &str) -> String {
+    base64::engine::general_purpose::STANDARD.encode(Sha256::digest(text.as_bytes()))
+}
+
+pub(crate) fn identify(password: &str, auth: Option<(String, String)>) -> String {
+    let mut data = json!({ "rpcVersion": RPC_VERSION, "eventSubscriptions": EVENT_SUBSCRIPTIONS });
+
+    if let Some((challenge, salt)) = auth {
+        let secret = hash(format!("{}{}", password, salt).as_str());
+        data["authentication"] = Value::from(hash(format!("{}{}", secret, challenge).as_str()));
+    }
+
+    json!({ "op": OP_IDENTIFY, "d": data }).to_string()
+}
+
+/// The request type is also used as id, as we only care about the type of the response
+pub(crate) fn request(request_type: &str, data: Option<Value>) -> String {
+    let mut msg = json!({ "op": OP_REQUEST, "d": { "requestType": request_type, "requestId": request_type } });
+
+    if let Some(data) = data {
+        msg["d"]["requestData"] = data;
+    }
+
+    msg.to_string()
+}
+
+pub(crate) fn set_scene(scene: &str) -> String {
+    request("SetCurrentProgramScene", Some(json!({ "sceneName": scene })))
+}
+
+fn scene_name(data: &Value) -> Option<String> {
+    // Newer versions also send sceneName, older only currentProgramSceneName
+    data.get("sceneName").or_else(|| data.get("currentProgramSceneName"))?.as_str().map(str::to_string)
+}
+
+fn output_active(data: &Value) -> Option<bool> {
+    data.get("outputActive")?.as_bool()
+}
+
+/// Parses a message, None if it is not valid json (or has no opcode)
+pub(crate) fn parse(text: &str) -> Option<Inbound> {
+    let msg: Value = serde_json::from_str(text).ok()?;
+    let data = msg.get("d").unwrap_or(&Value::Null);
+
+    Some(match msg.get("op")?.as_u64()? {
+        OP_HELLO => Inbound::Hello(data.get("authentication").and_then(|auth| Some((
+            auth.get("challenge")?.as_str()?.to_string(),
+            auth.get("salt")?.as_str()?.to_string()
+        )))),
+        OP_IDENTIFIED => Inbound::Identified,
+        OP_EVENT => {
+            let event_data = data.get("eventData").unwrap_or(&Value::Null);
+
+            let res = match data.get("eventType").and_then(Value::as_str).unwrap_or_default() {
+                "CurrentProgramSceneChanged" => scene_name(event_data).map(Inbound::Scene),
+                "StreamStateChanged" => output_active(event_data).map(Inbound::Streaming),
+                "RecordStateChanged" => output_active(event_data).map(Inbound::Recording),
+                _ => None
+            };
+            res.unwrap_or(Inbound::Other)
+        },
+        OP_REQUEST_RESPONSE => {
+            let request_type = data.get("requestType").and_then(Value::as_str).unwrap_or_default();
+            let status = data.get("requestStatus").unwrap_or(&Value::Null);
+
+            if !status.get("result").and_then(Value::as_bool).unwrap_or_default() {
+                let comment = status.get("comment").and_then(Value::as_str).unwrap_or("no reason given");
+                return Some(Inbound::Failed(request_type.to_string(), comment.to_string()));
+            }
+
+            let response_data = data.get("responseData").unwrap_or(&Value::Null);
+            let res = match request_type {
+                "GetCurrentProgramScene" => scene_name(response_data).map(Inbound::Scene),
+                "GetStreamStatus" => output_active(response_data).map(Inbound::Streaming),
+                "GetRecordStatus" => output_active(response_data).map(Inbound::Recording),
+                _ => None
+            };
+            res.unwrap_or(Inbound::Other)
+        },
+        _ => Inbound::Other
+    })
+}
@@ -1,5 +1,5 @@
 use std::{ffi::CString, os::raw::c_void};
-use crate::wrappers::{DataStoreReturnCode, EventHandle, PluginHandle, PluginLockGuard, Property, PropertyHandle};
+use crate::wrappers::{ArrayHandle, DataStoreReturnCode, EventHandle, FrameGuard, MessageType, NotificationSeverity, PluginHandle, PluginLockGuard, Property, PropertyHandle, PropertyType, SessionMarker};
 
 use datarace_plugin_api_sys as sys;
 
@@ -62,6 +62,87 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Creates a new string property that only accepts one of the allowed values
+    ///
+    /// Works the same as `create_property`, but updates with any other string are rejected with
+    /// TypeMissmatch.
+    /// The initial value has to be one of the allowed values.
+    pub fn create_enum_property <S: ToString>(&self, name: S, prop_handle: PropertyHandle, allowed: &[&str], init: &str) -> DataStoreReturnCode {
+        let Some(arr) = ArrayHandle::new(self, Property::Str(String::new()), allowed.len()) else {
+            return DataStoreReturnCode::ParameterCorrcupted;
+        };
+        for (index, value) in allowed.iter().enumerate() {
+            arr.set(self, index, Property::Str(value.to_string()));
+        }
+
+        let name_ptr = create_cstring!(name);
+        let init_ptr = create_cstring!(init);
+
+        let res = unsafe {
+            sys::create_enum_property(self.get_ptr(), name_ptr, prop_handle.get_inner(), arr.get_ptr(), init_ptr)
+        };
+        drop_cstring!(name_ptr);
+        drop_cstring!(init_ptr);
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Declares the version of your property names (1 if never declared, 0 is invalid)
+    ///
+    /// Bump it when renaming properties, and register the old names with `create_property_alias`,
+    /// so dashboards still using the old names keep working
+    pub fn set_property_namespace_version(&self, version: u32) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::set_property_namespace_version(self.get_ptr(), version)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Returns the namespace version another plugin declared (1 if it did not, or is not running)
+    pub fn get_property_namespace_version(&self, plugin: u64) -> u32 {
+        unsafe {
+            sys::get_property_namespace_version(self.get_ptr(), plugin)
+        }
+    }
+
+    /// Registers the name of a property from an older namespace version as alias of one of your
+    /// properties, dashboards (and rules etc.) reading the alias are served the target
+    ///
+    /// Same as `create_property` the name is without your plugin name and has to match the handle.
+    /// Plugins subscribing to the alias get nothing, they have to use the new name
+    pub fn create_property_alias<S: ToString>(&self, name: S, alias_handle: PropertyHandle, target: PropertyHandle, version: u32) -> DataStoreReturnCode {
+        let name_ptr = create_cstring!(name);
+
+        let res = unsafe {
+            sys::create_property_alias(self.get_ptr(), name_ptr, alias_handle.get_inner(), target.get_inner(), version)
+        };
+        drop_cstring!(name_ptr);
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Returns the values an enum property accepts (see `create_enum_property`)
+    ///
+    /// None if the property does not exist (or is not accessible) or is not an enum property
+    pub fn get_property_allowed_values(&self, prop_handle: PropertyHandle) -> Option<Vec<String>> {
+        let arr = unsafe {
+            sys::get_property_allowed_values(self.get_ptr(), prop_handle.get_inner())
+        };
+
+        if arr.is_null() {
+            return None;
+        }
+
+        match Property::new(sys::Property { sort: sys::PropertyType_Array, value: sys::PropertyValue { arr } }) {
+            Property::Array(arr) => Some(arr.iter().filter_map(|value| match value {
+                Property::Str(value) => Some(value),
+                _ => None
+            }).collect()),
+            _ => None
+        }
+    }
+
     /// Updates the value of a property
     /// 
     /// You can only update propertys that were created with this handle
@@ -78,6 +159,28 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Sets the max age of your property in ms: if it is not updated within it (for example because the game stopped
+    /// sending data), it is marked stale till the next update, and dashboards grey out the values.
+    /// Writing any value of an array counts as an update.
+    ///
+    /// The property does not have to be created yet. 0 removes the max age, properties without one are never stale
+    pub fn set_property_max_age(&self, prop_handle: PropertyHandle, max_age_ms: u64) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::set_property_max_age(self.get_ptr(), prop_handle.get_inner(), max_age_ms)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Checks if the property was not updated within its max age (see `set_property_max_age`)
+    ///
+    /// False for properties without a max age and ones that do not exist
+    pub fn is_property_stale(&self, prop_handle: PropertyHandle) -> bool {
+        unsafe {
+            sys::is_property_stale(self.get_ptr(), prop_handle.get_inner())
+        }
+    }
+
     /// Retrieves the value for a PropertyHandle that you have subscribe to (or created)
     pub fn get_property_value(&self, prop_handle: PropertyHandle) -> Result<Property, DataStoreReturnCode> {
         let res = unsafe {
@@ -92,6 +195,91 @@ impl PluginHandle {
         Ok(Property::new(res.value))
     }
 
+    /// Retrieves the value of a property by name (which you have subscribed to or created)
+    ///
+    /// This saves generating the PropertyHandle, but hashes the name on every call,
+    /// so for regular reads store the handle and use `get_property_value`
+    pub fn get_property_value_by_name<S: ToString>(&self, name: S) -> Result<Property, DataStoreReturnCode> {
+        let name_ptr = create_cstring!(name);
+
+        let res = unsafe {
+            sys::get_property_value_by_name(self.get_ptr(), name_ptr)
+        };
+        drop_cstring!(name_ptr);
+
+        let code = DataStoreReturnCode::from(res.code);
+        if code != DataStoreReturnCode::Ok {
+            return Err(code);
+        }
+
+        Ok(Property::new(res.value))
+    }
+
+    /// Retrieves the type of any property, without having to subscribe to it
+    ///
+    /// Returns DoesNotExist if the property does not exist (yet)
+    pub fn get_property_type(&self, prop_handle: PropertyHandle) -> Result<PropertyType, DataStoreReturnCode> {
+        let res = unsafe {
+            sys::get_property_type(self.get_ptr(), prop_handle.get_inner())
+        };
+
+        let code = DataStoreReturnCode::from(res.code);
+        if code != DataStoreReturnCode::Ok {
+            return Err(code);
+        }
+
+        Ok(PropertyType::from(res.value))
+    }
+
+    /// Checks if a property (of any plugin) exists
+    pub fn property_exists(&self, prop_handle: PropertyHandle) -> bool {
+        unsafe {
+            sys::property_exists(self.get_ptr(), prop_handle.get_inner())
+        }
+    }
+
+    /// Looks up the name (plugin.property) of a property handle, for debugging output
+    ///
+    /// Names are known once the property was created (and kept after it was deleted),
+    /// None for handles that never belonged to a property
+    pub fn lookup_property_name(&self, prop_handle: PropertyHandle) -> Option<String> {
+        let ptr = unsafe {
+            sys::lookup_property_name(self.get_ptr(), prop_handle.get_inner())
+        };
+
+        if ptr.is_null() {
+            return None;
+        }
+
+        let name = crate::get_string(ptr);
+        unsafe {
+            sys::deallocate_string(ptr);
+        }
+        name
+    }
+
+    /// Lists the properties you currently have registered, with their full name (plugin.property)
+    ///
+    /// Creates and deletes that are still queued are not reflected yet.
+    /// Useful to clean up properties created dynamically that are no longer needed
+    pub fn list_own_properties(&self) -> Vec<(PropertyHandle, String)> {
+        let arr = unsafe {
+            sys::list_own_properties(self.get_ptr())
+        };
+
+        if arr.is_null() {
+            return Vec::new();
+        }
+
+        // Wrapping it in an ArrayHandle drops it once we are done
+        match Property::new(sys::Property { sort: sys::PropertyType_Array, value: sys::PropertyValue { arr } }) {
+            Property::Array(arr) => arr.iter().filter_map(|name| match name {
+                Property::Str(name) => Some((generate_property_handle(name.as_str()).ok()?, name)),
+                _ => None
+            }).collect(),
+            _ => Vec::new()
+        }
+    }
 
     /// Deletes this property (queues the deletion)
     ///
@@ -139,6 +327,30 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Subscribes to a property (same as `subscribe_property`) and additionally registers a
+    /// callback, which is called directly every time the owner updates the value.
+    ///
+    /// The callback runs on the thread of the plugin updating the value (while it is locked), so:
+    /// - Keep it short
+    /// - `user_data` is passed to every call, it has to stay valid until `unsubscribe_property`
+    ///   (or replacing the callback) returned, which waits for running calls
+    /// - Convert the raw values with `PropertyHandle::from` and `Property::from`, which also
+    ///   deallocates the string/array
+    ///
+    /// Changes to single values of arrays are not delivered.
+    /// `unsubscribe_property` removes the callback again, as does a failed subscription.
+    ///
+    /// # Safety
+    /// `user_data` is handed to the callback from other threads, it has to be safe to access
+    /// from there until the callback is removed.
+    pub unsafe fn subscribe_property_callback(&self, prop_handle: PropertyHandle, callback: unsafe extern "C" fn(sys::PropertyHandle, sys::Property, *mut c_void), user_data: *mut c_void) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::subscribe_property_callback(self.get_ptr(), prop_handle.get_inner(), Some(callback), user_data)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
     /// Removes subscription for a certain property (it will queue it)
     ///
     /// Same as create/change_property/delete, this (after checking that the property was subscribed to) will send a Message to the loader
@@ -204,6 +416,23 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Subscribes to an event, but you are only informed of the triggers passing the filter,
+    /// for noisy events that trigger hundreds of times a second.
+    ///
+    /// With `debounce` you only receive the first trigger of a burst (a trigger after at least
+    /// `debounce` without any), with `every_nth` only every nth trigger (of those passing the debounce).
+    /// `Duration::ZERO` and 0 (or 1) disable the respective filter.
+    ///
+    /// Subscribing again replaces the filter, otherwise this works the same as `subscribe_event`
+    pub fn subscribe_event_filtered(&self, event_handle: EventHandle, debounce: std::time::Duration, every_nth: u64) -> DataStoreReturnCode {
+        let filter = sys::EventFilter { debounce_ms: debounce.as_millis().try_into().unwrap_or(u64::MAX), every_nth };
+        let res = unsafe {
+            sys::subscribe_event_filtered(self.get_ptr(), event_handle.get_inner(), filter)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
     /// Unsubscribes to an event
     ///
     /// This is done by sending a message to the event loop, so we don't know if the event even
@@ -235,13 +464,40 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Marks a lap, sector, stint or session boundary
+    ///
+    /// Advances the core counters (datarace.session.lap, .sector and .stint) and triggers the core event
+    /// for this marker (datarace.session.new_lap etc.).
+    /// Markers are shared between all plugins, so only the plugin reading the game should set them
+    pub fn mark_session(&self, marker: SessionMarker) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::mark_session(self.get_ptr(), marker.to_c())
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Raises a notification, shown as a toast on all connected dashboards and listed on the
+    /// notifications page (with your plugin name as source)
+    pub fn raise_notification<S: ToString>(&self, severity: NotificationSeverity, text: S) -> DataStoreReturnCode {
+        let ptr = create_cstring!(text);
+
+        let res = unsafe {
+            sys::raise_notification(self.get_ptr(), severity.to_c(), ptr)
+        };
+        drop_cstring!(ptr);
+
+        DataStoreReturnCode::from(res)
+    }
+
     /// Allows you to send a raw memory pointer to another plugin.  
     ///
     /// The target is plugin id of the target plugin.  
     /// reason serves as a way to communicate what this pointer is for, although the recipient is also
     /// told your plugin id.  
     /// Obviously managing void pointers is risky business, both recipients have to be on the same
-    /// package and understand what it stands for.
+    /// package and understand what it stands for.  
+    /// If the target is not running the pointer comes back through `Message::OperationFailed`.
     pub unsafe fn send_plugin_ptr_message(&self, target: u64, ptr: *mut c_void, reason: i64) -> DataStoreReturnCode {
         let res = unsafe {
             sys::send_ptr_msg_to_plugin(self.get_ptr(), target, ptr, reason)
@@ -250,6 +506,17 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+    /// Asks to be told when the plugin with this id (see `generate_foreign_plugin_id`) starts and stops,
+    /// through `Message::WatchedPluginStarted` and `Message::WatchedPluginStopped`.  
+    /// If it is already running the start is delivered right away, else once it is loaded (also later on).
+    pub fn watch_plugin(&self, plugin: u64) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::watch_plugin(self.get_ptr(), plugin)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
     /// Sends a message to the update function of your plugin.  
     /// This type of internal message is useful for sending messages from worker threads, for example
     /// that they failed, so you could restart them or shut the plugin down
@@ -261,6 +528,20 @@ impl PluginHandle {
         DataStoreReturnCode::from(res)
     }
 
+
+    /// Only messages of these types are passed to update, all others are skipped by the
+    /// pluginloader (by default all are delivered).  
+    /// Shutdown, PluginMessagePtr and GroupUpdate are always delivered.
+    /// Best called in init
+    pub fn set_message_filter(&self, wanted: &[MessageType]) -> DataStoreReturnCode {
+        let filter = wanted.iter().fold(0, |filter, sort| filter | sort.filter_bit());
+
+        let res = unsafe {
+            sys::set_message_filter(self.get_ptr(), filter)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
     
     /// This is a way to Sync between your worker thread and the pluginloader.
     /// While you set the plugin to locked the pluginloader will not intiate lock,
@@ -277,11 +558,106 @@ impl PluginHandle {
     /// deadlock (as the first can't be dropped to unlock)!
     ///
     /// Once the Guard is dropped the plugin unlocks
+    /// Creates a subscription group (queues it), returning the id of the group
+    ///
+    /// Instead of reading each property, all values in the group that changed are delivered
+    /// together in a `Message::GroupUpdate` every interval (nothing is sent when nothing changed).
+    /// Creating a group with the same name again does nothing
+    pub fn create_subscription_group<S: ToString>(&self, name: S, interval: std::time::Duration) -> Result<u64, DataStoreReturnCode> {
+        let name_ptr = create_cstring!(name);
+
+        let res = unsafe {
+            sys::create_subscription_group(self.get_ptr(), name_ptr, interval.as_millis() as u64)
+        };
+        drop_cstring!(name_ptr);
+
+        let code = DataStoreReturnCode::from(res.code);
+        if code != DataStoreReturnCode::Ok {
+            return Err(code);
+        }
+
+        Ok(res.value)
+    }
+
+    /// Adds a property to the subscription group (queued), subscribing to it if necessary
+    pub fn add_to_subscription_group(&self, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::add_to_subscription_group(self.get_ptr(), group, prop_handle.get_inner())
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Removes a property from the subscription group (queued), this does not unsubscribe
+    pub fn remove_from_subscription_group(&self, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::remove_from_subscription_group(self.get_ptr(), group, prop_handle.get_inner())
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Deletes the subscription group (queued), the properties stay subscribed
+    pub fn delete_subscription_group(&self, group: u64) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::delete_subscription_group(self.get_ptr(), group)
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
     pub fn lock_plugin<'a>(&'a self) -> PluginLockGuard<'a> {
         unsafe { sys::lock_plugin(self.get_ptr()) };
 
         PluginLockGuard { handle: self }
     }
+
+    /// Creates a frame group, returning the id of the group
+    ///
+    /// The properties in the group are published together: values written while a `FrameGuard`
+    /// (from `begin_frame`) is held are only seen by dashboards and subscription groups once it is
+    /// dropped. Creating a group with the same name again does nothing
+    pub fn create_frame_group<S: ToString>(&self, name: S) -> Result<u64, DataStoreReturnCode> {
+        let name_ptr = create_cstring!(name);
+
+        let res = unsafe {
+            sys::create_frame_group(self.get_ptr(), name_ptr)
+        };
+        drop_cstring!(name_ptr);
+
+        let code = DataStoreReturnCode::from(res.code);
+        if code != DataStoreReturnCode::Ok {
+            return Err(code);
+        }
+
+        Ok(res.value)
+    }
+
+    /// Adds one of your properties to the frame group (it does not have to be created yet)
+    pub fn add_to_frame_group(&self, group: u64, prop_handle: PropertyHandle) -> DataStoreReturnCode {
+        let res = unsafe {
+            sys::add_to_frame_group(self.get_ptr(), group, prop_handle.get_inner())
+        };
+
+        DataStoreReturnCode::from(res)
+    }
+
+    /// Begins a frame of the group, which is committed once the guard is dropped
+    ///
+    /// This waits for readers of the group to finish (which is quick), keep the guard only while
+    /// writing the values, as the group is not read in the meantime
+    pub fn begin_frame<'a>(&'a self, group: u64) -> Result<FrameGuard<'a>, DataStoreReturnCode> {
+        let res = unsafe {
+            sys::begin_frame(self.get_ptr(), group)
+        };
+
+        let code = DataStoreReturnCode::from(res);
+        if code != DataStoreReturnCode::Ok {
+            return Err(code);
+        }
+
+        Ok(FrameGuard { handle: self, group })
+    }
 }
 
 /// Generates the PropertyHandle used for reading and updating values.
@@ -339,6 +715,20 @@ pub fn generate_event_handle<S: ToString>(name: S) -> Result<EventHandle, DataSt
     Ok(EventHandle::new(res.value))
 }
 
+/// Returns the core clock, in microseconds since DataRace launched (same as datarace.clock, but exact)
+///
+/// The clock is monotonic and shared by all plugins, so timestamps taken with it can be passed to
+/// other plugins (or stored as `Property::Duration`) and compared, unlike your own `Instant`
+pub fn get_clock() -> i64 {
+    unsafe { sys::get_clock() }
+}
+
+/// Returns the microseconds passed since the timestamp (taken with `get_clock` or read from datarace.clock),
+/// negative if the timestamp is in the future
+pub fn clock_delta(since: i64) -> i64 {
+    unsafe { sys::clock_delta(since) }
+}
+
 /// Allows you to optain the id of another plugin based on it's name. 
 /// This function is intended for runtime use, compiletime macro is TODO
 ///
@@ -4,11 +4,15 @@ pub mod api;
 /// Contains wrappers around api data
 pub mod wrappers;
 
+/// Catches panics of the plugin, so they can be logged
+pub mod panic;
+
 /// Serves to reexport certain C structs for purposes such as building callback functions
 pub mod reexport {
     pub use datarace_plugin_api_sys::PluginHandle;
     pub use datarace_plugin_api_sys::Message;
     pub use datarace_plugin_api_sys::PluginDescription;
+    pub use datarace_plugin_api_sys::{Property, PropertyHandle};
 }
 
 /// For building callback functions simply
@@ -0,0 +1,58 @@
+use std::{backtrace::{Backtrace, BacktraceStatus}, cell::{Cell, RefCell}, panic::{PanicHookInfo, UnwindSafe}, sync::Once};
+
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    /// Set while we are inside catch_panic, panics elsewhere go to the previous hook
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs the function and catches a panic, returning the panic message (with location).
+///
+/// In debug builds (or with `RUST_BACKTRACE` set) the message also contains a backtrace.  
+/// This is used by the `plugin_init` and `plugin_update` macros to log panics, you shouldn't need
+/// to call it yourself.
+pub fn catch_panic<R, F: FnOnce() -> R + UnwindSafe>(func: F) -> Result<R, String> {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CATCHING.get() {
+                LAST_PANIC.set(Some(format_panic(info)));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+
+    let was_catching = CATCHING.replace(true);
+    let res = std::panic::catch_unwind(func);
+    CATCHING.set(was_catching);
+
+    res.map_err(|_| LAST_PANIC.take().unwrap_or_else(|| "unknown panic".to_string()))
+}
+
+fn format_panic(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let msg = if let Some(text) = payload.downcast_ref::<&str>() {
+        text.to_string()
+    } else if let Some(text) = payload.downcast_ref::<String>() {
+        text.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+
+    let location = info.location().map(|loc| format!(" at {}:{}:{}", loc.file(), loc.line(), loc.column())).unwrap_or_default();
+
+    let backtrace = if cfg!(debug_assertions) {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::capture()
+    };
+
+    if backtrace.status() == BacktraceStatus::Captured {
+        format!("{}{}\n{}", msg, location, backtrace)
+    } else {
+        format!("{}{}", msg, location)
+    }
+}
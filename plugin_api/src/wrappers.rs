@@ -63,6 +63,12 @@ impl PropertyHandle {
     }
 }
 
+impl From<sys::PropertyHandle> for PropertyHandle {
+    fn from(value: sys::PropertyHandle) -> Self {
+        PropertyHandle::new(value)
+    }
+}
+
 impl PartialEq for PropertyHandle {
     fn eq(&self, other: &Self) -> bool {
         self.inner.plugin == other.inner.plugin && self.inner.property == other.inner.property
@@ -135,6 +141,30 @@ impl ArrayHandle {
         }
     }
 
+    /// Creates a new ArrayHandle containing the values of the slice, like
+    /// `ArrayHandle::from_slice(&handle, &[1, 2, 3])`.
+    ///
+    /// The same restrictions on types apply as with `new`, and as the type is taken from the first
+    /// value an empty slice also returns None.
+    pub fn from_slice<T>(handle: &PluginHandle, values: &[T]) -> Option<Self> where T: Into<Property> + Clone {
+        let (first, rest) = values.split_first()?;
+        let arr = ArrayHandle::new(handle, first.clone().into(), values.len())?;
+
+        if rest.is_empty() || arr.set_range(handle, 1, rest.iter().cloned().map(Into::into).collect()) == DataStoreReturnCode::Ok {
+            Some(arr)
+        } else {
+            None
+        }
+    }
+
+    /// Collects the values of the array into a Vec, like `arr.to_vec::<i64>()`.
+    ///
+    /// Returns None if the values are of a different type then `T` (use `to_vec::<Property>()` to
+    /// get them regardless of type).
+    pub fn to_vec<T>(&self) -> Option<Vec<T>> where T: TryFrom<Property> {
+        self.iter().map(|value| T::try_from(value).ok()).collect()
+    }
+
     /// Retrieves a value at a certain index.
     ///
     /// None if the index is out of bounds.
@@ -166,6 +196,20 @@ impl ArrayHandle {
         res
     }
 
+    /// Sets the values starting at the index `start`, in a single call
+    ///
+    /// Nothing is written if you:
+    /// - Lack write permission (NotAuthenticated)
+    /// - Exceed the bounds with the range (DoesNotExist)
+    /// - Pass any value with a different Datatype then used in the array (TypeMissmatch)
+    pub fn set_range(&self, handle: &PluginHandle, start: usize, values: Vec<Property>) -> DataStoreReturnCode {
+        let values: Vec<sys::Property> = values.into_iter().map(Property::to_c).collect();
+
+        DataStoreReturnCode::from(unsafe {
+            sys::set_array_values(handle.ptr, self.ptr, start, values.as_ptr(), values.len())
+        })
+    }
+
     /// Returns the size of the array
     #[inline]
     pub fn len(&self) -> usize {
@@ -178,6 +222,10 @@ impl ArrayHandle {
     pub fn iter<'a>(&'a self) -> ArrayIterator<'a> {
         ArrayIterator { handle: self, index: 0 }
     }
+
+    pub(crate) fn get_ptr(&self) -> *mut sys::ArrayValueHandle {
+        self.ptr
+    }
 }
 
 impl Drop for ArrayHandle {
@@ -223,6 +271,11 @@ impl Iterator for ArrayIterator<'_> {
 /// Note:
 /// Duration is messured in micro seconds (1s = 1,000 ms = 1,000,000 us), and is signed
 /// So, while std::time::Duration does NOT support negative timespans, this DOES
+///
+/// Timestamp is a point in time in micro seconds since the unix epoch (1970-01-01 00:00:00 UTC),
+/// also signed for times before it
+///
+/// Vector is x, y, z (for 2D z is 0), which is updated as a unit
 #[derive(Debug, Clone)]
 pub enum Property {
     None,
@@ -231,6 +284,8 @@ pub enum Property {
     Bool(bool),
     Str(String),
     Duration(i64),
+    Timestamp(i64),
+    Vector(f64, f64, f64),
     Array(ArrayHandle)
 }
 
@@ -284,6 +339,20 @@ impl Property {
 
                 Property::Duration(val)
             },
+            sys::PropertyType_Timestamp => {
+                let val = unsafe {
+                    prop.value.timestamp
+                };
+
+                Property::Timestamp(val)
+            },
+            sys::PropertyType_Vector => {
+                let val = unsafe {
+                    prop.value.vector
+                };
+
+                Property::Vector(val.x, val.y, val.z)
+            },
             sys::PropertyType_Array => {
                 let ptr = unsafe {
                     prop.value.arr
@@ -310,6 +379,8 @@ impl Property {
                 sys::Property { sort: sys::PropertyType_Str, value: sys::PropertyValue { str_: c_str } }
             },
             Property::Duration(d) => sys::Property { sort: sys::PropertyType_Duration, value: sys::PropertyValue { dur: d } },
+            Property::Timestamp(t) => sys::Property { sort: sys::PropertyType_Timestamp, value: sys::PropertyValue { timestamp: t } },
+            Property::Vector(x, y, z) => sys::Property { sort: sys::PropertyType_Vector, value: sys::PropertyValue { vector: sys::Vector3 { x, y, z } } },
             Property::Array(mut arr) => {
                 let v = sys::Property { sort: sys::PropertyType_Array, value: sys::PropertyValue { arr: arr.ptr } };
 
@@ -382,10 +453,146 @@ impl Property {
         }
     }
 
+    /// If this is a Property::Duration this formats it as a laptime `mm:ss.mmm` (like `01:23.456`).  
+    /// Sub milliseconds are cut off, negative durations get a leading `-`
+    pub fn format_laptime(&self) -> Option<String> {
+        if let Property::Duration(t) = self {
+            let (sign, ms) = split_duration(*t, 1000);
+            Some(format!("{}{:02}:{:02}.{:03}", sign, ms / 60_000, ms / 1000 % 60, ms % 1000))
+        } else {
+            None
+        }
+    }
+
+    /// If this is a Property::Duration this formats it as clock time `h:mm:ss` (like `1:05:09`).  
+    /// Fractions of seconds are cut off, negative durations get a leading `-`
+    pub fn format_clock(&self) -> Option<String> {
+        if let Property::Duration(t) = self {
+            let (sign, sec) = split_duration(*t, 1_000_000);
+            Some(format!("{}{}:{:02}:{:02}", sign, sec / 3600, sec / 60 % 60, sec % 60))
+        } else {
+            None
+        }
+    }
+
+    /// If this is a Property::Duration this formats it as a gap, always signed (like `+0.123`, `-1.500`).  
+    /// Gaps of a minute or more include the minutes (like `+1:02.345`)
+    pub fn format_gap(&self) -> Option<String> {
+        if let Property::Duration(t) = self {
+            let (sign, ms) = split_duration(*t, 1000);
+            let sign = if sign.is_empty() { "+" } else { sign };
+
+            if ms < 60_000 {
+                Some(format!("{}{}.{:03}", sign, ms / 1000, ms % 1000))
+            } else {
+                Some(format!("{}{}:{:02}.{:03}", sign, ms / 60_000, ms / 1000 % 60, ms % 1000))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Parses a Property::Duration from the formats created by `format_laptime`, `format_clock`
+    /// and `format_gap`, so `h:mm:ss.ffffff`, `mm:ss.fff` or `ss.fff` with an optional `+`/`-`.  
+    /// The fraction is optional and can have up to 6 digits (micro seconds).  
+    /// Returns None if the text is not in one of these formats
+    pub fn parse_duration(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (neg, text) = if let Some(rest) = text.strip_prefix('-') {
+            (true, rest)
+        } else {
+            (false, text.strip_prefix('+').unwrap_or(text))
+        };
+
+        let mut parts = text.rsplit(':');
+        let last = parts.next()?;
+        let (sec, frac) = last.split_once('.').unwrap_or((last, ""));
+
+        let mut micros = parse_digits(sec)?.checked_mul(1_000_000)?;
+        if !frac.is_empty() {
+            if frac.len() > 6 {
+                return None;
+            }
+            micros += parse_digits(frac)? * 10_i64.pow(6 - frac.len() as u32);
+        }
+
+        for factor in [60, 3600] {
+            if let Some(part) = parts.next() {
+                micros = micros.checked_add(parse_digits(part)?.checked_mul(factor * 1_000_000)?)?;
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Property::Duration(if neg { -micros } else { micros }))
+    }
+
+    /// A Property::Timestamp of the current time
+    pub fn now() -> Self {
+        Property::from(std::time::SystemTime::now())
+    }
+
+    /// If this is a Property::Timestamp this converts it into a SystemTime
+    pub fn to_system_time(&self) -> Option<std::time::SystemTime> {
+        if let Property::Timestamp(t) = self {
+            let offset = std::time::Duration::from_micros(t.unsigned_abs());
+            if *t < 0 {
+                std::time::UNIX_EPOCH.checked_sub(offset)
+            } else {
+                std::time::UNIX_EPOCH.checked_add(offset)
+            }
+        } else {
+            None
+        }
+    }
+
     /// Uses `ToString` to convert text types into a Property.
     pub fn from_string<T>(value: T) -> Self where T: ToString {
         Property::Str(value.to_string())
     }
+
+    /// The value if this is a Property::Int
+    pub fn as_int(&self) -> Option<i64> {
+        if let Property::Int(i) = self { Some(*i) } else { None }
+    }
+
+    /// The value if this is a Property::Float
+    pub fn as_f64(&self) -> Option<f64> {
+        if let Property::Float(f) = self { Some(*f) } else { None }
+    }
+
+    /// The value if this is a Property::Bool
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Property::Bool(b) = self { Some(*b) } else { None }
+    }
+
+    /// The text if this is a Property::Str
+    pub fn as_str(&self) -> Option<&str> {
+        if let Property::Str(s) = self { Some(s.as_str()) } else { None }
+    }
+
+    /// The micro seconds if this is a Property::Duration.  
+    /// Use `to_duration` to get a Rust Duration instead
+    pub fn as_duration(&self) -> Option<i64> {
+        if let Property::Duration(d) = self { Some(*d) } else { None }
+    }
+}
+
+/// Splits the duration (in micro seconds) into the sign and the absolute value divided by the
+/// divisor
+fn split_duration(micros: i64, divisor: u64) -> (&'static str, u64) {
+    let sign = if micros < 0 { "-" } else { "" };
+    (sign, micros.unsigned_abs() / divisor)
+}
+
+/// Only accepts ascii digits (so no sign or whitespace)
+fn parse_digits(text: &str) -> Option<i64> {
+    if text.is_empty() || !text.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    text.parse().ok()
 }
 
 impl ToString for Property {
@@ -397,6 +604,8 @@ impl ToString for Property {
             Property::Bool(b) => b.to_string(),
             Property::Str(s) => s.clone(),
             Property::Duration(d) => format!("{}us", d.to_string()),
+            Property::Timestamp(t) => format!("{}us since epoch", t),
+            Property::Vector(x, y, z) => format!("({}, {}, {})", x, y, z),
             Property::Array(arr) => {
                 let mut ouput = "[".to_string();
 
@@ -511,6 +720,31 @@ impl From<std::time::Duration> for Property {
     }
 }
 
+impl From<std::time::SystemTime> for Property {
+    /// This function will panic if the time is more then 292,471 years away from 1970
+    fn from(value: std::time::SystemTime) -> Self {
+        let micros = match value.duration_since(std::time::UNIX_EPOCH) {
+            Ok(dur) => i64::try_from(dur.as_micros()),
+            Err(e) => i64::try_from(e.duration().as_micros()).map(|t| -t)
+        };
+
+        Property::Timestamp(micros.expect("Why in the ever loving world did you need more then 290k years?"))
+    }
+}
+
+impl From<(f64, f64, f64)> for Property {
+    fn from(value: (f64, f64, f64)) -> Self {
+        Property::Vector(value.0, value.1, value.2)
+    }
+}
+
+impl From<(f64, f64)> for Property {
+    /// 2D Vector, z is 0
+    fn from(value: (f64, f64)) -> Self {
+        Property::Vector(value.0, value.1, 0.0)
+    }
+}
+
 impl From<String> for Property {
     fn from(value: String) -> Self {
         Property::Str(value)
@@ -523,12 +757,173 @@ impl From<&str> for Property {
     }
 }
 
+/// Returns the Property back if it is not an Int
+impl TryFrom<Property> for i64 {
+    type Error = Property;
+
+    fn try_from(value: Property) -> Result<Self, Self::Error> {
+        match value {
+            Property::Int(i) => Ok(i),
+            _ => Err(value)
+        }
+    }
+}
+
+/// Returns the Property back if it is not a Float
+impl TryFrom<Property> for f64 {
+    type Error = Property;
+
+    fn try_from(value: Property) -> Result<Self, Self::Error> {
+        match value {
+            Property::Float(f) => Ok(f),
+            _ => Err(value)
+        }
+    }
+}
+
+/// Returns the Property back if it is not a Bool
+impl TryFrom<Property> for bool {
+    type Error = Property;
+
+    fn try_from(value: Property) -> Result<Self, Self::Error> {
+        match value {
+            Property::Bool(b) => Ok(b),
+            _ => Err(value)
+        }
+    }
+}
+
+/// Returns the Property back if it is not a Str
+impl TryFrom<Property> for String {
+    type Error = Property;
+
+    fn try_from(value: Property) -> Result<Self, Self::Error> {
+        match value {
+            Property::Str(s) => Ok(s),
+            _ => Err(value)
+        }
+    }
+}
+
+/// For the raw values passed into callbacks, this takes care of deallocating strings
+impl From<sys::Property> for Property {
+    fn from(value: sys::Property) -> Self {
+        Property::new(value)
+    }
+}
+
 impl From<ArrayHandle> for Property {
     fn from(value: ArrayHandle) -> Self {
         Property::Array(value)
     }
 }
 
+/// The type of a property (without the value), see `PluginHandle::get_property_type`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropertyType {
+    None,
+    Int,
+    Float,
+    Bool,
+    Str,
+    Duration,
+    Timestamp,
+    Vector,
+    Array,
+    Unknown
+}
+
+impl From<sys::PropertyType> for PropertyType {
+    fn from(value: sys::PropertyType) -> Self {
+        match value {
+            sys::PropertyType_None => PropertyType::None,
+            sys::PropertyType_Int => PropertyType::Int,
+            sys::PropertyType_Float => PropertyType::Float,
+            sys::PropertyType_Boolean => PropertyType::Bool,
+            sys::PropertyType_Str => PropertyType::Str,
+            sys::PropertyType_Duration => PropertyType::Duration,
+            sys::PropertyType_Timestamp => PropertyType::Timestamp,
+            sys::PropertyType_Vector => PropertyType::Vector,
+            sys::PropertyType_Array => PropertyType::Array,
+            _ => PropertyType::Unknown
+        }
+    }
+}
+
+/// Boundaries in a session, see `PluginHandle::mark_session`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionMarker {
+    /// A new lap started, the sector goes back to 1
+    Lap,
+    Sector,
+    /// A new stint started (like after a driver change), the lap keeps counting
+    Stint,
+    /// A new session started, all counters go back to 1
+    Session
+}
+
+impl SessionMarker {
+    pub(crate) fn to_c(self) -> sys::SessionMarker {
+        match self {
+            SessionMarker::Lap => sys::SessionMarker_Lap,
+            SessionMarker::Sector => sys::SessionMarker_Sector,
+            SessionMarker::Stint => sys::SessionMarker_Stint,
+            SessionMarker::Session => sys::SessionMarker_Session
+        }
+    }
+}
+
+/// How urgent a notification is, see `PluginHandle::raise_notification`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationSeverity {
+    Info,
+    Warning,
+    Error
+}
+
+impl NotificationSeverity {
+    pub(crate) fn to_c(self) -> sys::NotificationSeverity {
+        match self {
+            NotificationSeverity::Info => sys::NotificationSeverity_Info,
+            NotificationSeverity::Warning => sys::NotificationSeverity_Warning,
+            NotificationSeverity::Error => sys::NotificationSeverity_Error
+        }
+    }
+}
+
+/// Operations that are carried out after the call returned, see `Message::OperationFailed`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    CreateProperty,
+    ChangePropertyType,
+    DeleteProperty,
+    SubscribeProperty,
+    UnsubscribeProperty,
+    /// The pointer was not delivered, so it is still yours to deallocate
+    SendPluginMessage,
+    AddToSubscriptionGroup,
+    RemoveFromSubscriptionGroup,
+    DeleteSubscriptionGroup,
+    Unknown
+}
+
+impl From<sys::Operation> for Operation {
+    fn from(value: sys::Operation) -> Self {
+        match value {
+            sys::Operation_CreateProperty => Operation::CreateProperty,
+            sys::Operation_ChangePropertyType => Operation::ChangePropertyType,
+            sys::Operation_DeleteProperty => Operation::DeleteProperty,
+            sys::Operation_SubscribeProperty => Operation::SubscribeProperty,
+            sys::Operation_UnsubscribeProperty => Operation::UnsubscribeProperty,
+            sys::Operation_SendPluginMessage => Operation::SendPluginMessage,
+            sys::Operation_AddToSubscriptionGroup => Operation::AddToSubscriptionGroup,
+            sys::Operation_RemoveFromSubscriptionGroup => Operation::RemoveFromSubscriptionGroup,
+            sys::Operation_DeleteSubscriptionGroup => Operation::DeleteSubscriptionGroup,
+            _ => Operation::Unknown
+        }
+    }
+}
+
 /// Serve as status codes for api calls
 #[derive(Debug, PartialEq)]
 pub enum DataStoreReturnCode {
@@ -540,6 +935,8 @@ pub enum DataStoreReturnCode {
     NotImplemented = 6,
     ParameterCorrcupted = 10,
     DataCorrupted = 11,
+    QuotaExceeded = 12,
+    Busy = 13,
     Unknown = 255
 
 }
@@ -571,6 +968,8 @@ impl From<sys::DataStoreReturnCode> for DataStoreReturnCode {
             sys::DataStoreReturnCode_NotImplemented => DataStoreReturnCode::NotImplemented,
             sys::DataStoreReturnCode_ParameterCorrupted => DataStoreReturnCode::ParameterCorrcupted,
             sys::DataStoreReturnCode_DataCorrupted => DataStoreReturnCode::DataCorrupted,
+            sys::DataStoreReturnCode_QuotaExceeded => DataStoreReturnCode::QuotaExceeded,
+            sys::DataStoreReturnCode_Busy => DataStoreReturnCode::Busy,
             _ => DataStoreReturnCode::Unknown
         }
     }
@@ -587,11 +986,59 @@ impl Display for DataStoreReturnCode {
             DataStoreReturnCode::NotImplemented => "Action denied: This function has to still be implemented",
             DataStoreReturnCode::ParameterCorrcupted => "Action failed: Parameters are inproperly formated or otherwise incorrect",
             DataStoreReturnCode::DataCorrupted => "Error: Unable to parse input Data. This indicates a corrupted PluginHandle or Datastore, which are non recoverable",
+            DataStoreReturnCode::QuotaExceeded => "Action denied: A limit set for this plugin in the plugin_quota of the config was reached",
+            DataStoreReturnCode::Busy => "Action failed: The datastore is locked right now, try again later",
             DataStoreReturnCode::Unknown => "Action failed for an unknown reason. Plugin is too out of date to know this message, possibly the reason for the Error"
         })
     }
 }
 
+/// Error returned by the functions marked with `plugin_init` and `plugin_update`
+///
+/// Any type implementing ToString converts into a `Fatal` error with exit code 1,
+/// so `Result<(), String>` (and `?` on other errors) keeps working
+#[derive(Debug)]
+pub enum PluginError {
+    /// Only the current message is skipped, the plugin keeps running.
+    /// In `plugin_init` this is still treated as a failed init
+    Recoverable(String),
+    /// The plugin is shut down, the exit code is logged by DataRace (0 is replaced with 1)
+    Fatal(String, i32)
+}
+
+/// Returned by update for a `Recoverable` error, any negative value is treated like this
+pub const RECOVERABLE_ERROR_CODE: i32 = -1;
+
+impl PluginError {
+    /// The message logged by the plugin
+    pub fn message(&self) -> &str {
+        match self {
+            PluginError::Recoverable(msg) => msg.as_str(),
+            PluginError::Fatal(msg, _) => msg.as_str()
+        }
+    }
+
+    /// The code returned to DataRace, 0 is never returned (as it means success)
+    pub fn code(&self) -> i32 {
+        match self {
+            PluginError::Recoverable(_) => RECOVERABLE_ERROR_CODE,
+            PluginError::Fatal(_, 0) => 1,
+            PluginError::Fatal(_, code) => *code
+        }
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        self.code() > 0
+    }
+}
+
+// PluginError deliberatly does not implement Display, as this would conflict with From<T> for T
+impl<T: ToString> From<T> for PluginError {
+    fn from(value: T) -> Self {
+        PluginError::Fatal(value.to_string(), 1)
+    }
+}
+
 pub enum Message {
     Lock,
     Unlock,
@@ -599,6 +1046,10 @@ pub enum Message {
     Shutdown,
     StartupFinished,
     OtherPluginStarted(u64),
+    /// A plugin watched with `PluginHandle::watch_plugin` finished its startup (or was already running)
+    WatchedPluginStarted(u64),
+    /// A plugin watched with `PluginHandle::watch_plugin` stopped
+    WatchedPluginStopped(u64),
     
     InternalMsg(i64),
     PluginMessagePtr{origin: u64, ptr: *mut c_void, reason: i64 },
@@ -606,6 +1057,22 @@ pub enum Message {
     EventTriggered(EventHandle),
     EventUnsubscribed(EventHandle),
 
+    /// A subscription completed, contains the current value of the property
+    PropertySubscribed(PropertyHandle, Property),
+    /// The owner changed the type of a subscribed property, contains the current value in the new type
+    PropertyTypeChanged(PropertyHandle, Property),
+    /// The values of a subscription group that changed since the last update
+    GroupUpdate(u64, Vec<(PropertyHandle, Property)>),
+    /// Something queued by the plugin (like creating a property) failed after the call returned Ok.
+    /// property is zero for SendPluginMessage and DeleteSubscriptionGroup, target is the plugin id for
+    /// SendPluginMessage and the group for subscription group operations (else zero).
+    /// For SendPluginMessage ptr and reason are the ones that were not delivered, the pointer is yours
+    /// to free again (else ptr is null)
+    OperationFailed { what: Operation, code: DataStoreReturnCode, property: PropertyHandle, target: u64, ptr: *mut c_void, reason: i64 },
+    /// The owner of a subscribed property stopped, the subscription is gone (and the property removed
+    /// from subscription groups). Subscribe again once the owner is back (see `PluginHandle::watch_plugin`)
+    SubscriptionRemoved(PropertyHandle),
+
     // Update(PropertyHandle, Property),
     // Remove(PropertyHandle),
 
@@ -623,6 +1090,12 @@ impl From<sys::Message> for Message {
             sys::MessageType_OtherPluginStarted => {
                 Message::OtherPluginStarted(unsafe { value.value.plugin_id })
             },
+            sys::MessageType_WatchedPluginStarted => {
+                Message::WatchedPluginStarted(unsafe { value.value.plugin_id })
+            },
+            sys::MessageType_WatchedPluginStopped => {
+                Message::WatchedPluginStopped(unsafe { value.value.plugin_id })
+            },
             sys::MessageType_InternalMessage => {
                 Message::InternalMsg(unsafe {
                     value.value.internal_msg
@@ -648,6 +1121,46 @@ impl From<sys::Message> for Message {
 
                 Message::EventUnsubscribed(EventHandle::new(val))
             },
+            sys::MessageType_PropertySubscribed => {
+                let val = unsafe {
+                    value.value.update
+                };
+
+                Message::PropertySubscribed(PropertyHandle::new(val.handle), Property::new(val.value))
+            },
+            sys::MessageType_PropertyTypeChanged => {
+                let val = unsafe {
+                    value.value.update
+                };
+
+                Message::PropertyTypeChanged(PropertyHandle::new(val.handle), Property::new(val.value))
+            },
+            sys::MessageType_GroupUpdate => {
+                let val = unsafe {
+                    value.value.group_update
+                };
+
+                let updates = if val.updates.is_null() {
+                    Vec::new()
+                } else {
+                    unsafe { std::slice::from_raw_parts(val.updates, val.len) }.iter()
+                        .map(|up| (PropertyHandle::new(up.handle), Property::new(up.value)))
+                        .collect()
+                };
+                unsafe { sys::drop_group_update(val) };
+
+                Message::GroupUpdate(val.group, updates)
+            },
+            sys::MessageType_SubscriptionRemoved => {
+                Message::SubscriptionRemoved(PropertyHandle::new(unsafe { value.value.removed_property }))
+            },
+            sys::MessageType_OperationFailed => {
+                let val = unsafe {
+                    value.value.operation_failed
+                };
+
+                Message::OperationFailed { what: Operation::from(val.what), code: DataStoreReturnCode::from(val.code), property: PropertyHandle::new(val.property), target: val.target, ptr: val.message_ptr, reason: val.reason }
+            },
 
 
             // sys::MessageType_Update => {
@@ -669,6 +1182,44 @@ impl From<sys::Message> for Message {
     }
 }
 
+/// Types of messages that can be filtered out, see `PluginHandle::set_message_filter`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageType {
+    Lock,
+    Unlock,
+    StartupFinished,
+    OtherPluginStarted,
+    InternalMsg,
+    EventTriggered,
+    EventUnsubscribed,
+    PropertySubscribed,
+    PropertyTypeChanged,
+    OperationFailed,
+    WatchedPluginStarted,
+    WatchedPluginStopped,
+    SubscriptionRemoved
+}
+
+impl MessageType {
+    pub(crate) fn filter_bit(self) -> u64 {
+        1 << match self {
+            MessageType::Lock => sys::MessageType_Lock,
+            MessageType::Unlock => sys::MessageType_Unlock,
+            MessageType::StartupFinished => sys::MessageType_StartupFinished,
+            MessageType::OtherPluginStarted => sys::MessageType_OtherPluginStarted,
+            MessageType::InternalMsg => sys::MessageType_InternalMessage,
+            MessageType::EventTriggered => sys::MessageType_EventTriggered,
+            MessageType::EventUnsubscribed => sys::MessageType_EventUnsubscribed,
+            MessageType::PropertySubscribed => sys::MessageType_PropertySubscribed,
+            MessageType::PropertyTypeChanged => sys::MessageType_PropertyTypeChanged,
+            MessageType::OperationFailed => sys::MessageType_OperationFailed,
+            MessageType::WatchedPluginStarted => sys::MessageType_WatchedPluginStarted,
+            MessageType::WatchedPluginStopped => sys::MessageType_WatchedPluginStopped,
+            MessageType::SubscriptionRemoved => sys::MessageType_SubscriptionRemoved
+        }
+    }
+}
+
 impl Message {
     #[allow(dead_code)]
     pub(crate) fn to_c(self) -> sys::Message {
@@ -687,3 +1238,16 @@ impl<'a> Drop for PluginLockGuard<'a> {
         unsafe { sys::unlock_plugin(self.handle.get_ptr()) };
     }
 }
+
+/// A frame of a frame group (see `PluginHandle::begin_frame`), the values written while holding it
+/// are published together when it is dropped
+pub struct FrameGuard<'a> {
+    pub(crate) handle: &'a PluginHandle,
+    pub(crate) group: u64
+}
+
+impl<'a> Drop for FrameGuard<'a> {
+    fn drop(&mut self) {
+        unsafe { sys::commit_frame(self.handle.get_ptr(), self.group) };
+    }
+}
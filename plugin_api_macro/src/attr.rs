@@ -187,20 +187,21 @@ pub extern "C" fn init(handle: *mut datarace_plugin_api::reexport::PluginHandle)
     #sig #block
 
     let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
-    let res = std::panic::catch_unwind(|| {
+    let res = datarace_plugin_api::panic::catch_panic(|| {
         #init_handle
     });
 
     match res {
         Ok(Ok(_)) => 0,
-        Ok(Err(text)) => {
+        Ok(Err(err)) => {
             let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
-            han.log_error(text.to_string());
-            1
+            let err = datarace_plugin_api::wrappers::PluginError::from(err);
+            han.log_error(err.message());
+            err.code()
         },
-        Err(_) => {
+        Err(panic) => {
             let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
-            han.log_error("Plugin Init Paniced!");
+            han.log_error(format!("Plugin Init Paniced: {}", panic));
             10
         }
     }
@@ -340,20 +341,21 @@ pub extern "C" fn update(handle: *mut datarace_plugin_api::reexport::PluginHandl
 
     let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
     let message = datarace_plugin_api::wrappers::Message::from(msg);
-    let res = std::panic::catch_unwind(|| {
+    let res = datarace_plugin_api::panic::catch_panic(|| {
         #update_handle
     });
 
     match res {
         Ok(Ok(_)) => 0,
-        Ok(Err(text)) => {
+        Ok(Err(err)) => {
             let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
-            han.log_error(text.to_string());
-            1
+            let err = datarace_plugin_api::wrappers::PluginError::from(err);
+            han.log_error(err.message());
+            err.code()
         },
-        Err(_) => {
+        Err(panic) => {
             let han = unsafe { datarace_plugin_api::wrappers::PluginHandle::new(handle) };
-            han.log_error("Plugin Update Paniced!");
+            han.log_error(format!("Plugin Update Paniced: {}", panic));
             10
         }
     }
@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::{quote, quote_spanned, ToTokens};
-use syn::{parse::{Parse, ParseStream}, parse_macro_input, Ident, LitInt, LitStr, Token};
+use syn::{parse::{Parse, ParseStream}, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, Ident, Lit, LitInt, LitStr, Token};
 
 mod attr;
 mod genators;
@@ -190,11 +190,13 @@ pub extern "C" fn free_string(ptr: *mut std::os::raw::c_char) {
 /// Property names are not case sensitive, have to contain at least one dot, with the first dot
 /// deliminating between plugin and property (but the property part can contain further dots).
 /// You can not have any leading or trailing dots.
+///
+/// Besides a string literal the name can be composed with `concat!` (of literals, also nested),
+/// like `generate_property_handle!(concat!("sample_plugin", ".", "speed"))`.
+/// Consts can not be read by the macro, so they are not supported.
 #[proc_macro]
 pub fn generate_property_handle(input: TokenStream) -> TokenStream {
-    let name = parse_macro_input!(input as LitStr);
-
-    let name_val = name.value();
+    let HandleName { value: name_val, expr: name } = parse_macro_input!(input as HandleName);
     let handle = unsafe {
         let ptr = std::ffi::CString::new(name_val).expect("name can not be converted into CString").into_raw();
         let res = datarace_plugin_api_sys::generate_property_handle(ptr);
@@ -231,11 +233,13 @@ pub fn generate_property_handle(input: TokenStream) -> TokenStream {
 /// Event names are not case sensitive, have to contain at least one dot, with the first dot
 /// deliminating between plugin and event (but the event part can contain further dots).
 /// You can not have any leading or trailing dots.
+///
+/// Besides a string literal the name can be composed with `concat!` (of literals, also nested),
+/// like `generate_event_handle!(concat!("sample_plugin", ".", "pit"))`.
+/// Consts can not be read by the macro, so they are not supported.
 #[proc_macro]
 pub fn generate_event_handle(input: TokenStream) -> TokenStream {
-    let name = parse_macro_input!(input as LitStr);
-
-    let name_val = name.value();
+    let HandleName { value: name_val, expr: name } = parse_macro_input!(input as HandleName);
     let handle = unsafe {
         let ptr = std::ffi::CString::new(name_val).expect("name can not be converted into CString").into_raw();
         let res = datarace_plugin_api_sys::generate_event_handle(ptr);
@@ -261,6 +265,41 @@ pub fn generate_event_handle(input: TokenStream) -> TokenStream {
     }.into_token_stream().into()
 }
 
+/// Name passed into the handle macros, a string literal or concat! of literals
+struct HandleName {
+    value: String,
+    expr: Expr
+}
+
+impl Parse for HandleName {
+    fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        let expr: Expr = input.parse()?;
+
+        Ok(HandleName { value: eval_name(&expr)?, expr })
+    }
+}
+
+/// Evaluates literals and concat! into the string, the same way concat! would
+fn eval_name(expr: &Expr) -> syn::parse::Result<String> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(s) => Ok(s.value()),
+            Lit::Char(c) => Ok(c.value().to_string()),
+            Lit::Int(i) => Ok(i.base10_digits().to_string()),
+            Lit::Float(f) => Ok(f.base10_digits().to_string()),
+            Lit::Bool(b) => Ok(b.value.to_string()),
+            _ => Err(syn::Error::new(lit.span(), "Unsupported literal"))
+        },
+        Expr::Macro(mac) if mac.mac.path.is_ident("concat") => {
+            let parts = mac.mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+
+            parts.iter().map(eval_name).collect()
+        },
+        Expr::Group(group) => eval_name(&group.expr),
+        _ => Err(syn::Error::new(expr.span(), "Expected string literal or concat! of literals"))
+    }
+}
+
 struct StateSaveTokens {
     handle_name: Ident,
     state: Ident
@@ -12,15 +12,28 @@ pub use bindings::run;
 pub use bindings::{log_info, log_error};
 
 // Property Functions
-pub use bindings::{create_property, update_property, get_property_value, generate_property_handle, delete_property, change_property_type, subscribe_property, unsubscribe_property};
-pub use bindings::{create_array, get_array_value, set_array_value, clone_array_handle, drop_array_handle, get_array_length, get_array_type};
+pub use bindings::{create_property, create_enum_property, get_property_allowed_values, update_property, get_property_value, get_property_value_by_name, get_property_type, property_exists, lookup_property_name, list_own_properties, generate_property_handle, delete_property, change_property_type, subscribe_property, subscribe_property_callback, unsubscribe_property};
+pub use bindings::{set_property_namespace_version, get_property_namespace_version, create_property_alias};
+pub use bindings::{set_property_max_age, is_property_stale};
+pub use bindings::{create_subscription_group, add_to_subscription_group, remove_from_subscription_group, delete_subscription_group, drop_group_update};
+pub use bindings::{create_frame_group, add_to_frame_group, begin_frame, commit_frame};
+pub use bindings::{create_array, get_array_value, set_array_value, set_array_values, clone_array_handle, drop_array_handle, get_array_length, get_array_type};
 
 // Events
-pub use bindings::{generate_event_handle, create_event, delete_event, subscribe_event, unsubscribe_event, trigger_event};
+pub use bindings::{generate_event_handle, create_event, delete_event, subscribe_event, subscribe_event_filtered, unsubscribe_event, trigger_event};
+
+// Notifications
+pub use bindings::raise_notification;
+
+// Session markers
+pub use bindings::mark_session;
+
+// Clock
+pub use bindings::{get_clock, clock_delta};
 
 //Additional functions
 pub use bindings::deallocate_string;
-pub use bindings::{get_foreign_plugin_id, send_ptr_msg_to_plugin, send_internal_msg};
+pub use bindings::{get_foreign_plugin_id, watch_plugin, send_ptr_msg_to_plugin, send_internal_msg, set_message_filter};
 
 //State functions
 pub use bindings::{save_state_now, get_state};
@@ -30,26 +43,30 @@ pub use bindings::{lock_plugin, unlock_plugin};
 
 //Data
 // Enums
-pub use bindings::{DataStoreReturnCode, DataStoreReturnCode_Ok, DataStoreReturnCode_NotAuthenticated, DataStoreReturnCode_AlreadyExists, DataStoreReturnCode_DoesNotExist, DataStoreReturnCode_TypeMissmatch, DataStoreReturnCode_NotImplemented, DataStoreReturnCode_ParameterCorrupted, DataStoreReturnCode_DataCorrupted};
-pub use bindings::{PropertyType, PropertyType_None, PropertyType_Int, PropertyType_Float, PropertyType_Boolean, PropertyType_Str, PropertyType_Duration, PropertyType_Array};
-pub use bindings::{MessageType, MessageType_InternalMessage, MessageType_StartupFinished, MessageType_OtherPluginStarted, MessageType_PluginMessagePtr, MessageType_Lock, MessageType_Unlock, MessageType_Shutdown, MessageType_EventTriggered, MessageType_EventUnsubscribed}; 
+pub use bindings::{DataStoreReturnCode, DataStoreReturnCode_Ok, DataStoreReturnCode_NotAuthenticated, DataStoreReturnCode_AlreadyExists, DataStoreReturnCode_DoesNotExist, DataStoreReturnCode_TypeMissmatch, DataStoreReturnCode_NotImplemented, DataStoreReturnCode_ParameterCorrupted, DataStoreReturnCode_DataCorrupted, DataStoreReturnCode_QuotaExceeded, DataStoreReturnCode_Busy};
+pub use bindings::{PropertyType, PropertyType_None, PropertyType_Int, PropertyType_Float, PropertyType_Boolean, PropertyType_Str, PropertyType_Duration, PropertyType_Timestamp, PropertyType_Vector, PropertyType_Array};
+pub use bindings::{SessionMarker, SessionMarker_Lap, SessionMarker_Sector, SessionMarker_Stint, SessionMarker_Session};
+pub use bindings::{NotificationSeverity, NotificationSeverity_Info, NotificationSeverity_Warning, NotificationSeverity_Error};
+pub use bindings::{MessageType, MessageType_InternalMessage, MessageType_StartupFinished, MessageType_OtherPluginStarted, MessageType_PluginMessagePtr, MessageType_Lock, MessageType_Unlock, MessageType_Shutdown, MessageType_EventTriggered, MessageType_EventUnsubscribed, MessageType_PropertySubscribed, MessageType_GroupUpdate, MessageType_PropertyTypeChanged, MessageType_OperationFailed, MessageType_WatchedPluginStarted, MessageType_WatchedPluginStopped, MessageType_SubscriptionRemoved}; 
 
 // Message
 pub use bindings::{Message, MessageValue};
-pub use bindings::{UpdateValue, MessagePtr};
+pub use bindings::{UpdateValue, MessagePtr, SubscriptionGroupUpdate, FailedOperation};
+pub use bindings::{Operation, Operation_CreateProperty, Operation_ChangePropertyType, Operation_DeleteProperty, Operation_SubscribeProperty, Operation_UnsubscribeProperty, Operation_SendPluginMessage, Operation_AddToSubscriptionGroup, Operation_RemoveFromSubscriptionGroup, Operation_DeleteSubscriptionGroup};
 pub use bindings::reenqueue_message;
 
 // Property
-pub use bindings::{Property, PropertyValue, PropertyHandle, ArrayValueHandle};
+pub use bindings::{Property, PropertyValue, PropertyHandle, ArrayValueHandle, Vector3};
+pub use bindings::PropertyCallback;
 
 // Event
-pub use bindings::EventHandle;
+pub use bindings::{EventHandle, EventFilter};
 
 // Plugins
 pub use bindings::{PluginHandle,PluginDescription};
 
 // ReturnValues
-pub use bindings::{ReturnValue_PropertyHandle, ReturnValue_Property, ReturnValue_EventHandle};
+pub use bindings::{ReturnValue_PropertyHandle, ReturnValue_Property, ReturnValue_PropertyType, ReturnValue_EventHandle, ReturnValue_u64};
 pub use bindings::PluginNameHash;
 
 // Compiletime
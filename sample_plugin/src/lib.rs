@@ -1,4 +1,4 @@
-use datarace_plugin_api::wrappers::{DataStoreReturnCode, EventHandle, Message, PluginHandle, Property, PropertyHandle};
+use datarace_plugin_api::wrappers::{DataStoreReturnCode, EventHandle, Message, PluginError, PluginHandle, Property, PropertyHandle};
 
 pub(crate) type PluginState = State;
 
@@ -91,9 +91,10 @@ fn handle_init(handle: PluginHandle) -> Result<PluginState,String> {
 // it takes a PluginHandle and Message, and returns Result<(),ToString>
 //
 // Err(String) does not have to be string, just be a Type implementing ToString.
-// Returning Err will shutdown the plugin
+// Returning Err will shutdown the plugin, unless it is PluginError::Recoverable,
+// which only skips this message (any ToString converts into PluginError::Fatal)
 #[datarace_plugin_api::macros::plugin_update]
-fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
+fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), PluginError> {
 
     match msg {
         Message::StartupFinished => {
@@ -122,6 +123,11 @@ fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
 
             handle.log_info(format!("We got informed of the startup of another plugin: {id}"))
         },
+        Message::WatchedPluginStarted(id) | Message::WatchedPluginStopped(id) => {
+            // Only for plugins we asked for with watch_plugin, so we don't have to check every
+            // OtherPluginStarted
+            handle.log_info(format!("A watched plugin started or stopped: {id}"))
+        },
         Message::Lock => {
             // This message comes in to lock the plugin handle to perform some write (like creating
             // a Property). This means we need to stop performing any reads on the handle
@@ -156,6 +162,7 @@ fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
                     // Even if it got enqueued ahead of the 3 step, except
                     // there it would need an extra cycle to update the ValueContainer.
                     // But it would stay locked through this cycle, so we would only get 1 print
+                    // `launch_datarace ctl trace sample_plugin` shows this order of the messages
                     handle.log_info(format!("Value is {}", val.to_string()));
                 },
                 Err(e) => {
@@ -166,8 +173,8 @@ fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
 
             let res = handle.update_property(PROP_HANDLE, Property::Int(2));
             if res != DataStoreReturnCode::Ok {
-                handle.log_error(res);
-                return Ok(()); // We currently have no state, so we can't tell if this is the
+                // Logged by the macro, but the plugin keeps running
+                return Err(PluginError::Recoverable(res.to_string()));
             }
 
             match handle.get_property_value(PROP_HANDLE) {
@@ -251,7 +258,30 @@ fn handle_update(handle: PluginHandle, msg: Message) -> Result<(), String> {
             } else {
                 handle.log_info("Unknown Event unsubscribed OwO");
             }
-        }
+        },
+        Message::PropertySubscribed(_prop, value) => {
+            // Once a subscription is done we receive the current value,
+            // after this the value can be read any time via get_property_value
+            handle.log_info(format!("Subscribed to a property, current value: {}", value.to_string()));
+        },
+        Message::PropertyTypeChanged(_prop, value) => {
+            // The owner changed the type, reads return the new type from now on
+            handle.log_info(format!("Subscribed property changed type, current value: {}", value.to_string()));
+        },
+        Message::GroupUpdate(_group, values) => {
+            // All values of a subscription group that changed in the last interval
+            for (_prop, value) in values {
+                handle.log_info(format!("Group value changed: {}", value.to_string()));
+            }
+        },
+        Message::OperationFailed { what, code, .. } => {
+            // Something we queued (like creating a property) failed after the call had returned Ok
+            handle.log_error(format!("{:?} failed: {}", what, code));
+        },
+        Message::SubscriptionRemoved(prop) => {
+            // The plugin owning this property stopped, reading it will fail till we subscribe again
+            handle.log_info(format!("Lost subscription to {:?}", prop));
+        },
 
 
         Message::Unknown => {
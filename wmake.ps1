@@ -16,5 +16,23 @@ Set-Location $root
 New-Item plugins -ItemType Directory -Force
 Copy-Item .\target\release\sample_plugin.dll .\plugins\ -Force
 
+# Building iRacing Plugin
+Set-Location .\iracing_plugin
+cargo build --release
+Set-Location $root
+Copy-Item .\target\release\iracing_plugin.dll .\plugins\ -Force
+
+# Building ACC Plugin
+Set-Location .\acc_plugin
+cargo build --release
+Set-Location $root
+Copy-Item .\target\release\acc_plugin.dll .\plugins\ -Force
+
+# Building OBS Plugin
+Set-Location .\obs_plugin
+cargo build --release
+Set-Location $root
+Copy-Item .\target\release\obs_plugin.dll .\plugins\ -Force
+
 # Building and running main
 cargo run --release